@@ -49,14 +49,14 @@ fn main() {
             match host {
                 "x86_64-pc-windows-msvc" => {
                     let choco_installed = Command::new("cmd")
-                        .args(&["/C", "choco --version"])
+                        .args(["/C", "choco --version"])
                         .output()
                         .expect("Failed to check if Chocolatey is installed");
 
                     if !choco_installed.status.success() {
                         println!("cargo:warning=Chocolatey is not installed. Installing Chocolatey...");
                         let choco_installed = Command::new("cmd")
-                            .args(&["/C", "Set-ExecutionPolicy Bypass -Scope Process -Force; [System.Net.ServicePointManager]::SecurityProtocol = [System.Net.ServicePointManager]::SecurityProtocol -bor 3072; iex ((New-Object System.Net.WebClient).DownloadString('https://community.chocolatey.org/install.ps1'))"])
+                            .args(["/C", "Set-ExecutionPolicy Bypass -Scope Process -Force; [System.Net.ServicePointManager]::SecurityProtocol = [System.Net.ServicePointManager]::SecurityProtocol -bor 3072; iex ((New-Object System.Net.WebClient).DownloadString('https://community.chocolatey.org/install.ps1'))"])
                             .status()
                             .expect("Failed to install Chocolatey");
                         if !choco_installed.success() {
@@ -69,14 +69,14 @@ fn main() {
                         }
                         "aarch64-pc-windows-msvc" => {
                             let vs_is_installed = Command::new("cmd")
-                                .args(&["/C", "choco list --local-only visualstudio2019buildtools"])
+                                .args(["/C", "choco list --local-only visualstudio2019buildtools"])
                                 .output()
                                 .expect("Failed to check if Visual Studio Build Tools is installed");
                             if !vs_is_installed.status.success() || !String::from_utf8_lossy(&vs_is_installed.stdout).contains("visualstudio2019buildtools") {
                                 // Install Visual Studio Build Tools with the required components
                                 println!("cargo:warning=Installing Visual Studio Build Tools...");
                                 let vs_installed = Command::new("cmd")
-                                    .args(&["/C", "choco install visualstudio2019buildtools --package-parameters \"--add Microsoft.VisualStudio.Workload.VCTools --add Microsoft.VisualStudio.Component.VC.14.29.16.11.ARM64 --add Microsoft.VisualStudio.Component.Windows10SDK.19041 --includeRecommended --quiet --wait --norestart\""])
+                                    .args(["/C", "choco install visualstudio2019buildtools --package-parameters \"--add Microsoft.VisualStudio.Workload.VCTools --add Microsoft.VisualStudio.Component.VC.14.29.16.11.ARM64 --add Microsoft.VisualStudio.Component.Windows10SDK.19041 --includeRecommended --quiet --wait --norestart\""])
                                     .status()
                                     .expect("Failed to install Visual Studio Build Tools");
                                 if !vs_installed.success() {
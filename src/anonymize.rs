@@ -0,0 +1,323 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use byte::ctx::Endian;
+use byte::BytesExt;
+use memmap::MmapOptions;
+use sha2::{Digest, Sha256};
+
+use crate::get_endian_from_file;
+use crate::records::V4;
+use crate::types::{Cn, U4E};
+
+/// The fields that are anonymized when nothing else is configured.
+///
+/// Field names are the (lower case) STDF field names, a name covers that field
+/// in every record it appears in (`wafer_id` is both in WIR and WRR).
+pub const DEFAULT_FIELDS: [&str; 36] = [
+    // MIR
+    "lot_id", "part_typ", "node_nam", "job_nam", "sblot_id", "oper_nam",
+    "user_txt", "aux_file", "famly_id", "facil_id", "floor_id", "proc_id",
+    "spec_nam", "setup_id", "dsgn_rev", "eng_id", "rom_cod", "serl_num",
+    "supr_nam",
+    // SDR
+    "hand_id", "card_id", "load_id", "dib_id", "cabl_id", "cont_id",
+    "lasr_id", "extr_id",
+    // WIR & WRR
+    "wafer_id", "fabwf_id", "frame_id", "mask_id",
+    // MRR & WRR
+    "usr_desc", "exc_desc",
+    // PRR
+    "part_txt",
+    // DTR & ATR
+    "text_dat", "cmd_line",
+];
+
+/// Configuration of an [`Anonymizer`].
+#[derive(Debug, Clone)]
+pub struct AnonymizeConfig {
+    /// Secret mixed in the hash, the same secret yields the same aliases across files.
+    pub salt: String,
+    /// The (lower case) names of the fields to anonymize.
+    pub fields: HashSet<String>,
+    /// Number of seconds added to all (non-zero) `U4E` timestamps.
+    pub time_shift: i64,
+}
+
+impl Default for AnonymizeConfig {
+    fn default() -> Self {
+        AnonymizeConfig {
+            salt: String::new(),
+            fields: DEFAULT_FIELDS.iter().map(|field| field.to_string()).collect(),
+            time_shift: 0,
+        }
+    }
+}
+
+/// Replaces identifiers in STDF records by deterministic aliases.
+///
+/// An alias is the upper cased field name followed by the first 8 bytes of
+/// the SHA-256 hash of the (length prefixed) salt and the original value, so `lot_id` 'F6N910.1'
+/// becomes something like 'LOT_ID-3FA29C01D0E4B1A7'. Empty fields stay empty.
+pub struct Anonymizer {
+    config: AnonymizeConfig,
+}
+
+// Replaces the given Cn fields of a record by their scrubbed value, and re-binds
+// the record so it can borrow from the freshly created values.
+macro_rules! scrub {
+    ($anonymizer:ident, $record:ident, $changed:ident, $($field:ident),+) => {
+        $( let $field = $anonymizer.scrub(stringify!($field), &$record.$field); )+
+        $( $changed |= $field.as_slice() != $record.$field.0; )+
+        let mut $record = $record;
+        $( $record.$field = Cn(&$field); )+
+    };
+}
+
+// Shifts the given U4E fields of a record.
+macro_rules! shift {
+    ($anonymizer:ident, $record:ident, $changed:ident, $($field:ident),+) => {
+        $(
+            let shifted = $anonymizer.shift($record.$field);
+            $changed |= shifted != $record.$field;
+            $record.$field = shifted;
+        )+
+    };
+}
+
+impl Anonymizer {
+    pub fn new(config: AnonymizeConfig) -> Self {
+        Anonymizer { config }
+    }
+
+    /// Returns the alias for `value` of `field`.
+    pub fn alias(&self, field: &str, value: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        // the salt is length prefixed, so that ("ab", "c") and ("a", "bc") differ
+        hasher.update((self.config.salt.len() as u64).to_le_bytes());
+        hasher.update(self.config.salt.as_bytes());
+        hasher.update(value);
+        let digest = hasher.finalize();
+        let hash = digest[..8].iter().map(|b| format!("{:02X}", b)).collect::<String>();
+        format!("{}-{}", field.to_uppercase(), hash)
+    }
+
+    /// Returns the anonymized content of `value` if `field` is configured, the original otherwise.
+    pub fn scrub(&self, field: &str, value: &Cn) -> Vec<u8> {
+        if value.0.is_empty() || !self.config.fields.contains(field) {
+            value.0.to_vec()
+        } else {
+            self.alias(field, value.0).into_bytes()
+        }
+    }
+
+    /// Shifts a timestamp by the configured amount, a zero (= missing) timestamp stays zero.
+    pub fn shift(&self, timestamp: U4E) -> U4E {
+        if timestamp.0 == 0 || self.config.time_shift == 0 {
+            return timestamp;
+        }
+        let shifted = (timestamp.0 as i64 + self.config.time_shift).clamp(1, u32::MAX as i64);
+        U4E(shifted as u32)
+    }
+
+    /// Anonymizes a record.
+    ///
+    /// Returns the serialized (header included) anonymized record, or `None` if
+    /// the record doesn't hold anything to anonymize and can be copied as-is.
+    pub fn anonymize_record(&self, record: V4, endian: Endian) -> byte::Result<Option<Vec<u8>>> {
+        let mut changed = false;
+        let bytes = match record {
+            V4::ATR(atr) => {
+                scrub!(self, atr, changed, cmd_line);
+                shift!(self, atr, changed, mod_tim);
+                V4::ATR(atr).to_bytes(endian)?
+            }
+            V4::MIR(mir) => {
+                scrub!(self, mir, changed,
+                    lot_id, part_typ, node_nam, tstr_typ, job_nam, job_rev, sblot_id,
+                    oper_nam, exec_typ, exec_ver, test_cod, tst_temp, user_txt, aux_file,
+                    pkg_typ, famly_id, date_cod, facil_id, floor_id, proc_id, oper_frq,
+                    spec_nam, spec_ver, flow_id, setup_id, dsgn_rev, eng_id, rom_cod,
+                    serl_num, supr_nam);
+                shift!(self, mir, changed, setup_t, start_t);
+                V4::MIR(mir).to_bytes(endian)?
+            }
+            V4::MRR(mrr) => {
+                scrub!(self, mrr, changed, usr_desc, exc_desc);
+                shift!(self, mrr, changed, finish_t);
+                V4::MRR(mrr).to_bytes(endian)?
+            }
+            V4::SDR(sdr) => {
+                scrub!(self, sdr, changed,
+                    hand_typ, hand_id, card_typ, card_id, load_typ, load_id, dib_typ,
+                    dib_id, cabl_typ, cabl_id, cont_typ, cont_id, lasr_typ, lasr_id,
+                    extr_typ, extr_id);
+                V4::SDR(sdr).to_bytes(endian)?
+            }
+            V4::WIR(wir) => {
+                scrub!(self, wir, changed, wafer_id);
+                shift!(self, wir, changed, start_t);
+                V4::WIR(wir).to_bytes(endian)?
+            }
+            V4::WRR(wrr) => {
+                scrub!(self, wrr, changed, wafer_id, fabwf_id, frame_id, mask_id, usr_desc, exc_desc);
+                shift!(self, wrr, changed, finish_t);
+                V4::WRR(wrr).to_bytes(endian)?
+            }
+            V4::PRR(prr) => {
+                scrub!(self, prr, changed, part_id, part_txt);
+                V4::PRR(prr).to_bytes(endian)?
+            }
+            V4::DTR(dtr) => {
+                scrub!(self, dtr, changed, text_dat);
+                V4::DTR(dtr).to_bytes(endian)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(if changed { Some(bytes) } else { None })
+    }
+}
+
+/// Writes an anonymized copy of an STDF file.
+///
+/// Records without identifiers or timestamps are copied byte-exact, the others
+/// are re-serialized with their identifiers replaced by aliases.
+///
+/// # Returns
+///
+/// The number of records that were changed.
+///
+/// # Errors
+///
+/// This function will return an error if the input is not an STDF file, if a
+/// record can not be serialized, or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::Result;
+/// use stdf::anonymize::{anonymize_file, AnonymizeConfig, Anonymizer};
+///
+/// fn main() -> Result<()> {
+///     let mut input = File::open("tests/fixtures/test.std")?;
+///     let mut output = File::create("test_anon.std")?;
+///     let anonymizer = Anonymizer::new(AnonymizeConfig::default());
+///     let changed = anonymize_file(&mut input, &mut output, &anonymizer)?;
+///     println!("{} records anonymized", changed);
+///     Ok(())
+/// }
+/// ```
+pub fn anonymize_file<W: Write>(input: &mut File, output: &mut W, anonymizer: &Anonymizer) -> Result<u32> {
+    let endian = match get_endian_from_file(input)? {
+        Some(endian) => endian,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    };
+    let mmap = unsafe { MmapOptions::new().map(input)? };
+    let bytes = &mmap[..];
+    let offset = &mut 0;
+    let mut changed: u32 = 0;
+    loop {
+        let start = *offset;
+        let record = match bytes.read_with::<V4>(offset, endian) {
+            Ok(record) => record,
+            Err(_) => break,
+        };
+        match anonymizer.anonymize_record(record, endian) {
+            Ok(Some(anonymized)) => {
+                output.write_all(&anonymized)?;
+                changed += 1;
+            }
+            Ok(None) => output.write_all(&bytes[start..*offset])?,
+            Err(e) => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Can not write record at offset {} : {:?}", start, e)))
+            }
+        }
+    }
+    output.flush()?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom};
+    use tempfile::tempfile;
+
+    #[test]
+    fn test_alias_is_deterministic() {
+        let anonymizer = Anonymizer::new(AnonymizeConfig::default());
+        let alias = anonymizer.alias("lot_id", b"F6N910.1");
+        assert_eq!(alias, anonymizer.alias("lot_id", b"F6N910.1"));
+        assert_ne!(alias, anonymizer.alias("lot_id", b"F6N910.2"));
+        assert!(alias.starts_with("LOT_ID-"));
+        assert_eq!(alias.len(), "LOT_ID-".len() + 16);
+    }
+
+    #[test]
+    fn test_alias_depends_on_salt() {
+        let anonymizer = Anonymizer::new(AnonymizeConfig::default());
+        let salted = Anonymizer::new(AnonymizeConfig {
+            salt: "secret".to_string(),
+            ..AnonymizeConfig::default()
+        });
+        assert_ne!(anonymizer.alias("lot_id", b"F6N910.1"), salted.alias("lot_id", b"F6N910.1"));
+        // the split between the salt and the value is not ambiguous
+        let split = |salt: &str| {
+            Anonymizer::new(AnonymizeConfig {
+                salt: salt.to_string(),
+                ..AnonymizeConfig::default()
+            })
+        };
+        assert_ne!(split("ab").alias("lot_id", b"c"), split("a").alias("lot_id", b"bc"));
+    }
+
+    #[test]
+    fn test_scrub() {
+        let anonymizer = Anonymizer::new(AnonymizeConfig::default());
+        assert_eq!(anonymizer.scrub("lot_id", &Cn(b"")), b"");
+        assert_eq!(anonymizer.scrub("tstr_typ", &Cn(b"D-10")), b"D-10");
+        assert_ne!(anonymizer.scrub("lot_id", &Cn(b"F6N910.1")), b"F6N910.1");
+    }
+
+    #[test]
+    fn test_shift() {
+        let anonymizer = Anonymizer::new(AnonymizeConfig {
+            time_shift: -100,
+            ..AnonymizeConfig::default()
+        });
+        assert_eq!(anonymizer.shift(U4E(0)), U4E(0));
+        assert_eq!(anonymizer.shift(U4E(1000)), U4E(900));
+        assert_eq!(anonymizer.shift(U4E(50)), U4E(1));
+    }
+
+    #[test]
+    fn test_anonymize_file() {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let mut output = tempfile().unwrap();
+        let anonymizer = Anonymizer::new(AnonymizeConfig {
+            time_shift: 3600,
+            ..AnonymizeConfig::default()
+        });
+        let changed = anonymize_file(&mut input, &mut output, &anonymizer).unwrap();
+        assert!(changed > 0);
+
+        output.seek(SeekFrom::Start(0)).unwrap();
+        let endian = get_endian_from_file(&mut output).unwrap().unwrap();
+        let mmap = unsafe { MmapOptions::new().map(&output).unwrap() };
+        let bytes = &mmap[..];
+        let offset = &mut 0;
+        let mut record_count = 0;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            record_count += 1;
+            if let V4::MIR(mir) = record {
+                assert_eq!(mir.lot_id.0, anonymizer.alias("lot_id", b"F6N910.1").as_bytes());
+                assert_eq!(mir.tstr_typ, Cn(b"D-10"));
+                assert_eq!(mir.start_t, U4E(1526348699 + 3600));
+            }
+        }
+        assert_eq!(record_count, 918);
+        assert_eq!(*offset, bytes.len());
+    }
+}
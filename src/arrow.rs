@@ -0,0 +1,771 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Error, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, Float32Builder, Int16Builder, Int32Builder, Int8Builder, ListBuilder, StringBuilder,
+    UInt16Builder, UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use byte::BytesExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use crate::ftr::DecodedFtr;
+use crate::inheritance::{Limits, MprResolver, PtrResolver};
+use crate::parts::test_passed;
+use crate::pins::PinMap;
+use crate::records::{PRR, V4};
+use crate::table::{map, valid_result};
+
+/// The rows of a record batch, the batches of a table are written as they fill up.
+pub const BATCH_SIZE: usize = 65_536;
+
+/// The rows of a Parquet row group: large enough for the scans of DuckDB/pandas to be
+/// efficient, while the writer only buffers one (compressed) row group per table.
+pub const ROW_GROUP_SIZE: usize = 1_048_576;
+
+/// The tables of the Arrow/Parquet export.
+///
+/// The tables are joined on `part_index` (the parts, in the order their PIR was read)
+/// and on `rec`/`test_num` (the tests).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// One row per part (PRR).
+    Parts,
+    /// One row per PTR, the `result` is null if it is not a number or flagged invalid or not executed.
+    Ptr,
+    /// One row per pin of an MPR.
+    Mpr,
+    /// One row per FTR.
+    Ftr,
+    /// One row per test (PTR, MPR or FTR test number), with the limits of its first record.
+    Tests,
+}
+
+impl Table {
+    pub const ALL: [Table; 5] = [Table::Parts, Table::Ptr, Table::Mpr, Table::Ftr, Table::Tests];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Parts => "parts",
+            Table::Ptr => "ptr",
+            Table::Mpr => "mpr",
+            Table::Ftr => "ftr",
+            Table::Tests => "tests",
+        }
+    }
+
+    /// The schema of the table, columns are only ever added at the end.
+    pub fn schema(&self) -> SchemaRef {
+        let field = |name: &str, data_type: DataType, nullable: bool| Field::new(name, data_type, nullable);
+        let part = || {
+            vec![
+                field("part_index", DataType::UInt64, false),
+                field("test_num", DataType::UInt32, false),
+                field("head_num", DataType::UInt8, false),
+                field("site_num", DataType::UInt8, false),
+            ]
+        };
+        let limits = || {
+            vec![
+                field("lo_limit", DataType::Float32, true),
+                field("hi_limit", DataType::Float32, true),
+            ]
+        };
+        let fields = match self {
+            Table::Parts => vec![
+                field("part_index", DataType::UInt64, false),
+                field("lot_id", DataType::Utf8, false),
+                field("wafer_id", DataType::Utf8, true),
+                field("head_num", DataType::UInt8, false),
+                field("site_num", DataType::UInt8, false),
+                field("part_id", DataType::Utf8, false),
+                field("x_coord", DataType::Int16, true),
+                field("y_coord", DataType::Int16, true),
+                field("hard_bin", DataType::UInt16, false),
+                field("soft_bin", DataType::UInt16, true),
+                field("test_t", DataType::UInt32, true),
+                field("num_test", DataType::UInt16, false),
+                field("passed", DataType::Boolean, true),
+            ],
+            Table::Ptr => [
+                part(),
+                vec![
+                    field("result", DataType::Float32, true),
+                    field("passed", DataType::Boolean, true),
+                    field("test_flg", DataType::UInt8, false),
+                    field("parm_flg", DataType::UInt8, false),
+                ],
+                limits(),
+            ]
+            .concat(),
+            Table::Mpr => [
+                part(),
+                vec![
+                    field("pin_index", DataType::UInt16, true),
+                    field("pin_name", DataType::Utf8, false),
+                    field("result", DataType::Float32, true),
+                    field("rtn_stat", DataType::UInt8, true),
+                    field("passed", DataType::Boolean, true),
+                    field("test_flg", DataType::UInt8, false),
+                    field("parm_flg", DataType::UInt8, false),
+                ],
+                limits(),
+            ]
+            .concat(),
+            Table::Ftr => [
+                part(),
+                vec![
+                    field("passed", DataType::Boolean, true),
+                    field("test_flg", DataType::UInt8, false),
+                    field("vect_nam", DataType::Utf8, false),
+                    field("time_set", DataType::Utf8, false),
+                    field("cycl_cnt", DataType::UInt32, true),
+                    field("rel_vadr", DataType::UInt32, true),
+                    field("rept_cnt", DataType::UInt32, true),
+                    field("num_fail", DataType::UInt32, true),
+                    field("xfail_ad", DataType::Int32, true),
+                    field("yfail_ad", DataType::Int32, true),
+                    field("vect_off", DataType::Int16, true),
+                    field(
+                        "fail_pins",
+                        DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                        false,
+                    ),
+                ],
+            ]
+            .concat(),
+            Table::Tests => [
+                vec![
+                    field("rec", DataType::Utf8, false),
+                    field("test_num", DataType::UInt32, false),
+                    field("test_txt", DataType::Utf8, false),
+                    field("units", DataType::Utf8, false),
+                    field("res_scal", DataType::Int8, true),
+                    field("llm_scal", DataType::Int8, true),
+                    field("hlm_scal", DataType::Int8, true),
+                ],
+                limits(),
+                vec![
+                    field("lo_spec", DataType::Float32, true),
+                    field("hi_spec", DataType::Float32, true),
+                    field("c_resfmt", DataType::Utf8, false),
+                    field("c_llmfmt", DataType::Utf8, false),
+                    field("c_hlmfmt", DataType::Utf8, false),
+                ],
+            ]
+            .concat(),
+        };
+        Arc::new(Schema::new(fields))
+    }
+}
+
+/// The columns of the part, test number and head/site shared by the test tables.
+#[derive(Default)]
+struct TestKeys {
+    part_index: UInt64Builder,
+    test_num: UInt32Builder,
+    head_num: UInt8Builder,
+    site_num: UInt8Builder,
+}
+
+impl TestKeys {
+    fn append(&mut self, part_index: u64, test_num: u32, head_num: u8, site_num: u8) {
+        self.part_index.append_value(part_index);
+        self.test_num.append_value(test_num);
+        self.head_num.append_value(head_num);
+        self.site_num.append_value(site_num);
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(self.part_index.finish()),
+            Arc::new(self.test_num.finish()),
+            Arc::new(self.head_num.finish()),
+            Arc::new(self.site_num.finish()),
+        ]
+    }
+}
+
+#[derive(Default)]
+struct PartsBuilder {
+    rows: usize,
+    part_index: UInt64Builder,
+    lot_id: StringBuilder,
+    wafer_id: StringBuilder,
+    head_num: UInt8Builder,
+    site_num: UInt8Builder,
+    part_id: StringBuilder,
+    x_coord: Int16Builder,
+    y_coord: Int16Builder,
+    hard_bin: UInt16Builder,
+    soft_bin: UInt16Builder,
+    test_t: UInt32Builder,
+    num_test: UInt16Builder,
+    passed: BooleanBuilder,
+}
+
+impl PartsBuilder {
+    fn append(&mut self, part_index: u64, lot_id: &str, wafer_id: Option<&str>, prr: &PRR) {
+        self.rows += 1;
+        self.part_index.append_value(part_index);
+        self.lot_id.append_value(lot_id);
+        self.wafer_id.append_option(wafer_id);
+        self.head_num.append_value(prr.head_num.0);
+        self.site_num.append_value(prr.site_num.0);
+        self.part_id.append_value(prr.part_id.to_string().trim());
+        self.x_coord.append_option(Some(prr.x_coord.0).filter(|x| *x != i16::MIN));
+        self.y_coord.append_option(Some(prr.y_coord.0).filter(|y| *y != i16::MIN));
+        self.hard_bin.append_value(prr.hard_bin.0);
+        self.soft_bin.append_option(Some(prr.soft_bin.0).filter(|bin| *bin != 0xffff));
+        self.test_t.append_option(Some(prr.test_t.0).filter(|t| *t != 0));
+        self.num_test.append_value(prr.num_test.0);
+        self.passed.append_option(prr.part_flags().passed());
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        self.rows = 0;
+        vec![
+            Arc::new(self.part_index.finish()),
+            Arc::new(self.lot_id.finish()),
+            Arc::new(self.wafer_id.finish()),
+            Arc::new(self.head_num.finish()),
+            Arc::new(self.site_num.finish()),
+            Arc::new(self.part_id.finish()),
+            Arc::new(self.x_coord.finish()),
+            Arc::new(self.y_coord.finish()),
+            Arc::new(self.hard_bin.finish()),
+            Arc::new(self.soft_bin.finish()),
+            Arc::new(self.test_t.finish()),
+            Arc::new(self.num_test.finish()),
+            Arc::new(self.passed.finish()),
+        ]
+    }
+}
+
+#[derive(Default)]
+struct PtrBuilder {
+    rows: usize,
+    keys: TestKeys,
+    result: Float32Builder,
+    passed: BooleanBuilder,
+    test_flg: UInt8Builder,
+    parm_flg: UInt8Builder,
+    lo_limit: Float32Builder,
+    hi_limit: Float32Builder,
+}
+
+impl PtrBuilder {
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        self.rows = 0;
+        let mut columns = self.keys.finish();
+        columns.extend([
+            Arc::new(self.result.finish()) as ArrayRef,
+            Arc::new(self.passed.finish()),
+            Arc::new(self.test_flg.finish()),
+            Arc::new(self.parm_flg.finish()),
+            Arc::new(self.lo_limit.finish()),
+            Arc::new(self.hi_limit.finish()),
+        ]);
+        columns
+    }
+}
+
+#[derive(Default)]
+struct MprBuilder {
+    rows: usize,
+    keys: TestKeys,
+    pin_index: UInt16Builder,
+    pin_name: StringBuilder,
+    result: Float32Builder,
+    rtn_stat: UInt8Builder,
+    passed: BooleanBuilder,
+    test_flg: UInt8Builder,
+    parm_flg: UInt8Builder,
+    lo_limit: Float32Builder,
+    hi_limit: Float32Builder,
+}
+
+impl MprBuilder {
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        self.rows = 0;
+        let mut columns = self.keys.finish();
+        columns.extend([
+            Arc::new(self.pin_index.finish()) as ArrayRef,
+            Arc::new(self.pin_name.finish()),
+            Arc::new(self.result.finish()),
+            Arc::new(self.rtn_stat.finish()),
+            Arc::new(self.passed.finish()),
+            Arc::new(self.test_flg.finish()),
+            Arc::new(self.parm_flg.finish()),
+            Arc::new(self.lo_limit.finish()),
+            Arc::new(self.hi_limit.finish()),
+        ]);
+        columns
+    }
+}
+
+#[derive(Default)]
+struct FtrBuilder {
+    rows: usize,
+    keys: TestKeys,
+    passed: BooleanBuilder,
+    test_flg: UInt8Builder,
+    vect_nam: StringBuilder,
+    time_set: StringBuilder,
+    cycl_cnt: UInt32Builder,
+    rel_vadr: UInt32Builder,
+    rept_cnt: UInt32Builder,
+    num_fail: UInt32Builder,
+    xfail_ad: Int32Builder,
+    yfail_ad: Int32Builder,
+    vect_off: Int16Builder,
+    fail_pins: ListBuilder<StringBuilder>,
+}
+
+impl FtrBuilder {
+    fn append(&mut self, part_index: u64, ftr: &DecodedFtr, test_flg: u8) {
+        self.rows += 1;
+        self.keys.append(part_index, ftr.test_num, ftr.head_num, ftr.site_num);
+        self.passed.append_option(ftr.passed);
+        self.test_flg.append_value(test_flg);
+        self.vect_nam.append_value(&ftr.vect_nam);
+        self.time_set.append_value(&ftr.time_set);
+        self.cycl_cnt.append_option(ftr.cycl_cnt);
+        self.rel_vadr.append_option(ftr.rel_vadr);
+        self.rept_cnt.append_option(ftr.rept_cnt);
+        self.num_fail.append_option(ftr.num_fail);
+        self.xfail_ad.append_option(ftr.xfail_ad);
+        self.yfail_ad.append_option(ftr.yfail_ad);
+        self.vect_off.append_option(ftr.vect_off);
+        for pin in ftr.fail_pins.iter() {
+            self.fail_pins.values().append_value(&pin.name);
+        }
+        self.fail_pins.append(true);
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        self.rows = 0;
+        let mut columns = self.keys.finish();
+        columns.extend([
+            Arc::new(self.passed.finish()) as ArrayRef,
+            Arc::new(self.test_flg.finish()),
+            Arc::new(self.vect_nam.finish()),
+            Arc::new(self.time_set.finish()),
+            Arc::new(self.cycl_cnt.finish()),
+            Arc::new(self.rel_vadr.finish()),
+            Arc::new(self.rept_cnt.finish()),
+            Arc::new(self.num_fail.finish()),
+            Arc::new(self.xfail_ad.finish()),
+            Arc::new(self.yfail_ad.finish()),
+            Arc::new(self.vect_off.finish()),
+            Arc::new(self.fail_pins.finish()),
+        ]);
+        columns
+    }
+}
+
+fn tests_batch(tests: &[(&'static str, u32, String, Limits)]) -> Vec<ArrayRef> {
+    let mut rec = StringBuilder::new();
+    let mut test_num = UInt32Builder::new();
+    let mut test_txt = StringBuilder::new();
+    let mut units = StringBuilder::new();
+    let (mut res_scal, mut llm_scal, mut hlm_scal) = (Int8Builder::new(), Int8Builder::new(), Int8Builder::new());
+    let (mut lo_limit, mut hi_limit) = (Float32Builder::new(), Float32Builder::new());
+    let (mut lo_spec, mut hi_spec) = (Float32Builder::new(), Float32Builder::new());
+    let (mut c_resfmt, mut c_llmfmt, mut c_hlmfmt) = (StringBuilder::new(), StringBuilder::new(), StringBuilder::new());
+    for (name, number, text, limits) in tests.iter() {
+        rec.append_value(name);
+        test_num.append_value(*number);
+        test_txt.append_value(text);
+        units.append_value(&limits.units);
+        res_scal.append_option(limits.res_scal);
+        llm_scal.append_option(limits.llm_scal);
+        hlm_scal.append_option(limits.hlm_scal);
+        lo_limit.append_option(limits.lo_limit);
+        hi_limit.append_option(limits.hi_limit);
+        lo_spec.append_option(limits.lo_spec);
+        hi_spec.append_option(limits.hi_spec);
+        c_resfmt.append_value(&limits.c_resfmt);
+        c_llmfmt.append_value(&limits.c_llmfmt);
+        c_hlmfmt.append_value(&limits.c_hlmfmt);
+    }
+    vec![
+        Arc::new(rec.finish()),
+        Arc::new(test_num.finish()),
+        Arc::new(test_txt.finish()),
+        Arc::new(units.finish()),
+        Arc::new(res_scal.finish()),
+        Arc::new(llm_scal.finish()),
+        Arc::new(hlm_scal.finish()),
+        Arc::new(lo_limit.finish()),
+        Arc::new(hi_limit.finish()),
+        Arc::new(lo_spec.finish()),
+        Arc::new(hi_spec.finish()),
+        Arc::new(c_resfmt.finish()),
+        Arc::new(c_llmfmt.finish()),
+        Arc::new(c_hlmfmt.finish()),
+    ]
+}
+
+/// Turns the records of an STDF file into Arrow record batches.
+///
+/// The test records are resolved in file order (see [`PtrResolver`] and [`MprResolver`])
+/// and added to the part that is being tested on their head/site, test records outside
+/// of a part (no PIR) are dropped. A batch is returned as soon as it has `batch_size`
+/// rows, so memory does not grow with the size of the file. The `tests` table is only
+/// returned by [`RecordBatches::finish`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use byte::BytesExt;
+/// use memmap::MmapOptions;
+/// use stdf::arrow::{RecordBatches, BATCH_SIZE};
+/// use stdf::get_endian_from_file;
+/// use stdf::records::V4;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let endian = get_endian_from_file(&mut file).unwrap().unwrap();
+/// let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+/// let bytes = &mmap[..];
+/// let mut batches = RecordBatches::new(BATCH_SIZE);
+/// let offset = &mut 0;
+/// while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+///     for (table, batch) in batches.push(record).unwrap() {
+///         println!("{} : {} rows", table.name(), batch.num_rows());
+///     }
+/// }
+/// for (table, batch) in batches.finish().unwrap() {
+///     println!("{} : {} rows", table.name(), batch.num_rows());
+/// }
+/// ```
+pub struct RecordBatches {
+    batch_size: usize,
+    schemas: HashMap<Table, SchemaRef>,
+    parts: PartsBuilder,
+    ptr: PtrBuilder,
+    mpr: MprBuilder,
+    ftr: FtrBuilder,
+    tests: Vec<(&'static str, u32, String, Limits)>,
+    test_index: HashMap<(&'static str, u32), usize>,
+    ptr_resolver: PtrResolver,
+    mpr_resolver: MprResolver,
+    pin_map: PinMap,
+    lot_id: String,
+    wafer_id: Option<String>,
+    open: HashMap<(u8, u8), u64>,
+    next_part: u64,
+}
+
+impl RecordBatches {
+    pub fn new(batch_size: usize) -> Self {
+        RecordBatches {
+            batch_size: batch_size.max(1),
+            schemas: Table::ALL.iter().map(|table| (*table, table.schema())).collect(),
+            parts: PartsBuilder::default(),
+            ptr: PtrBuilder::default(),
+            mpr: MprBuilder::default(),
+            ftr: FtrBuilder::default(),
+            tests: Vec::new(),
+            test_index: HashMap::new(),
+            ptr_resolver: PtrResolver::new(),
+            mpr_resolver: MprResolver::new(),
+            pin_map: PinMap::new(),
+            lot_id: String::new(),
+            wafer_id: None,
+            open: HashMap::new(),
+            next_part: 0,
+        }
+    }
+
+    fn add_test(&mut self, rec: &'static str, test_num: u32, test_txt: String, limits: &Limits) {
+        if !self.test_index.contains_key(&(rec, test_num)) {
+            self.test_index.insert((rec, test_num), self.tests.len());
+            self.tests.push((rec, test_num, test_txt, limits.clone()));
+        }
+    }
+
+    fn batch(&self, table: Table, columns: Vec<ArrayRef>) -> Result<(Table, RecordBatch)> {
+        let batch = RecordBatch::try_new(self.schemas[&table].clone(), columns).map_err(Error::other)?;
+        Ok((table, batch))
+    }
+
+    /// Feeds the next record, returns the batches that are full.
+    ///
+    /// # Errors
+    ///
+    /// This function only returns an error if a batch does not match its schema.
+    pub fn push(&mut self, record: V4) -> Result<Vec<(Table, RecordBatch)>> {
+        match record {
+            V4::MIR(mir) => self.lot_id = mir.lot_id.to_string().trim().to_string(),
+            V4::WIR(wir) => self.wafer_id = Some(wir.wafer_id.to_string().trim().to_string()),
+            V4::PMR(pmr) => self.pin_map.add_pmr(&pmr),
+            V4::PGR(pgr) => self.pin_map.add_pgr(&pgr),
+            V4::PLR(plr) => self.pin_map.add_plr(&plr),
+            V4::PIR(pir) => {
+                self.open.insert((pir.head_num.0, pir.site_num.0), self.next_part);
+                self.next_part += 1;
+            }
+            V4::PRR(prr) => {
+                let part_index = match self.open.remove(&(prr.head_num.0, prr.site_num.0)) {
+                    Some(part_index) => part_index,
+                    None => {
+                        self.next_part += 1;
+                        self.next_part - 1
+                    }
+                };
+                self.parts.append(part_index, &self.lot_id, self.wafer_id.as_deref(), &prr);
+            }
+            V4::PTR(ptr) => {
+                let limits = self.ptr_resolver.resolve(&ptr);
+                self.add_test("PTR", ptr.test_num.0, ptr.test_txt.to_string(), &limits);
+                if let Some(part_index) = self.open.get(&(ptr.head_num.0, ptr.site_num.0)) {
+                    let builder = &mut self.ptr;
+                    builder.rows += 1;
+                    builder.keys.append(*part_index, ptr.test_num.0, ptr.head_num.0, ptr.site_num.0);
+                    builder.result.append_option(valid_result(Some(ptr.result.0), ptr.test_flg.0));
+                    builder.passed.append_option(test_passed(ptr.test_flg.0));
+                    builder.test_flg.append_value(ptr.test_flg.0);
+                    builder.parm_flg.append_value(ptr.parm_flg.0);
+                    builder.lo_limit.append_option(limits.lo_limit);
+                    builder.hi_limit.append_option(limits.hi_limit);
+                }
+            }
+            V4::MPR(mpr) => {
+                let resolved = self.mpr_resolver.resolve(&mpr, &self.pin_map);
+                self.add_test("MPR", mpr.test_num.0, mpr.test_txt.to_string(), &resolved.limits);
+                if let Some(part_index) = self.open.get(&(mpr.head_num.0, mpr.site_num.0)) {
+                    let builder = &mut self.mpr;
+                    for pin in resolved.pins.iter() {
+                        builder.rows += 1;
+                        builder.keys.append(*part_index, mpr.test_num.0, mpr.head_num.0, mpr.site_num.0);
+                        builder.pin_index.append_option(pin.pin_index);
+                        builder.pin_name.append_value(&pin.pin_name);
+                        builder.result.append_option(valid_result(pin.result, mpr.test_flg.0));
+                        builder.rtn_stat.append_option(pin.status);
+                        builder.passed.append_option(test_passed(mpr.test_flg.0));
+                        builder.test_flg.append_value(mpr.test_flg.0);
+                        builder.parm_flg.append_value(mpr.parm_flg.0);
+                        builder.lo_limit.append_option(resolved.limits.lo_limit);
+                        builder.hi_limit.append_option(resolved.limits.hi_limit);
+                    }
+                }
+            }
+            V4::FTR(ftr) => {
+                self.add_test("FTR", ftr.test_num.0, ftr.test_txt.to_string(), &Limits::default());
+                if let Some(part_index) = self.open.get(&(ftr.head_num.0, ftr.site_num.0)) {
+                    let decoded = DecodedFtr::new(&ftr, &self.pin_map);
+                    self.ftr.append(*part_index, &decoded, ftr.test_flg.0);
+                }
+            }
+            _ => {}
+        }
+        self.take(false)
+    }
+
+    /// The batches with `batch_size` rows, or with any rows when `all`.
+    fn take(&mut self, all: bool) -> Result<Vec<(Table, RecordBatch)>> {
+        let full = |rows: usize| rows > 0 && (all || rows >= self.batch_size);
+        let mut batches = Vec::new();
+        if full(self.parts.rows) {
+            let columns = self.parts.finish();
+            batches.push(self.batch(Table::Parts, columns)?);
+        }
+        if full(self.ptr.rows) {
+            let columns = self.ptr.finish();
+            batches.push(self.batch(Table::Ptr, columns)?);
+        }
+        if full(self.mpr.rows) {
+            let columns = self.mpr.finish();
+            batches.push(self.batch(Table::Mpr, columns)?);
+        }
+        if full(self.ftr.rows) {
+            let columns = self.ftr.finish();
+            batches.push(self.batch(Table::Ftr, columns)?);
+        }
+        Ok(batches)
+    }
+
+    /// Returns the rows that are left and the `tests` table.
+    ///
+    /// # Errors
+    ///
+    /// This function only returns an error if a batch does not match its schema.
+    pub fn finish(mut self) -> Result<Vec<(Table, RecordBatch)>> {
+        let mut batches = self.take(true)?;
+        batches.push(self.batch(Table::Tests, tests_batch(&self.tests))?);
+        Ok(batches)
+    }
+}
+
+/// Writes the tables of an STDF file as Parquet files (`parts.parquet`, `ptr.parquet`,
+/// `mpr.parquet`, `ftr.parquet` and `tests.parquet`) into the directory `dir`.
+///
+/// The files are zstd compressed, with row groups of [`ROW_GROUP_SIZE`] rows.
+/// `progress` is called after each batch with the offset read up to in the file.
+///
+/// # Returns
+///
+/// The number of rows written per table, in the order of [`Table::ALL`].
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file, if the directory
+/// can not be created or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::path::Path;
+/// use stdf::arrow::write_parquet;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// for (table, rows) in write_parquet(&mut file, Path::new("test_parquet"), |_| {}).unwrap() {
+///     println!("{} : {} rows", table.name(), rows);
+/// }
+/// ```
+pub fn write_parquet<F: FnMut(usize)>(file: &mut File, dir: &Path, mut progress: F) -> Result<Vec<(Table, usize)>> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+    fs::create_dir_all(dir)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_max_row_group_size(ROW_GROUP_SIZE)
+        .build();
+    let mut writers: HashMap<Table, ArrowWriter<File>> = HashMap::new();
+    let mut rows: HashMap<Table, usize> = HashMap::new();
+    for table in Table::ALL {
+        let output = File::create(dir.join(format!("{}.parquet", table.name())))?;
+        let writer = ArrowWriter::try_new(output, table.schema(), Some(props.clone())).map_err(Error::other)?;
+        writers.insert(table, writer);
+    }
+    let mut write = |batches: Vec<(Table, RecordBatch)>| -> Result<()> {
+        for (table, batch) in batches {
+            *rows.entry(table).or_default() += batch.num_rows();
+            writers.get_mut(&table).unwrap().write(&batch).map_err(Error::other)?;
+        }
+        Ok(())
+    };
+
+    let mut batches = RecordBatches::new(BATCH_SIZE);
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        let full = batches.push(record)?;
+        if !full.is_empty() {
+            write(full)?;
+            progress(*offset);
+        }
+    }
+    write(batches.finish()?)?;
+    progress(*offset);
+    for table in Table::ALL {
+        writers.remove(&table).unwrap().close().map_err(Error::other)?;
+    }
+    Ok(Table::ALL.iter().map(|table| (*table, rows.get(table).copied().unwrap_or(0))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{B1, R4};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_schemas() {
+        for table in Table::ALL {
+            let schema = table.schema();
+            assert!(!schema.fields().is_empty());
+            // a batch of no rows matches the schema
+            let mut batches = RecordBatches::new(1);
+            let columns = match table {
+                Table::Parts => batches.parts.finish(),
+                Table::Ptr => batches.ptr.finish(),
+                Table::Mpr => batches.mpr.finish(),
+                Table::Ftr => batches.ftr.finish(),
+                Table::Tests => tests_batch(&[]),
+            };
+            assert!(batches.batch(table, columns).is_ok(), "{}", table.name());
+        }
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let rows = write_parquet(&mut file, dir.path(), |_| {}).unwrap();
+        let rows: HashMap<Table, usize> = rows.into_iter().collect();
+        assert_eq!(rows[&Table::Parts], 22);
+        assert_eq!(rows[&Table::Ptr], 572);
+        assert_eq!(rows[&Table::Mpr], 0);
+        assert_eq!(rows[&Table::Ftr], 0);
+        assert!(rows[&Table::Tests] > 0);
+
+        for table in Table::ALL {
+            let file = File::open(dir.path().join(format!("{}.parquet", table.name()))).unwrap();
+            let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+            assert_eq!(reader.schema().fields(), table.schema().fields());
+            let read: usize = reader.build().unwrap().map(|batch| batch.unwrap().num_rows()).sum();
+            assert_eq!(read, rows[&table]);
+        }
+    }
+
+    #[test]
+    fn test_batch_size() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let (mmap, endian) = map(&mut file).unwrap();
+        let bytes = &mmap[..];
+        let mut batches = RecordBatches::new(100);
+        let mut ptr_batches = Vec::new();
+        let offset = &mut 0;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            for (table, batch) in batches.push(record).unwrap() {
+                if table == Table::Ptr {
+                    ptr_batches.push(batch.num_rows());
+                }
+            }
+        }
+        let last = batches.finish().unwrap();
+        assert!(ptr_batches.iter().all(|rows| *rows == 100));
+        assert_eq!(ptr_batches.len(), 5);
+        assert!(last.iter().any(|(table, batch)| *table == Table::Ptr && batch.num_rows() == 72));
+        assert_eq!(last.last().unwrap().0, Table::Tests);
+    }
+
+    #[test]
+    fn test_invalid_results() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let (mmap, endian) = map(&mut file).unwrap();
+        let bytes = &mmap[..];
+        let mut batches = RecordBatches::new(BATCH_SIZE);
+        let mut ptrs = 0;
+        let offset = &mut 0;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            let record = match record {
+                // a NaN result, then a result flagged invalid (TEST_FLG bit 1)
+                V4::PTR(mut ptr) if ptrs < 2 => {
+                    match ptrs {
+                        0 => ptr.result = R4(f32::NAN),
+                        _ => ptr.test_flg = B1(0b0000_0010),
+                    }
+                    ptrs += 1;
+                    V4::PTR(ptr)
+                }
+                record => record,
+            };
+            assert!(batches.push(record).unwrap().is_empty());
+        }
+        let batches = batches.finish().unwrap();
+        let (_, ptr) = batches.iter().find(|(table, _)| *table == Table::Ptr).unwrap();
+        let result = ptr.column_by_name("result").unwrap();
+        assert_eq!(result.null_count(), 2);
+        assert!(result.is_null(0) && result.is_null(1) && result.is_valid(2));
+    }
+}
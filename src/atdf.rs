@@ -0,0 +1,2046 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+use std::str::FromStr;
+
+use byte::{BytesExt, LE};
+use memmap::MmapOptions;
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+use crate::flags::{ParmFlag, TestFlag};
+use crate::get_endian_from_file;
+use crate::records::*;
+use crate::types::*;
+use crate::units::split_unit;
+
+/// The field separator, ATDF files may use another one (the 6th character of the FAR).
+pub const SEPARATOR: char = '|';
+
+/// The maximum length of a line, longer records are continued on the next line.
+pub const LINE_WIDTH: usize = 80;
+
+/// The ATDF header, an ATDF version 2 file for STDF V4 with scaled data.
+pub const FAR_RECORD: &str = "FAR:A|4|2|S";
+
+/// The Alarm Flags letters, if they are a PARM_FLG bit (or a TEST_FLG one) and the bit.
+const ALARMS: [(char, bool, u8); 10] = [
+    ('A', false, 0),
+    ('D', true, 1),
+    ('H', true, 3),
+    ('L', true, 4),
+    ('N', false, 4),
+    ('O', true, 2),
+    ('S', true, 0),
+    ('T', false, 3),
+    ('U', false, 2),
+    ('X', false, 5),
+];
+
+/// The site of the summary records (head 255), ATDF leaves it out.
+const SUMMARY_SITE: u8 = 0;
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+/// Formats a timestamp as ATDF does, eg: `8:14:59 23-JUL-1992` (UTC), empty if it is not set.
+///
+/// # Examples
+///
+/// ```
+/// use stdf::atdf::format_time;
+/// use stdf::types::U4E;
+///
+/// assert_eq!(format_time(U4E(711879299)), "8:14:59 23-JUL-1992");
+/// assert_eq!(format_time(U4E(0)), "");
+/// ```
+pub fn format_time(time: U4E) -> String {
+    match time.to_datetime() {
+        Some(datetime) => format!(
+            "{}:{:02}:{:02} {}-{}-{}",
+            datetime.hour(),
+            datetime.minute(),
+            datetime.second(),
+            datetime.day(),
+            MONTHS[datetime.month() as usize - 1],
+            datetime.year()
+        ),
+        None => String::new(),
+    }
+}
+
+/// Splits a record into lines of at most `width` characters, continuation lines start with a space.
+///
+/// Lines are not broken after a space, as trailing spaces may not survive an editor.
+pub fn wrap(record: &str, width: usize) -> String {
+    let chars: Vec<char> = record.chars().collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut start = 0;
+    let mut limit = width.max(2);
+    while chars.len() - start > limit {
+        let mut end = start + limit;
+        while end > start + 1 && chars[end - 1] == ' ' {
+            end -= 1;
+        }
+        if chars[end - 1] == ' ' {
+            end = start + limit;
+        }
+        lines.push(chars[start..end].iter().collect());
+        start = end;
+        limit = width.max(2) - 1;
+    }
+    lines.push(chars[start..].iter().collect());
+    lines.join("\n ")
+}
+
+/// Joins the fields of a record, leaving out the trailing empty (optional) fields.
+fn record(name: &str, mut fields: Vec<String>) -> String {
+    while fields.last().is_some_and(|field| field.is_empty()) {
+        fields.pop();
+    }
+    format!("{}:{}", name, fields.join(&SEPARATOR.to_string()))
+}
+
+/// A text field, with the separator escaped as `\|`.
+///
+/// Backslashes are only doubled where they would otherwise escape a separator, so
+/// text like `C:\temp\` reads back the same, and line breaks become spaces.
+fn text(cn: &Cn) -> String {
+    let chars: Vec<char> = String::from_utf8_lossy(cn.0)
+        .chars()
+        .map(|c| if c == '\r' || c == '\n' { ' ' } else { c })
+        .collect();
+    let mut escaped = String::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            let run = chars[i..].iter().take_while(|c| **c == '\\').count();
+            i += run;
+            let escapes_separator = i == chars.len() || chars[i] == SEPARATOR;
+            escaped.push_str(&"\\".repeat(if escapes_separator { 2 * run } else { run }));
+            continue;
+        }
+        if chars[i] == SEPARATOR {
+            escaped.push('\\');
+        }
+        escaped.push(chars[i]);
+        i += 1;
+    }
+    escaped
+}
+
+/// A single character field, empty for a space (the STDF missing value).
+fn character(c: C1) -> String {
+    match c.0 {
+        b' ' | 0 => String::new(),
+        c => (c as char).to_string(),
+    }
+}
+
+/// A number field, empty when it has the STDF missing value.
+fn number<T: PartialEq + ToString>(value: T, missing: T) -> String {
+    if value == missing {
+        String::new()
+    } else {
+        value.to_string()
+    }
+}
+
+/// A floating point field, empty for NaN, in E notation when very large or small.
+fn float(value: f32) -> String {
+    let magnitude = value.abs();
+    if value.is_nan() {
+        String::new()
+    } else if magnitude != 0.0 && magnitude.is_finite() && !(1e-4..1e7).contains(&magnitude) {
+        format!("{:E}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn double(value: f64) -> String {
+    let magnitude = value.abs();
+    if value.is_nan() {
+        String::new()
+    } else if magnitude != 0.0 && magnitude.is_finite() && !(1e-4..1e7).contains(&magnitude) {
+        format!("{:E}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn list<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/// An array of returned or programmed states, as hexadecimal digits.
+fn states(values: &[N1]) -> String {
+    values.iter().map(|value| format!("{:X}", value.0 & 0x0f)).collect::<Vec<String>>().join(",")
+}
+
+/// The PMR indexes of the bits that are set.
+fn bit_list(dn: &Dn) -> String {
+    dn.set_bits().map(|bit| bit.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/// The head and site fields, both empty for a summary over all sites (head 255).
+fn head_site(head: U1, site: U1) -> [String; 2] {
+    if head.0 == 255 {
+        [String::new(), String::new()]
+    } else {
+        [head.0.to_string(), site.0.to_string()]
+    }
+}
+
+/// A limit or spec limit (and its scale), empty when flagged as invalid or absent.
+fn limit(value: R4, invalid: bool) -> String {
+    if invalid {
+        String::new()
+    } else {
+        float(value.0)
+    }
+}
+
+fn scale(value: I1, invalid: bool) -> String {
+    if invalid || value.0 == i8::MIN {
+        String::new()
+    } else {
+        value.0.to_string()
+    }
+}
+
+/// The Pass/Fail Flag of a PTR, MPR or FTR.
+fn pass_fail(test: TestFlag, parm: Option<ParmFlag>) -> String {
+    let flag = if test.pass_fail_invalid() {
+        ""
+    } else if test.failed() {
+        "F"
+    } else if parm.is_some_and(|parm| parm.passed_alternate_limits()) {
+        "A"
+    } else {
+        "P"
+    };
+    flag.to_string()
+}
+
+/// The Alarm Flags of a PTR, MPR or FTR (an FTR has no PARM_FLG).
+fn alarms(test: TestFlag, parm: Option<ParmFlag>) -> String {
+    let parm = parm.unwrap_or_default();
+    [
+        (test.alarm(), 'A'),
+        (parm.drift_error(), 'D'),
+        (parm.above_hi_limit(), 'H'),
+        (parm.below_lo_limit(), 'L'),
+        (test.test_not_executed(), 'N'),
+        (parm.oscillation(), 'O'),
+        (parm.scale_error(), 'S'),
+        (test.timeout(), 'T'),
+        (test.result_unreliable(), 'U'),
+        (test.test_aborted(), 'X'),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, flag)| *flag)
+    .collect()
+}
+
+/// The Limit Compare field, `L` and/or `H` for inclusive limits.
+fn limit_compare(parm: ParmFlag) -> String {
+    let mut compare = String::new();
+    if parm.lo_limit_inclusive() {
+        compare.push('L');
+    }
+    if parm.hi_limit_inclusive() {
+        compare.push('H');
+    }
+    compare
+}
+
+/// A GRP_RADX as its ATDF letter, empty for the default radix.
+fn radix(value: U1) -> String {
+    match value.0 {
+        0 => String::new(),
+        2 => "B".to_string(),
+        8 => "O".to_string(),
+        10 => "D".to_string(),
+        16 => "H".to_string(),
+        20 => "S".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// The state characters of a pin list, one (CHAR) or two (CHAL and CHAR) per state,
+/// comma separated per group and `/` between groups.
+fn state_chars(chal: &[Cn], chars: &[Cn]) -> String {
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let left = chal.get(i).map(|cn| cn.0).unwrap_or_default();
+            group
+                .0
+                .iter()
+                .enumerate()
+                .map(|(j, c)| match left.get(j) {
+                    Some(l) if *l != b' ' => format!("{}{}", *l as char, *c as char),
+                    _ => (*c as char).to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+impl Atdf for FAR {
+    fn to_atdf(&self) -> String {
+        FAR_RECORD.to_string()
+    }
+}
+
+impl Atdf for ATR<'_> {
+    fn to_atdf(&self) -> String {
+        record("ATR", vec![format_time(self.mod_tim), text(&self.cmd_line)])
+    }
+}
+
+impl Atdf for MIR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "MIR",
+            vec![
+                text(&self.lot_id),
+                text(&self.part_typ),
+                text(&self.job_nam),
+                text(&self.node_nam),
+                text(&self.tstr_typ),
+                format_time(self.setup_t),
+                format_time(self.start_t),
+                text(&self.oper_nam),
+                character(self.mode_cod),
+                self.stat_num.0.to_string(),
+                text(&self.sblot_id),
+                text(&self.test_cod),
+                character(self.rtst_cod),
+                text(&self.job_rev),
+                text(&self.exec_typ),
+                text(&self.exec_ver),
+                character(self.prot_cod),
+                character(self.cmod_cod),
+                number(self.burn_tim.0, u16::MAX),
+                text(&self.tst_temp),
+                text(&self.user_txt),
+                text(&self.aux_file),
+                text(&self.pkg_typ),
+                text(&self.famly_id),
+                text(&self.date_cod),
+                text(&self.facil_id),
+                text(&self.floor_id),
+                text(&self.proc_id),
+                text(&self.oper_frq),
+                text(&self.spec_nam),
+                text(&self.spec_ver),
+                text(&self.flow_id),
+                text(&self.setup_id),
+                text(&self.dsgn_rev),
+                text(&self.eng_id),
+                text(&self.rom_cod),
+                text(&self.serl_num),
+                text(&self.supr_nam),
+            ],
+        )
+    }
+}
+
+impl Atdf for MRR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "MRR",
+            vec![
+                format_time(self.finish_t),
+                character(self.disp_cod),
+                text(&self.usr_desc),
+                text(&self.exc_desc),
+            ],
+        )
+    }
+}
+
+impl Atdf for PCR {
+    fn to_atdf(&self) -> String {
+        let [head, site] = head_site(self.head_num, self.site_num);
+        record(
+            "PCR",
+            vec![
+                head,
+                site,
+                self.part_cnt.0.to_string(),
+                number(self.rtst_cnt.0, u32::MAX),
+                number(self.abrt_cnt.0, u32::MAX),
+                number(self.good_cnt.0, u32::MAX),
+                number(self.func_cnt.0, u32::MAX),
+            ],
+        )
+    }
+}
+
+impl Atdf for HBR<'_> {
+    fn to_atdf(&self) -> String {
+        let [head, site] = head_site(self.head_num, self.site_num);
+        record(
+            "HBR",
+            vec![
+                head,
+                site,
+                self.hbin_num.0.to_string(),
+                self.hbin_cnt.0.to_string(),
+                character(self.hbin_pf),
+                text(&self.hbin_nam),
+            ],
+        )
+    }
+}
+
+impl Atdf for SBR<'_> {
+    fn to_atdf(&self) -> String {
+        let [head, site] = head_site(self.head_num, self.site_num);
+        record(
+            "SBR",
+            vec![
+                head,
+                site,
+                self.sbin_num.0.to_string(),
+                self.sbin_cnt.0.to_string(),
+                character(self.sbin_pf),
+                text(&self.sbin_nam),
+            ],
+        )
+    }
+}
+
+impl Atdf for PMR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "PMR",
+            vec![
+                self.pmr_index.0.to_string(),
+                self.chan_typ.0.to_string(),
+                text(&self.chan_nam),
+                text(&self.phy_nam),
+                text(&self.log_nam),
+                self.head_num.0.to_string(),
+                self.site_num.0.to_string(),
+            ],
+        )
+    }
+}
+
+impl Atdf for PGR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "PGR",
+            vec![self.grp_indx.0.to_string(), text(&self.grp_nam), list(&self.pmr_indx)],
+        )
+    }
+}
+
+impl Atdf for PLR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "PLR",
+            vec![
+                list(&self.grp_indx),
+                // written as the numbers `PinList::mode_name` knows (eg: 20 for SCIO)
+                list(&self.grp_mode),
+                self.grp_radx.iter().map(|value| radix(*value)).collect::<Vec<String>>().join(","),
+                state_chars(&self.pgm_chal, &self.pgm_char),
+                state_chars(&self.rtn_chal, &self.rtn_char),
+            ],
+        )
+    }
+}
+
+impl Atdf for RDR {
+    fn to_atdf(&self) -> String {
+        record("RDR", vec![list(&self.rtst_bin)])
+    }
+}
+
+impl Atdf for SDR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "SDR",
+            vec![
+                self.head_num.0.to_string(),
+                self.site_grp.0.to_string(),
+                list(&self.site_num),
+                text(&self.hand_typ),
+                text(&self.hand_id),
+                text(&self.card_typ),
+                text(&self.card_id),
+                text(&self.load_typ),
+                text(&self.load_id),
+                text(&self.dib_typ),
+                text(&self.dib_id),
+                text(&self.cabl_typ),
+                text(&self.cabl_id),
+                text(&self.cont_typ),
+                text(&self.cont_id),
+                text(&self.lasr_typ),
+                text(&self.lasr_id),
+                text(&self.extr_typ),
+                text(&self.extr_id),
+            ],
+        )
+    }
+}
+
+impl Atdf for WIR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "WIR",
+            vec![
+                self.head_num.0.to_string(),
+                format_time(self.start_t),
+                number(self.site_grp.0, u8::MAX),
+                text(&self.wafer_id),
+            ],
+        )
+    }
+}
+
+impl Atdf for WRR<'_> {
+    fn to_atdf(&self) -> String {
+        record(
+            "WRR",
+            vec![
+                self.head_num.0.to_string(),
+                format_time(self.finish_t),
+                self.part_cnt.0.to_string(),
+                text(&self.wafer_id),
+                number(self.site_grp.0, u8::MAX),
+                number(self.rtst_cnt.0, u32::MAX),
+                number(self.abrt_cnt.0, u32::MAX),
+                number(self.good_cnt.0, u32::MAX),
+                number(self.func_cnt.0, u32::MAX),
+                text(&self.fabwf_id),
+                text(&self.frame_id),
+                text(&self.mask_id),
+                text(&self.usr_desc),
+                text(&self.exc_desc),
+            ],
+        )
+    }
+}
+
+impl Atdf for WCR {
+    fn to_atdf(&self) -> String {
+        record(
+            "WCR",
+            vec![
+                character(self.wf_flat),
+                character(self.pos_x),
+                character(self.pos_y),
+                limit(self.wafr_siz, self.wafr_siz.0 == 0.0),
+                limit(self.die_ht, self.die_ht.0 == 0.0),
+                limit(self.die_wid, self.die_wid.0 == 0.0),
+                number(self.wf_units.0, 0),
+                number(self.center_x.0, i16::MIN),
+                number(self.center_y.0, i16::MIN),
+            ],
+        )
+    }
+}
+
+impl Atdf for PIR {
+    fn to_atdf(&self) -> String {
+        record("PIR", vec![self.head_num.0.to_string(), self.site_num.0.to_string()])
+    }
+}
+
+impl Atdf for PRR<'_> {
+    fn to_atdf(&self) -> String {
+        let flags = self.part_flags();
+        let pass_fail = match flags.passed() {
+            Some(true) => "P",
+            Some(false) => "F",
+            None => "",
+        };
+        let mut retest = String::new();
+        if flags.is_retest_by_id() {
+            retest.push('I');
+        }
+        if flags.is_retest_by_xy() {
+            retest.push('C');
+        }
+        record(
+            "PRR",
+            vec![
+                self.head_num.0.to_string(),
+                self.site_num.0.to_string(),
+                text(&self.part_id),
+                self.num_test.0.to_string(),
+                pass_fail.to_string(),
+                self.hard_bin.0.to_string(),
+                number(self.soft_bin.0, u16::MAX),
+                number(self.x_coord.0, i16::MIN),
+                number(self.y_coord.0, i16::MIN),
+                retest,
+                if flags.abnormal_end() { "Y" } else { "" }.to_string(),
+                number(self.test_t.0, 0),
+                text(&self.part_txt),
+                self.part_fix.to_string(),
+            ],
+        )
+    }
+}
+
+impl Atdf for TSR<'_> {
+    fn to_atdf(&self) -> String {
+        let [head, site] = head_site(self.head_num, self.site_num);
+        let opt_flag = self.opt_flag.0;
+        record(
+            "TSR",
+            vec![
+                head,
+                site,
+                self.test_num.0.to_string(),
+                text(&self.test_nam),
+                character(self.test_typ),
+                number(self.exec_cnt.0, u32::MAX),
+                number(self.fail_cnt.0, u32::MAX),
+                number(self.alrm_cnt.0, u32::MAX),
+                text(&self.seq_name),
+                text(&self.test_lbl),
+                limit(self.test_tim, opt_flag & 0b0000_0100 != 0),
+                limit(self.test_min, opt_flag & 0b0000_0001 != 0),
+                limit(self.test_max, opt_flag & 0b0000_0010 != 0),
+                limit(self.tst_sums, opt_flag & 0b0001_0000 != 0),
+                limit(self.tst_sqrs, opt_flag & 0b0010_0000 != 0),
+            ],
+        )
+    }
+}
+
+impl Atdf for PTR<'_> {
+    fn to_atdf(&self) -> String {
+        let test = self.test_flags();
+        let parm = self.parm_flags();
+        let opt = self.opt_flags();
+        let no_lo = opt.lo_limit_invalid() || opt.no_lo_limit();
+        let no_hi = opt.hi_limit_invalid() || opt.no_hi_limit();
+        record(
+            "PTR",
+            vec![
+                self.test_num.0.to_string(),
+                self.head_num.0.to_string(),
+                self.site_num.0.to_string(),
+                limit(self.result, test.result_invalid()),
+                pass_fail(test, Some(parm)),
+                alarms(test, Some(parm)),
+                text(&self.test_txt),
+                text(&self.alarm_id),
+                limit_compare(parm),
+                text(&self.units),
+                limit(self.lo_limit, no_lo),
+                limit(self.hi_limit, no_hi),
+                text(&self.c_resfmt),
+                text(&self.c_llmfmt),
+                text(&self.c_hlmfmt),
+                limit(self.lo_spec, opt.no_lo_spec()),
+                limit(self.hi_spec, opt.no_hi_spec()),
+                scale(self.res_scal, opt.res_scal_invalid()),
+                scale(self.llm_scal, no_lo),
+                scale(self.hlm_scal, no_hi),
+            ],
+        )
+    }
+}
+
+impl Atdf for MPR<'_> {
+    fn to_atdf(&self) -> String {
+        let test = self.test_flags();
+        let parm = self.parm_flags();
+        let opt = self.opt_flags();
+        let no_lo = opt.lo_limit_invalid() || opt.no_lo_limit();
+        let no_hi = opt.hi_limit_invalid() || opt.no_hi_limit();
+        let results: Vec<String> = self.rtn_rslt.iter().map(|value| float(value.0)).collect();
+        record(
+            "MPR",
+            vec![
+                self.test_num.0.to_string(),
+                self.head_num.0.to_string(),
+                self.site_num.0.to_string(),
+                states(&self.rtn_stat),
+                results.join(","),
+                pass_fail(test, Some(parm)),
+                alarms(test, Some(parm)),
+                text(&self.test_txt),
+                text(&self.alarm_id),
+                limit_compare(parm),
+                text(&self.units),
+                limit(self.lo_limit, no_lo),
+                limit(self.hi_limit, no_hi),
+                limit(self.start_in, opt.start_in_invalid()),
+                limit(self.incr_in, opt.start_in_invalid()),
+                text(&self.units_in),
+                list(&self.rtn_indx),
+                text(&self.c_resfmt),
+                text(&self.c_llmfmt),
+                text(&self.c_hlmfmt),
+                limit(self.lo_spec, opt.no_lo_spec()),
+                limit(self.hi_spec, opt.no_hi_spec()),
+                scale(self.res_scal, opt.res_scal_invalid()),
+                scale(self.llm_scal, no_lo),
+                scale(self.hlm_scal, no_hi),
+            ],
+        )
+    }
+}
+
+impl Atdf for FTR<'_> {
+    fn to_atdf(&self) -> String {
+        let test = self.test_flags();
+        let opt = self.opt_flags();
+        let valid = |value: String, invalid: bool| if invalid { String::new() } else { value };
+        record(
+            "FTR",
+            vec![
+                self.test_num.0.to_string(),
+                self.head_num.0.to_string(),
+                self.site_num.0.to_string(),
+                pass_fail(test, None),
+                alarms(test, None),
+                text(&self.vect_nam),
+                text(&self.time_set),
+                valid(self.cycl_cnt.0.to_string(), opt.cycl_cnt_invalid()),
+                valid(format!("{:X}", self.rel_vadr.0), opt.rel_vadr_invalid()),
+                valid(self.rept_cnt.0.to_string(), opt.rept_cnt_invalid()),
+                valid(self.num_fail.0.to_string(), opt.num_fail_invalid()),
+                valid(self.xfail_ad.0.to_string(), opt.fail_ad_invalid()),
+                valid(self.yfail_ad.0.to_string(), opt.fail_ad_invalid()),
+                valid(self.vect_off.0.to_string(), opt.vect_off_invalid()),
+                list(&self.rtn_indx),
+                states(&self.rtn_stat),
+                list(&self.pgm_indx),
+                states(&self.pgm_stat),
+                bit_list(&self.fail_pin),
+                text(&self.op_code),
+                text(&self.test_txt),
+                text(&self.alarm_id),
+                text(&self.prog_txt),
+                text(&self.rslt_txt),
+                number(self.patg_num.0, u8::MAX),
+                bit_list(&self.spin_map),
+            ],
+        )
+    }
+}
+
+impl Atdf for BPS<'_> {
+    fn to_atdf(&self) -> String {
+        record("BPS", vec![text(&self.seq_name)])
+    }
+}
+
+impl Atdf for EPS {
+    fn to_atdf(&self) -> String {
+        record("EPS", vec![])
+    }
+}
+
+impl Atdf for GDR<'_> {
+    fn to_atdf(&self) -> String {
+        let fields = self
+            .gen_data
+            .iter()
+            .filter_map(|value| match value {
+                // pad bytes have no meaning in ATDF
+                Vn::B0 => None,
+                Vn::U1(v) => Some(format!("U{}", v.0)),
+                Vn::U2(v) => Some(format!("M{}", v.0)),
+                Vn::U4(v) => Some(format!("B{}", v.0)),
+                Vn::I1(v) => Some(format!("I{}", v.0)),
+                Vn::I2(v) => Some(format!("S{}", v.0)),
+                Vn::I4(v) => Some(format!("L{}", v.0)),
+                Vn::R4(v) => Some(format!("F{}", float(v.0))),
+                Vn::R8(v) => Some(format!("D{}", double(v.0))),
+                Vn::Cn(v) => Some(format!("T{}", text(v))),
+                Vn::Bn(v) => Some(format!("X{}", v)),
+                Vn::Dn(v) => Some(format!("Y{}", Bn(v.1))),
+                Vn::N1(v) => Some(format!("N{:X}", v.0 & 0x0f)),
+            })
+            .collect();
+        record("GDR", fields)
+    }
+}
+
+impl Atdf for DTR<'_> {
+    fn to_atdf(&self) -> String {
+        record("DTR", vec![text(&self.text_dat)])
+    }
+}
+
+/// Converts an STDF file to ATDF.
+///
+/// Records that have no ATDF representation (unknown or invalid ones) are left out.
+///
+/// # Returns
+///
+/// The number of records written.
+///
+/// # Errors
+///
+/// This function will return an error if the input is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::{BufWriter, Result};
+/// use stdf::atdf::write_atdf;
+///
+/// fn main() -> Result<()> {
+///     let mut input = File::open("tests/fixtures/test.std")?;
+///     let mut output = BufWriter::new(File::create("test.atd")?);
+///     let written = write_atdf(&mut input, &mut output)?;
+///     println!("{} records written", written);
+///     Ok(())
+/// }
+/// ```
+pub fn write_atdf<W: Write>(input: &mut File, output: &mut W) -> Result<u32> {
+    let endian = match get_endian_from_file(input)? {
+        Some(endian) => endian,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    };
+    let mmap = unsafe { MmapOptions::new().map(input)? };
+    let bytes = &mmap[..];
+    let offset = &mut 0;
+    let mut written: u32 = 0;
+    while bytes.len() - *offset >= 4 {
+        let record = match bytes.read_with::<V4>(offset, endian) {
+            Ok(record) => record,
+            Err(_) => break,
+        };
+        if let Some(line) = record.to_atdf() {
+            writeln!(output, "{}", wrap(&line, LINE_WIDTH))?;
+            written += 1;
+        }
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+/// Splits the fields of a record and undoes the escapes of `text`.
+fn split_fields(fields: &str, separator: char) -> Vec<String> {
+    let chars: Vec<char> = fields.chars().collect();
+    let mut split = vec![String::new()];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            let run = chars[i..].iter().take_while(|c| **c == '\\').count();
+            i += run;
+            let field = split.last_mut().unwrap();
+            if i == chars.len() {
+                field.push_str(&"\\".repeat(run.div_ceil(2)));
+            } else if chars[i] == separator {
+                // an odd run escapes the separator, an even one only backslashes
+                field.push_str(&"\\".repeat(run / 2));
+                if run % 2 == 1 {
+                    field.push(separator);
+                    i += 1;
+                }
+            } else {
+                field.push_str(&"\\".repeat(run));
+            }
+            continue;
+        }
+        if chars[i] == separator {
+            split.push(String::new());
+        } else {
+            split.last_mut().unwrap().push(chars[i]);
+        }
+        i += 1;
+    }
+    split
+}
+
+/// Parses an ATDF date and time, eg: `8:14:59 23-JUL-1992` (UTC).
+///
+/// # Examples
+///
+/// ```
+/// use stdf::atdf::parse_time;
+/// use stdf::types::U4E;
+///
+/// assert_eq!(parse_time("8:14:59 23-JUL-1992"), Some(U4E(711879299)));
+/// assert_eq!(parse_time("23-Jul-1992"), None);
+/// ```
+pub fn parse_time(text: &str) -> Option<U4E> {
+    let (time, date) = text.trim().split_once(' ')?;
+    let mut hms = time.split(':').map(|part| part.parse::<u8>().ok());
+    let (hour, minute, second) = (hms.next()??, hms.next()??, hms.next().unwrap_or(Some(0))?);
+    let mut dmy = date.trim().split('-');
+    let day: u8 = dmy.next()?.parse().ok()?;
+    let month = dmy.next()?.to_ascii_uppercase();
+    let month = MONTHS.iter().position(|name| *name == month)? as u8 + 1;
+    let year: i32 = match dmy.next()?.parse().ok()? {
+        year @ 0..=69 => 2000 + year,
+        year @ 70..=99 => 1900 + year,
+        year => year,
+    };
+    let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+    let time = Time::from_hms(hour, minute, second).ok()?;
+    U4E::from_datetime(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+/// Decodes hexadecimal digits into bytes, an odd number of digits is an error.
+fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// A record read from an ATDF file, its fields unescaped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtdfRecord {
+    /// The record type, eg: `PTR`.
+    pub name: String,
+    pub fields: Vec<String>,
+    /// The line the record starts on, 0 if it was not read from a file.
+    pub line: usize,
+    /// The bytes decoded from the hexadecimal and bit list fields, for the `V4` record to borrow.
+    data: Vec<Vec<u8>>,
+}
+
+impl AtdfRecord {
+    /// Parses a record, its continuation lines already joined.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind `InvalidData` if the record has no name, or if a hexadecimal or
+    /// bit list field can not be decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stdf::atdf::AtdfRecord;
+    ///
+    /// let record = AtdfRecord::parse(r"DTR:a\|b|c", '|').unwrap();
+    /// assert_eq!(record.name, "DTR");
+    /// assert_eq!(record.fields, vec!["a|b", "c"]);
+    /// ```
+    pub fn parse(record: &str, separator: char) -> Result<AtdfRecord> {
+        let (name, fields) = match record.split_once(':') {
+            Some((name, fields)) if name.len() == 3 && name.chars().all(|c| c.is_ascii_alphabetic()) => {
+                (name.to_ascii_uppercase(), fields)
+            }
+            _ => {
+                let start: String = record.chars().take(20).collect();
+                return Err(Error::new(ErrorKind::InvalidData, format!("Not an ATDF record '{}'", start)));
+            }
+        };
+        let fields = if fields.is_empty() {
+            Vec::new()
+        } else {
+            split_fields(fields, separator)
+        };
+        let mut record = AtdfRecord {
+            name,
+            fields,
+            line: 0,
+            data: Vec::new(),
+        };
+        record.data = record.decode()?;
+        Ok(record)
+    }
+
+    /// The field at `index`, empty if the record is shorter (trailing fields are optional).
+    pub fn field(&self, index: usize) -> &str {
+        self.fields.get(index).map(|field| field.as_str()).unwrap_or_default()
+    }
+
+    /// The STDF record.
+    ///
+    /// Empty fields get the STDF missing values, and the flags that ATDF leaves implicit
+    /// (eg: the OPT_FLAG of a PTR) are set from the fields that are empty.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind `InvalidData` if the record type is unknown, a required field is
+    /// empty or a field has an invalid value.
+    pub fn to_v4(&self) -> Result<V4<'_>> {
+        let record = match self.name.as_str() {
+            "FAR" => {
+                let stdf_ver: u8 = self.required(1)?;
+                if stdf_ver != 4 {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("STDF version {} is not supported", stdf_ver),
+                    ));
+                }
+                V4::FAR(FAR {
+                    cpu_type: U1(2),
+                    stdf_ver: U1(stdf_ver),
+                })
+            }
+            "ATR" => V4::ATR(ATR {
+                mod_tim: self.time(0)?,
+                cmd_line: self.text(1),
+            }),
+            "MIR" => V4::MIR(MIR {
+                lot_id: self.text(0),
+                part_typ: self.text(1),
+                job_nam: self.text(2),
+                node_nam: self.text(3),
+                tstr_typ: self.text(4),
+                setup_t: self.time(5)?,
+                start_t: self.time(6)?,
+                oper_nam: self.text(7),
+                mode_cod: self.character(8)?,
+                stat_num: U1(self.number(9, 0)?),
+                sblot_id: self.text(10),
+                test_cod: self.text(11),
+                rtst_cod: self.character(12)?,
+                job_rev: self.text(13),
+                exec_typ: self.text(14),
+                exec_ver: self.text(15),
+                prot_cod: self.character(16)?,
+                cmod_cod: self.character(17)?,
+                burn_tim: U2(self.number(18, u16::MAX)?),
+                tst_temp: self.text(19),
+                user_txt: self.text(20),
+                aux_file: self.text(21),
+                pkg_typ: self.text(22),
+                famly_id: self.text(23),
+                date_cod: self.text(24),
+                facil_id: self.text(25),
+                floor_id: self.text(26),
+                proc_id: self.text(27),
+                oper_frq: self.text(28),
+                spec_nam: self.text(29),
+                spec_ver: self.text(30),
+                flow_id: self.text(31),
+                setup_id: self.text(32),
+                dsgn_rev: self.text(33),
+                eng_id: self.text(34),
+                rom_cod: self.text(35),
+                serl_num: self.text(36),
+                supr_nam: self.text(37),
+            }),
+            "MRR" => V4::MRR(MRR {
+                finish_t: self.time(0)?,
+                disp_cod: self.character(1)?,
+                usr_desc: self.text(2),
+                exc_desc: self.text(3),
+            }),
+            "PCR" => {
+                let (head_num, site_num) = self.head_site(0)?;
+                V4::PCR(PCR {
+                    head_num,
+                    site_num,
+                    part_cnt: U4(self.number(2, 0)?),
+                    rtst_cnt: U4(self.number(3, u32::MAX)?),
+                    abrt_cnt: U4(self.number(4, u32::MAX)?),
+                    good_cnt: U4(self.number(5, u32::MAX)?),
+                    func_cnt: U4(self.number(6, u32::MAX)?),
+                })
+            }
+            "HBR" => {
+                let (head_num, site_num) = self.head_site(0)?;
+                V4::HBR(HBR {
+                    head_num,
+                    site_num,
+                    hbin_num: U2(self.required(2)?),
+                    hbin_cnt: U4(self.number(3, 0)?),
+                    hbin_pf: self.character(4)?,
+                    hbin_nam: self.text(5),
+                })
+            }
+            "SBR" => {
+                let (head_num, site_num) = self.head_site(0)?;
+                V4::SBR(SBR {
+                    head_num,
+                    site_num,
+                    sbin_num: U2(self.required(2)?),
+                    sbin_cnt: U4(self.number(3, 0)?),
+                    sbin_pf: self.character(4)?,
+                    sbin_nam: self.text(5),
+                })
+            }
+            "PMR" => V4::PMR(PMR {
+                pmr_index: U2(self.required(0)?),
+                chan_typ: U2(self.number(1, 0)?),
+                chan_nam: self.text(2),
+                phy_nam: self.text(3),
+                log_nam: self.text(4),
+                head_num: U1(self.number(5, 1)?),
+                site_num: U1(self.number(6, 1)?),
+            }),
+            "PGR" => {
+                let pmr_indx: Vec<U2> = self.list(2)?.into_iter().map(U2).collect();
+                V4::PGR(PGR {
+                    grp_indx: U2(self.required(0)?),
+                    grp_nam: self.text(1),
+                    indx_cnt: U2(pmr_indx.len() as u16),
+                    pmr_indx,
+                })
+            }
+            "PLR" => {
+                let grp_indx: Vec<U2> = self.list(0)?.into_iter().map(U2).collect();
+                let groups = grp_indx.len();
+                let mut grp_mode: Vec<U2> = self.list(1)?.into_iter().map(U2).collect();
+                grp_mode.resize(groups, U2(0));
+                let mut grp_radx = self
+                    .field(2)
+                    .split(',')
+                    .filter(|radix| !radix.is_empty())
+                    .map(|radix| match radix.trim().to_ascii_uppercase().as_str() {
+                        "B" => Ok(U1(2)),
+                        "O" => Ok(U1(8)),
+                        "D" => Ok(U1(10)),
+                        "H" => Ok(U1(16)),
+                        "S" => Ok(U1(20)),
+                        other => other.parse().map(U1).map_err(|_| self.error(2)),
+                    })
+                    .collect::<Result<Vec<U1>>>()?;
+                grp_radx.resize(groups, U1(0));
+                let cns = |first: usize| (0..groups).map(|g| Cn(&self.data[first + 2 * g])).collect();
+                V4::PLR(PLR {
+                    grp_cnt: U2(groups as u16),
+                    grp_indx,
+                    grp_mode,
+                    grp_radx,
+                    pgm_chal: cns(0),
+                    pgm_char: cns(1),
+                    rtn_chal: cns(2 * groups),
+                    rtn_char: cns(2 * groups + 1),
+                })
+            }
+            "RDR" => {
+                let rtst_bin: Vec<U2> = self.list(0)?.into_iter().map(U2).collect();
+                V4::RDR(RDR {
+                    num_bins: U2(rtst_bin.len() as u16),
+                    rtst_bin,
+                })
+            }
+            "SDR" => {
+                let site_num: Vec<U1> = self.list(2)?.into_iter().map(U1).collect();
+                V4::SDR(SDR {
+                    head_num: U1(self.required(0)?),
+                    site_grp: U1(self.number(1, 0)?),
+                    site_cnt: U1(site_num.len() as u8),
+                    site_num,
+                    hand_typ: self.text(3),
+                    hand_id: self.text(4),
+                    card_typ: self.text(5),
+                    card_id: self.text(6),
+                    load_typ: self.text(7),
+                    load_id: self.text(8),
+                    dib_typ: self.text(9),
+                    dib_id: self.text(10),
+                    cabl_typ: self.text(11),
+                    cabl_id: self.text(12),
+                    cont_typ: self.text(13),
+                    cont_id: self.text(14),
+                    lasr_typ: self.text(15),
+                    lasr_id: self.text(16),
+                    extr_typ: self.text(17),
+                    extr_id: self.text(18),
+                })
+            }
+            "WIR" => V4::WIR(WIR {
+                head_num: U1(self.required(0)?),
+                start_t: self.time(1)?,
+                site_grp: U1(self.number(2, u8::MAX)?),
+                wafer_id: self.text(3),
+            }),
+            "WRR" => V4::WRR(WRR {
+                head_num: U1(self.required(0)?),
+                finish_t: self.time(1)?,
+                part_cnt: U4(self.number(2, 0)?),
+                wafer_id: self.text(3),
+                site_grp: U1(self.number(4, u8::MAX)?),
+                rtst_cnt: U4(self.number(5, u32::MAX)?),
+                abrt_cnt: U4(self.number(6, u32::MAX)?),
+                good_cnt: U4(self.number(7, u32::MAX)?),
+                func_cnt: U4(self.number(8, u32::MAX)?),
+                fabwf_id: self.text(9),
+                frame_id: self.text(10),
+                mask_id: self.text(11),
+                usr_desc: self.text(12),
+                exc_desc: self.text(13),
+            }),
+            "WCR" => V4::WCR(WCR {
+                wf_flat: self.character(0)?,
+                pos_x: self.character(1)?,
+                pos_y: self.character(2)?,
+                wafr_siz: R4(self.number(3, 0.0)?),
+                die_ht: R4(self.number(4, 0.0)?),
+                die_wid: R4(self.number(5, 0.0)?),
+                wf_units: U1(self.number(6, 0)?),
+                center_x: I2(self.number(7, i16::MIN)?),
+                center_y: I2(self.number(8, i16::MIN)?),
+            }),
+            "PIR" => V4::PIR(PIR {
+                head_num: U1(self.required(0)?),
+                site_num: U1(self.required(1)?),
+            }),
+            "PRR" => {
+                let mut part_flg = match self.field(4).trim() {
+                    "" => 0b0001_0000,
+                    "P" | "p" => 0,
+                    "F" | "f" => 0b0000_1000,
+                    _ => return Err(self.error(4)),
+                };
+                for c in self.field(9).chars() {
+                    part_flg |= match c.to_ascii_uppercase() {
+                        'I' => 0b0000_0001,
+                        'C' => 0b0000_0010,
+                        _ => return Err(self.error(9)),
+                    };
+                }
+                match self.field(10).trim() {
+                    "Y" | "y" => part_flg |= 0b0000_0100,
+                    "" | "N" | "n" => {}
+                    _ => return Err(self.error(10)),
+                }
+                V4::PRR(PRR {
+                    head_num: U1(self.required(0)?),
+                    site_num: U1(self.required(1)?),
+                    part_flg: B1(part_flg),
+                    num_test: U2(self.number(3, 0)?),
+                    hard_bin: U2(self.required(5)?),
+                    soft_bin: U2(self.number(6, u16::MAX)?),
+                    x_coord: I2(self.number(7, i16::MIN)?),
+                    y_coord: I2(self.number(8, i16::MIN)?),
+                    test_t: U4(self.number(11, 0)?),
+                    part_id: self.text(2),
+                    part_txt: self.text(12),
+                    part_fix: Bn(&self.data[0]),
+                })
+            }
+            "TSR" => {
+                let (head_num, site_num) = self.head_site(0)?;
+                // the reserved bits are set, like an STDF writer has to
+                let mut opt_flag = 0b1100_1000;
+                for (index, bit) in [(10, 2), (11, 0), (12, 1), (13, 4), (14, 5)] {
+                    if self.field(index).trim().is_empty() {
+                        opt_flag |= 1 << bit;
+                    }
+                }
+                V4::TSR(TSR {
+                    head_num,
+                    site_num,
+                    test_typ: self.character(4)?,
+                    test_num: U4(self.required(2)?),
+                    exec_cnt: U4(self.number(5, u32::MAX)?),
+                    fail_cnt: U4(self.number(6, u32::MAX)?),
+                    alrm_cnt: U4(self.number(7, u32::MAX)?),
+                    test_nam: self.text(3),
+                    seq_name: self.text(8),
+                    test_lbl: self.text(9),
+                    opt_flag: B1(opt_flag),
+                    test_tim: self.limit(10, true)?,
+                    test_min: self.limit(11, true)?,
+                    test_max: self.limit(12, true)?,
+                    tst_sums: self.limit(13, true)?,
+                    tst_sqrs: self.limit(14, true)?,
+                })
+            }
+            "PTR" => {
+                let (test_flg, parm_flg) = self.test_flags(4, 5, self.field(3).trim().is_empty())?;
+                let full = self.is_full(9);
+                V4::PTR(PTR {
+                    test_num: U4(self.required(0)?),
+                    head_num: U1(self.required(1)?),
+                    site_num: U1(self.required(2)?),
+                    test_flg,
+                    parm_flg: B1(parm_flg.0 | self.limit_compare(8)?),
+                    result: self.float(3)?,
+                    test_txt: self.text(6),
+                    alarm_id: self.text(7),
+                    opt_flag: B1(self.opt_flag(full, [17, 15, 16, 10, 11])),
+                    res_scal: self.scale(17, full)?,
+                    llm_scal: self.scale(18, full)?,
+                    hlm_scal: self.scale(19, full)?,
+                    lo_limit: self.limit(10, full)?,
+                    hi_limit: self.limit(11, full)?,
+                    units: self.text(9),
+                    c_resfmt: self.text(12),
+                    c_llmfmt: self.text(13),
+                    c_hlmfmt: self.text(14),
+                    lo_spec: self.limit(15, full)?,
+                    hi_spec: self.limit(16, full)?,
+                })
+            }
+            "MPR" => {
+                let rtn_stat = self.states(3)?;
+                let rtn_rslt: Vec<R4> = match self.field(4).trim() {
+                    "" => Vec::new(),
+                    results => results
+                        .split(',')
+                        .map(|result| match result.trim() {
+                            "" => Ok(R4(f32::NAN)),
+                            result => result.parse().map(R4).map_err(|_| self.error(4)),
+                        })
+                        .collect::<Result<Vec<R4>>>()?,
+                };
+                let mut rtn_indx: Vec<U2> = self.list(16)?.into_iter().map(U2).collect();
+                if rtn_indx.len() > rtn_stat.len() {
+                    return Err(self.error(16));
+                }
+                if !rtn_indx.is_empty() {
+                    rtn_indx.resize(rtn_stat.len(), U2(0));
+                }
+                let (test_flg, parm_flg) = self.test_flags(5, 6, false)?;
+                let full = self.is_full(10);
+                let mut opt_flag = self.opt_flag(full, [22, 20, 21, 11, 12]);
+                if full && self.field(13).trim().is_empty() && self.field(14).trim().is_empty() {
+                    opt_flag |= 0b0000_0010;
+                }
+                V4::MPR(MPR {
+                    test_num: U4(self.required(0)?),
+                    head_num: U1(self.required(1)?),
+                    site_num: U1(self.required(2)?),
+                    test_flg,
+                    parm_flg: B1(parm_flg.0 | self.limit_compare(9)?),
+                    rtn_icnt: U2(rtn_stat.len() as u16),
+                    rslt_cnt: U2(rtn_rslt.len() as u16),
+                    rtn_stat,
+                    rtn_rslt,
+                    test_txt: self.text(7),
+                    alarm_id: self.text(8),
+                    opt_flag: B1(opt_flag),
+                    res_scal: self.scale(22, full)?,
+                    llm_scal: self.scale(23, full)?,
+                    hlm_scal: self.scale(24, full)?,
+                    lo_limit: self.limit(11, full)?,
+                    hi_limit: self.limit(12, full)?,
+                    start_in: self.limit(13, full)?,
+                    incr_in: self.limit(14, full)?,
+                    rtn_indx,
+                    units: self.text(10),
+                    units_in: self.text(15),
+                    c_resfmt: self.text(17),
+                    c_llmfmt: self.text(18),
+                    c_hlmfmt: self.text(19),
+                    lo_spec: self.limit(20, full)?,
+                    hi_spec: self.limit(21, full)?,
+                })
+            }
+            "FTR" => {
+                let (test_flg, _) = self.test_flags(3, 4, false)?;
+                // the reserved bits are set, like an STDF writer has to
+                let mut opt_flag = 0b1100_0000;
+                for (index, bit) in [(7, 0), (8, 1), (9, 2), (10, 3), (13, 5)] {
+                    if self.field(index).trim().is_empty() {
+                        opt_flag |= 1 << bit;
+                    }
+                }
+                if self.field(11).trim().is_empty() && self.field(12).trim().is_empty() {
+                    opt_flag |= 0b0001_0000;
+                }
+                let (rtn_indx, rtn_stat) = self.indexed_states(14, 15)?;
+                let (pgm_indx, pgm_stat) = self.indexed_states(16, 17)?;
+                V4::FTR(FTR {
+                    test_num: U4(self.required(0)?),
+                    head_num: U1(self.required(1)?),
+                    site_num: U1(self.required(2)?),
+                    test_flg,
+                    opt_flag: B1(opt_flag),
+                    cycl_cnt: U4(self.number(7, 0)?),
+                    rel_vadr: U4(match self.field(8).trim() {
+                        "" => 0,
+                        value => u32::from_str_radix(value, 16).map_err(|_| self.error(8))?,
+                    }),
+                    rept_cnt: U4(self.number(9, 0)?),
+                    num_fail: U4(self.number(10, 0)?),
+                    xfail_ad: I4(self.number(11, 0)?),
+                    yfail_ad: I4(self.number(12, 0)?),
+                    vect_off: I2(self.number(13, 0)?),
+                    rtn_icnt: U2(rtn_indx.len() as u16),
+                    pgm_icnt: U2(pgm_indx.len() as u16),
+                    rtn_indx,
+                    rtn_stat,
+                    pgm_indx,
+                    pgm_stat,
+                    fail_pin: self.dn(18, 0)?,
+                    vect_nam: self.text(5),
+                    time_set: self.text(6),
+                    op_code: self.text(19),
+                    test_txt: self.text(20),
+                    alarm_id: self.text(21),
+                    prog_txt: self.text(22),
+                    rslt_txt: self.text(23),
+                    patg_num: U1(self.number(24, u8::MAX)?),
+                    spin_map: self.dn(25, 1)?,
+                })
+            }
+            "BPS" => V4::BPS(BPS { seq_name: self.text(0) }),
+            "EPS" => V4::EPS(EPS),
+            "GDR" => {
+                let gen_data = (0..self.fields.len()).map(|i| self.generic(i)).collect::<Result<Vec<Vn>>>()?;
+                V4::GDR(GDR {
+                    fld_cnt: U2(gen_data.len() as u16),
+                    gen_data,
+                })
+            }
+            "DTR" => V4::DTR(DTR { text_dat: self.text(0) }),
+            name => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unknown record type '{}'", name),
+                ))
+            }
+        };
+        Ok(record)
+    }
+
+    /// Tells if a PTR or MPR has fields from the units on.
+    ///
+    /// Without, it is a short record as written for all but the first result of a test,
+    /// which leaves the OPT_FLAG and all that follows out.
+    fn is_full(&self, units: usize) -> bool {
+        self.fields.len() > units
+    }
+
+    /// The OPT_FLAG of a PTR or MPR, from the fields that are empty.
+    ///
+    /// `fields` are the result scale, the low and high spec limits and the low and high limits.
+    fn opt_flag(&self, full: bool, fields: [usize; 5]) -> u8 {
+        if !full {
+            return 0;
+        }
+        let [res_scal, lo_spec, hi_spec, lo_limit, hi_limit] = fields.map(|index| self.field(index).trim().is_empty());
+        // bit 1 is reserved (and set) in a PTR, the MPR sets it from START_IN and INCR_IN
+        let mut opt_flag = if self.name == "PTR" { 0b0000_0010 } else { 0 };
+        for (empty, bit) in [(res_scal, 0), (lo_spec, 2), (hi_spec, 3), (lo_limit, 6), (hi_limit, 7)] {
+            if empty {
+                opt_flag |= 1 << bit;
+            }
+        }
+        opt_flag
+    }
+
+    /// The PMR indexes and their states of an FTR, the shorter of both is padded.
+    fn indexed_states(&self, indexes: usize, states: usize) -> Result<(Vec<U2>, Vec<N1>)> {
+        let mut indexes: Vec<U2> = self.list(indexes)?.into_iter().map(U2).collect();
+        let mut states = self.states(states)?;
+        let count = indexes.len().max(states.len());
+        indexes.resize(count, U2(0));
+        states.resize(count, N1(0));
+        Ok((indexes, states))
+    }
+
+    /// A field of a GDR, its type as the first character.
+    fn generic(&self, index: usize) -> Result<Vn<'_>> {
+        let field = self.field(index);
+        let value = field.get(1..).unwrap_or_default();
+        let vn = match field.get(..1) {
+            Some("U") => Vn::U1(U1(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("M") => Vn::U2(U2(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("B") => Vn::U4(U4(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("I") => Vn::I1(I1(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("S") => Vn::I2(I2(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("L") => Vn::I4(I4(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("F") if value.trim().is_empty() => Vn::R4(R4(f32::NAN)),
+            Some("F") => Vn::R4(R4(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("D") if value.trim().is_empty() => Vn::R8(R8(f64::NAN)),
+            Some("D") => Vn::R8(R8(value.trim().parse().map_err(|_| self.error(index))?)),
+            Some("T") => Vn::Cn(Cn(&value.as_bytes()[..value.len().min(255)])),
+            Some("X") => Vn::Bn(Bn(&self.data[index])),
+            Some("Y") => Vn::Dn(Dn((self.data[index].len() * 8) as u16, &self.data[index])),
+            Some("N") => match value.trim().chars().next().and_then(|c| c.to_digit(16)) {
+                Some(digit) => Vn::N1(N1(digit as u8)),
+                None => return Err(self.error(index)),
+            },
+            _ => return Err(self.error(index)),
+        };
+        Ok(vn)
+    }
+
+    /// Converts the results and limits of an unscaled PTR or MPR from `units` (eg: `mV`) to
+    /// base units, the prefix becomes the scale of the results and limits.
+    fn scale_to_base(&mut self, units: &str) {
+        let (units_field, values, results, scales): (usize, &[usize], Option<usize>, usize) = match self.name.as_str() {
+            "PTR" => (9, &[3, 10, 11, 15, 16], None, 17),
+            "MPR" => (10, &[11, 12, 20, 21], Some(4), 22),
+            _ => return,
+        };
+        let (scale, base) = split_unit(units);
+        if scale == 0 {
+            return;
+        }
+        let divisor = 10f64.powi(scale as i32);
+        let rescale = |value: &str| match value.trim().parse::<f64>() {
+            Ok(value) => float((value / divisor) as f32),
+            Err(_) => value.to_string(),
+        };
+        for index in values.iter().chain(results.iter()) {
+            if let Some(field) = self.fields.get_mut(*index) {
+                *field = field.split(',').map(rescale).collect::<Vec<String>>().join(",");
+            }
+        }
+        // a short record keeps the units and scales of the first one
+        if !self.field(units_field).is_empty() {
+            self.fields[units_field] = base.to_string();
+            self.fields.resize(self.fields.len().max(scales + 3), String::new());
+            for field in &mut self.fields[scales..scales + 3] {
+                *field = scale.to_string();
+            }
+        }
+    }
+
+    fn error(&self, index: usize) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} field {}: invalid value '{}'", self.name, index + 1, self.field(index)),
+        )
+    }
+
+    fn decode(&self) -> Result<Vec<Vec<u8>>> {
+        match self.name.as_str() {
+            "PRR" => Ok(vec![self.hex(13)?]),
+            "FTR" => Ok(vec![self.bits(18)?, self.bits(25)?]),
+            "GDR" => (0..self.fields.len())
+                .map(|i| match self.field(i).get(..1) {
+                    Some("X") | Some("Y") => hex_to_bytes(&self.field(i)[1..]).ok_or_else(|| self.error(i)),
+                    _ => Ok(Vec::new()),
+                })
+                .collect(),
+            "PLR" => {
+                let groups = self.list::<u16>(0)?.len();
+                let mut data = Vec::with_capacity(4 * groups);
+                for index in [3, 4] {
+                    let mut chars: Vec<&str> = self.field(index).split('/').collect();
+                    chars.resize(groups, "");
+                    for group in chars.iter().take(groups) {
+                        let states: Vec<Vec<char>> = group
+                            .split(',')
+                            .filter(|state| !state.is_empty())
+                            .map(|state| state.chars().collect())
+                            .collect();
+                        if states.iter().any(|state| state.len() > 2 || !state.iter().all(char::is_ascii)) {
+                            return Err(self.error(index));
+                        }
+                        // CHAL is only written when a state of the group has two characters
+                        let chal = if states.iter().any(|state| state.len() == 2) {
+                            states.iter().map(|state| if state.len() == 2 { state[0] as u8 } else { b' ' }).collect()
+                        } else {
+                            Vec::new()
+                        };
+                        data.push(chal);
+                        data.push(states.iter().map(|state| state[state.len() - 1] as u8).collect());
+                    }
+                }
+                Ok(data)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn hex(&self, index: usize) -> Result<Vec<u8>> {
+        hex_to_bytes(self.field(index)).ok_or_else(|| self.error(index))
+    }
+
+    /// The bytes of a Dn with the bits of a list of indexes set.
+    fn bits(&self, index: usize) -> Result<Vec<u8>> {
+        let bits = self.list::<u16>(index)?;
+        let mut bytes = vec![0u8; bits.iter().max().map_or(0, |max| *max as usize / 8 + 1)];
+        for bit in bits {
+            bytes[bit as usize / 8] |= 1 << (bit % 8);
+        }
+        Ok(bytes)
+    }
+
+    fn dn(&self, index: usize, data: usize) -> Result<Dn<'_>> {
+        let bits = self.list::<u16>(index)?.into_iter().max().map_or(0, |max| max + 1);
+        Ok(Dn(bits, &self.data[data]))
+    }
+
+    /// A number, `missing` when the field is empty.
+    fn number<T: FromStr>(&self, index: usize, missing: T) -> Result<T> {
+        match self.field(index).trim() {
+            "" => Ok(missing),
+            value => value.parse().map_err(|_| self.error(index)),
+        }
+    }
+
+    /// A number that may not be left out.
+    fn required<T: FromStr>(&self, index: usize) -> Result<T> {
+        match self.field(index).trim() {
+            "" => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} field {}: missing value", self.name, index + 1),
+            )),
+            value => value.parse().map_err(|_| self.error(index)),
+        }
+    }
+
+    fn float(&self, index: usize) -> Result<R4> {
+        self.number(index, f32::NAN).map(R4)
+    }
+
+    /// A value an OPT_FLAG tells if it is valid, 0 when empty in a full record (as
+    /// testers write them) and NaN (missing) otherwise.
+    fn limit(&self, index: usize, full: bool) -> Result<R4> {
+        self.number(index, if full { 0.0 } else { f32::NAN }).map(R4)
+    }
+
+    fn scale(&self, index: usize, full: bool) -> Result<I1> {
+        self.number(index, if full { 0 } else { i8::MIN }).map(I1)
+    }
+
+    /// A text, cut to the 255 bytes an STDF string can hold.
+    fn text(&self, index: usize) -> Cn<'_> {
+        let text = self.field(index).as_bytes();
+        Cn(&text[..text.len().min(255)])
+    }
+
+    fn character(&self, index: usize) -> Result<C1> {
+        match self.field(index).as_bytes() {
+            [] => Ok(C1(b' ')),
+            [c] => Ok(C1(*c)),
+            _ => Err(self.error(index)),
+        }
+    }
+
+    fn time(&self, index: usize) -> Result<U4E> {
+        match self.field(index).trim() {
+            "" => Ok(U4E(0)),
+            value => parse_time(value).ok_or_else(|| self.error(index)),
+        }
+    }
+
+    fn list<T: FromStr>(&self, index: usize) -> Result<Vec<T>> {
+        match self.field(index).trim() {
+            "" => Ok(Vec::new()),
+            values => values.split(',').map(|value| value.trim().parse().map_err(|_| self.error(index))).collect(),
+        }
+    }
+
+    fn states(&self, index: usize) -> Result<Vec<N1>> {
+        self.field(index)
+            .chars()
+            .filter(|c| *c != ',' && !c.is_whitespace())
+            .map(|c| c.to_digit(16).map(|digit| N1(digit as u8)).ok_or_else(|| self.error(index)))
+            .collect()
+    }
+
+    /// The head and site, head 255 when both are empty (a summary over all sites).
+    fn head_site(&self, index: usize) -> Result<(U1, U1)> {
+        if self.field(index).trim().is_empty() && self.field(index + 1).trim().is_empty() {
+            Ok((U1(255), U1(SUMMARY_SITE)))
+        } else {
+            Ok((U1(self.required(index)?), U1(self.required(index + 1)?)))
+        }
+    }
+
+    /// The TEST_FLG and PARM_FLG of a PTR, MPR or FTR, from the Pass/Fail and Alarm flags.
+    fn test_flags(&self, pass_fail: usize, alarms: usize, result_invalid: bool) -> Result<(B1, B1)> {
+        let (mut test, mut parm) = (0u8, 0u8);
+        for c in self.field(alarms).chars() {
+            match ALARMS.iter().find(|(letter, _, _)| *letter == c.to_ascii_uppercase()) {
+                Some((_, false, bit)) => test |= 1 << bit,
+                Some((_, true, bit)) => parm |= 1 << bit,
+                None => return Err(self.error(alarms)),
+            }
+        }
+        match self.field(pass_fail).trim() {
+            "" => test |= 0b0100_0000,
+            "P" | "p" => {}
+            "F" | "f" => test |= 0b1000_0000,
+            "A" | "a" => parm |= 0b0010_0000,
+            _ => return Err(self.error(pass_fail)),
+        }
+        if result_invalid {
+            test |= 0b0000_0010;
+        }
+        Ok((B1(test), B1(parm)))
+    }
+
+    /// The PARM_FLG bits of the Limit Compare field.
+    fn limit_compare(&self, index: usize) -> Result<u8> {
+        self.field(index).chars().try_fold(0, |parm, c| match c.to_ascii_uppercase() {
+            'L' => Ok(parm | 0b0100_0000),
+            'H' => Ok(parm | 0b1000_0000),
+            _ => Err(self.error(index)),
+        })
+    }
+}
+
+/// Reads the records of an ATDF file, the continuation lines joined.
+///
+/// The field separator is the one of the FAR, which has to be the first record. Results
+/// and limits of an unscaled file (a `U` Scaling Flag in the FAR) are converted to base
+/// units, the scales coming from the prefix of the units.
+pub struct AtdfReader<R> {
+    input: R,
+    separator: char,
+    scaled: bool,
+    /// The units of the tests of an unscaled file, short PTRs and MPRs leave them out.
+    units: HashMap<u32, String>,
+    line: usize,
+    /// The first line of the next record, read to find the end of the current one.
+    next: Option<(usize, String)>,
+    started: bool,
+}
+
+impl<R: BufRead> AtdfReader<R> {
+    pub fn new(input: R) -> Self {
+        AtdfReader {
+            input,
+            separator: SEPARATOR,
+            scaled: true,
+            units: HashMap::new(),
+            line: 0,
+            next: None,
+            started: false,
+        }
+    }
+
+    fn error(line: usize, message: &str) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("Line {}: {}", line, message))
+    }
+
+    /// A line without its line terminator, the text is not required to be UTF-8.
+    fn read_line(&mut self) -> Result<Option<String>> {
+        let mut bytes = Vec::new();
+        if self.input.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        while bytes.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+            bytes.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    fn read_record(&mut self) -> Result<Option<AtdfRecord>> {
+        let (line, mut text) = match self.next.take() {
+            Some(next) => next,
+            None => loop {
+                match self.read_line()? {
+                    None => return Ok(None),
+                    Some(text) if text.trim().is_empty() => continue,
+                    Some(text) if text.starts_with(' ') => {
+                        return Err(Self::error(self.line, "continuation line without a record"))
+                    }
+                    Some(text) => break (self.line, text),
+                }
+            },
+        };
+        while let Some(next) = self.read_line()? {
+            if let Some(continuation) = next.strip_prefix(' ') {
+                text.push_str(continuation);
+            } else if !next.trim().is_empty() {
+                self.next = Some((self.line, next));
+                break;
+            }
+        }
+        if !self.started {
+            if !text.starts_with("FAR:") {
+                return Err(Self::error(line, "not an ATDF file, the first record is not a FAR"));
+            }
+            self.separator = text.chars().nth(5).unwrap_or(SEPARATOR);
+            self.started = true;
+        }
+        let mut record = AtdfRecord::parse(&text, self.separator).map_err(|e| Self::error(line, &e.to_string()))?;
+        record.line = line;
+        if record.name == "FAR" {
+            self.scaled = !record.field(3).trim().eq_ignore_ascii_case("U");
+        } else if !self.scaled && (record.name == "PTR" || record.name == "MPR") {
+            let units_field = if record.name == "PTR" { 9 } else { 10 };
+            let test_num = record.field(0).trim().parse::<u32>().unwrap_or_default();
+            let units = match record.field(units_field) {
+                "" => self.units.get(&test_num).cloned().unwrap_or_default(),
+                units => {
+                    self.units.insert(test_num, units.to_string());
+                    units.to_string()
+                }
+            };
+            record.scale_to_base(&units);
+        }
+        Ok(Some(record))
+    }
+}
+
+impl<R: BufRead> Iterator for AtdfReader<R> {
+    type Item = Result<AtdfRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Converts an ATDF file to STDF (little endian).
+///
+/// # Returns
+///
+/// The number of records written.
+///
+/// # Errors
+///
+/// This function will return an error of kind `InvalidData`, with the line of the record,
+/// if the input is not ATDF, or an error on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::{BufReader, BufWriter, Result};
+/// use stdf::atdf::read_atdf;
+///
+/// fn main() -> Result<()> {
+///     let input = BufReader::new(File::open("test.atd")?);
+///     let mut output = BufWriter::new(File::create("test.std")?);
+///     let written = read_atdf(input, &mut output)?;
+///     println!("{} records written", written);
+///     Ok(())
+/// }
+/// ```
+pub fn read_atdf<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<u32> {
+    let mut written: u32 = 0;
+    for record in AtdfReader::new(input) {
+        let record = record?;
+        let at_line = |message: String| AtdfReader::<R>::error(record.line, &message);
+        let bytes = record
+            .to_v4()
+            .map_err(|e| at_line(e.to_string()))?
+            .to_bytes(LE)
+            .map_err(|e| at_line(format!("Can not write {} : {:?}", record.name, e)))?;
+        output.write_all(&bytes)?;
+        written += 1;
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(text(&Cn(b"a|b")), "a\\|b");
+        assert_eq!(text(&Cn(b"C:\\temp\\")), "C:\\temp\\\\");
+        assert_eq!(text(&Cn(b"line\nbreak")), "line break");
+        assert_eq!(float(997.3), "997.3");
+        assert_eq!(float(3.2e-7), "3.2E-7");
+        assert_eq!(float(f32::NAN), "");
+        assert_eq!(record("PRR", vec!["1".into(), "".into(), "3".into(), "".into(), "".into()]), "PRR:1||3");
+        assert_eq!(record("EPS", vec![]), "EPS:");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let record = format!("DTR:{}", "x".repeat(200));
+        let wrapped = wrap(&record, LINE_WIDTH);
+        let lines: Vec<&str> = wrapped.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.chars().count() <= LINE_WIDTH));
+        assert!(lines[1].starts_with(' ') && lines[2].starts_with(' '));
+        let joined: String = lines.iter().enumerate().map(|(i, line)| if i == 0 { *line } else { &line[1..] }).collect();
+        assert_eq!(joined, record);
+        assert_eq!(wrap("PIR:1|1", LINE_WIDTH), "PIR:1|1");
+        // not broken after a space
+        let record = format!("DTR:{} {}", "x".repeat(75), "y".repeat(10));
+        assert!(wrap(&record, LINE_WIDTH).lines().all(|line| !line.ends_with(' ')));
+    }
+
+    #[test]
+    fn test_records() {
+        let prr = PRR {
+            head_num: U1(2),
+            site_num: U1(1),
+            part_flg: B1(0b0000_1000),
+            num_test: U2(78),
+            hard_bin: U2(0),
+            soft_bin: U2(17),
+            x_coord: I2(-2),
+            y_coord: I2(7),
+            test_t: U4(644),
+            part_id: Cn(b"13"),
+            part_txt: Cn(b"Device at edge of wafer"),
+            part_fix: Bn(&[0xF1, 0x3C, 0x20]),
+        };
+        assert_eq!(prr.to_atdf(), "PRR:2|1|13|78|F|0|17|-2|7|||644|Device at edge of wafer|F13C20");
+
+        let ptr = PTR {
+            test_num: U4(23),
+            head_num: U1(2),
+            site_num: U1(1),
+            test_flg: B1(0b1000_0001),
+            parm_flg: B1(0b0000_1100),
+            result: R4(997.3),
+            test_txt: Cn(b"Check 2nd layer"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(0b0000_0000),
+            res_scal: I1(3),
+            llm_scal: I1(3),
+            hlm_scal: I1(4),
+            lo_limit: R4(-1.7),
+            hi_limit: R4(45.2),
+            units: Cn(b"A"),
+            c_resfmt: Cn(b" %9.4f"),
+            c_llmfmt: Cn(b"%7.2f"),
+            c_hlmfmt: Cn(b"%7.2f"),
+            lo_spec: R4(-1.75),
+            hi_spec: R4(45.25),
+        };
+        assert_eq!(
+            ptr.to_atdf(),
+            "PTR:23|2|1|997.3|F|AHO|Check 2nd layer|||A|-1.7|45.2| %9.4f|%7.2f|%7.2f|-1.75|45.25|3|3|4"
+        );
+
+        let hbr = HBR {
+            head_num: U1(255),
+            site_num: U1(0),
+            hbin_num: U2(1),
+            hbin_cnt: U4(1346),
+            hbin_pf: C1(b'P'),
+            hbin_nam: Cn(b"PASSED"),
+        };
+        assert_eq!(hbr.to_atdf(), "HBR:||1|1346|P|PASSED");
+
+        let wir = WIR {
+            head_num: U1(1),
+            site_grp: U1(255),
+            start_t: U4E(711879782),
+            wafer_id: Cn(b""),
+        };
+        assert_eq!(wir.to_atdf(), "WIR:1|8:23:02 23-JUL-1992");
+        assert_eq!(FAR { cpu_type: U1(2), stdf_ver: U1(4) }.to_atdf(), "FAR:A|4|2|S");
+    }
+
+    #[test]
+    fn test_write_atdf() {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let written = write_atdf(&mut input, &mut output).unwrap();
+        assert_eq!(written, 918);
+        let atdf = String::from_utf8(output).unwrap();
+        assert!(atdf.starts_with("FAR:A|4|2|S\n"));
+        assert!(atdf.lines().all(|line| line.chars().count() <= LINE_WIDTH));
+        assert_eq!(atdf.lines().filter(|line| line.starts_with("PRR:")).count(), 22);
+        assert!(atdf.trim_end().lines().last().unwrap().starts_with("MRR:"));
+    }
+
+    #[test]
+    fn test_split_fields() {
+        for value in ["a|b", "C:\\temp\\", "a\\|b", "x\\\\", "|", "\\", "", "no escapes"] {
+            assert_eq!(split_fields(&text(&Cn(value.as_bytes())), SEPARATOR), vec![value], "{}", value);
+        }
+        assert_eq!(split_fields("1||a\\|b|", SEPARATOR), vec!["1", "", "a|b", ""]);
+        assert_eq!(split_fields("1;2\\;3", ';'), vec!["1", "2;3"]);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        for time in [711879299, 711879782, 1, u32::MAX] {
+            assert_eq!(parse_time(&format_time(U4E(time))), Some(U4E(time)));
+        }
+        assert_eq!(parse_time("8:14:59 23-jul-92"), Some(U4E(711879299)));
+        assert_eq!(parse_time("8:14 23-JUL-1992"), Some(U4E(711879240)));
+        assert_eq!(parse_time("25:00:00 23-JUL-1992"), None);
+        assert_eq!(parse_time("8:14:59 31-FEB-1992"), None);
+    }
+
+    #[test]
+    fn test_read_records() {
+        let atdf = "FAR:A|4|2|U\n\
+                    PRR:1|2|part|5|F|7\n\
+                    \n\
+                    PTR:23|1|2|997.3|F|AHO|Check 2nd layer|||mV|-1.7|45.2\n \
+                    ||||-1.75|45.25\n\
+                    PTR:23|1|2|12|P\n\
+                    DTR:a\\|b\n \
+                    c|ignored\n";
+        let records: Vec<AtdfRecord> = AtdfReader::new(atdf.as_bytes()).collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[3].line, 6);
+        assert_eq!(records[4].fields, vec!["a|bc", "ignored"]);
+
+        let V4::PRR(prr) = records[1].to_v4().unwrap() else { panic!("not a PRR") };
+        assert_eq!(prr.part_flags().passed(), Some(false));
+        assert_eq!((prr.hard_bin, prr.soft_bin), (U2(7), U2(u16::MAX)));
+        assert_eq!((prr.x_coord, prr.y_coord, prr.test_t), (I2(i16::MIN), I2(i16::MIN), U4(0)));
+
+        // the unscaled results and limits are converted to base units
+        let V4::PTR(ptr) = records[2].to_v4().unwrap() else { panic!("not a PTR") };
+        assert_eq!(ptr.test_flg, B1(0b1000_0001));
+        assert_eq!(ptr.parm_flg, B1(0b0000_1100));
+        assert_eq!((ptr.result, ptr.units, ptr.res_scal), (R4(0.9973), Cn(b"V"), I1(3)));
+        assert_eq!((ptr.lo_limit, ptr.hi_spec), (R4(-0.0017), R4(0.04525)));
+        assert_eq!(ptr.opt_flag, B1(0b0000_0010));
+        let V4::PTR(ptr) = records[3].to_v4().unwrap() else { panic!("not a PTR") };
+        assert_eq!((ptr.result, ptr.opt_flag), (R4(0.012), B1(0)));
+        assert!(ptr.lo_limit.0.is_nan());
+        assert_eq!(ptr.test_flg, B1(0));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |atdf: &str| read_atdf(atdf.as_bytes(), &mut Vec::new()).unwrap_err().to_string();
+        assert_eq!(error("PIR:1|1\n"), "Line 1: not an ATDF file, the first record is not a FAR");
+        assert_eq!(error("FAR:A|4|2|S\n\nPIR:1|x\n"), "Line 3: PIR field 2: invalid value 'x'");
+        assert_eq!(error("FAR:A|4|2|S\nPIR:1\n"), "Line 2: PIR field 2: missing value");
+        assert_eq!(error("FAR:A|4|2|S\nPTR:1|1|1|2|Q\n"), "Line 2: PTR field 5: invalid value 'Q'");
+        assert_eq!(error("FAR:A|4|2|S\nXYZ:1\n"), "Line 2: Unknown record type 'XYZ'");
+        assert_eq!(error(" 1\nFAR:A|4|2|S\n"), "Line 1: continuation line without a record");
+    }
+
+    /// Asserts that a record read back from ATDF equals the original one, but for what ATDF can not tell.
+    fn assert_same(actual: V4, expected: V4, context: &str) {
+        match (actual, expected) {
+            // line breaks are written as spaces
+            (V4::DTR(actual), V4::DTR(expected)) => {
+                assert_eq!(actual.text_dat.to_string(), expected.text_dat.to_string().replace(['\r', '\n'], " "), "{}", context);
+            }
+            // the values flagged invalid are left empty, and the reserved OPT_FLAG bits set
+            (V4::TSR(mut actual), V4::TSR(mut expected)) => {
+                for tsr in [&mut actual, &mut expected] {
+                    let opt_flag = tsr.opt_flag.0;
+                    for (bit, value) in [(2, &mut tsr.test_tim), (0, &mut tsr.test_min), (1, &mut tsr.test_max), (4, &mut tsr.tst_sums), (5, &mut tsr.tst_sqrs)] {
+                        if opt_flag & (1 << bit) != 0 {
+                            *value = R4(0.0);
+                        }
+                    }
+                    tsr.opt_flag = B1(opt_flag & 0b0011_0111);
+                }
+                assert_eq!(actual, expected, "{}", context);
+            }
+            (actual, expected) => assert_eq!(actual, expected, "{}", context),
+        }
+    }
+
+    /// STDF → ATDF → STDF gives the same records, as far as ATDF can tell: the values
+    /// flagged invalid, line breaks in texts and the CPU type are not part of ATDF.
+    #[test]
+    fn test_round_trip() {
+        for fixture in ["tests/fixtures/test.std", "tests/fixtures/hatb_hw0_ft_device1_production_tp1.stdf"] {
+            let mut input = File::open(fixture).unwrap();
+            let mut atdf: Vec<u8> = Vec::new();
+            let written = write_atdf(&mut input, &mut atdf).unwrap();
+            let mut stdf: Vec<u8> = Vec::new();
+            assert_eq!(read_atdf(atdf.as_slice(), &mut stdf).unwrap(), written);
+
+            let original = unsafe { MmapOptions::new().map(&input).unwrap() };
+            let endian = get_endian_from_file(&mut input).unwrap().unwrap();
+            let (left, right) = (&mut 0, &mut 0);
+            let mut compared = 0;
+            while original.len() - *left >= 4 {
+                let expected = original.read_with::<V4>(left, endian).unwrap();
+                let actual = stdf.read_with::<V4>(right, LE).unwrap();
+                assert_same(actual, expected, &format!("{} record {}", fixture, compared));
+                compared += 1;
+            }
+            assert_eq!(*right, stdf.len());
+            assert_eq!(compared, written);
+        }
+    }
+}
@@ -5,6 +5,7 @@ use std::process;
 
 use stdf::records::V4;
 use stdf::get_endian_from_file;
+use stdf::parts::PartAssembler;
 use stdf::tally::{count_parts, count_records};
 
 use memmap::MmapOptions;
@@ -118,45 +119,34 @@ fn main() {
     let pb = ProgressBar::new(part_count as u64 * 301_u64);
     pb.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}").unwrap());
 
-    let mut loop_map: HashMap<u8, u16> = (1..=8).map(|key| (key, 0)).collect();
-    loop {
-        match bytes.read_with::<V4>(offset, endian) {
-            Ok(v4) => {
-                match v4 {
-                    V4::MRR(_) => {break},
-                    V4::PIR(pir) => {
-                        let site_num:u8 = pir.site_num.into();
-                        let site_loops = *loop_map.get(&site_num).unwrap() as u32;
-                        let col: u32 = (8 * site_loops) + site_num as u32 ;
-                        sheet.get_cell_mut((col+1, 1)).set_value_number(site_loops+1).get_style_mut().set_alignment(center_alignment.clone());
-                        sheet.get_cell_mut((col+1, 2)).set_value_number(col).get_style_mut().set_alignment(center_alignment.clone());
-                        sheet.get_cell_mut((col+1, 3)).set_value_string(format!("Site{}", site_num)).get_style_mut().set_alignment(center_alignment.clone());
-                    },
-                    V4::PRR(prr) => {
-                        let site_num:u8 = prr.site_num.into();
-                        if let Some(value) = loop_map.get_mut(&site_num) {
-                            *value += 1;
-                        }
-                        if use_progress_bar {
-                            pb.inc(1);
-                        }
-                    },
-                    V4::PTR(ptr) => {
-                        let test_num: u32 = ptr.test_num.into();
-                        if (18606..=18905).contains(&test_num) {
-                            let row = test_num - 18606 + 4;
-                            let site_num:u8 = ptr.site_num.into();
-                            let site_loops = *loop_map.get(&site_num).unwrap() as u32;
-                            let col: u32 = (8 * site_loops) + site_num as u32 + 1;
-                            let value: f32 = ptr.result.into();
-                            sheet.get_cell_mut((col, row)).set_value_number(value as f64);
-                        }
-                    },
-                    _ => {},
+    let mut assembler = PartAssembler::new();
+    let mut loop_map: HashMap<u8, u32> = HashMap::new();
+    while let Ok(v4) = bytes.read_with::<V4>(offset, endian) {
+        if let V4::MRR(_) = v4 {
+            break;
+        }
+        if let Some(part) = assembler.push(v4) {
+            let site_num = part.site_num();
+            let site_loops = loop_map.entry(site_num).or_default();
+            let col: u32 = (8 * *site_loops) + site_num as u32;
+            sheet.get_cell_mut((col+1, 1)).set_value_number(*site_loops+1).get_style_mut().set_alignment(center_alignment.clone());
+            sheet.get_cell_mut((col+1, 2)).set_value_number(col).get_style_mut().set_alignment(center_alignment.clone());
+            sheet.get_cell_mut((col+1, 3)).set_value_string(format!("Site{}", site_num)).get_style_mut().set_alignment(center_alignment.clone());
+            *site_loops += 1;
+            for test in part.tests {
+                if let V4::PTR(ptr) = test {
+                    let test_num: u32 = ptr.test_num.into();
+                    if (18606..=18905).contains(&test_num) {
+                        let row = test_num - 18606 + 4;
+                        let value: f32 = ptr.result.into();
+                        sheet.get_cell_mut((col+1, row)).set_value_number(value as f64);
+                    }
                 }
-            },
-            _ => break,
-        };
+            }
+            if use_progress_bar {
+                pb.inc(1);
+            }
+        }
     }
 
     match writer::xlsx::write(&book, output_file.clone()) {
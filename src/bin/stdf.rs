@@ -1,16 +1,38 @@
 extern crate clap;
 
-use clap::{Arg, Command, crate_version, crate_authors, ArgAction, value_parser};
+use clap::{Arg, ArgMatches, Command, crate_version, crate_authors, ArgAction, value_parser};
 use stdf::records::{PRR, V4, typ_sub_to_name, is_supported_records};
 
-use std::{fs::File, io::{Seek, SeekFrom}};
+use std::{fs::{self, File}, io::{BufReader, BufWriter, Seek, SeekFrom}};
+use std::path::Path;
 use std::process;
 
 use stdf::{get_endian_from_file, get_index_from_stdf_file};
 // use stdf::conversions::dummy_function;
-use stdf::tally::count_records;
+use stdf::tally::{count_records, count_tests};
+use stdf::anonymize::{anonymize_file, AnonymizeConfig, Anonymizer};
+use stdf::strip::{strip_file, StripConfig};
+use stdf::query::{filter_file, Query, RecordFilter};
+use stdf::diff::{diff_files, Alignment, DiffConfig};
+use stdf::parts::PartAssembler;
+use stdf::ftr::ftr_report;
+use stdf::pins::PinMap;
+use stdf::atdf::{read_atdf, write_atdf};
+use stdf::table::{write_csv, CsvLayout};
+use stdf::xlsx::write_xlsx;
+use stdf::arrow::write_parquet;
+use stdf::npy::write_npz;
+use stdf::jsonl::{read_jsonl, write_jsonl};
+use stdf::stats::{test_statistics, write_stats_csv, write_stats_table, Breakdown};
+use stdf::sqlite::write_sqlite;
+use stdf::types::TimeZone;
+use time::UtcOffset;
+use rusqlite::Connection;
+#[cfg(feature = "hdf5")]
+use stdf::hdf5::{write_hdf5, Hdf5Layout};
 
 use memmap::MmapOptions;
+use indicatif::{ProgressBar, ProgressStyle};
 use byte::BytesExt;
 
 fn main() {
@@ -77,6 +99,286 @@ fn main() {
                 .help("Displays a status bar while processing"),
             ),
         )
+        .subcommand(Command::new("anonymize")
+            .about("Replaces the customer and fab identifiers of the STDF file by deterministic aliases.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the input file to use"),
+            )
+            .arg(Arg::new("output_file")
+                .short('o')
+                .long("output")
+                .required(false)
+                .help("Sets the output file to use (default: <input>_anon.<ext>)"),
+            )
+            .arg(Arg::new("salt")
+                .short('s')
+                .long("salt")
+                .required(false)
+                .default_value("")
+                .help("Sets the secret used for hashing, the same secret gives the same aliases across files"),
+            )
+            .arg(Arg::new("time_shift")
+                .short('t')
+                .long("time-shift")
+                .required(false)
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .value_parser(value_parser!(i64))
+                .help("Shifts all timestamps by the given number of seconds"),
+            )
+            .arg(Arg::new("keep")
+                .short('k')
+                .long("keep")
+                .required(false)
+                .num_args(1..)
+                .help("Sets the fields (eg: part_typ) to leave untouched"),
+            )
+            .arg(Arg::new("scrub")
+                .long("scrub")
+                .required(false)
+                .num_args(1..)
+                .help("Sets additional fields (eg: part_id) to anonymize"),
+            )
+            .arg(Arg::new("delete")
+                .short('d')
+                .long("delete")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .help("Deletes the input file afterwards"),
+            ),
+        )
+        .subcommand(Command::new("strip")
+            .about("Removes records from the STDF file, the other records are copied as-is.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the input file to use"),
+            )
+            .arg(Arg::new("output_file")
+                .short('o')
+                .long("output")
+                .required(false)
+                .help("Sets the output file to use (default: <input>_stripped.<ext>)"),
+            )
+            .arg(Arg::new("atr")
+                .long("atr")
+                .action(ArgAction::SetTrue)
+                .help("Removes the ATR records"),
+            )
+            .arg(Arg::new("dtr")
+                .long("dtr")
+                .action(ArgAction::SetTrue)
+                .help("Removes the DTR records"),
+            )
+            .arg(Arg::new("gdr")
+                .long("gdr")
+                .action(ArgAction::SetTrue)
+                .help("Removes the GDR records"),
+            )
+            .arg(Arg::new("pcr")
+                .long("pcr")
+                .action(ArgAction::SetTrue)
+                .help("Removes the PCR records"),
+            )
+            .arg(Arg::new("sbr")
+                .long("sbr")
+                .action(ArgAction::SetTrue)
+                .help("Removes the SBR records"),
+            )
+            .arg(Arg::new("hbr")
+                .long("hbr")
+                .action(ArgAction::SetTrue)
+                .help("Removes the HBR records"),
+            )
+            .arg(Arg::new("records")
+                .short('r')
+                .long("records")
+                .required(false)
+                .num_args(1..)
+                .help("Sets other records to remove (FAR, MIR and MRR can not be removed)"),
+            )
+            .arg(Arg::new("tests")
+                .short('t')
+                .long("tests")
+                .required(false)
+                .num_args(1..)
+                .value_parser(value_parser!(u32))
+                .help("Removes the PTR, MPR, FTR and TSR records of the given test numbers"),
+            ),
+        )
+        .subcommand(Command::new("filter")
+            .about("Selects the records of the STDF file matching an expression.")
+            .long_about("Selects the records of the STDF file matching an expression, eg:\n  \
+                rec == PTR && test_num in 1000..2000 && site_num == 3\n\
+                Fields are the lower case STDF field names, `rec` is the record name.\n\
+                The part of a test record is available via the PRR fields (x_coord/x, y_coord/y,\n\
+                hard_bin/hbin, soft_bin/sbin, part_id, ...) and the wafer via wafer_id/wafer.\n\
+                `and`, `or` and `not` can be used instead of &&, || and !.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the input file to use"),
+            )
+            .arg(Arg::new("where")
+                .short('w')
+                .long("where")
+                .required(true)
+                .help("Sets the expression the records must match"),
+            )
+            .arg(Arg::new("output_file")
+                .short('o')
+                .long("output")
+                .required(false)
+                .help("Writes the matching records (and FAR, MIR, MRR) to a new STDF file instead of the console"),
+            ),
+        )
+        .subcommand(Command::new("diff")
+            .about("Compares two STDF files field by field, exits with 1 if they differ.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the reference file"),
+            )
+            .arg(Arg::new("right_file")
+                .short('r')
+                .long("right")
+                .required(true)
+                .help("Sets the file to compare with the reference"),
+            )
+            .arg(Arg::new("align")
+                .short('a')
+                .long("align")
+                .required(false)
+                .value_parser(["records", "part_id", "xy"])
+                .default_value("records")
+                .help("Sets how the records are paired: in order of appearance, or per part by part_id or by wafer and X/Y"),
+            )
+            .arg(Arg::new("tolerance")
+                .short('t')
+                .long("tolerance")
+                .required(false)
+                .value_parser(value_parser!(f64))
+                .default_value("0")
+                .help("Sets the absolute difference under which floats are equal"),
+            )
+            .arg(Arg::new("rel_tolerance")
+                .long("rel-tolerance")
+                .required(false)
+                .value_parser(value_parser!(f64))
+                .default_value("0")
+                .help("Sets the relative difference under which floats are equal"),
+            )
+            .arg(Arg::new("ignore")
+                .long("ignore")
+                .required(false)
+                .num_args(1..)
+                .help("Ignores `timestamps`, records (eg: ATR) or fields (eg: test_t)"),
+            )
+            .arg(Arg::new("detailed")
+                .short('d')
+                .long("detailed")
+                .action(ArgAction::SetTrue)
+                .help("Lists every difference, not only the summary"),
+            )
+            .arg(Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Writes the report as JSON"),
+            ),
+        )
+        .subcommand(Command::new("ftr-report")
+            .about("Reports the failing pins of the functional tests (FTR) of the STDF file.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the input file to use"),
+            )
+            .arg(Arg::new("top")
+                .short('n')
+                .long("top")
+                .required(false)
+                .value_parser(value_parser!(usize))
+                .default_value("10")
+                .help("Sets the number of pins listed per test"),
+            )
+            .arg(Arg::new("detailed")
+                .short('d')
+                .long("detailed")
+                .action(ArgAction::SetTrue)
+                .help("Also lists every failing FTR, decoded"),
+            )
+            .arg(Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Writes the report as JSON"),
+            ),
+        )
+        .subcommand(Command::new("stats")
+            .about("Computes the statistics of the PTR results per test: count, fails, min, max, mean, std dev, quartiles, Cp and Cpk.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the input file to use"),
+            )
+            .arg(Arg::new("by")
+                .short('b')
+                .long("by")
+                .required(false)
+                .num_args(1..)
+                .value_parser(["head", "site", "wafer"])
+                .help("Breaks the statistics of each test down by head, site and/or wafer"),
+            )
+            .arg(Arg::new("format")
+                .short('f')
+                .long("format")
+                .required(false)
+                .value_parser(["table", "csv", "json"])
+                .default_value("table")
+                .help("Sets the output format"),
+            )
+            .arg(Arg::new("output_file")
+                .short('o')
+                .long("output")
+                .required(false)
+                .help("Sets the output file to use, standard output if omitted"),
+            )
+            .arg(Arg::new("progress_bar")
+                .short('p')
+                .long("progress")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .help("Displays a status bar while processing"),
+            ),
+        )
+        .subcommand(Command::new("pin-map")
+            .about("Exports the pin map (PMR, PGR and PLR records) of the STDF file as CSV.")
+            .arg(Arg::new("input_file")
+                .short('i')
+                .long("input")
+                .required(true)
+                .help("Sets the input file to use"),
+            )
+            .arg(Arg::new("output_file")
+                .short('o')
+                .long("output")
+                .required(false)
+                .help("Sets the CSV file to write, standard output if omitted"),
+            )
+            .arg(Arg::new("groups")
+                .short('g')
+                .long("groups")
+                .action(ArgAction::SetTrue)
+                .help("Exports the pin groups instead of the pins"),
+            ),
+        )
         .subcommand(Command::new("is")
             .about("Checks various things on the STDF file.")
             .subcommand(Command::new("ws")
@@ -211,6 +513,13 @@ fn main() {
                     .action(ArgAction::SetTrue)
                     .help("Sets the verbosity to high"),
                 )
+                .arg(Arg::new("stats")
+                    .short('s')
+                    .long("stats")
+                    .required(false)
+                    .action(ArgAction::SetTrue)
+                    .help("Lists the statistics of each PTR test instead, as 'stdf stats' does"),
+                )
             )
             .subcommand(Command::new("sites")
                 .about("Counts the number of sites in the STDF file.")
@@ -233,6 +542,7 @@ fn main() {
         ) 
         .subcommand(Command::new("dump")
             .about("Dumps various things of the STDF file in a more readable form to the console.")
+            .arg(time_zone_arg())
             .subcommand(Command::new("record")
                 .about("Dumps the record at a postion of the STDF file.")
                 .arg(Arg::new("input_file")
@@ -262,16 +572,56 @@ fn main() {
                     .required(false)
                     .num_args(1..)
                     .help("Sets the list of records to dump\n`stdf list records` for a list of valid records\nInvalid records will be ignored"),
+                )
+                .arg(Arg::new("where")
+                    .short('w')
+                    .long("where")
+                    .required(false)
+                    .help("Only dumps the records matching the expression (see `stdf filter`)"),
                 ),
             )
             .subcommand(Command::new("parts")
-                .about("Dumps the parts of the STDF file.")
+                .about("Dumps the parts of the STDF file, with their test results.")
                 .arg(Arg::new("input_file")
                     .short('i')
                     .long("input_file")
                     .required(true)
                     .help("Sets the input file to use"),
                 )
+                .arg(Arg::new("part_id")
+                    .long("part-id")
+                    .required(false)
+                    .num_args(1..)
+                    .help("Only dumps the parts with the given part ids"),
+                )
+                .arg(Arg::new("xy")
+                    .long("xy")
+                    .required(false)
+                    .num_args(2)
+                    .value_names(["X", "Y"])
+                    .allow_hyphen_values(true)
+                    .value_parser(value_parser!(i16))
+                    .help("Only dumps the part at the given die coordinates"),
+                )
+                .arg(Arg::new("hbin")
+                    .long("hbin")
+                    .required(false)
+                    .num_args(1..)
+                    .value_parser(value_parser!(u16))
+                    .help("Only dumps the parts in the given hard bins"),
+                )
+                .arg(Arg::new("sbin")
+                    .long("sbin")
+                    .required(false)
+                    .num_args(1..)
+                    .value_parser(value_parser!(u16))
+                    .help("Only dumps the parts in the given soft bins"),
+                )
+                .arg(Arg::new("json")
+                    .long("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Dumps the parts as JSON, one object per line"),
+                )
             )
             .subcommand(Command::new("index")
                 .about("Dumps the index of the STDF file.")
@@ -301,6 +651,7 @@ fn main() {
         )
         .subcommand(Command::new("to")
             .about("Converts the STDF file into another format.")
+            .arg(time_zone_arg())
             .subcommand(Command::new("csv")
                 .about("Converts the STDF file to CSV format.")
                 .arg(Arg::new("input_file")
@@ -321,10 +672,32 @@ fn main() {
                     .required(false)
                     .action(ArgAction::SetTrue)
                     .help("Displays a status bar while processing"),
+                )
+                .arg(Arg::new("long")
+                    .short('l')
+                    .long("long")
+                    .required(false)
+                    .action(ArgAction::SetTrue)
+                    .help("Writes one row per result instead of one row per part"),
+                ),
+            )
+            .subcommand(Command::new("atdf")
+                .about("Converts the STDF file to ATDF format.")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_file")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the output file to use (default: the input file with an .atd extension)"),
                 ),
             )
             .subcommand(Command::new("xlsx")
-                .about("Converts the STDF file to XLSX format.")
+                .about("Converts the STDF file to an XLSX lot report (summary, bins, test statistics and data).")
                 .arg(Arg::new("input_file")
                     .short('i')
                     .long("input")
@@ -345,6 +718,28 @@ fn main() {
                     .help("Displays a status bar while processing"),
                 ),
             )
+            .subcommand(Command::new("parquet")
+                .about("Converts the STDF file to Parquet files (parts, ptr, mpr, ftr and tests tables).")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_dir")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the directory to write the Parquet files to (default: the input file name without extension and with a _parquet suffix)"),
+                )
+                .arg(Arg::new("progress_bar")
+                    .short('p')
+                    .long("progress")
+                    .required(false)
+                    .action(ArgAction::SetTrue)
+                    .help("Displays a status bar while processing"),
+                ),
+            )
             .subcommand(Command::new("be")
                 .about("Converts the STDF file to Big Endian format.")
                 .arg(Arg::new("input_file")
@@ -390,7 +785,29 @@ fn main() {
                 ),
             )
             .subcommand(Command::new("npy")
-                .about("Converts the STDF file to Numpy format.")
+                .about("Converts the PTR results to a NumPy .npz archive (parts x tests float32 matrix).")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_file")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the output file to use"),
+                )
+                .arg(Arg::new("progress_bar")
+                    .short('p')
+                    .long("progress")
+                    .required(false)
+                    .action(ArgAction::SetTrue)
+                    .help("Displays a status bar while processing"),
+                ),
+            )
+            .subcommand(Command::new("jsonl")
+                .about("Converts the STDF file to JSON Lines: one object per record with its type, offset and fields.")
                 .arg(Arg::new("input_file")
                     .short('i')
                     .long("input")
@@ -412,7 +829,29 @@ fn main() {
                 ),
             )
             .subcommand(Command::new("hdf5")
-                .about("Converts the STDF file to HDF5 format.")
+                .about("Converts the STDF file to HDF5: lot, wafer and test groups (needs the 'hdf5' feature).")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_file")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the output file to use"),
+                )
+                .arg(Arg::new("progress_bar")
+                    .short('p')
+                    .long("progress")
+                    .required(false)
+                    .action(ArgAction::SetTrue)
+                    .help("Displays a status bar while processing"),
+                ),
+            )
+            .subcommand(Command::new("metis")
+                .about("Converts the STDF file to HDF5 in the Semi-ATE Metis layout (needs the 'hdf5' feature).")
                 .arg(Arg::new("input_file")
                     .short('i')
                     .long("input")
@@ -432,15 +871,70 @@ fn main() {
                     .action(ArgAction::SetTrue)
                     .help("Displays a status bar while processing"),
                 ),
+            )
+            .subcommand(Command::new("sqlite")
+                .about("Adds the STDF file to an SQLite database (lots, wafers, parts, tests, results and bins).")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_file")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the database to create or add to"),
+                )
+                .arg(Arg::new("progress_bar")
+                    .short('p')
+                    .long("progress")
+                    .required(false)
+                    .action(ArgAction::SetTrue)
+                    .help("Displays a status bar while processing"),
+                ),
             ),
         )
-        .get_matches();
-
-    match matches.subcommand() {
-        Some(("endian", sub_m)) => {
-            let input_file = sub_m.get_one::<String>("input_file").unwrap();
-            let file = File::open(input_file);
-            if file.is_err() {
+        .subcommand(Command::new("from")
+            .about("Converts a file of another format into an STDF file.")
+            .subcommand(Command::new("atdf")
+                .about("Converts the ATDF file to STDF (little endian).")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_file")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the output file to use (default: the input file with an .std extension)"),
+                ),
+            )
+            .subcommand(Command::new("jsonl")
+                .about("Converts a JSON Lines file, as written by 'to jsonl', to STDF.")
+                .arg(Arg::new("input_file")
+                    .short('i')
+                    .long("input")
+                    .required(true)
+                    .help("Sets the input file to use"),
+                )
+                .arg(Arg::new("output_file")
+                    .short('o')
+                    .long("output")
+                    .required(false)
+                    .help("Sets the output file to use (default: the input file with an .std extension)"),
+                ),
+            ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some(("endian", sub_m)) => {
+            let input_file = sub_m.get_one::<String>("input_file").unwrap();
+            let file = File::open(input_file);
+            if file.is_err() {
                 println!("Error: {}", file.err().unwrap());
                 process::exit(1);
             }
@@ -507,6 +1001,348 @@ fn main() {
                 _ => eprintln!("No valid subcommand was used for list"),
             }
         }
+        Some(("anonymize", sub_m)) => {
+            let input_file_name = sub_m.get_one::<String>("input_file").unwrap();
+            let default_output_file = suffixed_file_name(input_file_name, "anon");
+            let output_file_name = sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+            if same_file(output_file_name, input_file_name) {
+                eprintln!("Error: The output file can not be the input file");
+                process::exit(1);
+            }
+
+            let mut config = AnonymizeConfig {
+                salt: sub_m.get_one::<String>("salt").unwrap().to_string(),
+                time_shift: *sub_m.get_one::<i64>("time_shift").unwrap(),
+                ..AnonymizeConfig::default()
+            };
+            if let Some(fields) = sub_m.get_many::<String>("scrub") {
+                config.fields.extend(fields.map(|field| field.to_lowercase()));
+            }
+            if let Some(fields) = sub_m.get_many::<String>("keep") {
+                for field in fields {
+                    config.fields.remove(&field.to_lowercase());
+                }
+            }
+
+            let mut input_file = match File::open(input_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let output_file = match File::create(output_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let mut writer = BufWriter::new(output_file);
+            match anonymize_file(&mut input_file, &mut writer, &Anonymizer::new(config)) {
+                Ok(changed) => println!("{} records anonymized into '{}'", changed, output_file_name),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+            if sub_m.get_flag("delete") {
+                drop(input_file);
+                if let Err(e) = fs::remove_file(input_file_name) {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(("strip", sub_m)) => {
+            let input_file_name = sub_m.get_one::<String>("input_file").unwrap();
+            let default_output_file = suffixed_file_name(input_file_name, "stripped");
+            let output_file_name = sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+            if same_file(output_file_name, input_file_name) {
+                eprintln!("Error: The output file can not be the input file");
+                process::exit(1);
+            }
+
+            let mut record_names: Vec<String> = ["atr", "dtr", "gdr", "pcr", "sbr", "hbr"]
+                .iter()
+                .filter(|flag| sub_m.get_flag(flag))
+                .map(|flag| flag.to_uppercase())
+                .collect();
+            if let Some(records) = sub_m.get_many::<String>("records") {
+                record_names.extend(records.cloned());
+            }
+            let test_nums: Vec<u32> = sub_m
+                .get_many::<u32>("tests")
+                .map(|vals| vals.copied().collect())
+                .unwrap_or_default();
+            let config = match StripConfig::new(&record_names, &test_nums) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+
+            let mut input_file = match File::open(input_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let output_file = match File::create(output_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let mut writer = BufWriter::new(output_file);
+            match strip_file(&mut input_file, &mut writer, &config) {
+                Ok(removed) => println!("{} records removed, result written to '{}'", removed, output_file_name),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(("filter", sub_m)) => {
+            let input_file_name = sub_m.get_one::<String>("input_file").unwrap();
+            let query = match Query::parse(sub_m.get_one::<String>("where").unwrap()) {
+                Ok(query) => query,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let mut input_file = match File::open(input_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            if let Some(output_file_name) = sub_m.get_one::<String>("output_file") {
+                if same_file(output_file_name, input_file_name) {
+                    eprintln!("Error: The output file can not be the input file");
+                    process::exit(1);
+                }
+                let output_file = match File::create(output_file_name) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                };
+                let mut writer = BufWriter::new(output_file);
+                match filter_file(&mut input_file, &mut writer, query) {
+                    Ok(written) => println!("{} records written to '{}'", written, output_file_name),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                }
+            } else {
+                let endian = match get_endian_from_file(&mut input_file) {
+                    Ok(Some(endian)) => endian,
+                    Ok(None) => {
+                        eprintln!("Error: NO STDF file!");
+                        process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                };
+                let m = unsafe { MmapOptions::new().map(&input_file).unwrap() };
+                let bytes = &m[..];
+                let mut filter = RecordFilter::new(query);
+                let offset = &mut 0;
+                loop {
+                    let start = *offset;
+                    match bytes.read_with::<V4>(offset, endian) {
+                        Ok(v4) => filter.push(start..*offset, v4).iter().for_each(|s| println!("{}", s.record)),
+                        Err(_) => {
+                            filter.finish().iter().for_each(|s| println!("{}", s.record));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Some(("diff", sub_m)) => {
+            let mut config = DiffConfig {
+                alignment: match sub_m.get_one::<String>("align").unwrap().as_str() {
+                    "part_id" => Alignment::PartId,
+                    "xy" => Alignment::XY,
+                    _ => Alignment::Records,
+                },
+                abs_tolerance: *sub_m.get_one::<f64>("tolerance").unwrap(),
+                rel_tolerance: *sub_m.get_one::<f64>("rel_tolerance").unwrap(),
+                ..Default::default()
+            };
+            if let Some(ignores) = sub_m.get_many::<String>("ignore") {
+                for ignore in ignores {
+                    config.ignore(ignore);
+                }
+            }
+            let mut files = Vec::new();
+            for arg in ["input_file", "right_file"] {
+                match File::open(sub_m.get_one::<String>(arg).unwrap()) {
+                    Ok(file) => files.push(file),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                }
+            }
+            let (left, right) = files.split_at_mut(1);
+            let report = match diff_files(&mut left[0], &mut right[0], &config) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            if sub_m.get_flag("json") {
+                let mut json = serde_json::to_value(&report).unwrap();
+                if !sub_m.get_flag("detailed") {
+                    json.as_object_mut().unwrap().remove("differences");
+                }
+                println!("{}", serde_json::to_string_pretty(&json).unwrap());
+            } else {
+                if sub_m.get_flag("detailed") {
+                    for difference in report.differences.iter() {
+                        print!("{}", difference);
+                    }
+                    println!();
+                }
+                print!("{}", report);
+            }
+            if !report.is_identical() {
+                process::exit(1);
+            }
+        }
+        Some(("ftr-report", sub_m)) => {
+            let input_file_name = sub_m.get_one::<String>("input_file").unwrap();
+            let top = *sub_m.get_one::<usize>("top").unwrap();
+            let detailed = sub_m.get_flag("detailed");
+            let json = sub_m.get_flag("json");
+            let mut input_file = match File::open(input_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let mut failing_ftrs = Vec::new();
+            let pareto = match ftr_report(&mut input_file, |ftr| {
+                if detailed && ftr.passed == Some(false) {
+                    if json {
+                        failing_ftrs.push(ftr.clone());
+                    } else {
+                        println!("{}", ftr);
+                    }
+                }
+            }) {
+                Ok(pareto) => pareto,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            if json {
+                let mut report = serde_json::json!({ "tests": pareto.tests });
+                if detailed {
+                    report["failing_ftrs"] = serde_json::to_value(&failing_ftrs).unwrap();
+                }
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                for (test_num, test) in pareto.failing_tests() {
+                    println!("TEST {} '{}' : {} of {} failed", test_num, test.test_txt, test.failures, test.executions);
+                    for (pin, count) in test.pareto().into_iter().take(top) {
+                        println!("   {:<20} {:>8} {:>6.1}%", pin, count, 100.0 * count as f64 / test.failures as f64);
+                    }
+                }
+            }
+        }
+        Some(("stats", sub_m)) => {
+            let input_file_name = sub_m.get_one::<String>("input_file").unwrap();
+            let by: Vec<&String> = sub_m.get_many::<String>("by").map(|vals| vals.collect()).unwrap_or_default();
+            let breakdown = Breakdown {
+                head: by.iter().any(|by| *by == "head"),
+                site: by.iter().any(|by| *by == "site"),
+                wafer: by.iter().any(|by| *by == "wafer"),
+            };
+            let mut input_file = match File::open(input_file_name) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let writer: Box<dyn std::io::Write> = match sub_m.get_one::<String>("output_file") {
+                Some(output_file_name) => match File::create(output_file_name) {
+                    Ok(file) => Box::new(BufWriter::new(file)),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                },
+                None => Box::new(std::io::stdout()),
+            };
+            let pb = progress_bar(sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+            let stats = match test_statistics(&mut input_file, breakdown, |offset| pb.set_position(offset as u64)) {
+                Ok(stats) => {
+                    pb.finish_and_clear();
+                    stats
+                }
+                Err(e) => {
+                    pb.abandon();
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let result = match sub_m.get_one::<String>("format").unwrap().as_str() {
+                "csv" => write_stats_csv(&stats, writer),
+                "json" => serde_json::to_writer_pretty(writer, &stats).map_err(std::io::Error::from),
+                _ => write_stats_table(&stats, writer),
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(("pin-map", sub_m)) => {
+            let input_file_name = sub_m.get_one::<String>("input_file").unwrap();
+            let groups = sub_m.get_flag("groups");
+            let pin_map = match File::open(input_file_name).and_then(|mut file| PinMap::from_file(&mut file)) {
+                Ok(pin_map) => pin_map,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let writer: Box<dyn std::io::Write> = match sub_m.get_one::<String>("output_file") {
+                Some(output_file_name) => match File::create(output_file_name) {
+                    Ok(file) => Box::new(BufWriter::new(file)),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                },
+                None => Box::new(std::io::stdout()),
+            };
+            let result = if groups {
+                pin_map.write_groups_csv(writer)
+            } else {
+                pin_map.write_pins_csv(writer)
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
         Some(("is", sub_m)) => {
             match sub_m.subcommand() {
                 Some(("ws", sub_sub_m)) => {
@@ -656,7 +1492,7 @@ fn main() {
                                 *offset = *pos as usize;
                                 match bytes.read_with::<PRR>(offset, endian) {
                                     Ok(prr) =>{
-                                        if prr.part_flg.0 & 0b00011000 == 0 {
+                                        if prr.part_flags().is_pass() {
                                             pass_count += 1;
                                         } else {
                                             fail_count += 1;
@@ -677,10 +1513,32 @@ fn main() {
                 }
                 Some(("tests", sub_sub_m)) => {
                     let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
-                    let mut input_file = File::open(input_file_name).unwrap();
-                    let endian = get_endian_from_file(&mut input_file).unwrap();
-                    if endian.is_none() {
-                        panic!("Endianess not detected");
+                    if sub_sub_m.get_flag("stats") {
+                        let result = File::open(input_file_name)
+                            .and_then(|mut file| test_statistics(&mut file, Breakdown::default(), |_| {}))
+                            .and_then(|stats| write_stats_table(&stats, std::io::stdout()));
+                        if let Err(e) = result {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    } else {
+                        let tests = match File::open(input_file_name).and_then(|mut file| count_tests(&mut file)) {
+                            Ok(tests) => tests,
+                            Err(e) => {
+                                eprintln!("Error: {}", e);
+                                process::exit(1);
+                            }
+                        };
+                        let total: usize = tests.values().sum();
+                        if sub_sub_m.get_flag("verbosity") {
+                            for (name, count) in tests.iter() {
+                                println!("{} : {:>10}", name, count);
+                            }
+                            println!("    + -----------");
+                            println!("TTL : {:>10}", total);
+                        } else {
+                            println!("{}", total);
+                        }
                     }
                 }
                 Some(("sites", sub_sub_m)) => {
@@ -703,6 +1561,7 @@ fn main() {
             }
         }
         Some(("dump", sub_m)) => {
+            let zone_offset = time_zone(sub_m);
             match sub_m.subcommand() {
                 Some(("record", sub_sub_m)) => {
                     let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
@@ -768,45 +1627,31 @@ fn main() {
 
                     let m = unsafe { MmapOptions::new().map(&input_file).unwrap() };
                     let bytes = &m[..];
+                    let mut filter = match sub_sub_m.get_one::<String>("where").map(|expr| Query::parse(expr)) {
+                        Some(Ok(query)) => Some(RecordFilter::new(query)),
+                        Some(Err(e)) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                        None => None,
+                    };
                     let offset = &mut 0;
-                    loop {
-                        match bytes.read_with::<V4>(offset, endian) {
-                            Ok(v4) => {
-                                if records_to_dump.contains(&v4.name()) {
-                                    match v4 {
-                                        V4::FAR(record) => println!("{}", record),
-                                        V4::ATR(record) => println!("{}", record),
-                                        V4::MIR(record) => println!("{}", record),
-                                        V4::MRR(record) => println!("{}", record),
-                                        V4::PCR(record) => println!("{}", record),
-                                        V4::HBR(record) => println!("{}", record),
-                                        V4::SBR(record) => println!("{}", record),
-                                        V4::PMR(record) => println!("{}", record),
-                                        V4::PGR(record) => println!("{}", record),
-                                        V4::PLR(record) => println!("{}", record),
-                                        V4::RDR(record) => println!("{}", record),
-                                        V4::SDR(record) => println!("{}", record),
-                                        V4::WIR(record) => println!("{}", record),
-                                        V4::WRR(record) => println!("{}", record),
-                                        V4::WCR(record) => println!("{}", record),
-                                        V4::PIR(record) => println!("{}", record),
-                                        V4::PRR(record) => println!("{}", record),
-                                        V4::TSR(record) => println!("{}", record),
-                                        V4::PTR(record) => println!("{}", record),
-                                        V4::MPR(record) => println!("{}", record),
-                                        V4::FTR(record) => println!("{}", record),
-                                        V4::BPS(record) => println!("{}", record),
-                                        V4::EPS(record) => println!("{}", record),
-                                        V4::GDR(record) => println!("{}", record),
-                                        V4::DTR(record) => println!("{}", record),
-                                        _ => println!("???"),
-                                    }
-                                }
-                            },
-                            // Err(byte::Error::BadOffset(x)) => println!("Error : bad offset {} before EOF", x),
-                            // Err(e) => println!("Error : {:?}", e),
-                            _ => break,
+                    let mut done = false;
+                    while !done {
+                        let start = *offset;
+                        let records = match (bytes.read_with::<V4>(offset, endian), filter.as_mut()) {
+                            (Ok(v4), None) => vec![v4],
+                            (Ok(v4), Some(filter)) => filter.push(start..*offset, v4).into_iter().map(|s| s.record).collect(),
+                            (Err(_), filter) => {
+                                done = true;
+                                filter.map(|filter| filter.finish().into_iter().map(|s| s.record).collect()).unwrap_or_default()
+                            }
                         };
+                        for v4 in records {
+                            if records_to_dump.contains(&v4.name()) {
+                                println!("{}", v4.display_in(zone_offset));
+                            }
+                        }
                     }
                 }
                 Some(("parts", sub_sub_m)) => {
@@ -823,7 +1668,34 @@ fn main() {
                             process::exit(1);
                         }
                     };
-                    println!("TO BE IMPLEMENTED {:?}", endian);
+                    let part_ids: Option<Vec<String>> = sub_sub_m.get_many::<String>("part_id").map(|vals| vals.cloned().collect());
+                    let xy: Option<Vec<i16>> = sub_sub_m.get_many::<i16>("xy").map(|vals| vals.copied().collect());
+                    let hbins: Option<Vec<u16>> = sub_sub_m.get_many::<u16>("hbin").map(|vals| vals.copied().collect());
+                    let sbins: Option<Vec<u16>> = sub_sub_m.get_many::<u16>("sbin").map(|vals| vals.copied().collect());
+                    let json = sub_sub_m.get_flag("json");
+
+                    let m = unsafe { MmapOptions::new().map(&input_file).unwrap() };
+                    let bytes = &m[..];
+                    let mut assembler = PartAssembler::new();
+                    let offset = &mut 0;
+                    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+                        let part = match assembler.push(record) {
+                            Some(part) => part,
+                            None => continue,
+                        };
+                        let selected = part_ids.as_ref().is_none_or(|ids| ids.contains(&part.part_id()))
+                            && xy.as_ref().is_none_or(|xy| part.xy() == Some((xy[0], xy[1])))
+                            && hbins.as_ref().is_none_or(|bins| bins.contains(&part.prr.hard_bin.0))
+                            && sbins.as_ref().is_none_or(|bins| bins.contains(&part.prr.soft_bin.0));
+                        if !selected {
+                            continue;
+                        }
+                        if json {
+                            println!("{}", part.to_json());
+                        } else {
+                            println!("{}", part);
+                        }
+                    }
                 }
                 Some(("index", sub_sub_m)) => {
                     let file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
@@ -876,25 +1748,413 @@ fn main() {
             }
         }
         Some(("to", sub_m)) => {
+            let zone_offset = time_zone(sub_m);
             match sub_m.subcommand() {
                 Some(("csv", sub_sub_m)) => {
-                    let input_file = sub_sub_m.get_one::<String>("input_file").unwrap();
-                    let default_output_file = format!("{}.csv", input_file);
-                    let output_file = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
-                    println!("Converting the STDF file '{}' to CSV file '{}'", input_file, output_file);
-                    println!("Progress bar: {}", sub_sub_m.get_flag("progress_bar"));
-                    // Add your logic for the "csv" subcommand here
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = format!("{}.csv", input_file_name);
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let layout = if sub_sub_m.get_flag("long") { CsvLayout::Long } else { CsvLayout::Wide };
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    let mut writer = BufWriter::new(output_file);
+                    match write_csv(&mut input_file, &mut writer, layout, |offset| pb.set_position(offset as u64)) {
+                        Ok(parts) => {
+                            pb.finish_and_clear();
+                            println!("{} parts written to '{}'", parts, output_file_name);
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                Some(("atdf", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("atd").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let mut writer = BufWriter::new(output_file);
+                    match write_atdf(&mut input_file, &mut writer) {
+                        Ok(written) => println!("{} records written to '{}'", written, output_file_name),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
                 }
                 Some(("xlsx", sub_sub_m)) => {
-                    let input_file = sub_sub_m.get_one::<String>("input_file").unwrap();
-                    let default_output_file = format!("{}.xlsx", input_file);
-                    let output_file = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
-                    println!("Converting the STDF file '{}' to XLSX file '{}'", input_file, output_file);
-                    // Add your logic for the "xlsx" subcommand here
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = format!("{}.xlsx", input_file_name);
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    match write_xlsx(&mut input_file, BufWriter::new(output_file), zone_offset.unwrap_or(UtcOffset::UTC), |offset| pb.set_position(offset as u64)) {
+                        Ok(parts) => {
+                            pb.finish_and_clear();
+                            println!("{} parts written to '{}'", parts, output_file_name);
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                Some(("parquet", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_dir = format!("{}_parquet", Path::new(input_file_name).with_extension("").to_string_lossy());
+                    let output_dir = sub_sub_m.get_one::<String>("output_dir").unwrap_or(&default_output_dir);
+                    if same_file(output_dir, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    match write_parquet(&mut input_file, Path::new(output_dir), |offset| pb.set_position(offset as u64)) {
+                        Ok(tables) => {
+                            pb.finish_and_clear();
+                            for (table, rows) in tables {
+                                println!("{} rows written to '{}'", rows, Path::new(output_dir).join(format!("{}.parquet", table.name())).display());
+                            }
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                Some(("npy", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("npz").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    match write_npz(&mut input_file, BufWriter::new(output_file), |offset| pb.set_position(offset as u64)) {
+                        Ok(shape) => {
+                            pb.finish_and_clear();
+                            println!("{} parts x {} tests written to '{}'", shape.parts, shape.tests, output_file_name);
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                Some(("jsonl", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("jsonl").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    match write_jsonl(&mut input_file, BufWriter::new(output_file), |offset| pb.set_position(offset as u64)) {
+                        Ok(written) => {
+                            pb.finish_and_clear();
+                            println!("{} records written to '{}'", written, output_file_name);
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                Some(("sqlite", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("db").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let mut connection = match Connection::open(output_file_name) {
+                        Ok(connection) => connection,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    match write_sqlite(&mut input_file, &mut connection, input_file_name, |offset| pb.set_position(offset as u64)) {
+                        Ok(Some(parts)) => {
+                            pb.finish_and_clear();
+                            println!("{} parts written to '{}'", parts, output_file_name);
+                        }
+                        Ok(None) => {
+                            pb.finish_and_clear();
+                            println!("The lot of '{}' is already in '{}'", input_file_name, output_file_name);
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                #[cfg(feature = "hdf5")]
+                Some((layout @ ("hdf5" | "metis"), sub_sub_m)) => {
+                    let layout = if layout == "metis" { Hdf5Layout::Metis } else { Hdf5Layout::Tree };
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("h5").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let mut input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let pb = progress_bar(sub_sub_m, input_file.metadata().map(|m| m.len()).unwrap_or(0));
+                    match write_hdf5(&mut input_file, Path::new(output_file_name), layout, |offset| pb.set_position(offset as u64)) {
+                        Ok(parts) => {
+                            pb.finish_and_clear();
+                            println!("{} parts written to '{}'", parts, output_file_name);
+                        }
+                        Err(e) => {
+                            pb.abandon();
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                #[cfg(not(feature = "hdf5"))]
+                Some(("hdf5" | "metis", _)) => {
+                    eprintln!("Error: stdf was built without HDF5 support, rebuild it with '--features hdf5'");
+                    process::exit(1);
                 }
                 _ => eprintln!("No valid subcommand was used for convert_to"),
             }
         }
+        Some(("from", sub_m)) => {
+            match sub_m.subcommand() {
+                Some(("atdf", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("std").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let mut writer = BufWriter::new(output_file);
+                    match read_atdf(BufReader::new(input_file), &mut writer) {
+                        Ok(written) => println!("{} records written to '{}'", written, output_file_name),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                Some(("jsonl", sub_sub_m)) => {
+                    let input_file_name = sub_sub_m.get_one::<String>("input_file").unwrap();
+                    let default_output_file = Path::new(input_file_name).with_extension("std").to_string_lossy().to_string();
+                    let output_file_name = sub_sub_m.get_one::<String>("output_file").unwrap_or(&default_output_file);
+                    if same_file(output_file_name, input_file_name) {
+                        eprintln!("Error: The output file can not be the input file");
+                        process::exit(1);
+                    }
+                    let input_file = match File::open(input_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let output_file = match File::create(output_file_name) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    };
+                    let mut writer = BufWriter::new(output_file);
+                    match read_jsonl(BufReader::new(input_file), &mut writer) {
+                        Ok(written) => println!("{} records written to '{}'", written, output_file_name),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                _ => eprintln!("No valid subcommand was used for convert_from"),
+            }
+        }
         _ => eprintln!("No valid subcommand was used"),
     }
-}
\ No newline at end of file
+}
+
+/// Returns `<stem>_<suffix>.<ext>` next to the given file.
+fn suffixed_file_name(file_name: &str, suffix: &str) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let new_name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(new_name).to_string_lossy().to_string()
+}
+
+/// Tells if both names are the same file, eg: `./a.std` and `a.std`, even if `output` does not exist yet.
+fn same_file(output: &str, input: &str) -> bool {
+    let canonical = |name: &str| {
+        let path = Path::new(name);
+        path.canonicalize().ok().or_else(|| {
+            let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            Some(parent.canonicalize().ok()?.join(path.file_name()?))
+        })
+    };
+    match (canonical(output), canonical(input)) {
+        (Some(output), Some(input)) => output == input,
+        _ => Path::new(output) == Path::new(input),
+    }
+}
+
+/// The progress bar over the `len` bytes of the input file, hidden without `--progress_bar`.
+fn progress_bar(matches: &ArgMatches, len: u64) -> ProgressBar {
+    if !matches.get_flag("progress_bar") {
+        return ProgressBar::hidden();
+    }
+    let pb = ProgressBar::new(len);
+    pb.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>7}/{total_bytes:7} {msg}").unwrap());
+    pb
+}
+
+/// The `--time-zone` option of the commands that show timestamps.
+fn time_zone_arg() -> Arg {
+    Arg::new("time_zone")
+        .long("time-zone")
+        .global(true)
+        .required(false)
+        .value_parser(|text: &str| TimeZone::parse(text).ok_or("expected utc, local or an offset as +HH:MM"))
+        .help("Shows the timestamps as RFC 3339 in the given time zone: utc, local or an offset as +HH:MM")
+}
+
+/// The offset of the `--time-zone` if given, determined before any thread is started.
+fn time_zone(matches: &ArgMatches) -> Option<UtcOffset> {
+    let zone = matches.get_one::<TimeZone>("time_zone")?;
+    match zone.offset() {
+        Ok(offset) => Some(offset),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
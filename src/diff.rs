@@ -0,0 +1,594 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+
+use byte::ctx::Endian;
+use byte::BytesExt;
+use memmap::MmapOptions;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::get_endian_from_file;
+use crate::records::{name_to_typ_sub, V4};
+
+/// The fields that hold a date/time, ignored with `--ignore timestamps`.
+pub const TIMESTAMP_FIELDS: [&str; 4] = ["mod_tim", "setup_t", "start_t", "finish_t"];
+
+const LIMIT_FIELDS: [&str; 4] = ["lo_limit", "hi_limit", "lo_spec", "hi_spec"];
+
+/// How the records of both files are paired up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alignment {
+    /// Records are paired by type, head/site and test/bin number, in order of appearance.
+    #[default]
+    Records,
+    /// Parts are paired by their PRR `part_id`, test records are paired within the part.
+    PartId,
+    /// Parts are paired by wafer id and die X/Y, test records are paired within the part.
+    XY,
+}
+
+/// Settings of a [`diff_files`] run.
+#[derive(Debug, Clone)]
+pub struct DiffConfig {
+    pub alignment: Alignment,
+    /// Floats that differ less than this are considered equal.
+    pub abs_tolerance: f64,
+    /// Floats that differ less than this fraction of the largest of both are considered equal.
+    pub rel_tolerance: f64,
+    /// The (rec_typ, rec_sub) of the records that are not compared.
+    pub ignore_records: HashSet<(u8, u8)>,
+    /// The (lower case) names of the fields that are not compared.
+    pub ignore_fields: HashSet<String>,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        DiffConfig {
+            alignment: Alignment::default(),
+            abs_tolerance: 0.0,
+            rel_tolerance: 0.0,
+            ignore_records: HashSet::new(),
+            ignore_fields: HashSet::new(),
+        }
+    }
+}
+
+impl DiffConfig {
+    /// Ignores `timestamps` (all date/time fields), a record name (eg: `ATR`) or a field name (eg: `test_t`).
+    pub fn ignore(&mut self, what: &str) {
+        if what.eq_ignore_ascii_case("timestamps") {
+            self.ignore_fields.extend(TIMESTAMP_FIELDS.iter().map(|f| f.to_string()));
+            return;
+        }
+        let typ_sub = name_to_typ_sub(&what.to_uppercase());
+        if typ_sub != (0, 0) {
+            self.ignore_records.insert(typ_sub);
+        } else {
+            self.ignore_fields.insert(what.to_lowercase());
+        }
+    }
+
+    fn floats_equal(&self, left: f64, right: f64) -> bool {
+        let delta = (left - right).abs();
+        delta <= self.abs_tolerance || delta <= self.rel_tolerance * left.abs().max(right.abs())
+    }
+}
+
+/// A field that differs between the two files.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub left: Value,
+    pub right: Value,
+}
+
+/// A difference between the two files, `key` identifies the record (eg: `PTR head 1 site 2 test 100 #3`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Difference {
+    /// The record is only in the left file.
+    Removed { key: String },
+    /// The record is only in the right file.
+    Added { key: String },
+    /// The record is in both files, but some fields differ.
+    Changed { key: String, changes: Vec<FieldChange> },
+}
+
+/// The outcome of a [`diff_files`] run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffReport {
+    /// The number of records found in both files.
+    pub compared: u64,
+    pub differences: Vec<Difference>,
+    /// The number of changes per `RECORD.field`.
+    pub field_changes: BTreeMap<String, u64>,
+    /// The test numbers only found in the right file.
+    pub added_tests: BTreeSet<u32>,
+    /// The test numbers only found in the left file.
+    pub removed_tests: BTreeSet<u32>,
+    /// The test numbers of which a PTR/MPR limit changed.
+    pub limit_changes: BTreeSet<u32>,
+    /// The number of parts (PRR) of which the hard or soft bin changed.
+    pub bin_changes: u64,
+}
+
+impl DiffReport {
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    fn count(&self, kind: fn(&Difference) -> bool) -> usize {
+        self.differences.iter().filter(|d| kind(d)).count()
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let changed = self.count(|d| matches!(d, Difference::Changed { .. }));
+        writeln!(f, "Records compared : {}", self.compared)?;
+        writeln!(f, "Records changed  : {}", changed)?;
+        writeln!(f, "Records removed  : {}", self.count(|d| matches!(d, Difference::Removed { .. })))?;
+        writeln!(f, "Records added    : {}", self.count(|d| matches!(d, Difference::Added { .. })))?;
+        writeln!(f, "Tests removed    : {}", join(&self.removed_tests))?;
+        writeln!(f, "Tests added      : {}", join(&self.added_tests))?;
+        writeln!(f, "Limits changed   : {}", join(&self.limit_changes))?;
+        writeln!(f, "Bins changed     : {} parts", self.bin_changes)?;
+        if !self.field_changes.is_empty() {
+            writeln!(f, "Changed fields   :")?;
+            for (field, count) in &self.field_changes {
+                writeln!(f, "   {:<20} : {}", field, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn join(tests: &BTreeSet<u32>) -> String {
+    if tests.is_empty() {
+        return "-".to_string();
+    }
+    tests.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Removed { key } => writeln!(f, "- {}", key),
+            Difference::Added { key } => writeln!(f, "+ {}", key),
+            Difference::Changed { key, changes } => {
+                writeln!(f, "~ {}", key)?;
+                for change in changes {
+                    writeln!(f, "     {:<8} : {} → {}", change.field.to_uppercase(), change.left, change.right)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A record of a file, with the key it is paired on.
+struct Keyed {
+    key: String,
+    offset: usize,
+    test_num: Option<u32>,
+}
+
+fn test_num(record: &V4) -> Option<u32> {
+    match record {
+        V4::PTR(r) => Some(r.test_num.0),
+        V4::MPR(r) => Some(r.test_num.0),
+        V4::FTR(r) => Some(r.test_num.0),
+        V4::TSR(r) => Some(r.test_num.0),
+        _ => None,
+    }
+}
+
+/// The identity of a record within its kind, without the occurrence count.
+fn identity(record: &V4) -> String {
+    let name = record.name();
+    match record {
+        V4::PTR(r) => format!("{} head {} site {} test {}", name, r.head_num.0, r.site_num.0, r.test_num.0),
+        V4::MPR(r) => format!("{} head {} site {} test {}", name, r.head_num.0, r.site_num.0, r.test_num.0),
+        V4::FTR(r) => format!("{} head {} site {} test {}", name, r.head_num.0, r.site_num.0, r.test_num.0),
+        V4::TSR(r) => format!("{} head {} site {} test {}", name, r.head_num.0, r.site_num.0, r.test_num.0),
+        V4::HBR(r) => format!("{} head {} site {} bin {}", name, r.head_num.0, r.site_num.0, r.hbin_num.0),
+        V4::SBR(r) => format!("{} head {} site {} bin {}", name, r.head_num.0, r.site_num.0, r.sbin_num.0),
+        V4::PCR(r) => format!("{} head {} site {}", name, r.head_num.0, r.site_num.0),
+        V4::PIR(r) => format!("{} head {} site {}", name, r.head_num.0, r.site_num.0),
+        V4::PRR(r) => format!("{} head {} site {}", name, r.head_num.0, r.site_num.0),
+        V4::PMR(r) => format!("{} index {}", name, r.pmr_index.0),
+        V4::PGR(r) => format!("{} index {}", name, r.grp_indx.0),
+        _ => name,
+    }
+}
+
+/// The identity of a record within a part (head and site are implied by the part).
+fn part_identity(record: &V4) -> String {
+    match test_num(record) {
+        Some(test_num) => format!("{} test {}", record.name(), test_num),
+        None => record.name(),
+    }
+}
+
+fn numbered(counts: &mut HashMap<String, u32>, identity: String) -> String {
+    let count = counts.entry(identity.clone()).or_insert(0);
+    *count += 1;
+    format!("{} #{}", identity, count)
+}
+
+/// Gives every record of the file a key to pair it with a record of the other file.
+fn keyed_records(bytes: &[u8], endian: Endian, config: &DiffConfig) -> Vec<Keyed> {
+    let mut keyed: Vec<Keyed> = Vec::new();
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    let mut part_counts: HashMap<String, u32> = HashMap::new();
+    // (head, site) → indices in `keyed` of the records of the part being tested
+    let mut open_parts: HashMap<(u8, u8), Vec<usize>> = HashMap::new();
+    let mut wafer_id = String::new();
+    let offset = &mut 0;
+    loop {
+        let start = *offset;
+        let record = match bytes.read_with::<V4>(offset, endian) {
+            Ok(record) => record,
+            Err(_) => break,
+        };
+        if config.ignore_records.contains(&record.rec_typ_sub()) {
+            continue;
+        }
+        if let V4::WIR(ref wir) = record {
+            wafer_id = wir.wafer_id.to_string();
+        }
+        let part_key = match &record {
+            V4::PIR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::PRR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::PTR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::MPR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::FTR(r) => Some((r.head_num.0, r.site_num.0)),
+            _ => None,
+        };
+        let index = keyed.len();
+        keyed.push(Keyed {
+            key: String::new(),
+            offset: start,
+            test_num: test_num(&record),
+        });
+        let part_key = match part_key {
+            Some(part_key) if config.alignment != Alignment::Records => part_key,
+            _ => {
+                keyed[index].key = numbered(&mut counts, identity(&record));
+                continue;
+            }
+        };
+        match record {
+            V4::PIR(_) => {
+                // a part on the same head and site without a PRR is closed as is
+                if let Some(members) = open_parts.insert(part_key, vec![index]) {
+                    key_unclosed(&mut keyed, members, &mut counts, bytes, endian);
+                }
+            }
+            V4::PRR(ref prr) if open_parts.contains_key(&part_key) => {
+                let mut members = open_parts.remove(&part_key).unwrap();
+                members.push(index);
+                let part = match config.alignment {
+                    Alignment::PartId if !prr.part_id.0.is_empty() => format!("part {}", prr.part_id),
+                    Alignment::XY => format!("wafer {} x {} y {}", wafer_id, prr.x_coord.0, prr.y_coord.0),
+                    _ => format!("part at head {} site {}", prr.head_num.0, prr.site_num.0),
+                };
+                let part = numbered(&mut part_counts, part);
+                let mut member_counts: HashMap<String, u32> = HashMap::new();
+                for member in members {
+                    let record = bytes.read_with::<V4>(&mut keyed[member].offset.clone(), endian).unwrap();
+                    keyed[member].key = format!("{} | {}", part, numbered(&mut member_counts, part_identity(&record)));
+                }
+            }
+            _ => match open_parts.get_mut(&part_key) {
+                Some(members) => members.push(index),
+                None => keyed[index].key = numbered(&mut counts, identity(&record)),
+            },
+        }
+    }
+    // parts that never got a PRR
+    for members in open_parts.into_values() {
+        key_unclosed(&mut keyed, members, &mut counts, bytes, endian);
+    }
+    keyed
+}
+
+/// Keys the records of a part that never got a PRR on their own, as records outside a part.
+fn key_unclosed(
+    keyed: &mut [Keyed],
+    members: Vec<usize>,
+    counts: &mut HashMap<String, u32>,
+    bytes: &[u8],
+    endian: Endian,
+) {
+    for member in members {
+        let record = bytes.read_with::<V4>(&mut keyed[member].offset.clone(), endian).unwrap();
+        keyed[member].key = numbered(counts, identity(&record));
+    }
+}
+
+fn values_equal(left: &Value, right: &Value, config: &DiffConfig) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) if l.is_f64() || r.is_f64() => {
+            config.floats_equal(l.as_f64().unwrap_or(f64::NAN), r.as_f64().unwrap_or(f64::NAN))
+        }
+        (Value::Array(l), Value::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| values_equal(l, r, config))
+        }
+        (l, r) => l == r,
+    }
+}
+
+/// Lists the fields that differ between two records of the same type.
+pub fn compare_records(left: &V4, right: &V4, config: &DiffConfig) -> Vec<FieldChange> {
+    let left = left.to_json();
+    let right = right.to_json();
+    let (left, right) = match (left.as_object(), right.as_object()) {
+        (Some(l), Some(r)) => (l.clone(), r.clone()),
+        _ if left == right => return vec![],
+        _ => {
+            return vec![FieldChange {
+                field: "contents".to_string(),
+                left,
+                right,
+            }]
+        }
+    };
+    let mut changes = Vec::new();
+    for (field, left_value) in left {
+        if config.ignore_fields.contains(&field) {
+            continue;
+        }
+        let right_value = right.get(&field).cloned().unwrap_or(Value::Null);
+        if !values_equal(&left_value, &right_value, config) {
+            changes.push(FieldChange {
+                field,
+                left: left_value,
+                right: right_value,
+            });
+        }
+    }
+    changes
+}
+
+/// Compares two STDF files semantically.
+///
+/// The records are paired according to `config.alignment` and compared field by field,
+/// floats within the configured tolerances are considered equal. Records of one file
+/// that have no counterpart in the other are reported as added or removed.
+///
+/// # Arguments
+///
+/// * `left` - The reference STDF file
+/// * `right` - The STDF file to compare with the reference
+/// * `config` - The alignment, tolerances and what to ignore
+///
+/// # Returns
+///
+/// A [`DiffReport`] with the differences and a summary of them.
+///
+/// # Errors
+///
+/// This function will return an error if one of the files is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::Result;
+/// use stdf::diff::{diff_files, Alignment, DiffConfig};
+///
+/// fn main() -> Result<()> {
+///     let mut left = File::open("lot1_v1.std")?;
+///     let mut right = File::open("lot1_v2.std")?;
+///     let mut config = DiffConfig { alignment: Alignment::PartId, abs_tolerance: 1e-6, ..Default::default() };
+///     config.ignore("timestamps");
+///     config.ignore("ATR");
+///     let report = diff_files(&mut left, &mut right, &config)?;
+///     print!("{}", report);
+///     Ok(())
+/// }
+/// ```
+pub fn diff_files(left: &mut File, right: &mut File, config: &DiffConfig) -> Result<DiffReport> {
+    let left_endian = endian_of(left)?;
+    let right_endian = endian_of(right)?;
+    let left_mmap = unsafe { MmapOptions::new().map(&*left)? };
+    let right_mmap = unsafe { MmapOptions::new().map(&*right)? };
+    let left_bytes = &left_mmap[..];
+    let right_bytes = &right_mmap[..];
+
+    let left_records = keyed_records(left_bytes, left_endian, config);
+    let right_records = keyed_records(right_bytes, right_endian, config);
+
+    let left_tests: BTreeSet<u32> = left_records.iter().filter_map(|k| k.test_num).collect();
+    let right_tests: BTreeSet<u32> = right_records.iter().filter_map(|k| k.test_num).collect();
+    let mut report = DiffReport {
+        added_tests: right_tests.difference(&left_tests).copied().collect(),
+        removed_tests: left_tests.difference(&right_tests).copied().collect(),
+        ..Default::default()
+    };
+
+    let mut unmatched: HashMap<&str, &Keyed> = left_records.iter().map(|k| (k.key.as_str(), k)).collect();
+    for right_record in right_records.iter() {
+        let left_record = match unmatched.remove(right_record.key.as_str()) {
+            Some(left_record) => left_record,
+            None => {
+                report.differences.push(Difference::Added {
+                    key: right_record.key.clone(),
+                });
+                continue;
+            }
+        };
+        report.compared += 1;
+        let l = left_bytes.read_with::<V4>(&mut left_record.offset.clone(), left_endian).unwrap();
+        let r = right_bytes.read_with::<V4>(&mut right_record.offset.clone(), right_endian).unwrap();
+        let changes = compare_records(&l, &r, config);
+        if changes.is_empty() {
+            continue;
+        }
+        let name = l.name();
+        for change in changes.iter() {
+            *report.field_changes.entry(format!("{}.{}", name, change.field)).or_insert(0) += 1;
+        }
+        if matches!(l, V4::PTR(_) | V4::MPR(_)) && changes.iter().any(|c| LIMIT_FIELDS.contains(&c.field.as_str())) {
+            report.limit_changes.insert(left_record.test_num.unwrap());
+        }
+        if matches!(l, V4::PRR(_)) && changes.iter().any(|c| c.field == "hard_bin" || c.field == "soft_bin") {
+            report.bin_changes += 1;
+        }
+        report.differences.push(Difference::Changed {
+            key: right_record.key.clone(),
+            changes,
+        });
+    }
+    // report the removed records in the order of the left file
+    for left_record in left_records.iter() {
+        if unmatched.contains_key(left_record.key.as_str()) {
+            report.differences.push(Difference::Removed {
+                key: left_record.key.clone(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+fn endian_of(file: &mut File) -> Result<Endian> {
+    match get_endian_from_file(file)? {
+        Some(endian) => Ok(endian),
+        None => Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::PTR;
+    use crate::types::*;
+    use std::io::{Seek, SeekFrom, Write};
+    use tempfile::tempfile;
+
+    /// Copies test.std, passing every record through `edit`.
+    fn edited_copy(edit: fn(V4) -> Option<V4>) -> File {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let endian = get_endian_from_file(&mut input).unwrap().unwrap();
+        let mmap = unsafe { MmapOptions::new().map(&input).unwrap() };
+        let bytes = &mmap[..];
+        let mut output = tempfile().unwrap();
+        let offset = &mut 0;
+        loop {
+            let start = *offset;
+            match bytes.read_with::<V4>(offset, endian) {
+                Ok(record) => {
+                    if let Some(record) = edit(record) {
+                        let original = bytes.read_with::<V4>(&mut start.clone(), endian).unwrap();
+                        if record.to_json() == original.to_json() {
+                            output.write_all(&bytes[start..*offset]).unwrap();
+                        } else {
+                            output.write_all(&record.to_bytes(endian).unwrap()).unwrap();
+                        }
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        output.seek(SeekFrom::Start(0)).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_identical() {
+        let mut left = File::open("tests/fixtures/test.std").unwrap();
+        let mut right = File::open("tests/fixtures/test.std").unwrap();
+        for alignment in [Alignment::Records, Alignment::PartId, Alignment::XY] {
+            let config = DiffConfig {
+                alignment,
+                ..Default::default()
+            };
+            let report = diff_files(&mut left, &mut right, &config).unwrap();
+            assert!(report.is_identical());
+            assert_eq!(report.compared, 918);
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut left = File::open("tests/fixtures/test.std").unwrap();
+        let mut right = edited_copy(|record| match record {
+            V4::ATR(_) => None,
+            V4::MIR(mut mir) => {
+                mir.start_t = U4E(mir.start_t.0 + 60);
+                Some(V4::MIR(mir))
+            }
+            V4::PTR(ptr) if ptr.head_num.0 == 0 && ptr.site_num.0 == 1 => Some(V4::PTR(PTR {
+                result: R4(ptr.result.0 * 1.000_001),
+                hi_limit: R4(if ptr.hi_limit.0.is_nan() { f32::NAN } else { ptr.hi_limit.0 + 1.0 }),
+                ..ptr
+            })),
+            record => Some(record),
+        });
+
+        let report = diff_files(&mut left, &mut right, &DiffConfig::default()).unwrap();
+        assert_eq!(report.count(|d| matches!(d, Difference::Removed { .. })), 1);
+        assert_eq!(report.field_changes["MIR.start_t"], 1);
+        assert!(report.field_changes["PTR.result"] > 0);
+        assert!(!report.limit_changes.is_empty());
+        assert!(report.added_tests.is_empty() && report.removed_tests.is_empty());
+
+        let mut config = DiffConfig {
+            alignment: Alignment::PartId,
+            rel_tolerance: 1e-5,
+            ..Default::default()
+        };
+        config.ignore("timestamps");
+        config.ignore("atr");
+        config.ignore("hi_limit");
+        let report = diff_files(&mut left, &mut right, &config).unwrap();
+        assert!(report.is_identical(), "{:?}", report.differences.first());
+    }
+
+    #[test]
+    fn test_part_without_prr() {
+        // part 1 (head 0 site 0) has no PRR, the next PIR on that site reopens the site
+        let mut file = edited_copy(|record| match record {
+            V4::PRR(ref prr) if prr.part_id.0 == b"1" => None,
+            record => Some(record),
+        });
+        let endian = get_endian_from_file(&mut file).unwrap().unwrap();
+        let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+        for alignment in [Alignment::Records, Alignment::PartId, Alignment::XY] {
+            let config = DiffConfig {
+                alignment,
+                ..Default::default()
+            };
+            let keyed = keyed_records(&mmap[..], endian, &config);
+            let keys: HashSet<&str> = keyed.iter().map(|keyed| keyed.key.as_str()).collect();
+            assert!(!keys.contains(""));
+            assert_eq!(keys.len(), keyed.len());
+        }
+    }
+
+    #[test]
+    fn test_removed_tests_and_bins() {
+        let mut left = File::open("tests/fixtures/test.std").unwrap();
+        let mut right = edited_copy(|record| match record {
+            V4::PTR(ref ptr) if ptr.test_num.0 % 2 == 1 => None,
+            V4::TSR(ref tsr) if tsr.test_num.0 % 2 == 1 => None,
+            V4::PRR(mut prr) => {
+                prr.soft_bin = U2(prr.soft_bin.0 + 1);
+                Some(V4::PRR(prr))
+            }
+            record => Some(record),
+        });
+        let config = DiffConfig {
+            alignment: Alignment::PartId,
+            ..Default::default()
+        };
+        let report = diff_files(&mut left, &mut right, &config).unwrap();
+        assert!(!report.removed_tests.is_empty());
+        assert!(report.removed_tests.iter().all(|t| t % 2 == 1));
+        assert_eq!(report.bin_changes, 22);
+        assert_eq!(report.field_changes["PRR.soft_bin"], 22);
+    }
+}
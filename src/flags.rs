@@ -0,0 +1,279 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::types::{B1, C1};
+
+macro_rules! bit_flags {
+    ($name:ident { $($bit:literal => $predicate:ident, $text:literal;)* }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+        pub struct $name(pub u8);
+
+        impl $name {
+            $(
+                #[doc = $text]
+                pub fn $predicate(&self) -> bool {
+                    self.0 & (1 << $bit) != 0
+                }
+            )*
+
+            /// The descriptions of the bits that are set.
+            pub fn descriptions(&self) -> Vec<&'static str> {
+                let mut descriptions = Vec::new();
+                $(
+                    if self.$predicate() {
+                        descriptions.push($text);
+                    }
+                )*
+                descriptions
+            }
+        }
+
+        impl From<B1> for $name {
+            fn from(flag: B1) -> Self {
+                $name(flag.0)
+            }
+        }
+
+        impl From<u8> for $name {
+            fn from(flag: u8) -> Self {
+                $name(flag)
+            }
+        }
+    };
+}
+
+bit_flags!(TestFlag {
+    0 => alarm, "Alarm";
+    1 => result_invalid, "RESULT not valid";
+    2 => result_unreliable, "RESULT is unreliable";
+    3 => timeout, "Timeout";
+    4 => test_not_executed, "Test not executed";
+    5 => test_aborted, "Test aborted";
+    6 => pass_fail_invalid, "No pass/fail indication";
+    7 => failed, "Test failed";
+});
+
+impl TestFlag {
+    /// `None` when the test was not executed or carries no pass/fail indication.
+    pub fn passed(&self) -> Option<bool> {
+        if self.test_not_executed() || self.pass_fail_invalid() {
+            None
+        } else {
+            Some(!self.failed())
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        self.passed() == Some(true)
+    }
+
+    pub fn is_fail(&self) -> bool {
+        self.passed() == Some(false)
+    }
+}
+
+/// The TEST_FLG of a PTR, MPR or FTR, spelled out as `(info, ...) → PASS|FAIL|?`.
+impl fmt::Display for TestFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info: Vec<&str> = self
+            .descriptions()
+            .into_iter()
+            .filter(|text| !matches!(*text, "No pass/fail indication" | "Test failed"))
+            .collect();
+        let verdict = if self.pass_fail_invalid() {
+            "?"
+        } else if self.failed() {
+            "FAIL"
+        } else {
+            "PASS"
+        };
+        write!(f, "({}) → {}", info.join(", "), verdict)
+    }
+}
+
+bit_flags!(ParmFlag {
+    0 => scale_error, "Scale error";
+    1 => drift_error, "Drift error";
+    2 => oscillation, "Oscillation detected";
+    3 => above_hi_limit, "RESULT > HI_LIMIT";
+    4 => below_lo_limit, "RESULT < LO_LIMIT";
+    5 => passed_alternate_limits, "Passed alternate limits";
+    6 => lo_limit_inclusive, "LO_LIMIT ≤ RESULT passes";
+    7 => hi_limit_inclusive, "RESULT ≤ HI_LIMIT passes";
+});
+
+/// The PARM_FLG of a PTR or MPR, spelled out as `(info, ...)`.
+impl fmt::Display for ParmFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({})", self.descriptions().join(", "))
+    }
+}
+
+bit_flags!(OptFlag {
+    0 => res_scal_invalid, "RES_SCAL is invalid";
+    2 => no_lo_spec, "No low spec limit";
+    3 => no_hi_spec, "No high spec limit";
+    4 => lo_limit_invalid, "LO_LIMIT and LLM_SCAL are invalid";
+    5 => hi_limit_invalid, "HI_LIMIT and HLM_SCAL are invalid";
+    6 => no_lo_limit, "no LO_LIMIT";
+    7 => no_hi_limit, "no HI_LIMIT";
+});
+
+impl OptFlag {
+    /// START_IN and INCR_IN are invalid, for an MPR only (the bit is reserved in a PTR).
+    pub fn start_in_invalid(&self) -> bool {
+        self.0 & 0b0000_0010 != 0
+    }
+}
+
+/// The OPT_FLAG of a PTR or MPR, spelled out as `(info, ...)`.
+impl fmt::Display for OptFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({})", self.descriptions().join(", "))
+    }
+}
+
+bit_flags!(FtrOptFlag {
+    0 => cycl_cnt_invalid, "CYCL_CNT is invalid";
+    1 => rel_vadr_invalid, "REL_VADR is invalid";
+    2 => rept_cnt_invalid, "REPT_CNT is invalid";
+    3 => num_fail_invalid, "NUM_FAIL is invalid";
+    4 => fail_ad_invalid, "XFAIL_AD and YFAIL_AD are invalid";
+    5 => vect_off_invalid, "VECT_OFF is invalid";
+});
+
+/// The OPT_FLAG of an FTR, spelled out as `(info, ...)`.
+impl fmt::Display for FtrOptFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({})", self.descriptions().join(", "))
+    }
+}
+
+bit_flags!(PartFlag {
+    0 => is_retest_by_id, "Retest of PART_ID";
+    1 => is_retest_by_xy, "Retest of X_COORD/Y_COORD";
+    2 => abnormal_end, "Abnormal end of testing";
+    3 => failed, "Part failed";
+    4 => pass_fail_invalid, "No pass/fail indication";
+});
+
+impl PartFlag {
+    /// `None` when the PRR carries no pass/fail indication.
+    pub fn passed(&self) -> Option<bool> {
+        if self.pass_fail_invalid() {
+            None
+        } else {
+            Some(!self.failed())
+        }
+    }
+
+    pub fn is_pass(&self) -> bool {
+        self.passed() == Some(true)
+    }
+
+    pub fn is_fail(&self) -> bool {
+        self.passed() == Some(false)
+    }
+
+    pub fn is_retest(&self) -> bool {
+        self.is_retest_by_id() || self.is_retest_by_xy()
+    }
+}
+
+/// The PART_FLG of a PRR, spelled out as `→ PASS|FAIL|? (info, ...)`.
+impl fmt::Display for PartFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.passed() {
+            None => "?",
+            Some(true) => "PASS",
+            Some(false) => "FAIL",
+        };
+        write!(f, "→ {}", verdict)?;
+        let info: Vec<&str> = self
+            .descriptions()
+            .into_iter()
+            .filter(|text| !matches!(*text, "No pass/fail indication" | "Part failed"))
+            .collect();
+        if !info.is_empty() {
+            write!(f, " ({})", info.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A wafer direction, as used by the WF_FLAT, POS_X and POS_Y fields of a WCR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Orientation {
+    Up,
+    Down,
+    Left,
+    Right,
+    Unknown,
+}
+
+impl From<C1> for Orientation {
+    fn from(c: C1) -> Self {
+        match c.0.to_ascii_uppercase() {
+            b'U' => Orientation::Up,
+            b'D' => Orientation::Down,
+            b'L' => Orientation::Left,
+            b'R' => Orientation::Right,
+            _ => Orientation::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Orientation::Up => "up",
+            Orientation::Down => "down",
+            Orientation::Left => "left",
+            Orientation::Right => "right",
+            Orientation::Unknown => "unknown",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_flag() {
+        let flag = TestFlag::from(B1(0b1010_0001));
+        assert!(flag.alarm() && flag.test_aborted() && flag.is_fail());
+        assert_eq!(flag.to_string(), "(Alarm, Test aborted) → FAIL");
+        assert_eq!(TestFlag(0b0100_0000).passed(), None);
+        assert_eq!(TestFlag(0b0001_0000).passed(), None);
+        assert!(TestFlag(0).is_pass());
+        assert_eq!(TestFlag(0).to_string(), "() → PASS");
+    }
+
+    #[test]
+    fn test_opt_flag() {
+        let flag = OptFlag(0b1101_0001);
+        assert!(flag.res_scal_invalid() && flag.lo_limit_invalid() && flag.no_lo_limit() && flag.no_hi_limit());
+        assert!(!flag.hi_limit_invalid() && !flag.no_lo_spec());
+        assert_eq!(FtrOptFlag(0b0000_1001).descriptions(), vec!["CYCL_CNT is invalid", "NUM_FAIL is invalid"]);
+        assert_eq!(ParmFlag(0b0000_1000).to_string(), "(RESULT > HI_LIMIT)");
+    }
+
+    #[test]
+    fn test_part_flag() {
+        let flag = PartFlag(0b0000_1110);
+        assert!(flag.is_retest_by_xy() && flag.is_retest() && !flag.is_retest_by_id() && flag.is_fail());
+        assert_eq!(flag.to_string(), "→ FAIL (Retest of X_COORD/Y_COORD, Abnormal end of testing)");
+        assert_eq!(PartFlag(0b0001_1000).to_string(), "→ ?");
+        assert!(PartFlag(0).is_pass());
+    }
+
+    #[test]
+    fn test_orientation() {
+        assert_eq!(Orientation::from(C1(b'L')), Orientation::Left);
+        assert_eq!(Orientation::from(C1(b'd')), Orientation::Down);
+        assert_eq!(Orientation::from(C1(b' ')), Orientation::Unknown);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+
+use byte::BytesExt;
+use memmap::MmapOptions;
+use serde::Serialize;
+
+use crate::get_endian_from_file;
+use crate::pins::PinMap;
+use crate::records::{FTR, V4};
+
+/// A pin of an FTR with its name and, for `rtn_indx`/`pgm_indx`, its state nibble.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FtrPin {
+    pub index: u16,
+    /// The pin name from the PMR, `#<index>` if the pin is unknown.
+    pub name: String,
+    pub state: Option<u8>,
+}
+
+/// An FTR with its optional data checked against `opt_flag` and its pins named.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedFtr {
+    pub test_num: u32,
+    pub head_num: u8,
+    pub site_num: u8,
+    pub test_txt: String,
+    pub passed: Option<bool>,
+    pub vect_nam: String,
+    pub time_set: String,
+    pub op_code: String,
+    pub patg_num: Option<u8>,
+    pub cycl_cnt: Option<u32>,
+    pub rel_vadr: Option<u32>,
+    pub rept_cnt: Option<u32>,
+    pub num_fail: Option<u32>,
+    pub xfail_ad: Option<i32>,
+    pub yfail_ad: Option<i32>,
+    pub vect_off: Option<i16>,
+    /// The pins flagged in `fail_pin`.
+    pub fail_pins: Vec<FtrPin>,
+    pub rtn_pins: Vec<FtrPin>,
+    pub pgm_pins: Vec<FtrPin>,
+}
+
+fn named(pin_map: &PinMap, head_num: u8, site_num: u8, index: u16, state: Option<u8>) -> FtrPin {
+    FtrPin {
+        index,
+        name: pin_map
+            .name(head_num, site_num, index)
+            .unwrap_or_else(|| format!("#{}", index)),
+        state,
+    }
+}
+
+impl DecodedFtr {
+    /// Decodes `ftr`, the pin names are looked up in `pin_map` for the head/site of the FTR.
+    pub fn new(ftr: &FTR, pin_map: &PinMap) -> Self {
+        let (head_num, site_num) = (ftr.head_num.0, ftr.site_num.0);
+        let opt_flag = ftr.opt_flags();
+        let pins = |indx: &[crate::types::U2], stat: &[crate::types::N1]| -> Vec<FtrPin> {
+            indx.iter()
+                .enumerate()
+                .map(|(i, index)| named(pin_map, head_num, site_num, index.0, stat.get(i).map(|s| s.0)))
+                .collect()
+        };
+        DecodedFtr {
+            test_num: ftr.test_num.0,
+            head_num,
+            site_num,
+            test_txt: ftr.test_txt.to_string(),
+            passed: ftr.test_flags().passed(),
+            vect_nam: ftr.vect_nam.to_string(),
+            time_set: ftr.time_set.to_string(),
+            op_code: ftr.op_code.to_string(),
+            patg_num: if ftr.patg_num.0 == 0xff { None } else { Some(ftr.patg_num.0) },
+            cycl_cnt: (!opt_flag.cycl_cnt_invalid()).then_some(ftr.cycl_cnt.0),
+            rel_vadr: (!opt_flag.rel_vadr_invalid()).then_some(ftr.rel_vadr.0),
+            rept_cnt: (!opt_flag.rept_cnt_invalid()).then_some(ftr.rept_cnt.0),
+            num_fail: (!opt_flag.num_fail_invalid()).then_some(ftr.num_fail.0),
+            xfail_ad: (!opt_flag.fail_ad_invalid()).then_some(ftr.xfail_ad.0),
+            yfail_ad: (!opt_flag.fail_ad_invalid()).then_some(ftr.yfail_ad.0),
+            vect_off: (!opt_flag.vect_off_invalid()).then_some(ftr.vect_off.0),
+            fail_pins: ftr.fail_pin
+                .set_bits()
+                .map(|index| named(pin_map, head_num, site_num, index, None))
+                .collect(),
+            rtn_pins: pins(&ftr.rtn_indx, &ftr.rtn_stat),
+            pgm_pins: pins(&ftr.pgm_indx, &ftr.pgm_stat),
+        }
+    }
+}
+
+fn or_slash<T: fmt::Display>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "∕".to_string(),
+    }
+}
+
+fn or_slash_str(value: &str) -> String {
+    if value.is_empty() {
+        "∕".to_string()
+    } else {
+        format!("'{}'", value)
+    }
+}
+
+impl fmt::Display for DecodedFtr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let result = match self.passed {
+            Some(true) => "PASS",
+            Some(false) => "FAIL",
+            None => "?",
+        };
+        writeln!(f, "FTR {} '{}' : HEAD {} SITE {} → {}", self.test_num, self.test_txt, self.head_num, self.site_num, result)?;
+        writeln!(f, "   VECT_NAM : {}", or_slash_str(&self.vect_nam))?;
+        writeln!(f, "   TIME_SET : {}", or_slash_str(&self.time_set))?;
+        writeln!(f, "   OP_CODE  : {}", or_slash_str(&self.op_code))?;
+        writeln!(f, "   PATG_NUM : {}", or_slash(&self.patg_num))?;
+        writeln!(f, "   CYCL_CNT : {}", or_slash(&self.cycl_cnt))?;
+        writeln!(f, "   REL_VADR : {}", or_slash(&self.rel_vadr))?;
+        writeln!(f, "   REPT_CNT : {}", or_slash(&self.rept_cnt))?;
+        writeln!(f, "   NUM_FAIL : {}", or_slash(&self.num_fail))?;
+        writeln!(f, "   X/YFAIL  : {}, {}", or_slash(&self.xfail_ad), or_slash(&self.yfail_ad))?;
+        writeln!(f, "   VECT_OFF : {}", or_slash(&self.vect_off))?;
+        let names = |pins: &[FtrPin]| -> String {
+            pins.iter()
+                .map(|pin| match pin.state {
+                    Some(state) => format!("{}={:X}", pin.name, state),
+                    None => pin.name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(f, "   FAIL_PIN : {}", names(&self.fail_pins))?;
+        writeln!(f, "   RTN_PINS : {}", names(&self.rtn_pins))?;
+        writeln!(f, "   PGM_PINS : {}", names(&self.pgm_pins))
+    }
+}
+
+/// The failures of one functional test.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TestFailures {
+    pub test_txt: String,
+    pub executions: u64,
+    pub failures: u64,
+    /// The number of failing executions per pin name.
+    pub pins: HashMap<String, u64>,
+}
+
+impl TestFailures {
+    /// The failing pins, most failing first.
+    pub fn pareto(&self) -> Vec<(&str, u64)> {
+        let mut pareto: Vec<(&str, u64)> = self.pins.iter().map(|(pin, count)| (pin.as_str(), *count)).collect();
+        pareto.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        pareto
+    }
+}
+
+/// Collects the pin failures of the functional tests, per test number.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FailPareto {
+    pub tests: BTreeMap<u32, TestFailures>,
+}
+
+impl FailPareto {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, ftr: &DecodedFtr) {
+        let test = self.tests.entry(ftr.test_num).or_default();
+        if test.test_txt.is_empty() {
+            test.test_txt = ftr.test_txt.clone();
+        }
+        test.executions += 1;
+        if ftr.passed != Some(false) {
+            return;
+        }
+        test.failures += 1;
+        for pin in ftr.fail_pins.iter() {
+            *test.pins.entry(pin.name.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// The tests with at least one failure, most failing first.
+    pub fn failing_tests(&self) -> Vec<(u32, &TestFailures)> {
+        let mut tests: Vec<(u32, &TestFailures)> = self
+            .tests
+            .iter()
+            .filter(|(_, test)| test.failures > 0)
+            .map(|(test_num, test)| (*test_num, test))
+            .collect();
+        tests.sort_by(|a, b| b.1.failures.cmp(&a.1.failures).then(a.0.cmp(&b.0)));
+        tests
+    }
+}
+
+/// Decodes all FTRs of an STDF file into a per test pin-failure pareto.
+///
+/// The pin names are taken from the PMR records of the file.
+///
+/// # Arguments
+///
+/// * `file` - The STDF file
+/// * `on_ftr` - Called with every decoded FTR, eg: to list the failing ones
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use stdf::ftr::ftr_report;
+///
+/// let mut file = File::open("tests/fixtures/hatb_hw0_ft_device1_production_tp1.stdf").unwrap();
+/// let pareto = ftr_report(&mut file, |_| {}).unwrap();
+/// for (test_num, test) in pareto.failing_tests() {
+///     println!("{} {} : {:?}", test_num, test.test_txt, test.pareto());
+/// }
+/// ```
+pub fn ftr_report<F: FnMut(&DecodedFtr)>(file: &mut File, mut on_ftr: F) -> Result<FailPareto> {
+    let pin_map = PinMap::from_file(file)?;
+    let endian = match get_endian_from_file(file)? {
+        Some(endian) => endian,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    };
+    let mmap = unsafe { MmapOptions::new().map(&*file)? };
+    let bytes = &mmap[..];
+    let mut pareto = FailPareto::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        if let V4::FTR(ftr) = record {
+            let decoded = DecodedFtr::new(&ftr, &pin_map);
+            on_ftr(&decoded);
+            pareto.add(&decoded);
+        }
+    }
+    Ok(pareto)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::PMR;
+    use crate::types::*;
+
+    fn ftr<'a>(site_num: u8, test_flg: u8, fail_pin: &'a [u8]) -> FTR<'a> {
+        FTR {
+            test_num: U4(42),
+            head_num: U1(1),
+            site_num: U1(site_num),
+            test_flg: B1(test_flg),
+            opt_flag: B1(0b1111_0010),
+            cycl_cnt: U4(1234),
+            rel_vadr: U4(99),
+            rept_cnt: U4(1),
+            num_fail: U4(3),
+            xfail_ad: I4(0),
+            yfail_ad: I4(0),
+            vect_off: I2(0),
+            rtn_icnt: U2(2),
+            pgm_icnt: U2(0),
+            rtn_indx: vec![U2(1), U2(7)],
+            rtn_stat: vec![N1(0x1), N1(0x8)],
+            pgm_indx: vec![],
+            pgm_stat: vec![],
+            fail_pin: Dn(10, fail_pin),
+            vect_nam: Cn(b"scan_chain"),
+            time_set: Cn(b"ts1"),
+            op_code: Cn(b""),
+            test_txt: Cn(b"scan"),
+            alarm_id: Cn(b""),
+            prog_txt: Cn(b""),
+            rslt_txt: Cn(b""),
+            patg_num: U1(0xff),
+            spin_map: Dn(0, b""),
+        }
+    }
+
+    fn pin_map() -> PinMap {
+        let mut pin_map = PinMap::new();
+        for (index, name) in [(1, b"SDI"), (3, b"SDO"), (9, b"CLK")] {
+            pin_map.add_pmr(&PMR {
+                pmr_index: U2(index),
+                chan_typ: U2(0),
+                chan_nam: Cn(b""),
+                phy_nam: Cn(b""),
+                log_nam: Cn(name),
+                head_num: U1(1),
+                site_num: U1(1),
+            });
+        }
+        pin_map
+    }
+
+    #[test]
+    fn test_decode_ftr() {
+        let decoded = DecodedFtr::new(&ftr(1, 0b1000_0000, &[0b0000_1010, 0b0000_0010]), &pin_map());
+        assert_eq!(decoded.passed, Some(false));
+        assert_eq!(decoded.cycl_cnt, Some(1234));
+        assert_eq!(decoded.rel_vadr, None);
+        assert_eq!(decoded.xfail_ad, None);
+        let names: Vec<&str> = decoded.fail_pins.iter().map(|pin| pin.name.as_str()).collect();
+        assert_eq!(names, vec!["SDI", "SDO", "CLK"]);
+        assert_eq!(decoded.rtn_pins[1], FtrPin { index: 7, name: "#7".to_string(), state: Some(8) });
+        assert!(decoded.to_string().contains("FAIL_PIN : SDI, SDO, CLK"));
+    }
+
+    #[test]
+    fn test_fail_pareto() {
+        let pin_map = pin_map();
+        let mut pareto = FailPareto::new();
+        pareto.add(&DecodedFtr::new(&ftr(1, 0b1000_0000, &[0b0000_1010, 0]), &pin_map));
+        pareto.add(&DecodedFtr::new(&ftr(1, 0b1000_0000, &[0b0000_1000, 0]), &pin_map));
+        pareto.add(&DecodedFtr::new(&ftr(1, 0, &[0, 0]), &pin_map));
+        let tests = pareto.failing_tests();
+        assert_eq!(tests.len(), 1);
+        let (test_num, test) = tests[0];
+        assert_eq!((test_num, test.executions, test.failures), (42, 3, 2));
+        assert_eq!(test.pareto(), vec![("SDO", 2), ("SDI", 1)]);
+    }
+
+    #[test]
+    fn test_ftr_report() {
+        let mut file = File::open("tests/fixtures/hatb_hw0_ft_device1_production_tp1.stdf").unwrap();
+        let mut count = 0;
+        let pareto = ftr_report(&mut file, |_| count += 1).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(pareto.tests.values().map(|t| t.executions).sum::<u64>(), 2);
+    }
+}
@@ -0,0 +1,695 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Result;
+use std::path::Path;
+
+use byte::BytesExt;
+
+use crate::records::V4;
+use crate::table::{map, PartRow, PartRows, TestColumn};
+
+/// The layout of the HDF5 file written by [`write_hdf5`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hdf5Layout {
+    /// The lot (MIR fields) as attributes of the root, one group per wafer with a `parts` group
+    /// (one dataset per part field) and a `tests` group (one dataset per test, with the test
+    /// name, units and limits as attributes).
+    #[default]
+    Tree,
+    /// The layout of Semi-ATE Metis: a group per lot (named after MIR.LOT_ID, with the MIR
+    /// fields as attributes) holding a group per wafer, each with a `data` matrix
+    /// (parts × tests) and the part and test fields as 1-D datasets along its rows and columns.
+    Metis,
+}
+
+/// The parts of a wafer (or of the lot, for packaged parts), buffered until the file is read.
+#[derive(Debug, Default)]
+struct Wafer {
+    id: String,
+    start_t: Option<u32>,
+    finish_t: Option<u32>,
+    part_id: Vec<String>,
+    head_num: Vec<u8>,
+    site_num: Vec<u8>,
+    x: Vec<i16>,
+    y: Vec<i16>,
+    hard_bin: Vec<u16>,
+    soft_bin: Vec<u16>,
+    test_t: Vec<u32>,
+    passed: Vec<i8>,
+    /// (column, value) of the tests of each part, the value of an FTR is 1.0 if it passed.
+    values: Vec<Vec<(usize, f32)>>,
+}
+
+impl Wafer {
+    fn push(&mut self, row: PartRow, columns: &[TestColumn]) {
+        self.part_id.push(row.part_id);
+        self.head_num.push(row.head_num);
+        self.site_num.push(row.site_num);
+        self.x.push(row.x.unwrap_or(i16::MIN));
+        self.y.push(row.y.unwrap_or(i16::MIN));
+        self.hard_bin.push(row.hard_bin);
+        self.soft_bin.push(row.soft_bin.unwrap_or(u16::MAX));
+        self.test_t.push(row.test_t.unwrap_or(0));
+        self.passed.push(row.passed.map_or(-1, i8::from));
+        let values = row
+            .values
+            .iter()
+            .filter_map(|value| match columns[value.column].rec.as_str() {
+                "FTR" => value.passed.map(|passed| (value.column, f32::from(u8::from(passed)))),
+                _ => value.result.map(|result| (value.column, result)),
+            })
+            .collect();
+        self.values.push(values);
+    }
+
+    /// The parts × tests matrix in row-major order, NaN where a part was not tested.
+    fn matrix(&self, columns: usize) -> Vec<f32> {
+        let mut matrix = vec![f32::NAN; self.values.len() * columns];
+        for (row, values) in self.values.iter().enumerate() {
+            for (column, value) in values {
+                matrix[row * columns + column] = *value;
+            }
+        }
+        matrix
+    }
+
+    /// The group name of the wafer, parts tested without a WIR go in `no_wafer`.
+    fn name(&self) -> String {
+        match self.id.as_str() {
+            "" => "no_wafer".to_string(),
+            id => name(id),
+        }
+    }
+
+    fn write_attributes(&self, group: &h5::Handle) -> Result<()> {
+        h5::set_attr_str(group, "wafer_id", &self.id)?;
+        h5::set_attr(group, "part_cnt", self.part_id.len() as u32)?;
+        if let Some(start_t) = self.start_t {
+            h5::set_attr(group, "start_t", start_t)?;
+        }
+        if let Some(finish_t) = self.finish_t {
+            h5::set_attr(group, "finish_t", finish_t)?;
+        }
+        Ok(())
+    }
+
+    fn write_parts(&self, group: &h5::Handle) -> Result<()> {
+        let parts = self.part_id.len();
+        h5::write_strings(group, "part_id", &self.part_id)?;
+        h5::write_dataset(group, "head_num", &[parts], &self.head_num)?;
+        h5::write_dataset(group, "site_num", &[parts], &self.site_num)?;
+        h5::write_dataset(group, "x", &[parts], &self.x)?;
+        h5::write_dataset(group, "y", &[parts], &self.y)?;
+        h5::write_dataset(group, "hard_bin", &[parts], &self.hard_bin)?;
+        h5::write_dataset(group, "soft_bin", &[parts], &self.soft_bin)?;
+        h5::write_dataset(group, "test_t", &[parts], &self.test_t)?;
+        h5::write_dataset(group, "passed", &[parts], &self.passed)?;
+        Ok(())
+    }
+}
+
+/// An HDF5 object name: `/` separates the groups and `.` names the current one.
+fn name(text: &str) -> String {
+    match text.trim().replace('/', "_") {
+        name if name.is_empty() || name == "." || name == ".." => "_".to_string(),
+        name => name,
+    }
+}
+
+/// `name` made unique among `names` with a `_2`, `_3`, ... suffix, as HDF5 fails to create an
+/// object whose name is taken (e.g. by the pin `IO/1` next to the pin `IO_1`).
+fn unique(names: &mut HashSet<String>, name: String) -> String {
+    let mut unique = name.clone();
+    let mut n = 1;
+    while !names.insert(unique.clone()) {
+        n += 1;
+        unique = format!("{}_{}", name, n);
+    }
+    unique
+}
+
+/// The dataset name of a test in the `tests` group of the tree layout,
+/// e.g. `PTR_1000`, or `MPR_2000_<pin>` for a pin of an MPR.
+fn test_name(column: &TestColumn) -> String {
+    match &column.pin {
+        Some(pin) => name(&format!("{}_{}_{}", column.rec, column.test_num, pin)),
+        None => format!("{}_{}", column.rec, column.test_num),
+    }
+}
+
+fn write_test_attributes(dataset: &h5::Handle, column: &TestColumn) -> Result<()> {
+    h5::set_attr_str(dataset, "rec", &column.rec)?;
+    h5::set_attr(dataset, "test_num", column.test_num)?;
+    h5::set_attr_str(dataset, "test_txt", &column.test_txt)?;
+    if let Some(pin) = &column.pin {
+        h5::set_attr_str(dataset, "pin", pin)?;
+    }
+    h5::set_attr_str(dataset, "units", &column.limits.units)?;
+    if let Some(res_scal) = column.limits.res_scal {
+        h5::set_attr(dataset, "res_scal", res_scal)?;
+    }
+    if let Some(lo_limit) = column.limits.lo_limit {
+        h5::set_attr(dataset, "lo_limit", lo_limit)?;
+    }
+    if let Some(hi_limit) = column.limits.hi_limit {
+        h5::set_attr(dataset, "hi_limit", hi_limit)?;
+    }
+    Ok(())
+}
+
+/// Writes the parts and test results of an STDF file as an HDF5 file.
+///
+/// The results are float32 in the base units of the tests (i.e. without RES_SCAL), NaN where a
+/// part was not tested; a functional test (FTR) is 1.0 if it passed and 0.0 if it failed.
+/// Missing values of the part fields are 65535 (`soft_bin`), -32768 (`x`, `y`), 0 (`test_t`)
+/// and -1 (`passed`). The times are UNIX timestamps.
+///
+/// The parts are buffered until the whole file is read. `progress` is called after each part
+/// with the offset read up to in the file.
+///
+/// This function is only available with the `hdf5` feature, which links against libhdf5.
+///
+/// # Returns
+///
+/// The number of parts written.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file, on any I/O error or if
+/// the HDF5 library fails to write the file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::path::Path;
+/// use stdf::hdf5::{write_hdf5, Hdf5Layout};
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let parts = write_hdf5(&mut file, Path::new("test.h5"), Hdf5Layout::Tree, |_| {}).unwrap();
+/// println!("{} parts", parts);
+/// ```
+pub fn write_hdf5<F: FnMut(usize)>(file: &mut File, path: &Path, layout: Hdf5Layout, mut progress: F) -> Result<usize> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+
+    let mut lot: Vec<(&str, String)> = Vec::new();
+    let mut times: Vec<(&str, u32)> = Vec::new();
+    let mut wafers: Vec<Wafer> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut wafer_index = |wafers: &mut Vec<Wafer>, id: String| -> usize {
+        *index.entry(id.clone()).or_insert_with(|| {
+            wafers.push(Wafer { id, ..Default::default() });
+            wafers.len() - 1
+        })
+    };
+    let mut parts = 0;
+    let mut rows = PartRows::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        match &record {
+            V4::MIR(mir) => {
+                for (field, value) in [
+                    ("lot_id", &mir.lot_id),
+                    ("sblot_id", &mir.sblot_id),
+                    ("part_typ", &mir.part_typ),
+                    ("job_nam", &mir.job_nam),
+                    ("job_rev", &mir.job_rev),
+                    ("test_cod", &mir.test_cod),
+                    ("tst_temp", &mir.tst_temp),
+                    ("node_nam", &mir.node_nam),
+                    ("tstr_typ", &mir.tstr_typ),
+                    ("exec_typ", &mir.exec_typ),
+                    ("exec_ver", &mir.exec_ver),
+                    ("oper_nam", &mir.oper_nam),
+                    ("facil_id", &mir.facil_id),
+                    ("flow_id", &mir.flow_id),
+                    ("pkg_typ", &mir.pkg_typ),
+                ] {
+                    lot.push((field, value.to_string().trim().to_string()));
+                }
+                lot.push(("mode_cod", mir.mode_cod.to_string()));
+                times.push(("setup_t", mir.setup_t.0));
+                times.push(("start_t", mir.start_t.0));
+            }
+            V4::MRR(mrr) => times.push(("finish_t", mrr.finish_t.0)),
+            V4::WIR(wir) => {
+                let w = wafer_index(&mut wafers, wir.wafer_id.to_string().trim().to_string());
+                wafers[w].start_t = Some(wir.start_t.0).filter(|t| *t != 0);
+            }
+            V4::WRR(wrr) => {
+                let w = wafer_index(&mut wafers, wrr.wafer_id.to_string().trim().to_string());
+                wafers[w].finish_t = Some(wrr.finish_t.0).filter(|t| *t != 0);
+            }
+            _ => {}
+        }
+        if let Some(row) = rows.push(record) {
+            let w = wafer_index(&mut wafers, row.wafer_id.clone());
+            wafers[w].push(row, &rows.catalog.columns);
+            parts += 1;
+            progress(*offset);
+        }
+    }
+    // a WIR/WRR without parts (e.g. a retest that was aborted) is not written
+    wafers.retain(|wafer| !wafer.part_id.is_empty());
+    let columns = &rows.catalog.columns;
+
+    let file = h5::create_file(path)?;
+    let root = match layout {
+        Hdf5Layout::Tree => h5::open_group(&file, "/")?,
+        Hdf5Layout::Metis => {
+            let lot_id = lot.iter().find(|(field, _)| *field == "lot_id").map(|(_, id)| id.as_str());
+            h5::create_group(&file, &name(lot_id.unwrap_or("")))?
+        }
+    };
+    for (field, value) in lot.iter() {
+        h5::set_attr_str(&root, field, value)?;
+    }
+    for (field, value) in times.iter() {
+        h5::set_attr(&root, field, *value)?;
+    }
+    let mut names = HashSet::new();
+    let test_names: Vec<String> = columns.iter().map(|column| unique(&mut names, test_name(column))).collect();
+    let mut wafer_names = HashSet::new();
+    for wafer in wafers.iter() {
+        let group = h5::create_group(&root, &unique(&mut wafer_names, wafer.name()))?;
+        wafer.write_attributes(&group)?;
+        match layout {
+            Hdf5Layout::Tree => {
+                wafer.write_parts(&h5::create_group(&group, "parts")?)?;
+                let tests = h5::create_group(&group, "tests")?;
+                let matrix = wafer.matrix(columns.len());
+                for (i, column) in columns.iter().enumerate() {
+                    let values: Vec<f32> = matrix.iter().skip(i).step_by(columns.len()).copied().collect();
+                    let dataset = h5::write_dataset(&tests, &test_names[i], &[values.len()], &values)?;
+                    write_test_attributes(&dataset, column)?;
+                }
+            }
+            Hdf5Layout::Metis => {
+                let matrix = wafer.matrix(columns.len());
+                h5::write_dataset(&group, "data", &[wafer.part_id.len(), columns.len()], &matrix)?;
+                wafer.write_parts(&group)?;
+                let test_num: Vec<u32> = columns.iter().map(|column| column.test_num).collect();
+                let rec: Vec<String> = columns.iter().map(|column| column.rec.clone()).collect();
+                let test_txt: Vec<String> = columns.iter().map(|column| column.name()).collect();
+                let units: Vec<String> = columns.iter().map(|column| column.limits.units.clone()).collect();
+                let lo_limit: Vec<f32> = columns.iter().map(|column| column.limits.lo_limit.unwrap_or(f32::NAN)).collect();
+                let hi_limit: Vec<f32> = columns.iter().map(|column| column.limits.hi_limit.unwrap_or(f32::NAN)).collect();
+                h5::write_dataset(&group, "test_num", &[columns.len()], &test_num)?;
+                h5::write_strings(&group, "rec", &rec)?;
+                h5::write_strings(&group, "test_txt", &test_txt)?;
+                h5::write_strings(&group, "units", &units)?;
+                h5::write_dataset(&group, "lo_limit", &[columns.len()], &lo_limit)?;
+                h5::write_dataset(&group, "hi_limit", &[columns.len()], &hi_limit)?;
+            }
+        }
+    }
+    Ok(parts)
+}
+
+/// A thin layer over the HDF5 C API, the handles are closed when dropped.
+mod h5 {
+    use std::ffi::{c_void, CString};
+    use std::io::{Error, ErrorKind, Result};
+    use std::os::raw::c_char;
+    use std::path::Path;
+    use std::ptr;
+
+    use hdf5_sys::h5::{herr_t, hsize_t, H5open};
+    use hdf5_sys::h5a::{H5Aclose, H5Acreate2, H5Awrite};
+    use hdf5_sys::h5d::{H5Dclose, H5Dcreate2, H5Dwrite};
+    use hdf5_sys::h5f::{H5Fclose, H5Fcreate, H5F_ACC_TRUNC};
+    use hdf5_sys::h5g::{H5Gclose, H5Gcreate2, H5Gopen2};
+    use hdf5_sys::h5i::hid_t;
+    use hdf5_sys::h5p::{H5Pclose, H5Pcreate, H5Pset_chunk, H5Pset_deflate, H5P_CLS_DATASET_CREATE, H5P_DEFAULT};
+    use hdf5_sys::h5s::{H5S_class_t, H5Sclose, H5Screate, H5Screate_simple, H5S_ALL};
+    use hdf5_sys::h5t::{
+        H5T_cset_t, H5Tclose, H5Tcopy, H5Tset_cset, H5Tset_size, H5T_C_S1, H5T_NATIVE_FLOAT, H5T_NATIVE_INT16,
+        H5T_NATIVE_INT8, H5T_NATIVE_UINT16, H5T_NATIVE_UINT32, H5T_NATIVE_UINT8, H5T_VARIABLE,
+    };
+
+    /// The elements of a chunk of a compressed dataset.
+    const CHUNK_SIZE: usize = 65_536;
+
+    /// An open HDF5 object (file, group, dataset, attribute, dataspace, datatype or property list).
+    pub struct Handle {
+        id: hid_t,
+        close: unsafe extern "C" fn(hid_t) -> herr_t,
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { (self.close)(self.id) };
+        }
+    }
+
+    fn handle(id: hid_t, close: unsafe extern "C" fn(hid_t) -> herr_t, what: &str) -> Result<Handle> {
+        if id < 0 {
+            return Err(Error::other(format!("HDF5: failed to {}", what)));
+        }
+        Ok(Handle { id, close })
+    }
+
+    fn check(status: herr_t, what: &str) -> Result<()> {
+        if status < 0 {
+            return Err(Error::other(format!("HDF5: failed to {}", what)));
+        }
+        Ok(())
+    }
+
+    fn c_string(text: &str) -> Result<CString> {
+        CString::new(text).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+    }
+
+    /// The native HDF5 type of the elements of a dataset or attribute.
+    pub trait H5Type: Copy {
+        fn type_id() -> hid_t;
+    }
+
+    macro_rules! h5_type {
+        ($rust:ty, $h5:ident) => {
+            impl H5Type for $rust {
+                fn type_id() -> hid_t {
+                    *$h5
+                }
+            }
+        };
+    }
+
+    h5_type!(u8, H5T_NATIVE_UINT8);
+    h5_type!(i8, H5T_NATIVE_INT8);
+    h5_type!(u16, H5T_NATIVE_UINT16);
+    h5_type!(i16, H5T_NATIVE_INT16);
+    h5_type!(u32, H5T_NATIVE_UINT32);
+    h5_type!(f32, H5T_NATIVE_FLOAT);
+
+    /// A variable length UTF-8 string type.
+    fn string_type() -> Result<Handle> {
+        let string = handle(unsafe { H5Tcopy(*H5T_C_S1) }, H5Tclose, "create a string type")?;
+        check(unsafe { H5Tset_size(string.id, H5T_VARIABLE) }, "create a string type")?;
+        check(unsafe { H5Tset_cset(string.id, H5T_cset_t::H5T_CSET_UTF8) }, "create a string type")?;
+        Ok(string)
+    }
+
+    /// Creates (or truncates) an HDF5 file.
+    pub fn create_file(path: &Path) -> Result<Handle> {
+        check(unsafe { H5open() }, "initialize the library")?;
+        let path = c_string(&path.to_string_lossy())?;
+        let id = unsafe { H5Fcreate(path.as_ptr(), H5F_ACC_TRUNC, H5P_DEFAULT, H5P_DEFAULT) };
+        handle(id, H5Fclose, "create the file")
+    }
+
+    pub fn create_group(location: &Handle, name: &str) -> Result<Handle> {
+        let c_name = c_string(name)?;
+        let id = unsafe { H5Gcreate2(location.id, c_name.as_ptr(), H5P_DEFAULT, H5P_DEFAULT, H5P_DEFAULT) };
+        handle(id, H5Gclose, &format!("create group '{}'", name))
+    }
+
+    pub fn open_group(location: &Handle, name: &str) -> Result<Handle> {
+        let c_name = c_string(name)?;
+        let id = unsafe { H5Gopen2(location.id, c_name.as_ptr(), H5P_DEFAULT) };
+        handle(id, H5Gclose, &format!("open group '{}'", name))
+    }
+
+    /// Writes a deflated dataset of `shape` (1 or 2 dimensions), returns it to add attributes.
+    pub fn write_dataset<T: H5Type>(location: &Handle, name: &str, shape: &[usize], data: &[T]) -> Result<Handle> {
+        let dims: Vec<hsize_t> = shape.iter().map(|d| *d as hsize_t).collect();
+        let what = format!("write dataset '{}'", name);
+        let space = handle(unsafe { H5Screate_simple(dims.len() as i32, dims.as_ptr(), ptr::null()) }, H5Sclose, &what)?;
+        let properties = handle(unsafe { H5Pcreate(*H5P_CLS_DATASET_CREATE) }, H5Pclose, &what)?;
+        // an empty dataset can not be chunked
+        if !data.is_empty() {
+            let row = shape[1..].iter().product::<usize>().max(1);
+            let mut chunk = dims.clone();
+            chunk[0] = (CHUNK_SIZE / row).clamp(1, shape[0]) as hsize_t;
+            check(unsafe { H5Pset_chunk(properties.id, chunk.len() as i32, chunk.as_ptr()) }, &what)?;
+            check(unsafe { H5Pset_deflate(properties.id, 4) }, &what)?;
+        }
+        let c_name = c_string(name)?;
+        let id = unsafe {
+            H5Dcreate2(location.id, c_name.as_ptr(), T::type_id(), space.id, H5P_DEFAULT, properties.id, H5P_DEFAULT)
+        };
+        let dataset = handle(id, H5Dclose, &what)?;
+        if !data.is_empty() {
+            let buffer = data.as_ptr() as *const c_void;
+            check(unsafe { H5Dwrite(dataset.id, T::type_id(), H5S_ALL, H5S_ALL, H5P_DEFAULT, buffer) }, &what)?;
+        }
+        Ok(dataset)
+    }
+
+    /// Writes a 1-D dataset of variable length UTF-8 strings.
+    pub fn write_strings(location: &Handle, name: &str, values: &[String]) -> Result<Handle> {
+        let what = format!("write dataset '{}'", name);
+        let strings = values.iter().map(|value| c_string(value)).collect::<Result<Vec<_>>>()?;
+        let pointers: Vec<*const c_char> = strings.iter().map(|string| string.as_ptr()).collect();
+        let string = string_type()?;
+        let dims = [values.len() as hsize_t];
+        let space = handle(unsafe { H5Screate_simple(1, dims.as_ptr(), ptr::null()) }, H5Sclose, &what)?;
+        let c_name = c_string(name)?;
+        let id = unsafe {
+            H5Dcreate2(location.id, c_name.as_ptr(), string.id, space.id, H5P_DEFAULT, H5P_DEFAULT, H5P_DEFAULT)
+        };
+        let dataset = handle(id, H5Dclose, &what)?;
+        if !values.is_empty() {
+            let buffer = pointers.as_ptr() as *const c_void;
+            check(unsafe { H5Dwrite(dataset.id, string.id, H5S_ALL, H5S_ALL, H5P_DEFAULT, buffer) }, &what)?;
+        }
+        Ok(dataset)
+    }
+
+    fn write_attr(location: &Handle, name: &str, type_id: hid_t, buffer: *const c_void) -> Result<()> {
+        let what = format!("write attribute '{}'", name);
+        let space = handle(unsafe { H5Screate(H5S_class_t::H5S_SCALAR) }, H5Sclose, &what)?;
+        let c_name = c_string(name)?;
+        let id = unsafe { H5Acreate2(location.id, c_name.as_ptr(), type_id, space.id, H5P_DEFAULT, H5P_DEFAULT) };
+        let attribute = handle(id, H5Aclose, &what)?;
+        check(unsafe { H5Awrite(attribute.id, type_id, buffer) }, &what)
+    }
+
+    pub fn set_attr<T: H5Type>(location: &Handle, name: &str, value: T) -> Result<()> {
+        write_attr(location, name, T::type_id(), &value as *const T as *const c_void)
+    }
+
+    pub fn set_attr_str(location: &Handle, name: &str, value: &str) -> Result<()> {
+        let string = string_type()?;
+        let value = c_string(value)?;
+        let pointer = value.as_ptr();
+        write_attr(location, name, string.id, &pointer as *const *const c_char as *const c_void)
+    }
+
+    /// Reads the files back, for the tests.
+    #[cfg(test)]
+    pub mod read {
+        use std::ffi::{c_void, CStr};
+        use std::io::Result;
+        use std::os::raw::c_char;
+        use std::path::Path;
+        use std::ptr;
+
+        use hdf5_sys::h5::{hsize_t, H5free_memory, H5open};
+        use hdf5_sys::h5a::{H5Aclose, H5Aopen, H5Aread};
+        use hdf5_sys::h5d::{H5Dclose, H5Dget_space, H5Dopen2, H5Dread};
+        use hdf5_sys::h5f::{H5Fclose, H5Fopen, H5F_ACC_RDONLY};
+        use hdf5_sys::h5i::hid_t;
+        use hdf5_sys::h5l::H5Lexists;
+        use hdf5_sys::h5p::H5P_DEFAULT;
+        use hdf5_sys::h5s::{H5Sclose, H5Sget_simple_extent_dims, H5Sget_simple_extent_ndims, H5S_ALL};
+
+        use super::{c_string, check, handle, string_type, H5Type, Handle};
+
+        pub fn open_file(path: &Path) -> Result<Handle> {
+            check(unsafe { H5open() }, "initialize the library")?;
+            let path = c_string(&path.to_string_lossy())?;
+            let id = unsafe { H5Fopen(path.as_ptr(), H5F_ACC_RDONLY, H5P_DEFAULT) };
+            handle(id, H5Fclose, "open the file")
+        }
+
+        /// Whether `location` has a group or a dataset named `name`.
+        pub fn exists(location: &Handle, name: &str) -> Result<bool> {
+            let c_name = c_string(name)?;
+            let status = unsafe { H5Lexists(location.id, c_name.as_ptr(), H5P_DEFAULT) };
+            check(status, &format!("look for '{}'", name))?;
+            Ok(status > 0)
+        }
+
+        /// Opens a dataset, returns it (to read its attributes) with its shape.
+        pub fn open_dataset(location: &Handle, name: &str) -> Result<(Handle, Vec<usize>)> {
+            let what = format!("read dataset '{}'", name);
+            let c_name = c_string(name)?;
+            let dataset = handle(unsafe { H5Dopen2(location.id, c_name.as_ptr(), H5P_DEFAULT) }, H5Dclose, &what)?;
+            let space = handle(unsafe { H5Dget_space(dataset.id) }, H5Sclose, &what)?;
+            let rank = unsafe { H5Sget_simple_extent_ndims(space.id) };
+            check(rank, &what)?;
+            let mut dims = vec![0 as hsize_t; rank as usize];
+            check(unsafe { H5Sget_simple_extent_dims(space.id, dims.as_mut_ptr(), ptr::null_mut()) }, &what)?;
+            Ok((dataset, dims.iter().map(|d| *d as usize).collect()))
+        }
+
+        /// Reads a dataset, returns its shape and its elements in row-major order.
+        pub fn read_dataset<T: H5Type + Default>(location: &Handle, name: &str) -> Result<(Vec<usize>, Vec<T>)> {
+            let (dataset, shape) = open_dataset(location, name)?;
+            let mut data = vec![T::default(); shape.iter().product()];
+            if !data.is_empty() {
+                let buffer = data.as_mut_ptr() as *mut c_void;
+                let status = unsafe { H5Dread(dataset.id, T::type_id(), H5S_ALL, H5S_ALL, H5P_DEFAULT, buffer) };
+                check(status, &format!("read dataset '{}'", name))?;
+            }
+            Ok((shape, data))
+        }
+
+        /// Copies the strings allocated by the library, then frees them.
+        fn take_strings(pointers: Vec<*mut c_char>) -> Vec<String> {
+            pointers
+                .into_iter()
+                .map(|pointer| match pointer.is_null() {
+                    true => String::new(),
+                    false => {
+                        let string = unsafe { CStr::from_ptr(pointer) }.to_string_lossy().into_owned();
+                        unsafe { H5free_memory(pointer as *mut c_void) };
+                        string
+                    }
+                })
+                .collect()
+        }
+
+        pub fn read_strings(location: &Handle, name: &str) -> Result<Vec<String>> {
+            let (dataset, shape) = open_dataset(location, name)?;
+            let string = string_type()?;
+            let mut pointers: Vec<*mut c_char> = vec![ptr::null_mut(); shape.iter().product()];
+            if !pointers.is_empty() {
+                let buffer = pointers.as_mut_ptr() as *mut c_void;
+                let status = unsafe { H5Dread(dataset.id, string.id, H5S_ALL, H5S_ALL, H5P_DEFAULT, buffer) };
+                check(status, &format!("read dataset '{}'", name))?;
+            }
+            Ok(take_strings(pointers))
+        }
+
+        fn read_attr(location: &Handle, name: &str, type_id: hid_t, buffer: *mut c_void) -> Result<()> {
+            let what = format!("read attribute '{}'", name);
+            let c_name = c_string(name)?;
+            let attribute = handle(unsafe { H5Aopen(location.id, c_name.as_ptr(), H5P_DEFAULT) }, H5Aclose, &what)?;
+            check(unsafe { H5Aread(attribute.id, type_id, buffer) }, &what)
+        }
+
+        pub fn get_attr<T: H5Type + Default>(location: &Handle, name: &str) -> Result<T> {
+            let mut value = T::default();
+            read_attr(location, name, T::type_id(), &mut value as *mut T as *mut c_void)?;
+            Ok(value)
+        }
+
+        pub fn get_attr_str(location: &Handle, name: &str) -> Result<String> {
+            let string = string_type()?;
+            let mut pointer: *mut c_char = ptr::null_mut();
+            read_attr(location, name, string.id, &mut pointer as *mut *mut c_char as *mut c_void)?;
+            Ok(take_strings(vec![pointer]).remove(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::h5::read;
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(name(" W01/2 "), "W01_2");
+        assert_eq!(name(""), "_");
+        assert_eq!(name(".."), "_");
+        let mut column = TestColumn {
+            rec: "MPR".to_string(),
+            test_num: 2000,
+            test_txt: "leakage".to_string(),
+            pin: Some("IO/1".to_string()),
+            limits: Default::default(),
+        };
+        assert_eq!(test_name(&column), "MPR_2000_IO_1");
+        let mut names = HashSet::new();
+        assert_eq!(unique(&mut names, test_name(&column)), "MPR_2000_IO_1");
+        column.pin = Some("IO_1".to_string());
+        assert_eq!(unique(&mut names, test_name(&column)), "MPR_2000_IO_1_2");
+        assert_eq!(unique(&mut names, "MPR_2000_IO_1_2".to_string()), "MPR_2000_IO_1_2_2");
+        column.rec = "PTR".to_string();
+        column.pin = None;
+        assert_eq!(test_name(&column), "PTR_2000");
+    }
+
+    #[test]
+    fn test_wafer_names() {
+        let mut names = HashSet::new();
+        let no_wafer = Wafer::default();
+        let wafer = Wafer { id: "no_wafer".to_string(), ..Default::default() };
+        assert_eq!(unique(&mut names, no_wafer.name()), "no_wafer");
+        assert_eq!(unique(&mut names, wafer.name()), "no_wafer_2");
+    }
+
+    fn write(layout: Hdf5Layout) -> (tempfile::TempDir, h5::Handle) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.h5");
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        assert_eq!(write_hdf5(&mut file, &path, layout, |_| {}).unwrap(), 22);
+        let file = read::open_file(&path).unwrap();
+        (dir, file)
+    }
+
+    #[test]
+    fn test_write_hdf5_tree() {
+        let (_dir, file) = write(Hdf5Layout::Tree);
+        let root = h5::open_group(&file, "/").unwrap();
+        assert_eq!(read::get_attr_str(&root, "lot_id").unwrap(), "F6N910.1");
+        assert_eq!(read::get_attr_str(&root, "part_typ").unwrap(), "spm3f_dual");
+        assert_eq!(read::get_attr::<u32>(&root, "start_t").unwrap(), 1526348699);
+        // the test file has no WIR
+        let wafer = h5::open_group(&root, "no_wafer").unwrap();
+        assert_eq!(read::get_attr_str(&wafer, "wafer_id").unwrap(), "");
+        assert_eq!(read::get_attr::<u32>(&wafer, "part_cnt").unwrap(), 22);
+
+        let parts = h5::open_group(&wafer, "parts").unwrap();
+        assert_eq!(read::read_strings(&parts, "part_id").unwrap()[..3], ["1", "2", "3"]);
+        assert_eq!(read::read_dataset::<u8>(&parts, "site_num").unwrap().1[..3], [0, 1, 0]);
+        assert_eq!(read::read_dataset::<u16>(&parts, "hard_bin").unwrap().1[..3], [2, 2, 4]);
+        assert_eq!(read::read_dataset::<u16>(&parts, "soft_bin").unwrap().1[..3], [30, 30, 2]);
+        assert_eq!(read::read_dataset::<i16>(&parts, "x").unwrap(), (vec![22], vec![i16::MIN; 22]));
+
+        let tests = h5::open_group(&wafer, "tests").unwrap();
+        for test_num in 10..=35 {
+            assert!(read::exists(&tests, &format!("PTR_{}", test_num)).unwrap());
+        }
+        assert!(!read::exists(&tests, "PTR_36").unwrap());
+        let (shape, values) = read::read_dataset::<f32>(&tests, "PTR_10").unwrap();
+        assert_eq!(shape, [22]);
+        assert_eq!(values[0], -0.5845893);
+        let dataset = read::open_dataset(&tests, "PTR_10").unwrap().0;
+        assert_eq!(read::get_attr_str(&dataset, "rec").unwrap(), "PTR");
+        assert_eq!(read::get_attr::<u32>(&dataset, "test_num").unwrap(), 10);
+        assert_eq!(read::get_attr_str(&dataset, "test_txt").unwrap(), "Contact_C1");
+        assert_eq!(read::get_attr_str(&dataset, "units").unwrap(), "V");
+        assert_eq!(read::get_attr::<i8>(&dataset, "res_scal").unwrap(), 3);
+        assert_eq!(read::get_attr::<f32>(&dataset, "lo_limit").unwrap(), -0.8);
+        assert_eq!(read::get_attr::<f32>(&dataset, "hi_limit").unwrap(), -0.1);
+    }
+
+    #[test]
+    fn test_write_hdf5_metis() {
+        let (_dir, file) = write(Hdf5Layout::Metis);
+        assert!(!read::exists(&file, "no_wafer").unwrap());
+        let lot = h5::open_group(&file, "F6N910.1").unwrap();
+        assert_eq!(read::get_attr_str(&lot, "lot_id").unwrap(), "F6N910.1");
+        assert_eq!(read::get_attr::<u32>(&lot, "start_t").unwrap(), 1526348699);
+        let wafer = h5::open_group(&lot, "no_wafer").unwrap();
+        assert_eq!(read::get_attr::<u32>(&wafer, "part_cnt").unwrap(), 22);
+
+        let (shape, data) = read::read_dataset::<f32>(&wafer, "data").unwrap();
+        assert_eq!(shape, [22, 26]);
+        assert_eq!(data[0], -0.5845893);
+        assert_eq!(read::read_strings(&wafer, "part_id").unwrap().len(), 22);
+        assert_eq!(read::read_dataset::<u16>(&wafer, "hard_bin").unwrap().1[..3], [2, 2, 4]);
+        assert_eq!(read::read_dataset::<u32>(&wafer, "test_num").unwrap(), (vec![26], (10..=35).collect()));
+        assert_eq!(read::read_strings(&wafer, "rec").unwrap(), vec!["PTR"; 26]);
+        assert_eq!(read::read_strings(&wafer, "test_txt").unwrap()[..2], ["Contact_C1", "Contact_C2"]);
+        assert_eq!(read::read_strings(&wafer, "units").unwrap()[..5], ["V"; 5]);
+        assert_eq!(read::read_dataset::<f32>(&wafer, "lo_limit").unwrap().1[0], -0.8);
+        assert_eq!(read::read_dataset::<f32>(&wafer, "hi_limit").unwrap().1[0], -0.1);
+    }
+}
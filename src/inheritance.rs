@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::flags::OptFlag;
+use crate::pins::PinMap;
+use crate::records::{MPR, PTR};
+use crate::types::{Cn, I1, R4};
+use crate::units::Measurement;
+
+/// The effective (inherited where needed) limits, units, scales and formats of a PTR or MPR.
+///
+/// `None` means the value is not known or, for the limits, that the test has no such limit.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct Limits {
+    pub res_scal: Option<i8>,
+    pub llm_scal: Option<i8>,
+    pub hlm_scal: Option<i8>,
+    pub lo_limit: Option<f32>,
+    pub hi_limit: Option<f32>,
+    pub units: String,
+    pub c_resfmt: String,
+    pub c_llmfmt: String,
+    pub c_hlmfmt: String,
+    pub lo_spec: Option<f32>,
+    pub hi_spec: Option<f32>,
+}
+
+impl Limits {
+    /// Tells if `result` is within the limits, `None` if there are no limits at all.
+    pub fn passes(&self, result: f32) -> Option<bool> {
+        if self.lo_limit.is_none() && self.hi_limit.is_none() {
+            return None;
+        }
+        Some(self.lo_limit.is_none_or(|lo| result >= lo) && self.hi_limit.is_none_or(|hi| result <= hi))
+    }
+
+    /// A result of the test, in base units and shown with RES_SCAL.
+    pub fn result(&self, result: f32) -> Measurement {
+        Measurement::new(result as f64, &self.units, self.res_scal)
+    }
+
+    /// The low limit, in base units and shown with LLM_SCAL.
+    pub fn lo(&self) -> Option<Measurement> {
+        self.lo_limit.map(|lo| Measurement::new(lo as f64, &self.units, self.llm_scal))
+    }
+
+    /// The high limit, in base units and shown with HLM_SCAL.
+    pub fn hi(&self) -> Option<Measurement> {
+        self.hi_limit.map(|hi| Measurement::new(hi as f64, &self.units, self.hlm_scal))
+    }
+}
+
+fn scale(scal: I1) -> Option<i8> {
+    if scal.0 == i8::MIN {
+        None
+    } else {
+        Some(scal.0)
+    }
+}
+
+fn limit(value: R4) -> Option<f32> {
+    if value.0.is_nan() {
+        None
+    } else {
+        Some(value.0)
+    }
+}
+
+fn text(value: &Cn) -> Option<String> {
+    if value.0.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Resolves the PTR fields that may be omitted after the first PTR of a test.
+///
+/// Per the STDF V4 specification, the first PTR of a test number sets the defaults
+/// for `res_scal`, `llm_scal`, `hlm_scal`, `lo_limit`, `hi_limit`, `units`, the
+/// format strings and the spec limits. Later PTRs of that test may leave them out,
+/// or mark them invalid in `opt_flag`, in which case the default applies.
+/// The `opt_flag` bits 6 and 7 (no low/high limit) and 2 and 3 (no low/high spec
+/// limit) remove the limit instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use byte::BytesExt;
+/// use memmap::MmapOptions;
+/// use stdf::get_endian_from_file;
+/// use stdf::inheritance::PtrResolver;
+/// use stdf::records::V4;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let endian = get_endian_from_file(&mut file).unwrap().unwrap();
+/// let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+/// let bytes = &mmap[..];
+/// let mut resolver = PtrResolver::new();
+/// let offset = &mut 0;
+/// while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+///     if let V4::PTR(ptr) = record {
+///         let limits = resolver.resolve(&ptr);
+///         println!("{} : {:?} .. {:?} {}", ptr.test_num, limits.lo_limit, limits.hi_limit, limits.units);
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct PtrResolver {
+    defaults: HashMap<u32, Limits>,
+}
+
+impl PtrResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the effective limits of `ptr`, the first PTR of a test number sets the defaults.
+    pub fn resolve(&mut self, ptr: &PTR) -> Limits {
+        let fields = optional_fields!(ptr);
+        match self.defaults.get(&ptr.test_num.0) {
+            Some(defaults) => fields.inherit(Some(defaults)),
+            None => {
+                let first = fields.inherit(None);
+                self.defaults.insert(ptr.test_num.0, first.clone());
+                first
+            }
+        }
+    }
+}
+
+/// The result of one pin of an MPR.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PinResult {
+    pub pin_index: Option<u16>,
+    /// The pin name from the PMR, empty if the pin is unknown.
+    pub pin_name: String,
+    pub result: Option<f32>,
+    /// The return state nibble (`rtn_stat`).
+    pub status: Option<u8>,
+}
+
+/// An MPR with its effective limits and its results per pin.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedMpr {
+    pub limits: Limits,
+    pub start_in: Option<f32>,
+    pub incr_in: Option<f32>,
+    pub units_in: String,
+    pub pins: Vec<PinResult>,
+}
+
+#[derive(Debug, Clone)]
+struct MprDefaults {
+    limits: Limits,
+    rtn_indx: Vec<u16>,
+    start_in: Option<f32>,
+    incr_in: Option<f32>,
+    units_in: String,
+}
+
+/// Resolves the MPR fields that may be omitted after the first MPR of a test, see [`PtrResolver`].
+///
+/// Besides the limits, later MPRs may leave out `rtn_indx` (the pins), `start_in`,
+/// `incr_in` and `units_in`. Each MPR is expanded in one row per pin, named after
+/// the PMR of that pin for the head/site of the MPR.
+#[derive(Debug, Default)]
+pub struct MprResolver {
+    defaults: HashMap<u32, MprDefaults>,
+}
+
+impl MprResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the effective limits and the per pin results of `mpr`.
+    pub fn resolve(&mut self, mpr: &MPR, pin_map: &PinMap) -> ResolvedMpr {
+        let fields = optional_fields!(mpr);
+        let defaults = self.defaults.get(&mpr.test_num.0);
+        let limits = fields.inherit(defaults.map(|d| &d.limits));
+        let rtn_indx: Vec<u16> = if mpr.rtn_indx.is_empty() {
+            defaults.map(|d| d.rtn_indx.clone()).unwrap_or_default()
+        } else {
+            mpr.rtn_indx.iter().map(|i| i.0).collect()
+        };
+        let (start_in, incr_in) = if fields.bit(OptFlag::start_in_invalid) {
+            (None, None)
+        } else {
+            (limit(mpr.start_in), limit(mpr.incr_in))
+        };
+        let resolved = ResolvedMpr {
+            limits,
+            start_in: start_in.or(defaults.and_then(|d| d.start_in)),
+            incr_in: incr_in.or(defaults.and_then(|d| d.incr_in)),
+            units_in: text(&mpr.units_in).unwrap_or_else(|| defaults.map(|d| d.units_in.clone()).unwrap_or_default()),
+            pins: Vec::new(),
+        };
+        if defaults.is_none() {
+            self.defaults.insert(
+                mpr.test_num.0,
+                MprDefaults {
+                    limits: resolved.limits.clone(),
+                    rtn_indx: rtn_indx.clone(),
+                    start_in: resolved.start_in,
+                    incr_in: resolved.incr_in,
+                    units_in: resolved.units_in.clone(),
+                },
+            );
+        }
+
+        let count = rtn_indx.len().max(mpr.rtn_stat.len()).max(mpr.rtn_rslt.len());
+        let pins = (0..count)
+            .map(|i| {
+                let pin_index = rtn_indx.get(i).copied();
+                PinResult {
+                    pin_index,
+                    pin_name: pin_index
+                        .and_then(|index| pin_map.name(mpr.head_num.0, mpr.site_num.0, index))
+                        .unwrap_or_default(),
+                    result: mpr.rtn_rslt.get(i).map(|r| r.0),
+                    status: mpr.rtn_stat.get(i).map(|s| s.0),
+                }
+            })
+            .collect();
+        ResolvedMpr { pins, ..resolved }
+    }
+}
+
+/// The optional fields that PTR and MPR have in common.
+struct OptionalFields<'r, 'a> {
+    opt_flag: Option<OptFlag>,
+    res_scal: I1,
+    llm_scal: I1,
+    hlm_scal: I1,
+    lo_limit: R4,
+    hi_limit: R4,
+    units: &'r Cn<'a>,
+    c_resfmt: &'r Cn<'a>,
+    c_llmfmt: &'r Cn<'a>,
+    c_hlmfmt: &'r Cn<'a>,
+    lo_spec: R4,
+    hi_spec: R4,
+}
+
+macro_rules! optional_fields {
+    ($rec:expr) => {
+        OptionalFields {
+            // a record that ends before OPT_FLAG (or RES_SCAL) carries no optional data at all
+            opt_flag: if $rec.opt_flag.0 == 0 && $rec.res_scal.0 == i8::MIN {
+                None
+            } else {
+                Some(OptFlag::from($rec.opt_flag))
+            },
+            res_scal: $rec.res_scal,
+            llm_scal: $rec.llm_scal,
+            hlm_scal: $rec.hlm_scal,
+            lo_limit: $rec.lo_limit,
+            hi_limit: $rec.hi_limit,
+            units: &$rec.units,
+            c_resfmt: &$rec.c_resfmt,
+            c_llmfmt: &$rec.c_llmfmt,
+            c_hlmfmt: &$rec.c_hlmfmt,
+            lo_spec: $rec.lo_spec,
+            hi_spec: $rec.hi_spec,
+        }
+    };
+}
+use optional_fields;
+
+impl OptionalFields<'_, '_> {
+    fn bit(&self, predicate: fn(&OptFlag) -> bool) -> bool {
+        self.opt_flag.as_ref().is_some_and(predicate)
+    }
+
+    /// The effective limits, missing or invalid fields are taken from `defaults`.
+    fn inherit(&self, defaults: Option<&Limits>) -> Limits {
+        let none = Limits::default();
+        let defaults = defaults.unwrap_or(&none);
+        let mut limits = Limits {
+            res_scal: if self.bit(OptFlag::res_scal_invalid) { None } else { scale(self.res_scal) }.or(defaults.res_scal),
+            llm_scal: if self.bit(OptFlag::lo_limit_invalid) { None } else { scale(self.llm_scal) }.or(defaults.llm_scal),
+            hlm_scal: if self.bit(OptFlag::hi_limit_invalid) { None } else { scale(self.hlm_scal) }.or(defaults.hlm_scal),
+            lo_limit: if self.bit(OptFlag::lo_limit_invalid) { None } else { limit(self.lo_limit) }.or(defaults.lo_limit),
+            hi_limit: if self.bit(OptFlag::hi_limit_invalid) { None } else { limit(self.hi_limit) }.or(defaults.hi_limit),
+            units: text(self.units).unwrap_or_else(|| defaults.units.clone()),
+            c_resfmt: text(self.c_resfmt).unwrap_or_else(|| defaults.c_resfmt.clone()),
+            c_llmfmt: text(self.c_llmfmt).unwrap_or_else(|| defaults.c_llmfmt.clone()),
+            c_hlmfmt: text(self.c_hlmfmt).unwrap_or_else(|| defaults.c_hlmfmt.clone()),
+            lo_spec: limit(self.lo_spec).or(defaults.lo_spec),
+            hi_spec: limit(self.hi_spec).or(defaults.hi_spec),
+        };
+        apply_no_limit_bits(&mut limits, self.opt_flag);
+        limits
+    }
+}
+
+fn apply_no_limit_bits(limits: &mut Limits, opt_flag: Option<OptFlag>) {
+    let flag = match opt_flag {
+        Some(flag) => flag,
+        None => return,
+    };
+    if flag.no_lo_spec() {
+        limits.lo_spec = None;
+    }
+    if flag.no_hi_spec() {
+        limits.hi_spec = None;
+    }
+    if flag.no_lo_limit() {
+        limits.lo_limit = None;
+        limits.llm_scal = None;
+    }
+    if flag.no_hi_limit() {
+        limits.hi_limit = None;
+        limits.hlm_scal = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    fn first_ptr() -> PTR<'static> {
+        PTR {
+            test_num: U4(100),
+            head_num: U1(1),
+            site_num: U1(1),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            result: R4(1.5),
+            test_txt: Cn(b"vdd"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(0b0000_1110),
+            res_scal: I1(-3),
+            llm_scal: I1(-3),
+            hlm_scal: I1(-3),
+            lo_limit: R4(1.0),
+            hi_limit: R4(2.0),
+            units: Cn(b"V"),
+            c_resfmt: Cn(b"%7.3f"),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        }
+    }
+
+    /// A PTR that ends after TEST_TXT, as read by the derive.
+    fn short_ptr(test_num: u32) -> PTR<'static> {
+        PTR {
+            test_num: U4(test_num),
+            result: R4(2.5),
+            opt_flag: B1(0),
+            res_scal: I1(i8::MIN),
+            llm_scal: I1(i8::MIN),
+            hlm_scal: I1(i8::MIN),
+            lo_limit: R4(f32::NAN),
+            hi_limit: R4(f32::NAN),
+            units: Cn(b""),
+            c_resfmt: Cn(b""),
+            ..first_ptr()
+        }
+    }
+
+    #[test]
+    fn test_inherit_from_first_ptr() {
+        let mut resolver = PtrResolver::new();
+        let first = resolver.resolve(&first_ptr());
+        assert_eq!(first.lo_limit, Some(1.0));
+        assert_eq!(first.units, "V");
+
+        let later = resolver.resolve(&short_ptr(100));
+        assert_eq!(later, first);
+        assert_eq!(later.passes(2.5), Some(false));
+
+        // another test number has no defaults
+        let other = resolver.resolve(&short_ptr(200));
+        assert_eq!(other.lo_limit, None);
+        assert_eq!(other.units, "");
+        assert_eq!(other.passes(2.5), None);
+    }
+
+    #[test]
+    fn test_opt_flag_bits() {
+        let mut resolver = PtrResolver::new();
+        resolver.resolve(&first_ptr());
+
+        // limits marked invalid: use the defaults, own limits are ignored
+        let invalid = PTR {
+            opt_flag: B1(0b0011_0011),
+            res_scal: I1(0),
+            lo_limit: R4(-10.0),
+            hi_limit: R4(10.0),
+            ..first_ptr()
+        };
+        let limits = resolver.resolve(&invalid);
+        assert_eq!((limits.lo_limit, limits.hi_limit), (Some(1.0), Some(2.0)));
+        assert_eq!(limits.res_scal, Some(-3));
+
+        // no low limit, own high limit
+        let no_low = PTR {
+            opt_flag: B1(0b0100_0010),
+            hi_limit: R4(3.0),
+            ..first_ptr()
+        };
+        let limits = resolver.resolve(&no_low);
+        assert_eq!((limits.lo_limit, limits.hi_limit), (None, Some(3.0)));
+        assert_eq!(limits.llm_scal, None);
+        assert_eq!(limits.passes(-100.0), Some(true));
+
+        // a first PTR without limits
+        let no_limits = PTR {
+            test_num: U4(300),
+            opt_flag: B1(0b1100_0010),
+            ..first_ptr()
+        };
+        let limits = resolver.resolve(&no_limits);
+        assert_eq!((limits.lo_limit, limits.hi_limit), (None, None));
+        let limits = resolver.resolve(&short_ptr(300));
+        assert_eq!((limits.lo_limit, limits.hi_limit), (None, None));
+    }
+
+    fn mpr(rtn_indx: &[u16], lo_limit: f32) -> MPR<'static> {
+        MPR {
+            test_num: U4(500),
+            head_num: U1(1),
+            site_num: U1(2),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            rtn_icnt: U2(rtn_indx.len() as u16),
+            rslt_cnt: U2(2),
+            rtn_stat: rtn_indx.iter().map(|_| N1(1)).collect(),
+            rtn_rslt: vec![R4(0.1), R4(0.2)],
+            test_txt: Cn(b"leakage"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(if lo_limit.is_nan() { 0 } else { 0b0000_0010 }),
+            res_scal: I1(if lo_limit.is_nan() { i8::MIN } else { 0 }),
+            llm_scal: I1(0),
+            hlm_scal: I1(0),
+            lo_limit: R4(lo_limit),
+            hi_limit: R4(lo_limit + 1.0),
+            start_in: R4(f32::NAN),
+            incr_in: R4(f32::NAN),
+            rtn_indx: rtn_indx.iter().map(|i| U2(*i)).collect(),
+            units: Cn(b""),
+            units_in: Cn(b""),
+            c_resfmt: Cn(b""),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        }
+    }
+
+    #[test]
+    fn test_mpr_resolver() {
+        let mut pin_map = PinMap::new();
+        for (index, site_num, name) in [(1, 1, b"A_s1"), (1, 2, b"A_s2"), (2, 2, b"B_s2")] {
+            pin_map.add_pmr(&crate::records::PMR {
+                pmr_index: U2(index),
+                chan_typ: U2(0),
+                chan_nam: Cn(b""),
+                phy_nam: Cn(b""),
+                log_nam: Cn(name),
+                head_num: U1(1),
+                site_num: U1(site_num),
+            });
+        }
+        let mut resolver = MprResolver::new();
+        let first = resolver.resolve(&mpr(&[1, 2], 0.0), &pin_map);
+        assert_eq!(first.limits.lo_limit, Some(0.0));
+        assert_eq!(first.pins.len(), 2);
+        assert_eq!(first.pins[0].pin_name, "A_s2");
+        assert_eq!(first.pins[1].pin_name, "B_s2");
+        assert_eq!(first.pins[1].status, Some(1));
+
+        let later = resolver.resolve(&mpr(&[], f32::NAN), &pin_map);
+        assert_eq!(later.limits, first.limits);
+        assert_eq!(
+            later.pins,
+            vec![
+                PinResult { pin_index: Some(1), pin_name: "A_s2".to_string(), result: Some(0.1), status: None },
+                PinResult { pin_index: Some(2), pin_name: "B_s2".to_string(), result: Some(0.2), status: None },
+            ]
+        );
+    }
+}
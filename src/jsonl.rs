@@ -0,0 +1,595 @@
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+
+use byte::{BytesExt, BE, LE};
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::records::*;
+use crate::table::map;
+use crate::types::*;
+
+/// The type of the Unknown and Invalid records, their fields are `rec_typ`, `rec_sub` and the
+/// raw `contents` bytes.
+pub const RAW: &str = "???";
+
+/// A line of the JSON Lines export.
+#[derive(Serialize)]
+struct Line<'r, 'a> {
+    #[serde(rename = "type")]
+    name: String,
+    offset: usize,
+    fields: RecordFields<'r, 'a>,
+}
+
+/// The fields of a record, in the order of the record (unlike `V4::to_json`, whose keys are sorted).
+struct RecordFields<'r, 'a>(&'r V4<'a>);
+
+impl Serialize for RecordFields<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0 {
+            V4::FAR(rec) => rec.serialize(serializer),
+            V4::ATR(rec) => rec.serialize(serializer),
+            V4::MIR(rec) => rec.serialize(serializer),
+            V4::MRR(rec) => rec.serialize(serializer),
+            V4::PCR(rec) => rec.serialize(serializer),
+            V4::HBR(rec) => rec.serialize(serializer),
+            V4::SBR(rec) => rec.serialize(serializer),
+            V4::PMR(rec) => rec.serialize(serializer),
+            V4::PGR(rec) => rec.serialize(serializer),
+            V4::PLR(rec) => rec.serialize(serializer),
+            V4::RDR(rec) => rec.serialize(serializer),
+            V4::SDR(rec) => rec.serialize(serializer),
+            V4::WIR(rec) => rec.serialize(serializer),
+            V4::WRR(rec) => rec.serialize(serializer),
+            V4::WCR(rec) => rec.serialize(serializer),
+            V4::PIR(rec) => rec.serialize(serializer),
+            V4::PRR(rec) => rec.serialize(serializer),
+            V4::TSR(rec) => rec.serialize(serializer),
+            V4::PTR(rec) => rec.serialize(serializer),
+            V4::MPR(rec) => rec.serialize(serializer),
+            V4::FTR(rec) => rec.serialize(serializer),
+            V4::BPS(rec) => rec.serialize(serializer),
+            V4::EPS(_) => serializer.serialize_map(Some(0))?.end(),
+            V4::GDR(rec) => rec.serialize(serializer),
+            V4::DTR(rec) => rec.serialize(serializer),
+            V4::Unknown(raw) | V4::Invalid(raw) => {
+                let mut fields = serializer.serialize_struct("Raw", 3)?;
+                fields.serialize_field("rec_typ", &raw.rec_typ)?;
+                fields.serialize_field("rec_sub", &raw.rec_sub)?;
+                fields.serialize_field("contents", raw.contents)?;
+                fields.end()
+            }
+        }
+    }
+}
+
+/// Converts an STDF file to JSON Lines, one object per record.
+///
+/// Each line holds the record `type` (eg: `PTR`, [`RAW`] for unknown or invalid records), the
+/// `offset` of the record in the file and its decoded `fields`, as serialized by serde: `Cn`
+/// as strings (as arrays of bytes if they are not UTF-8), `Bn` as arrays of bytes, `Dn` as `[bit count, [bytes]]` and the `Vn` of a GDR
+/// as `{"U2": 7}` objects (`"B0"` for the pad bytes). A NaN or infinite float is written as `null`.
+/// `progress` is called after each record with the offset read up to in the file.
+///
+/// # Returns
+///
+/// The number of records written.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use stdf::jsonl::write_jsonl;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let output = BufWriter::new(File::create("test.jsonl").unwrap());
+/// let written = write_jsonl(&mut file, output, |_| {}).unwrap();
+/// println!("{} records written", written);
+/// ```
+pub fn write_jsonl<W: Write, F: FnMut(usize)>(file: &mut File, mut writer: W, mut progress: F) -> Result<usize> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+    let offset = &mut 0;
+    let mut written = 0;
+    while bytes.len() - *offset >= 4 {
+        let start = *offset;
+        let record = match bytes.read_with::<V4>(offset, endian) {
+            Ok(record) => record,
+            Err(_) => break,
+        };
+        let line = Line {
+            name: record.name(),
+            offset: start,
+            fields: RecordFields(&record),
+        };
+        serde_json::to_writer(&mut writer, &line)?;
+        writer.write_all(b"\n")?;
+        written += 1;
+        progress(*offset);
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// A line of the JSON Lines export, as read back.
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(rename = "type")]
+    name: String,
+    #[serde(default)]
+    offset: Option<u64>,
+    #[serde(default)]
+    fields: Value,
+}
+
+/// A record read from a JSON Lines file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonRecord {
+    /// The record type, eg: `PTR`.
+    pub name: String,
+    /// The offset of the record in the STDF file it was exported from, if it is known.
+    pub offset: Option<u64>,
+    pub fields: Value,
+    /// The line of the record, 0 if it was not read from a file.
+    pub line: usize,
+    /// The bit counts and bytes of the `Bn` and `Dn` fields, and of the `Cn` of a GDR that are
+    /// not UTF-8, in order, for the `V4` record to borrow.
+    data: Vec<(u16, Vec<u8>)>,
+    /// The fields with the `Cn` that are not UTF-8 taken out, set by `to_v4`.
+    texts: OnceCell<Texts>,
+}
+
+/// The `Cn` that are not UTF-8 are written as arrays of bytes, which a record can not borrow
+/// from a `Value`: they are blanked in a copy of the fields to deserialize, and their bytes are
+/// kept here for the record to borrow once it is deserialized.
+#[derive(Debug, Clone, PartialEq)]
+struct Texts {
+    fields: Value,
+    /// The bytes by field, and by position in a `kxCn` field.
+    bytes: HashMap<(&'static str, Option<usize>), Vec<u8>>,
+}
+
+impl Texts {
+    /// Takes the arrays of bytes of the `Cn` and `kxCn` fields `names` out of `fields`.
+    fn new(fields: &Value, names: &[&'static str]) -> Texts {
+        let mut fields = fields.clone();
+        let mut bytes = HashMap::new();
+        for name in names {
+            let Some(value) = fields.get_mut(*name) else { continue };
+            if let Some(text) = text(value) {
+                bytes.insert((*name, None), text);
+                *value = Value::from("");
+            } else if let Value::Array(values) = value {
+                for (i, value) in values.iter_mut().enumerate() {
+                    if let Some(text) = text(value) {
+                        bytes.insert((*name, Some(i)), text);
+                        *value = Value::from("");
+                    }
+                }
+            }
+        }
+        Texts { fields, bytes }
+    }
+}
+
+// Deserializes a record with `Cn` fields, setting those that are not UTF-8 from their bytes.
+macro_rules! with_texts {
+    ($json:ident, $record:ident, $($field:ident),+) => {{
+        let texts = $json.texts(&[$(stringify!($field)),+]);
+        let mut record: $record = $json.deserialize(&texts.fields)?;
+        $(
+            if let Some(text) = texts.bytes.get(&(stringify!($field), None)) {
+                record.$field = Cn(text);
+            }
+        )+
+        record
+    }};
+}
+
+impl JsonRecord {
+    /// Parses a line of a JSON Lines export.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind `InvalidData` if the line is not a JSON object with a `type`, or if
+    /// a `Bn` or `Dn` field is not an array of bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stdf::jsonl::JsonRecord;
+    ///
+    /// let record = JsonRecord::parse(r#"{"type":"BPS","fields":{"seq_name":"init"}}"#).unwrap();
+    /// assert_eq!(record.name, "BPS");
+    /// assert_eq!(record.fields["seq_name"], "init");
+    /// ```
+    pub fn parse(line: &str) -> Result<JsonRecord> {
+        let entry: Entry = serde_json::from_str(line)?;
+        let fields = entry.fields;
+        let mut data = Vec::new();
+        match entry.name.as_str() {
+            "PRR" => data.push((0, bytes(&fields["part_fix"], "PART_FIX")?)),
+            "FTR" => {
+                data.push(bits(&fields["fail_pin"], "FAIL_PIN")?);
+                data.push(bits(&fields["spin_map"], "SPIN_MAP")?);
+            }
+            "GDR" => {
+                for value in fields["gen_data"].as_array().into_iter().flatten() {
+                    if let Some(value) = value.get("Bn") {
+                        data.push((0, bytes(value, "GEN_DATA")?));
+                    } else if let Some(value) = value.get("Cn").filter(|value| value.is_array()) {
+                        data.push((0, bytes(value, "GEN_DATA")?));
+                    } else if let Some(value) = value.get("Dn") {
+                        data.push(bits(value, "GEN_DATA")?);
+                    }
+                }
+            }
+            RAW => data.push((0, bytes(&fields["contents"], "contents")?)),
+            _ => {}
+        }
+        Ok(JsonRecord {
+            name: entry.name,
+            offset: entry.offset,
+            fields,
+            line: 0,
+            data,
+            texts: OnceCell::new(),
+        })
+    }
+
+    /// Converts the record to an STDF record, which borrows its strings and bytes.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind `InvalidData` if the record type is unknown, or if a field is missing
+    /// or does not fit its type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stdf::jsonl::JsonRecord;
+    /// use stdf::records::V4;
+    ///
+    /// let record = JsonRecord::parse(r#"{"type":"PIR","fields":{"head_num":1,"site_num":2}}"#).unwrap();
+    /// match record.to_v4().unwrap() {
+    ///     V4::PIR(pir) => assert_eq!(pir.site_num.0, 2),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn to_v4(&self) -> Result<V4<'_>> {
+        let mut data = self.data.iter().map(|(bits, bytes)| (*bits, bytes.as_slice()));
+        let record = match self.name.as_str() {
+            "FAR" => V4::FAR(self.record()?),
+            "ATR" => V4::ATR(with_texts!(self, ATR, cmd_line)),
+            "MIR" => V4::MIR(with_texts!(self, MIR,
+                lot_id, part_typ, node_nam, tstr_typ, job_nam, job_rev, sblot_id,
+                oper_nam, exec_typ, exec_ver, test_cod, tst_temp, user_txt, aux_file,
+                pkg_typ, famly_id, date_cod, facil_id, floor_id, proc_id, oper_frq,
+                spec_nam, spec_ver, flow_id, setup_id, dsgn_rev, eng_id, rom_cod,
+                serl_num, supr_nam)),
+            "MRR" => V4::MRR(with_texts!(self, MRR, usr_desc, exc_desc)),
+            "PCR" => V4::PCR(self.record()?),
+            "HBR" => V4::HBR(with_texts!(self, HBR, hbin_nam)),
+            "SBR" => V4::SBR(with_texts!(self, SBR, sbin_nam)),
+            "PMR" => V4::PMR(with_texts!(self, PMR, chan_nam, phy_nam, log_nam)),
+            "PGR" => V4::PGR(with_texts!(self, PGR, grp_nam)),
+            "PLR" => {
+                let texts = self.texts(&["pgm_char", "rtn_char", "pgm_chal", "rtn_chal"]);
+                let mut plr: PLR = self.deserialize(&texts.fields)?;
+                for (name, values) in [
+                    ("pgm_char", &mut plr.pgm_char),
+                    ("rtn_char", &mut plr.rtn_char),
+                    ("pgm_chal", &mut plr.pgm_chal),
+                    ("rtn_chal", &mut plr.rtn_chal),
+                ] {
+                    for (i, value) in values.iter_mut().enumerate() {
+                        if let Some(text) = texts.bytes.get(&(name, Some(i))) {
+                            *value = Cn(text);
+                        }
+                    }
+                }
+                V4::PLR(plr)
+            }
+            "RDR" => V4::RDR(self.record()?),
+            "SDR" => V4::SDR(with_texts!(self, SDR,
+                hand_typ, hand_id, card_typ, card_id, load_typ, load_id, dib_typ,
+                dib_id, cabl_typ, cabl_id, cont_typ, cont_id, lasr_typ, lasr_id,
+                extr_typ, extr_id)),
+            "WIR" => V4::WIR(with_texts!(self, WIR, wafer_id)),
+            "WRR" => V4::WRR(with_texts!(self, WRR, wafer_id, fabwf_id, frame_id, mask_id, usr_desc, exc_desc)),
+            "WCR" => V4::WCR(self.record()?),
+            "PIR" => V4::PIR(self.record()?),
+            "PRR" => {
+                let mut prr = with_texts!(self, PRR, part_id, part_txt);
+                prr.part_fix = Bn(data.next().unwrap_or_default().1);
+                V4::PRR(prr)
+            }
+            "TSR" => V4::TSR(with_texts!(self, TSR, test_nam, seq_name, test_lbl)),
+            "PTR" => V4::PTR(with_texts!(self, PTR, test_txt, alarm_id, units, c_resfmt, c_llmfmt, c_hlmfmt)),
+            "MPR" => V4::MPR(with_texts!(self, MPR, test_txt, alarm_id, units, units_in, c_resfmt, c_llmfmt, c_hlmfmt)),
+            "FTR" => {
+                let mut ftr = with_texts!(self, FTR, vect_nam, time_set, op_code, test_txt, alarm_id, prog_txt, rslt_txt);
+                let (bits, bytes) = data.next().unwrap_or_default();
+                ftr.fail_pin = Dn(bits, bytes);
+                let (bits, bytes) = data.next().unwrap_or_default();
+                ftr.spin_map = Dn(bits, bytes);
+                V4::FTR(ftr)
+            }
+            "BPS" => V4::BPS(with_texts!(self, BPS, seq_name)),
+            "EPS" => V4::EPS(EPS),
+            "GDR" => {
+                let values = self.fields["gen_data"].as_array().ok_or_else(|| self.error("missing field `gen_data`"))?;
+                let mut gen_data = Vec::with_capacity(values.len());
+                for value in values {
+                    let vn = match value.get("Cn") {
+                        Some(Value::Array(_)) => Vn::Cn(Cn(data.next().unwrap_or_default().1)),
+                        Some(text) => Vn::Cn(self.deserialize(text)?),
+                        None if value.get("Bn").is_some() => Vn::Bn(Bn(data.next().unwrap_or_default().1)),
+                        None if value.get("Dn").is_some() => {
+                            let (bits, bytes) = data.next().unwrap_or_default();
+                            Vn::Dn(Dn(bits, bytes))
+                        }
+                        None => self.deserialize(value)?,
+                    };
+                    gen_data.push(vn);
+                }
+                if gen_data.len() > u16::MAX as usize {
+                    return Err(self.error("too many `gen_data` values"));
+                }
+                V4::GDR(GDR {
+                    fld_cnt: U2(gen_data.len() as u16),
+                    gen_data,
+                })
+            }
+            "DTR" => V4::DTR(with_texts!(self, DTR, text_dat)),
+            RAW => V4::Unknown(Raw {
+                rec_typ: self.deserialize(&self.fields["rec_typ"])?,
+                rec_sub: self.deserialize(&self.fields["rec_sub"])?,
+                contents: data.next().unwrap_or_default().1,
+            }),
+            name => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Unknown record type '{}'", name)));
+            }
+        };
+        Ok(record)
+    }
+
+    /// Deserializes the fields, borrowing the strings.
+    fn record<'a, T: Deserialize<'a>>(&'a self) -> Result<T> {
+        self.deserialize(&self.fields)
+    }
+
+    fn deserialize<'a, T: Deserialize<'a>>(&self, value: &'a Value) -> Result<T> {
+        T::deserialize(value).map_err(|e| self.error(&e.to_string()))
+    }
+
+    /// The fields, with the `Cn` fields `names` that are not UTF-8 taken out (see [`Texts`]).
+    fn texts(&self, names: &[&'static str]) -> &Texts {
+        self.texts.get_or_init(|| Texts::new(&self.fields, names))
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("Invalid {} : {}", self.name, message))
+    }
+}
+
+/// The bytes of a non-empty array of bytes.
+fn text(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Array(values) if !values.is_empty() => {
+            values.iter().map(|value| value.as_u64().and_then(|byte| u8::try_from(byte).ok())).collect()
+        }
+        _ => None,
+    }
+}
+
+/// Decodes an array of bytes, a missing field is empty.
+fn bytes(value: &Value, field: &str) -> Result<Vec<u8>> {
+    let values = match value {
+        Value::Null => return Ok(Vec::new()),
+        Value::Array(values) => values,
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("{} is not an array of bytes", field))),
+    };
+    values
+        .iter()
+        .map(|value| {
+            value
+                .as_u64()
+                .and_then(|byte| u8::try_from(byte).ok())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} is not an array of bytes", field)))
+        })
+        .collect()
+}
+
+/// Decodes a `[bit count, [bytes]]` pair, a missing field is empty.
+fn bits(value: &Value, field: &str) -> Result<(u16, Vec<u8>)> {
+    match value {
+        Value::Null => Ok((0, Vec::new())),
+        Value::Array(pair) if pair.len() == 2 => {
+            let count = pair[0]
+                .as_u64()
+                .and_then(|count| u16::try_from(count).ok())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} has an invalid bit count", field)))?;
+            Ok((count, bytes(&pair[1], field)?))
+        }
+        _ => Err(Error::new(ErrorKind::InvalidData, format!("{} is not a [bit count, [bytes]] pair", field))),
+    }
+}
+
+/// Converts a JSON Lines file, as written by [`write_jsonl`], to STDF.
+///
+/// The records are written in the byte order of the FAR CPU_TYPE (1 for big endian, little
+/// endian otherwise). The `offset` of the lines is ignored and empty lines are skipped.
+///
+/// # Returns
+///
+/// The number of records written.
+///
+/// # Errors
+///
+/// This function will return an error of kind `InvalidData`, with the line of the record,
+/// if a line is not a record, or an error on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::{BufReader, BufWriter, Result};
+/// use stdf::jsonl::read_jsonl;
+///
+/// fn main() -> Result<()> {
+///     let input = BufReader::new(File::open("test.jsonl")?);
+///     let mut output = BufWriter::new(File::create("test.std")?);
+///     let written = read_jsonl(input, &mut output)?;
+///     println!("{} records written", written);
+///     Ok(())
+/// }
+/// ```
+pub fn read_jsonl<R: BufRead, W: Write>(input: R, output: &mut W) -> Result<u32> {
+    let mut written: u32 = 0;
+    let mut endian = LE;
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at_line = |message: String| Error::new(ErrorKind::InvalidData, format!("Line {}: {}", index + 1, message));
+        let mut record = JsonRecord::parse(&line).map_err(|e| at_line(e.to_string()))?;
+        record.line = index + 1;
+        let v4 = record.to_v4().map_err(|e| at_line(e.to_string()))?;
+        if let V4::FAR(far) = &v4 {
+            endian = if far.cpu_type.0 == 1 { BE } else { LE };
+        }
+        let bytes = v4
+            .to_bytes(endian)
+            .map_err(|e| at_line(format!("Can not write {} : {:?}", record.name, e)))?;
+        output.write_all(&bytes)?;
+        written += 1;
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() {
+        let original = std::fs::read("tests/fixtures/test.std").unwrap();
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let mut jsonl = Vec::new();
+        let written = write_jsonl(&mut file, &mut jsonl, |_| {}).unwrap();
+        assert_eq!(jsonl.iter().filter(|byte| **byte == b'\n').count(), written);
+
+        let first: Value = serde_json::from_slice(jsonl.split(|byte| *byte == b'\n').next().unwrap()).unwrap();
+        assert_eq!(first["type"], "FAR");
+        assert_eq!(first["offset"], 0);
+
+        let mut stdf = Vec::new();
+        let read = read_jsonl(Cursor::new(&jsonl), &mut stdf).unwrap();
+        assert_eq!(read as usize, written);
+        assert_eq!(stdf, original);
+    }
+
+    #[test]
+    fn test_records() {
+        let line = r#"{"type":"PTR","fields":{"test_num":10,"head_num":1,"site_num":0,"test_flg":0,"parm_flg":0,
+            "result":1.5,"test_txt":"vdd","alarm_id":"","opt_flag":2,"res_scal":0,"llm_scal":0,"hlm_scal":0,
+            "lo_limit":1.0,"hi_limit":null,"units":"V","c_resfmt":"","c_llmfmt":"","c_hlmfmt":"","lo_spec":0.0,"hi_spec":0.0}}"#
+            .replace('\n', "");
+        let record = JsonRecord::parse(&line).unwrap();
+        match record.to_v4().unwrap() {
+            V4::PTR(ptr) => {
+                assert_eq!(ptr.test_txt, Cn(b"vdd"));
+                assert_eq!(ptr.result, R4(1.5));
+                assert!(ptr.hi_limit.0.is_nan());
+            }
+            _ => panic!("not a PTR"),
+        }
+
+        let line = r#"{"type":"GDR","fields":{"fld_cnt":0,"gen_data":["B0",{"U2":7},{"Cn":"x"},{"Bn":[1,2]},{"Dn":[3,[5]]}]}}"#;
+        let record = JsonRecord::parse(line).unwrap();
+        match record.to_v4().unwrap() {
+            V4::GDR(gdr) => {
+                assert_eq!(gdr.fld_cnt, U2(5));
+                assert_eq!(
+                    gdr.gen_data,
+                    vec![Vn::B0, Vn::U2(U2(7)), Vn::Cn(Cn(b"x")), Vn::Bn(Bn(&[1, 2])), Vn::Dn(Dn(3, &[5]))]
+                );
+            }
+            _ => panic!("not a GDR"),
+        }
+
+        let record = JsonRecord::parse(r#"{"type":"PIR","fields":{"head_num":1}}"#).unwrap();
+        assert!(record.to_v4().unwrap_err().to_string().contains("site_num"));
+        let record = JsonRecord::parse(r#"{"type":"XYZ","fields":{}}"#).unwrap();
+        assert!(record.to_v4().is_err());
+        assert!(JsonRecord::parse(r#"{"type":"PRR","fields":{"part_fix":[256]}}"#).is_err());
+    }
+
+    #[test]
+    fn test_texts() {
+        // µA in Latin-1 is not UTF-8, the test name has escapes
+        let ptr = V4::PTR(PTR {
+            test_num: U4(10),
+            head_num: U1(1),
+            site_num: U1(0),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            result: R4(1.5e-6),
+            test_txt: Cn("\"Iµ\"\tleak\\".as_bytes()),
+            alarm_id: Cn(b""),
+            opt_flag: B1(0),
+            res_scal: I1(6),
+            llm_scal: I1(6),
+            hlm_scal: I1(6),
+            lo_limit: R4(0.0),
+            hi_limit: R4(2e-6),
+            units: Cn(b"\xb5A"),
+            c_resfmt: Cn(b""),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(0.0),
+            hi_spec: R4(0.0),
+        });
+        let gdr = V4::GDR(GDR {
+            fld_cnt: U2(3),
+            gen_data: vec![Vn::Cn(Cn(b"\xb5A")), Vn::Cn(Cn(b"x")), Vn::U1(U1(181))],
+        });
+        for record in [ptr, gdr] {
+            let line = Line {
+                name: record.name(),
+                offset: 0,
+                fields: RecordFields(&record),
+            };
+            let line = serde_json::to_string(&line).unwrap();
+            assert!(line.contains("[181,65]"), "{}", line);
+            let parsed = JsonRecord::parse(&line).unwrap();
+            assert_eq!(parsed.to_v4().unwrap(), record, "{}", line);
+
+            let mut stdf = Vec::new();
+            read_jsonl(Cursor::new(format!("{}\n", line)), &mut stdf).unwrap();
+            assert_eq!(stdf, record.to_bytes(LE).unwrap());
+        }
+
+        let line = r#"{"type":"PLR","fields":{"grp_cnt":2,"grp_indx":[1,2],"grp_mode":[0,0],"grp_radx":[0,0],
+            "pgm_char":["\u00b5",[181]],"rtn_char":["",""],"pgm_chal":["",""],"rtn_chal":["",""]}}"#
+            .replace('\n', "");
+        let record = JsonRecord::parse(&line).unwrap();
+        match record.to_v4().unwrap() {
+            V4::PLR(plr) => assert_eq!(plr.pgm_char, vec![Cn("µ".as_bytes()), Cn(b"\xb5")]),
+            _ => panic!("not a PLR"),
+        }
+    }
+}
@@ -5,8 +5,29 @@ extern crate stdf_record_derive;
 
 pub mod records;
 pub mod types;
+pub mod flags;
+pub mod units;
+pub mod printf;
 pub mod conversions;
 pub mod tally;
+pub mod anonymize;
+pub mod strip;
+pub mod query;
+pub mod diff;
+pub mod parts;
+pub mod inheritance;
+pub mod pins;
+pub mod ftr;
+pub mod atdf;
+pub mod table;
+pub mod xlsx;
+pub mod arrow;
+pub mod npy;
+pub mod jsonl;
+pub mod stats;
+pub mod sqlite;
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
 
 use std::collections::HashMap;
 use std::fs::File;
@@ -86,7 +107,7 @@ pub fn mrr_offset_in_file(file: &mut File) -> Option<u64> {
 /// use stdf::get_index_from_stdf_file;
 /// 
 /// fn main() -> Result<()> {
-///     let mut file = File::open("tests/fixtures/test.stdf")?;
+///     let mut file = File::open("tests/fixtures/test.std")?;
 ///     let index = get_index_from_stdf_file(&mut file)?;
 ///     println!("{:?}", index);
 ///     Ok(())
@@ -171,7 +192,7 @@ pub fn get_index_from_stdf_file(file: &mut File) -> Result<HashMap<(u8, u8), Vec
 /// use stdf::get_endian_from_file;
 /// 
 /// fn main() -> Result<()> {
-///     let mut file = File::open("tests/fixtures/test.stdf")?;
+///     let mut file = File::open("tests/fixtures/test.std")?;
 ///     match get_endian_from_file(&mut file)? {
 ///         Some(Endian::Little) => println!("File is little-endian"),
 ///         Some(Endian::Big) => println!("File is big-endian"),
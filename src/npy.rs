@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{Error, Result, Seek, Write};
+
+use byte::BytesExt;
+use npyz::npz::NpzWriter;
+use npyz::zip::write::FileOptions;
+use npyz::zip::CompressionMethod;
+use npyz::{AutoSerialize, DType, TypeStr, WriterBuilder};
+
+use crate::records::V4;
+use crate::table::{map, PartRows};
+
+/// The shape of the `results` matrix of an NPZ export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NpzShape {
+    /// The rows: one per part, in the order their PRR was read.
+    pub parts: usize,
+    /// The columns: one per PTR test, in the order the tests first appear in the file.
+    pub tests: usize,
+}
+
+/// The per part arrays of the export, collected while the matrix is streamed out.
+#[derive(Debug, Default)]
+struct PartArrays {
+    part_id: Vec<String>,
+    head_num: Vec<u8>,
+    site_num: Vec<u8>,
+    hard_bin: Vec<u16>,
+    soft_bin: Vec<u16>,
+    x: Vec<i16>,
+    y: Vec<i16>,
+    passed: Vec<i8>,
+}
+
+/// Writes the PTR results of an STDF file as a NumPy `.npz` archive.
+///
+/// The archive holds a dense `results` matrix (float32, parts × PTR tests, NaN where a part
+/// was not tested) with the results in the base units of the tests, i.e. without RES_SCAL.
+/// The companion arrays are:
+///
+/// * per part: `part_id`, `head_num`, `site_num`, `hard_bin`, `soft_bin` (65535 if missing),
+///   `x`, `y` (-32768 if missing) and `passed` (1, 0 or -1 if unknown);
+/// * per test: `test_num`, `test_txt`, `units`, `lo_limit` and `hi_limit` (NaN if missing).
+///
+/// The file is read twice: once to size the matrix, then to write its rows.
+/// `progress` is called after each part of the second pass with the offset read up to in the file.
+///
+/// # Returns
+///
+/// The shape of the `results` matrix.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use stdf::npy::write_npz;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let output = BufWriter::new(File::create("test.npz").unwrap());
+/// let shape = write_npz(&mut file, output, |_| {}).unwrap();
+/// println!("{} parts x {} tests", shape.parts, shape.tests);
+/// ```
+pub fn write_npz<W: Write + Seek, F: FnMut(usize)>(file: &mut File, writer: W, mut progress: F) -> Result<NpzShape> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+
+    let mut parts = 0;
+    let mut rows = PartRows::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        if rows.push(record).is_some() {
+            parts += 1;
+        }
+    }
+    // The PTR columns of the catalog, and their position in the matrix.
+    let catalog = rows.catalog;
+    let mut ptrs = Vec::new();
+    let mut positions = vec![None; catalog.columns.len()];
+    for (index, column) in catalog.columns.iter().enumerate() {
+        if column.rec == "PTR" {
+            positions[index] = Some(ptrs.len());
+            ptrs.push(column);
+        }
+    }
+    let tests = ptrs.len();
+
+    let mut npz = NpzWriter::new(writer);
+    let mut arrays = PartArrays::default();
+    let mut results = npz
+        .array::<f32>("results", options())?
+        .default_dtype()
+        .shape(&[parts as u64, tests as u64])
+        .begin_nd()?;
+    let mut cells = vec![f32::NAN; tests];
+    let mut rows = PartRows::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        let row = match rows.push(record) {
+            Some(row) => row,
+            None => continue,
+        };
+        cells.fill(f32::NAN);
+        for value in row.values.iter() {
+            if let (Some(position), Some(result)) = (positions[value.column], value.result) {
+                cells[position] = result;
+            }
+        }
+        results.extend(cells.iter().copied())?;
+        arrays.part_id.push(row.part_id);
+        arrays.head_num.push(row.head_num);
+        arrays.site_num.push(row.site_num);
+        arrays.hard_bin.push(row.hard_bin);
+        arrays.soft_bin.push(row.soft_bin.unwrap_or(u16::MAX));
+        arrays.x.push(row.x.unwrap_or(i16::MIN));
+        arrays.y.push(row.y.unwrap_or(i16::MIN));
+        arrays.passed.push(row.passed.map_or(-1, i8::from));
+        progress(*offset);
+    }
+    results.finish()?;
+
+    write_strings(&mut npz, "part_id", &arrays.part_id)?;
+    write_numbers(&mut npz, "head_num", &arrays.head_num)?;
+    write_numbers(&mut npz, "site_num", &arrays.site_num)?;
+    write_numbers(&mut npz, "hard_bin", &arrays.hard_bin)?;
+    write_numbers(&mut npz, "soft_bin", &arrays.soft_bin)?;
+    write_numbers(&mut npz, "x", &arrays.x)?;
+    write_numbers(&mut npz, "y", &arrays.y)?;
+    write_numbers(&mut npz, "passed", &arrays.passed)?;
+
+    let test_num: Vec<u32> = ptrs.iter().map(|column| column.test_num).collect();
+    let test_txt: Vec<String> = ptrs.iter().map(|column| column.test_txt.clone()).collect();
+    let units: Vec<String> = ptrs.iter().map(|column| column.limits.units.clone()).collect();
+    let lo_limit: Vec<f32> = ptrs.iter().map(|column| column.limits.lo_limit.unwrap_or(f32::NAN)).collect();
+    let hi_limit: Vec<f32> = ptrs.iter().map(|column| column.limits.hi_limit.unwrap_or(f32::NAN)).collect();
+    write_numbers(&mut npz, "test_num", &test_num)?;
+    write_strings(&mut npz, "test_txt", &test_txt)?;
+    write_strings(&mut npz, "units", &units)?;
+    write_numbers(&mut npz, "lo_limit", &lo_limit)?;
+    write_numbers(&mut npz, "hi_limit", &hi_limit)?;
+
+    npz.zip_writer().finish()?;
+    Ok(NpzShape { parts, tests })
+}
+
+/// The arrays are deflated, as with `numpy.savez_compressed`: the matrix is mostly repeated NaNs
+/// for files with per site or per flow tests.
+fn options() -> FileOptions {
+    FileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+fn write_numbers<W: Write + Seek, T: AutoSerialize + Copy>(npz: &mut NpzWriter<W>, name: &str, values: &[T]) -> Result<()> {
+    let mut array = npz
+        .array::<T>(name, options())?
+        .default_dtype()
+        .shape(&[values.len() as u64])
+        .begin_nd()?;
+    array.extend(values.iter().copied())?;
+    array.finish()
+}
+
+/// Strings are written as fixed width unicode (`<U`), the width of the longest one.
+fn write_strings<W: Write + Seek>(npz: &mut NpzWriter<W>, name: &str, values: &[String]) -> Result<()> {
+    let width = values.iter().map(|value| value.chars().count()).max().unwrap_or(0).max(1);
+    let type_str: TypeStr = format!("<U{}", width).parse().map_err(Error::other)?;
+    let mut array = npz
+        .array::<str>(name, options())?
+        .dtype(DType::Plain(type_str))
+        .shape(&[values.len() as u64])
+        .begin_nd()?;
+    for value in values {
+        array.push(value)?;
+    }
+    array.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use npyz::npz::NpzArchive;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_npz() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let mut output = Cursor::new(Vec::new());
+        let shape = write_npz(&mut file, &mut output, |_| {}).unwrap();
+        assert_eq!(shape, NpzShape { parts: 22, tests: 26 });
+
+        let mut npz = NpzArchive::new(Cursor::new(output.into_inner())).unwrap();
+        let results = npz.by_name("results").unwrap().unwrap();
+        assert_eq!(results.shape(), &[22, 26]);
+        let results = results.into_vec::<f32>().unwrap();
+        assert_eq!(results.iter().filter(|value| !value.is_nan()).count(), 572);
+
+        let site_num = npz.by_name("site_num").unwrap().unwrap().into_vec::<u8>().unwrap();
+        assert_eq!(site_num.len(), 22);
+        assert!(site_num.iter().all(|site| *site <= 1));
+        let hard_bin = npz.by_name("hard_bin").unwrap().unwrap().into_vec::<u16>().unwrap();
+        assert_eq!(hard_bin.len(), 22);
+
+        let part_id = npz.by_name("part_id").unwrap().unwrap().into_vec::<String>().unwrap();
+        assert_eq!(part_id[0], "1");
+        let test_num = npz.by_name("test_num").unwrap().unwrap().into_vec::<u32>().unwrap();
+        let test_txt = npz.by_name("test_txt").unwrap().unwrap().into_vec::<String>().unwrap();
+        assert_eq!(test_num.len(), 26);
+        assert_eq!(test_txt.len(), 26);
+        let lo_limit = npz.by_name("lo_limit").unwrap().unwrap().into_vec::<f32>().unwrap();
+        assert_eq!(lo_limit.len(), 26);
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::flags::TestFlag;
+use crate::inheritance::{MprResolver, PtrResolver};
+use crate::pins::PinMap;
+use crate::records::{PIR, PRR, V4};
+use crate::units::Measurement;
+
+/// A tested part: the PIR, the test records (PTR, MPR, FTR) in between and the PRR of one head/site.
+#[derive(Debug)]
+pub struct Part<'a> {
+    /// The index (from 0) of the touchdown the part was tested in.
+    pub touchdown: usize,
+    pub pir: Option<PIR>,
+    pub tests: Vec<V4<'a>>,
+    pub prr: PRR<'a>,
+    results: Vec<TestResult>,
+}
+
+/// The result of one test of a part.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestResult {
+    pub rec: String,
+    pub test_num: u32,
+    pub test_txt: String,
+    /// One value for a PTR, one per pin for an MPR and none for an FTR.
+    pub results: Vec<f32>,
+    /// The names of the pins of the MPR results, empty for a PTR/FTR.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<String>,
+    pub units: String,
+    /// The scale the results are shown with, `None` if the record has none.
+    pub res_scal: Option<i8>,
+    /// The C printf-style format of the results (C_RESFMT), empty if none.
+    pub c_resfmt: String,
+    /// `None` when the test flags carry no (valid) pass/fail indication.
+    pub passed: Option<bool>,
+}
+
+impl TestResult {
+    /// The results in base units, shown with the scale of the record.
+    pub fn measurements(&self) -> Vec<Measurement> {
+        self.results
+            .iter()
+            .map(|result| Measurement::new(*result as f64, &self.units, self.res_scal))
+            .collect()
+    }
+}
+
+/// Decodes the pass/fail bits of a PTR/MPR/FTR `test_flg`.
+pub fn test_passed(test_flg: u8) -> Option<bool> {
+    TestFlag(test_flg).passed()
+}
+
+impl Part<'_> {
+    pub fn head_num(&self) -> u8 {
+        self.prr.head_num.0
+    }
+
+    pub fn site_num(&self) -> u8 {
+        self.prr.site_num.0
+    }
+
+    pub fn part_id(&self) -> String {
+        self.prr.part_id.to_string().trim().to_string()
+    }
+
+    /// The die coordinates, `None` when not given (-32768).
+    pub fn xy(&self) -> Option<(i16, i16)> {
+        if self.prr.x_coord.0 == i16::MIN || self.prr.y_coord.0 == i16::MIN {
+            None
+        } else {
+            Some((self.prr.x_coord.0, self.prr.y_coord.0))
+        }
+    }
+
+    /// `None` when the PRR has no valid pass/fail indication.
+    pub fn passed(&self) -> Option<bool> {
+        self.prr.part_flags().passed()
+    }
+
+    /// The results of the test records of the part, in the order they were tested.
+    ///
+    /// The fields that a PTR/MPR leaves to the first record of its test (units, scale, format
+    /// and name) are filled in, and the MPR pins are named after the PMRs.
+    pub fn results(&self) -> &[TestResult] {
+        &self.results
+    }
+
+    /// The part as a JSON object, with its results.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "head_num": self.head_num(),
+            "site_num": self.site_num(),
+            "touchdown": self.touchdown,
+            "part_id": self.part_id(),
+            "x_coord": self.xy().map(|xy| xy.0),
+            "y_coord": self.xy().map(|xy| xy.1),
+            "hard_bin": self.prr.hard_bin.0,
+            "soft_bin": if self.prr.soft_bin.0 == u16::MAX { None } else { Some(self.prr.soft_bin.0) },
+            "test_t": self.prr.test_t.0,
+            "passed": self.passed(),
+            "results": self.results(),
+        })
+    }
+}
+
+fn pass_fail(passed: Option<bool>) -> &'static str {
+    match passed {
+        Some(true) => "PASS",
+        Some(false) => "FAIL",
+        None => "?",
+    }
+}
+
+impl fmt::Display for Part<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let part_id = self.part_id();
+        writeln!(f, "PART {} : HEAD {} SITE {}", if part_id.is_empty() { "∕".to_string() } else { format!("'{}'", part_id) }, self.head_num(), self.site_num())?;
+        match self.xy() {
+            Some((x, y)) => writeln!(f, "   X, Y     : {}, {}", x, y)?,
+            None => writeln!(f, "   X, Y     : ∕")?,
+        }
+        writeln!(f, "   HARD_BIN : {}", self.prr.hard_bin)?;
+        writeln!(f, "   SOFT_BIN : {}", if self.prr.soft_bin.0 == u16::MAX { "∕".to_string() } else { self.prr.soft_bin.to_string() })?;
+        writeln!(f, "   TEST_T   : {}", if self.prr.test_t.0 == 0 { "∕".to_string() } else { format!("{:.3} sec", self.prr.test_t.0 as f32 / 1_000.0) })?;
+        writeln!(f, "   RESULT   : {}", pass_fail(self.passed()))?;
+        for result in self.results() {
+            let value = match result.results.len() {
+                0 => String::new(),
+                1 if result.pins.is_empty() => result.measurements()[0].to_string_with(&result.c_resfmt),
+                _ => {
+                    let values = result.measurements().into_iter().enumerate().map(|(i, m)| match result.pins.get(i).filter(|name| !name.is_empty()) {
+                        Some(name) => format!("{}: {}", name, m.to_string_with(&result.c_resfmt)),
+                        None => m.to_string_with(&result.c_resfmt),
+                    });
+                    format!("[{}]", values.collect::<Vec<_>>().join(", "))
+                }
+            };
+            writeln!(f, "   {} {:>10} {:<4} {:<32} {}", result.rec, result.test_num, pass_fail(result.passed), result.test_txt, value.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups the records of a V4 stream into parts.
+///
+/// Parts are kept apart per head/site, so the records of sites that are tested
+/// in parallel may be interleaved. Any head and site number is supported.
+///
+/// Every part is also given the touchdown (insertion) it was tested in: a PIR
+/// starts a new touchdown when its head/site is already used in the current
+/// touchdown, so sites that are tested one after the other in an insertion
+/// stay in the same touchdown.
+///
+/// The test records are resolved in file order (see [`PtrResolver`] and [`MprResolver`]),
+/// so the records of all the parts need to be fed, as well as the PMR/PGR/PLR for the pin names.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use byte::BytesExt;
+/// use memmap::MmapOptions;
+/// use stdf::get_endian_from_file;
+/// use stdf::parts::PartAssembler;
+/// use stdf::records::V4;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let endian = get_endian_from_file(&mut file).unwrap().unwrap();
+/// let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+/// let bytes = &mmap[..];
+/// let mut assembler = PartAssembler::new();
+/// let offset = &mut 0;
+/// while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+///     if let Some(part) = assembler.push(record) {
+///         println!("{}", part);
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct PartAssembler<'a> {
+    open: HashMap<(u8, u8), OpenPart<'a>>,
+    touchdown: usize,
+    touchdown_sites: HashSet<(u8, u8)>,
+    ptr_resolver: PtrResolver,
+    mpr_resolver: MprResolver,
+    pin_map: PinMap,
+    /// The first non-empty TEST_TXT of each (record, test number).
+    names: HashMap<(String, u32), String>,
+}
+
+#[derive(Default)]
+struct OpenPart<'a> {
+    touchdown: usize,
+    pir: Option<PIR>,
+    tests: Vec<V4<'a>>,
+    results: Vec<TestResult>,
+}
+
+impl<'a> PartAssembler<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the next record, returns the part that the record completes (if it is a PRR).
+    ///
+    /// Records that are not part of a part are dropped, as are test records of a head/site without PIR.
+    pub fn push(&mut self, record: V4<'a>) -> Option<Part<'a>> {
+        match record {
+            V4::PIR(pir) => {
+                let key = (pir.head_num.0, pir.site_num.0);
+                if self.touchdown_sites.contains(&key) {
+                    self.touchdown += 1;
+                    self.touchdown_sites.clear();
+                }
+                self.touchdown_sites.insert(key);
+                self.open.insert(
+                    key,
+                    OpenPart {
+                        touchdown: self.touchdown,
+                        pir: Some(pir),
+                        tests: Vec::new(),
+                        results: Vec::new(),
+                    },
+                );
+                None
+            }
+            V4::PMR(ref pmr) => {
+                self.pin_map.add_pmr(pmr);
+                None
+            }
+            V4::PGR(ref pgr) => {
+                self.pin_map.add_pgr(pgr);
+                None
+            }
+            V4::PLR(ref plr) => {
+                self.pin_map.add_plr(plr);
+                None
+            }
+            V4::PTR(ref r) => {
+                let limits = self.ptr_resolver.resolve(r);
+                let result = TestResult {
+                    rec: record.name(),
+                    test_num: r.test_num.0,
+                    test_txt: self.name(&record, r.test_num.0, r.test_txt.to_string()),
+                    results: vec![r.result.0],
+                    pins: Vec::new(),
+                    units: limits.units,
+                    res_scal: limits.res_scal,
+                    c_resfmt: limits.c_resfmt,
+                    passed: r.test_flags().passed(),
+                };
+                self.add((r.head_num.0, r.site_num.0), record, result)
+            }
+            V4::MPR(ref r) => {
+                let resolved = self.mpr_resolver.resolve(r, &self.pin_map);
+                let result = TestResult {
+                    rec: record.name(),
+                    test_num: r.test_num.0,
+                    test_txt: self.name(&record, r.test_num.0, r.test_txt.to_string()),
+                    results: r.rtn_rslt.iter().map(|v| v.0).collect(),
+                    pins: resolved.pins.into_iter().take(r.rtn_rslt.len()).map(|pin| pin.pin_name).collect(),
+                    units: resolved.limits.units,
+                    res_scal: resolved.limits.res_scal,
+                    c_resfmt: resolved.limits.c_resfmt,
+                    passed: r.test_flags().passed(),
+                };
+                self.add((r.head_num.0, r.site_num.0), record, result)
+            }
+            V4::FTR(ref r) => {
+                let result = TestResult {
+                    rec: record.name(),
+                    test_num: r.test_num.0,
+                    test_txt: self.name(&record, r.test_num.0, r.test_txt.to_string()),
+                    results: vec![],
+                    pins: Vec::new(),
+                    units: String::new(),
+                    res_scal: None,
+                    c_resfmt: String::new(),
+                    passed: r.test_flags().passed(),
+                };
+                self.add((r.head_num.0, r.site_num.0), record, result)
+            }
+            V4::PRR(prr) => {
+                let key = (prr.head_num.0, prr.site_num.0);
+                let open = self.open.remove(&key).unwrap_or(OpenPart {
+                    touchdown: self.touchdown,
+                    ..Default::default()
+                });
+                Some(Part {
+                    touchdown: open.touchdown,
+                    pir: open.pir,
+                    tests: open.tests,
+                    prr,
+                    results: open.results,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn add(&mut self, key: (u8, u8), record: V4<'a>, result: TestResult) -> Option<Part<'a>> {
+        if let Some(open) = self.open.get_mut(&key) {
+            open.tests.push(record);
+            open.results.push(result);
+        }
+        None
+    }
+
+    /// The TEST_TXT of a test record, the one of the first record of the test when it is empty.
+    fn name(&mut self, record: &V4, test_num: u32, test_txt: String) -> String {
+        let key = (record.name(), test_num);
+        if test_txt.trim().is_empty() {
+            self.names.get(&key).cloned().unwrap_or(test_txt)
+        } else {
+            self.names.entry(key).or_insert_with(|| test_txt.clone());
+            test_txt
+        }
+    }
+
+    /// The lowest touchdown index of the parts that are not complete yet.
+    fn oldest_open_touchdown(&self) -> Option<usize> {
+        self.open.values().map(|open| open.touchdown).min()
+    }
+}
+
+/// The parts that were tested together in one insertion, ordered by head and site.
+#[derive(Debug)]
+pub struct Touchdown<'a> {
+    pub index: usize,
+    pub parts: Vec<Part<'a>>,
+}
+
+/// Groups the records of a V4 stream into touchdowns, see [`PartAssembler`].
+///
+/// A touchdown is returned once all its parts are complete and the next touchdown has started.
+#[derive(Default)]
+pub struct TouchdownAssembler<'a> {
+    parts: PartAssembler<'a>,
+    complete: BTreeMap<usize, Vec<Part<'a>>>,
+}
+
+impl<'a> TouchdownAssembler<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the next record, returns the touchdowns that are now complete.
+    pub fn push(&mut self, record: V4<'a>) -> Vec<Touchdown<'a>> {
+        if let Some(part) = self.parts.push(record) {
+            self.complete.entry(part.touchdown).or_default().push(part);
+        }
+        let limit = self.parts.oldest_open_touchdown().unwrap_or(usize::MAX).min(self.parts.touchdown);
+        self.take_before(limit)
+    }
+
+    /// Returns the touchdowns that are left at the end of the stream, parts without PRR are dropped.
+    pub fn finish(&mut self) -> Vec<Touchdown<'a>> {
+        self.parts.open.clear();
+        self.take_before(usize::MAX)
+    }
+
+    fn take_before(&mut self, limit: usize) -> Vec<Touchdown<'a>> {
+        let mut touchdowns = Vec::new();
+        while let Some(entry) = self.complete.first_entry() {
+            if *entry.key() >= limit {
+                break;
+            }
+            let (index, mut parts) = entry.remove_entry();
+            parts.sort_by_key(|part| (part.head_num(), part.site_num()));
+            touchdowns.push(Touchdown { index, parts });
+        }
+        touchdowns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_endian_from_file;
+    use crate::records::{MPR, PMR, PTR};
+    use byte::BytesExt;
+    use crate::types::*;
+    use memmap::MmapOptions;
+    use std::fs::File;
+
+    #[test]
+    fn test_test_passed() {
+        assert_eq!(test_passed(0), Some(true));
+        assert_eq!(test_passed(0b1000_0000), Some(false));
+        assert_eq!(test_passed(0b0100_0000), None);
+        assert_eq!(test_passed(0b0001_0000), None);
+    }
+
+    #[test]
+    fn test_part_assembler() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let endian = get_endian_from_file(&mut file).unwrap().unwrap();
+        let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+        let bytes = &mmap[..];
+        let mut assembler = PartAssembler::new();
+        let mut parts = Vec::new();
+        let offset = &mut 0;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            if let Some(part) = assembler.push(record) {
+                parts.push(part);
+            }
+        }
+        assert_eq!(parts.len(), 22);
+        // two sites, tested in parallel
+        assert!(parts.iter().enumerate().all(|(i, part)| part.touchdown == i / 2));
+        assert_eq!(parts.iter().map(|p| p.tests.len()).sum::<usize>(), 572);
+        for part in parts.iter() {
+            assert!(part.pir.is_some());
+            assert_eq!(part.results().len(), part.tests.len());
+            assert!(part.tests.iter().all(|t| match t {
+                V4::PTR(ptr) => ptr.site_num.0 == part.site_num(),
+                _ => false,
+            }));
+        }
+        let json = parts[0].to_json();
+        assert_eq!(json["part_id"], "1");
+        assert_eq!(json["results"].as_array().unwrap().len(), parts[0].tests.len());
+    }
+
+    fn pir(site_num: u8) -> V4<'static> {
+        V4::PIR(PIR {
+            head_num: U1(1),
+            site_num: U1(site_num),
+        })
+    }
+
+    fn prr(site_num: u8) -> V4<'static> {
+        V4::PRR(PRR {
+            head_num: U1(1),
+            site_num: U1(site_num),
+            part_flg: B1(0),
+            num_test: U2(0),
+            hard_bin: U2(1),
+            soft_bin: U2(1),
+            x_coord: I2(i16::MIN),
+            y_coord: I2(i16::MIN),
+            test_t: U4(0),
+            part_id: Cn(b""),
+            part_txt: Cn(b""),
+            part_fix: Bn(b""),
+        })
+    }
+
+    fn ptr(first: bool) -> V4<'static> {
+        V4::PTR(PTR {
+            test_num: U4(400),
+            head_num: U1(1),
+            site_num: U1(1),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            result: R4(0.0015),
+            test_txt: Cn(if first { b"vdd" } else { b"" }),
+            alarm_id: Cn(b""),
+            opt_flag: B1(if first { 0 } else { 0b0000_1111 }),
+            res_scal: I1(3),
+            llm_scal: I1(3),
+            hlm_scal: I1(3),
+            lo_limit: R4(0.001),
+            hi_limit: R4(0.002),
+            units: Cn(if first { b"V" } else { b"" }),
+            c_resfmt: Cn(if first { b"%.3f" } else { b"" }),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        })
+    }
+
+    fn mpr() -> V4<'static> {
+        V4::MPR(MPR {
+            test_num: U4(500),
+            head_num: U1(1),
+            site_num: U1(1),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            rtn_icnt: U2(1),
+            rslt_cnt: U2(1),
+            rtn_stat: vec![N1(1)],
+            rtn_rslt: vec![R4(0.1)],
+            test_txt: Cn(b"leakage"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(0),
+            res_scal: I1(0),
+            llm_scal: I1(0),
+            hlm_scal: I1(0),
+            lo_limit: R4(0.0),
+            hi_limit: R4(1.0),
+            start_in: R4(f32::NAN),
+            incr_in: R4(f32::NAN),
+            rtn_indx: vec![U2(7)],
+            units: Cn(b"A"),
+            units_in: Cn(b""),
+            c_resfmt: Cn(b""),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        })
+    }
+
+    #[test]
+    fn test_resolved_results() {
+        let pmr = V4::PMR(PMR {
+            pmr_index: U2(7),
+            chan_typ: U2(0),
+            chan_nam: Cn(b""),
+            phy_nam: Cn(b""),
+            log_nam: Cn(b"VDD"),
+            head_num: U1(1),
+            site_num: U1(1),
+        });
+        // the second part leaves the units, scale, format and name of the PTR to the first one
+        let records = vec![pmr, pir(1), ptr(true), prr(1), pir(1), ptr(false), mpr(), prr(1)];
+        let mut assembler = PartAssembler::new();
+        let parts: Vec<Part> = records.into_iter().filter_map(|record| assembler.push(record)).collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].results()[0], parts[0].results()[0]);
+        let results = parts[1].results();
+        assert_eq!(results[0].test_txt, "vdd");
+        assert_eq!(results[0].units, "V");
+        assert_eq!(results[0].res_scal, Some(3));
+        assert_eq!(results[0].c_resfmt, "%.3f");
+        assert_eq!(results[1].pins, vec!["VDD".to_string()]);
+        assert!(parts[1].to_string().contains("MPR        500 PASS leakage"));
+        assert!(parts[1].to_string().contains("[VDD: "));
+        assert_eq!(parts[1].to_json()["results"][1]["pins"][0], "VDD");
+    }
+
+    #[test]
+    fn test_touchdowns() {
+        // two touchdowns on sites 12 and 200, the second with the sites ending out of order
+        let records = vec![pir(12), pir(200), prr(200), prr(12), pir(200), pir(12), prr(12), pir(12), prr(200), prr(12)];
+        let mut assembler = TouchdownAssembler::new();
+        let mut touchdowns = Vec::new();
+        for record in records {
+            touchdowns.extend(assembler.push(record));
+        }
+        assert_eq!(touchdowns.len(), 2);
+        touchdowns.extend(assembler.finish());
+        let sites: Vec<Vec<u8>> = touchdowns
+            .iter()
+            .map(|td| td.parts.iter().map(|part| part.site_num()).collect())
+            .collect();
+        assert_eq!(sites, vec![vec![12, 200], vec![12, 200], vec![12]]);
+        assert_eq!(touchdowns.iter().map(|td| td.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_serial_touchdowns() {
+        // sites 1 and 2 tested one after the other in each insertion
+        let records = vec![pir(1), prr(1), pir(2), prr(2), pir(1), prr(1), pir(2), prr(2)];
+        let mut assembler = TouchdownAssembler::new();
+        let mut touchdowns = Vec::new();
+        for record in records {
+            touchdowns.extend(assembler.push(record));
+        }
+        touchdowns.extend(assembler.finish());
+        let sites: Vec<Vec<u8>> = touchdowns
+            .iter()
+            .map(|td| td.parts.iter().map(|part| part.site_num()).collect())
+            .collect();
+        assert_eq!(sites, vec![vec![1, 2], vec![1, 2]]);
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use byte::BytesExt;
+use memmap::MmapOptions;
+use serde::Serialize;
+
+use crate::get_endian_from_file;
+use crate::records::{PGR, PLR, PMR, V4};
+
+/// A tester channel/pin, as described by a PMR.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pin {
+    pub index: u16,
+    pub chan_typ: u16,
+    pub chan_nam: String,
+    pub phy_nam: String,
+    pub log_nam: String,
+    pub head_num: u8,
+    pub site_num: u8,
+}
+
+impl Pin {
+    /// The most descriptive name of the pin: the logical, physical or channel name.
+    pub fn name(&self) -> &str {
+        [&self.log_nam, &self.phy_nam, &self.chan_nam]
+            .into_iter()
+            .find(|name| !name.is_empty())
+            .map(|name| name.as_str())
+            .unwrap_or("")
+    }
+}
+
+impl From<&PMR<'_>> for Pin {
+    fn from(pmr: &PMR) -> Self {
+        Pin {
+            index: pmr.pmr_index.0,
+            chan_typ: pmr.chan_typ.0,
+            chan_nam: pmr.chan_nam.to_string().trim().to_string(),
+            phy_nam: pmr.phy_nam.to_string().trim().to_string(),
+            log_nam: pmr.log_nam.to_string().trim().to_string(),
+            head_num: pmr.head_num.0,
+            site_num: pmr.site_num.0,
+        }
+    }
+}
+
+/// A named group of pins, as described by a PGR.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PinGroup {
+    pub index: u16,
+    pub name: String,
+    /// The PMR indices of the pins in the group.
+    pub members: Vec<u16>,
+}
+
+/// The display settings of a pin or pin group, as described by a PLR.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PinList {
+    pub mode: u16,
+    pub radix: u8,
+    pub pgm_char: String,
+    pub rtn_char: String,
+    pub pgm_chal: String,
+    pub rtn_chal: String,
+}
+
+impl PinList {
+    pub fn mode_name(&self) -> &'static str {
+        match self.mode {
+            0 => "Unknown",
+            10 => "Normal",
+            20 => "SCIO (Same Cycle I/O)",
+            21 => "SCIO Midband",
+            22 => "SCIO Valid",
+            23 => "SCIO Window Sustain",
+            30 => "Dual drive (two drive bits per cycle)",
+            31 => "Dual drive Midband",
+            32 => "Dual drive Valid",
+            33 => "Dual drive Window Sustain",
+            32768.. => "User defined",
+            _ => "Reserved",
+        }
+    }
+
+    pub fn radix_name(&self) -> &'static str {
+        match self.radix {
+            0 => "Default",
+            2 => "Binary",
+            8 => "Octal",
+            10 => "Decimal",
+            16 => "Hexadecimal",
+            20 => "Symbolic",
+            _ => "Invalid",
+        }
+    }
+
+    /// The programmed state of the nth pin of the group, made of the high (CHAL) and low (CHAR) character.
+    pub fn program_state(&self, n: usize) -> Option<String> {
+        state(&self.pgm_chal, &self.pgm_char, n)
+    }
+
+    /// The returned state of the nth pin of the group, made of the high (CHAL) and low (CHAR) character.
+    pub fn return_state(&self, n: usize) -> Option<String> {
+        state(&self.rtn_chal, &self.rtn_char, n)
+    }
+}
+
+fn state(chal: &str, char: &str, n: usize) -> Option<String> {
+    let low = char.chars().nth(n);
+    let high = chal.chars().nth(n).filter(|c| *c != ' ');
+    match (high, low) {
+        (None, None) => None,
+        (high, low) => Some(high.into_iter().chain(low).collect()),
+    }
+}
+
+/// Resolves the pin and pin group indices used by MPR and FTR records.
+///
+/// Pins come from the PMRs (per head/site), groups from the PGRs and the
+/// display settings of pins and groups from the PLRs.
+#[derive(Debug, Default)]
+pub struct PinMap {
+    pins: HashMap<(u8, u8, u16), Pin>,
+    by_index: HashMap<u16, Pin>,
+    groups: HashMap<u16, PinGroup>,
+    lists: HashMap<u16, PinList>,
+}
+
+impl PinMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the pin map from the PMR, PGR and PLR records of an STDF file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file is not an STDF file or on any I/O error.
+    pub fn from_file(file: &mut File) -> Result<PinMap> {
+        let endian = match get_endian_from_file(file)? {
+            Some(endian) => endian,
+            None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+        };
+        let mmap = unsafe { MmapOptions::new().map(&*file)? };
+        let bytes = &mmap[..];
+        let mut pin_map = PinMap::new();
+        let offset = &mut 0;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            match record {
+                V4::PMR(pmr) => pin_map.add_pmr(&pmr),
+                V4::PGR(pgr) => pin_map.add_pgr(&pgr),
+                V4::PLR(plr) => pin_map.add_plr(&plr),
+                _ => {}
+            }
+        }
+        Ok(pin_map)
+    }
+
+    pub fn add_pmr(&mut self, pmr: &PMR) {
+        let pin = Pin::from(pmr);
+        self.by_index.entry(pin.index).or_insert_with(|| pin.clone());
+        self.pins.insert((pin.head_num, pin.site_num, pin.index), pin);
+    }
+
+    pub fn add_pgr(&mut self, pgr: &PGR) {
+        let group = PinGroup {
+            index: pgr.grp_indx.0,
+            name: pgr.grp_nam.to_string().trim().to_string(),
+            members: pgr.pmr_indx.iter().map(|i| i.0).collect(),
+        };
+        self.groups.insert(group.index, group);
+    }
+
+    pub fn add_plr(&mut self, plr: &PLR) {
+        let text = |list: &[crate::types::Cn], i: usize| list.get(i).map(|c| c.to_string()).unwrap_or_default();
+        for (i, index) in plr.grp_indx.iter().enumerate() {
+            let list = PinList {
+                mode: plr.grp_mode.get(i).map(|m| m.0).unwrap_or(0),
+                radix: plr.grp_radx.get(i).map(|r| r.0).unwrap_or(0),
+                pgm_char: text(&plr.pgm_char, i),
+                rtn_char: text(&plr.rtn_char, i),
+                pgm_chal: text(&plr.pgm_chal, i),
+                rtn_chal: text(&plr.rtn_chal, i),
+            };
+            self.lists.insert(index.0, list);
+        }
+    }
+
+    /// The pin with `index` for head/site, or any pin with that index when the PMRs are not per site.
+    pub fn pin(&self, head_num: u8, site_num: u8, index: u16) -> Option<&Pin> {
+        self.pins
+            .get(&(head_num, site_num, index))
+            .or_else(|| self.by_index.get(&index))
+    }
+
+    pub fn group(&self, index: u16) -> Option<&PinGroup> {
+        self.groups.get(&index)
+    }
+
+    /// The PLR settings of a pin or group.
+    pub fn list(&self, index: u16) -> Option<&PinList> {
+        self.lists.get(&index)
+    }
+
+    /// The pins of a pin or group index, for head/site.
+    pub fn resolve(&self, head_num: u8, site_num: u8, index: u16) -> Vec<&Pin> {
+        match self.groups.get(&index) {
+            Some(group) => group
+                .members
+                .iter()
+                .filter_map(|member| self.pin(head_num, site_num, *member))
+                .collect(),
+            None => self.pin(head_num, site_num, index).into_iter().collect(),
+        }
+    }
+
+    /// The name of a pin or group index, for head/site.
+    pub fn name(&self, head_num: u8, site_num: u8, index: u16) -> Option<String> {
+        match self.groups.get(&index) {
+            Some(group) => Some(group.name.clone()),
+            None => self.pin(head_num, site_num, index).map(|pin| pin.name().to_string()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Writes the pins as CSV, one row per head/site/pin, with the groups each pin is in.
+    pub fn write_pins_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut groups_of: HashMap<u16, Vec<&str>> = HashMap::new();
+        let sorted_groups: BTreeMap<u16, &PinGroup> = self.groups.iter().map(|(i, g)| (*i, g)).collect();
+        for group in sorted_groups.values() {
+            for member in group.members.iter() {
+                groups_of.entry(*member).or_default().push(&group.name);
+            }
+        }
+        let mut csv = csv::Writer::from_writer(writer);
+        csv.write_record(["head_num", "site_num", "pmr_index", "chan_typ", "chan_nam", "phy_nam", "log_nam", "groups"])?;
+        let sorted: BTreeMap<&(u8, u8, u16), &Pin> = self.pins.iter().collect();
+        for pin in sorted.values() {
+            csv.write_record([
+                pin.head_num.to_string(),
+                pin.site_num.to_string(),
+                pin.index.to_string(),
+                pin.chan_typ.to_string(),
+                pin.chan_nam.clone(),
+                pin.phy_nam.clone(),
+                pin.log_nam.clone(),
+                groups_of.get(&pin.index).map(|g| g.join(";")).unwrap_or_default(),
+            ])?;
+        }
+        csv.flush()
+    }
+
+    /// Writes the pin groups as CSV, with their PLR mode and radix and their member pins.
+    pub fn write_groups_csv<W: Write>(&self, writer: W) -> Result<()> {
+        let mut csv = csv::Writer::from_writer(writer);
+        csv.write_record(["grp_indx", "grp_nam", "grp_mode", "grp_radx", "members"])?;
+        let sorted: BTreeMap<&u16, &PinGroup> = self.groups.iter().collect();
+        for group in sorted.values() {
+            let list = self.lists.get(&group.index);
+            let members: Vec<String> = group
+                .members
+                .iter()
+                .map(|member| match self.by_index.get(member) {
+                    Some(pin) => pin.name().to_string(),
+                    None => format!("#{}", member),
+                })
+                .collect();
+            csv.write_record([
+                group.index.to_string(),
+                group.name.clone(),
+                list.map(|l| l.mode_name().to_string()).unwrap_or_default(),
+                list.map(|l| l.radix_name().to_string()).unwrap_or_default(),
+                members.join(";"),
+            ])?;
+        }
+        csv.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    #[test]
+    fn test_pin_map_from_file() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let pin_map = PinMap::from_file(&mut file).unwrap();
+        assert_eq!(pin_map.pins.len(), 120);
+        let pin = pin_map.pins.values().next().unwrap();
+        assert_eq!(pin_map.pin(pin.head_num, pin.site_num, pin.index), Some(pin));
+        assert!(pin_map.pin(0, 0, u16::MAX).is_none());
+    }
+
+    fn pin_map() -> PinMap {
+        let mut pin_map = PinMap::new();
+        for (index, site_num, name) in [(1, 1, b"A"), (2, 1, b"B"), (1, 2, b"a"), (2, 2, b"b")] {
+            pin_map.add_pmr(&PMR {
+                pmr_index: U2(index),
+                chan_typ: U2(0),
+                chan_nam: Cn(b""),
+                phy_nam: Cn(b"ch"),
+                log_nam: Cn(name),
+                head_num: U1(1),
+                site_num: U1(site_num),
+            });
+        }
+        pin_map.add_pgr(&PGR {
+            grp_indx: U2(32768),
+            grp_nam: Cn(b"BUS"),
+            indx_cnt: U2(2),
+            pmr_indx: vec![U2(1), U2(2)],
+        });
+        pin_map.add_plr(&PLR {
+            grp_cnt: U2(1),
+            grp_indx: vec![U2(32768)],
+            grp_mode: vec![U2(20)],
+            grp_radx: vec![U1(2)],
+            pgm_char: vec![Cn(b"01")],
+            rtn_char: vec![Cn(b"HL")],
+            pgm_chal: vec![Cn(b"  ")],
+            rtn_chal: vec![Cn(b" M")],
+        });
+        pin_map
+    }
+
+    #[test]
+    fn test_groups() {
+        let pin_map = pin_map();
+        let names: Vec<&str> = pin_map.resolve(1, 2, 32768).iter().map(|pin| pin.name()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(pin_map.resolve(1, 1, 2)[0].name(), "B");
+        assert_eq!(pin_map.name(1, 1, 32768), Some("BUS".to_string()));
+        let list = pin_map.list(32768).unwrap();
+        assert_eq!(list.mode_name(), "SCIO (Same Cycle I/O)");
+        assert_eq!(list.radix_name(), "Binary");
+        assert_eq!(list.program_state(1), Some("1".to_string()));
+        assert_eq!(list.return_state(1), Some("ML".to_string()));
+        assert_eq!(list.return_state(2), None);
+    }
+
+    #[test]
+    fn test_csv() {
+        let pin_map = pin_map();
+        let mut pins: Vec<u8> = Vec::new();
+        pin_map.write_pins_csv(&mut pins).unwrap();
+        let pins = String::from_utf8(pins).unwrap();
+        assert_eq!(pins.lines().count(), 5);
+        assert_eq!(pins.lines().nth(1), Some("1,1,1,0,,ch,A,BUS"));
+        let mut groups: Vec<u8> = Vec::new();
+        pin_map.write_groups_csv(&mut groups).unwrap();
+        let groups = String::from_utf8(groups).unwrap();
+        assert_eq!(groups.lines().nth(1), Some("32768,BUS,SCIO (Same Cycle I/O),Binary,A;B"));
+    }
+}
@@ -0,0 +1,282 @@
+/// A C printf-style conversion specification, as found in the C_RESFMT, C_LLMFMT and C_HLMFMT fields.
+///
+/// Only the `%[flags][width][.precision](f|e|g|d)` subset (and their upper case and `i`
+/// variants) is supported, with literal text around the conversion and `%%` escapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CFormat {
+    prefix: String,
+    suffix: String,
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+/// Widths and precisions above this are refused, they are not meant for display.
+const MAX_WIDTH: usize = 64;
+
+impl CFormat {
+    /// Parses a format string, `None` if it is empty, malformed or not of the supported subset.
+    pub fn parse(format: &str) -> Option<CFormat> {
+        let chars: Vec<char> = format.trim().chars().collect();
+        let mut prefix = String::new();
+        let mut i = 0;
+        // literal text before the conversion
+        loop {
+            match chars.get(i) {
+                Some('%') if chars.get(i + 1) == Some(&'%') => {
+                    prefix.push('%');
+                    i += 2;
+                }
+                Some('%') => break,
+                Some(c) => {
+                    prefix.push(*c);
+                    i += 1;
+                }
+                None => return None,
+            }
+        }
+        i += 1;
+        let mut spec = CFormat {
+            prefix,
+            suffix: String::new(),
+            left: false,
+            plus: false,
+            space: false,
+            zero: false,
+            alternate: false,
+            width: 0,
+            precision: None,
+            conversion: 'f',
+        };
+        while let Some(c) = chars.get(i) {
+            match c {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        spec.width = number(&chars, &mut i)?.unwrap_or(0);
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            spec.precision = Some(number(&chars, &mut i)?.unwrap_or(0));
+        }
+        // length modifiers mean nothing for the value we format
+        while matches!(chars.get(i), Some('l') | Some('h') | Some('L')) {
+            i += 1;
+        }
+        spec.conversion = match chars.get(i) {
+            Some(c @ ('f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'd' | 'i')) => *c,
+            _ => return None,
+        };
+        i += 1;
+        // literal text after the conversion, a second conversion is not supported
+        while let Some(c) = chars.get(i) {
+            match c {
+                '%' if chars.get(i + 1) == Some(&'%') => {
+                    spec.suffix.push('%');
+                    i += 2;
+                }
+                '%' => return None,
+                c => {
+                    spec.suffix.push(*c);
+                    i += 1;
+                }
+            }
+        }
+        Some(spec)
+    }
+
+    /// Tells if the format has literal text around the conversion, eg: a unit as in `%5.2f V`.
+    pub fn has_text(&self) -> bool {
+        !self.prefix.trim().is_empty() || !self.suffix.trim().is_empty()
+    }
+
+    /// Formats `value` as C printf would.
+    pub fn format(&self, value: f64) -> String {
+        let upper = self.conversion.is_ascii_uppercase();
+        let integer = matches!(self.conversion, 'd' | 'i');
+        let (negative, body) = if value.is_nan() {
+            (value.is_sign_negative(), "nan".to_string())
+        } else if value.is_infinite() {
+            (value < 0.0, "inf".to_string())
+        } else {
+            let magnitude = value.abs();
+            let body = match self.conversion.to_ascii_lowercase() {
+                'f' => fixed(magnitude, self.precision.unwrap_or(6), self.alternate),
+                'e' => exponential(magnitude, self.precision.unwrap_or(6), self.alternate),
+                'g' => general(magnitude, self.precision.unwrap_or(6), self.alternate),
+                _ => {
+                    let digits = format!("{}", magnitude.round());
+                    match self.precision {
+                        Some(precision) if digits.len() < precision => format!("{:0>width$}", digits, width = precision),
+                        _ => digits,
+                    }
+                }
+            };
+            // like C, a negative value that rounds to zero keeps its sign, except as an integer
+            let negative = value.is_sign_negative() && (!integer || value.round() != 0.0);
+            (negative, body)
+        };
+        let body = if upper { body.to_ascii_uppercase() } else { body };
+        let sign = if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        };
+        let length = sign.chars().count() + body.chars().count();
+        let padding = self.width.saturating_sub(length);
+        let zero_pad = self.zero && !self.left && value.is_finite() && !(integer && self.precision.is_some());
+        let field = if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(padding))
+        } else if zero_pad {
+            format!("{}{}{}", sign, "0".repeat(padding), body)
+        } else {
+            format!("{}{}{}", " ".repeat(padding), sign, body)
+        };
+        format!("{}{}{}", self.prefix, field, self.suffix)
+    }
+}
+
+fn number(chars: &[char], i: &mut usize) -> Option<Option<usize>> {
+    let start = *i;
+    while chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+        *i += 1;
+    }
+    if *i == start {
+        return Some(None);
+    }
+    let number: usize = chars[start..*i].iter().collect::<String>().parse().ok()?;
+    if number > MAX_WIDTH {
+        None
+    } else {
+        Some(Some(number))
+    }
+}
+
+fn fixed(value: f64, precision: usize, alternate: bool) -> String {
+    let text = format!("{:.*}", precision, value);
+    if alternate && precision == 0 {
+        text + "."
+    } else {
+        text
+    }
+}
+
+fn exponential(value: f64, precision: usize, alternate: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let point = if alternate && precision == 0 { "." } else { "" };
+    format!("{}{}e{}{:02}", mantissa, point, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    // the exponent after rounding to `precision` significant digits
+    let exponent = if value == 0.0 {
+        0
+    } else {
+        let text = format!("{:.*e}", precision - 1, value);
+        text.split_once('e').and_then(|(_, e)| e.parse::<i32>().ok()).unwrap_or(0)
+    };
+    let text = if exponent < -4 || exponent >= precision as i32 {
+        exponential(value, precision - 1, alternate)
+    } else {
+        fixed(value, (precision as i32 - 1 - exponent) as usize, alternate)
+    };
+    if alternate {
+        return text;
+    }
+    // without '#', trailing zeros (and a trailing point) are removed
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => format!("{}e{}", trim_zeros(mantissa), exponent),
+        None => trim_zeros(&text).to_string(),
+    }
+}
+
+fn trim_zeros(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
+
+/// Formats `value` with the C printf-style `format`, `None` if the format is empty or not supported.
+///
+/// # Examples
+///
+/// ```
+/// use stdf::printf::c_format;
+///
+/// assert_eq!(c_format("%7.3f", 1.23456), Some("  1.235".to_string()));
+/// assert_eq!(c_format("%9.2e", -0.000123), Some("-1.23e-04".to_string()));
+/// assert_eq!(c_format("%s", 1.0), None);
+/// ```
+pub fn c_format(format: &str, value: f64) -> Option<String> {
+    CFormat::parse(format).map(|spec| spec.format(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed() {
+        assert_eq!(c_format("%f", 1.5).unwrap(), "1.500000");
+        assert_eq!(c_format("%7.3f", -1.5).unwrap(), " -1.500");
+        assert_eq!(c_format("%-7.1f|", 1.25).unwrap(), "1.2    |");
+        assert_eq!(c_format("%+08.2f", 1.23456).unwrap(), "+0001.23");
+        assert_eq!(c_format("% .0f", 2.0).unwrap(), " 2");
+        assert_eq!(c_format("%#.0f", 2.0).unwrap(), "2.");
+        assert_eq!(c_format("%.1f", -0.01).unwrap(), "-0.0");
+        assert_eq!(c_format("%d", -0.4).unwrap(), "0");
+        assert_eq!(c_format("%lf", 0.5).unwrap(), "0.500000");
+        assert_eq!(c_format("%8.3F", f64::NAN).unwrap(), "     NAN");
+        assert_eq!(c_format("%05.1f", f64::NEG_INFINITY).unwrap(), " -inf");
+    }
+
+    #[test]
+    fn test_exponential_and_general() {
+        assert_eq!(c_format("%e", 12345.678).unwrap(), "1.234568e+04");
+        assert_eq!(c_format("%.2E", 0.000123).unwrap(), "1.23E-04");
+        assert_eq!(c_format("%.0e", 5.0).unwrap(), "5e+00");
+        assert_eq!(c_format("%g", 0.0001).unwrap(), "0.0001");
+        assert_eq!(c_format("%g", 0.00001).unwrap(), "1e-05");
+        assert_eq!(c_format("%g", 123456.0).unwrap(), "123456");
+        assert_eq!(c_format("%g", 1234567.0).unwrap(), "1.23457e+06");
+        assert_eq!(c_format("%.3g", 2.5).unwrap(), "2.5");
+        assert_eq!(c_format("%#.3g", 2.5).unwrap(), "2.50");
+        assert_eq!(c_format("%G", 1e-10).unwrap(), "1E-10");
+        assert_eq!(c_format("%g", 0.0).unwrap(), "0");
+    }
+
+    #[test]
+    fn test_integer_and_text() {
+        assert_eq!(c_format("%d", 2.6).unwrap(), "3");
+        assert_eq!(c_format("%5i", -42.0).unwrap(), "  -42");
+        assert_eq!(c_format("%.4d", 7.0).unwrap(), "0007");
+        assert_eq!(c_format("%05d", -7.0).unwrap(), "-0007");
+        assert_eq!(c_format("V=%6.2f %%", 1.0).unwrap(), "V=  1.00 %");
+    }
+
+    #[test]
+    fn test_malformed() {
+        for format in ["", "%", "%s", "%x", "7.3f", "%7.3", "%f %f", "%1000f", "%.99f", "%-"] {
+            assert_eq!(c_format(format, 1.0), None, "{}", format);
+        }
+    }
+}
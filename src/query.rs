@@ -0,0 +1,801 @@
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::ops::Range;
+
+use byte::BytesExt;
+use memmap::MmapOptions;
+
+use crate::get_endian_from_file;
+use crate::records::{is_supported_records, V4};
+
+/// The PRR fields that can be used on any record of a part (part-level context).
+const PART_FIELDS: [&str; 10] = [
+    "part_flg", "num_test", "hard_bin", "soft_bin", "x_coord", "y_coord", "test_t", "part_id", "part_txt", "part_fix",
+];
+
+/// Short names that can be used in a query instead of the STDF field names.
+const ALIASES: [(&str, &str); 5] = [
+    ("x", "x_coord"),
+    ("y", "y_coord"),
+    ("hbin", "hard_bin"),
+    ("sbin", "soft_bin"),
+    ("wafer", "wafer_id"),
+];
+
+/// A value a query expression evaluates to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Missing,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+}
+
+impl Value {
+    fn from_json(json: &serde_json::Value) -> Value {
+        match json {
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => n.as_f64().map(Value::Num).unwrap_or(Value::Missing),
+            serde_json::Value::String(s) => Value::Str(s.clone()),
+            _ => Value::Missing,
+        }
+    }
+
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Missing => false,
+            Value::Bool(b) => *b,
+            Value::Num(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+        }
+    }
+
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Str(s) => s.trim().parse::<f64>().ok(),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::Missing => None,
+        }
+    }
+
+    fn compare(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Missing, _) | (_, Value::Missing) => None,
+            (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+            (a, b) => a.as_num()?.partial_cmp(&b.as_num()?),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Field(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CmpOp, Box<Expr>),
+    InRange(Box<Expr>, f64, f64, bool),
+    InList(Box<Expr>, Vec<Value>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(f64),
+    Str(String),
+    Op(&'static str),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            match word.as_str() {
+                "and" => tokens.push(Token::Op("&&")),
+                "or" => tokens.push(Token::Op("||")),
+                "not" => tokens.push(Token::Op("!")),
+                _ => tokens.push(Token::Ident(word)),
+            }
+        } else if c.is_ascii_digit() || (c == '-' && i + 1 < chars.len() && chars[i + 1].is_ascii_digit() && !ends_operand(&tokens)) {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                let d = chars[i];
+                let range_follows = d == '.' && i + 1 < chars.len() && chars[i + 1] == '.';
+                let exponent_sign = (d == '-' || d == '+') && matches!(chars[i - 1], 'e' | 'E');
+                if range_follows || !(d.is_ascii_digit() || d == '.' || d == 'e' || d == 'E' || exponent_sign) {
+                    break;
+                }
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            match number.parse::<f64>() {
+                Ok(n) => tokens.push(Token::Num(n)),
+                Err(_) => return Err(query_error(format!("invalid number '{}'", number))),
+            }
+        } else if c == '"' || c == '\'' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err(query_error("unterminated string".to_string()));
+            }
+            tokens.push(Token::Str(chars[start..i].iter().collect()));
+            i += 1;
+        } else {
+            let two: String = chars[i..(i + 3).min(chars.len())].iter().collect();
+            let op = ["..=", "==", "!=", "<=", ">=", "&&", "||", "..", "<", ">", "!", "(", ")", "[", "]", ","]
+                .into_iter()
+                .find(|op| two.starts_with(op));
+            match op {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                }
+                None => return Err(query_error(format!("unexpected character '{}'", c))),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// A '-' directly after an operand is a (not supported) subtraction, not a negative number.
+// The `in` keyword is an operator: `result in -5..5` has a negative bound.
+fn ends_operand(tokens: &[Token]) -> bool {
+    match tokens.last() {
+        Some(Token::Ident(word)) => word != "in",
+        Some(Token::Num(_)) | Some(Token::Str(_)) | Some(Token::Op(")")) => true,
+        _ => false,
+    }
+}
+
+fn query_error(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("Invalid query: {}", msg))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(token)) if *token == op) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(query_error(format!("expected '{}'", op)))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr> {
+        let left = self.parse_operand()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CmpOp::Eq,
+            Some(Token::Op("!=")) => CmpOp::Ne,
+            Some(Token::Op("<")) => CmpOp::Lt,
+            Some(Token::Op("<=")) => CmpOp::Le,
+            Some(Token::Op(">")) => CmpOp::Gt,
+            Some(Token::Op(">=")) => CmpOp::Ge,
+            Some(Token::Ident(kw)) if kw == "in" => {
+                self.pos += 1;
+                return self.parse_in(left);
+            }
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_operand()?;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn parse_in(&mut self, left: Expr) -> Result<Expr> {
+        if self.eat("[") {
+            let mut values = Vec::new();
+            if !self.eat("]") {
+                loop {
+                    values.push(self.parse_literal()?);
+                    if self.eat("]") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            return Ok(Expr::InList(Box::new(left), values));
+        }
+        let from = self.parse_number()?;
+        let inclusive = if self.eat("..=") {
+            true
+        } else {
+            self.expect("..")?;
+            false
+        };
+        let to = self.parse_number()?;
+        Ok(Expr::InRange(Box::new(left), from, to, inclusive))
+    }
+
+    fn parse_number(&mut self) -> Result<f64> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(n),
+            _ => Err(query_error("expected a number".to_string())),
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Value::Num(n)),
+            Some(Token::Str(s)) => Ok(Value::Str(s)),
+            Some(Token::Ident(s)) => Ok(Value::Str(s)),
+            _ => Err(query_error("expected a literal".to_string())),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Op("(")) => {
+                let expr = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Num(n)) => Ok(Expr::Literal(Value::Num(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::Str(s))),
+            Some(Token::Ident(s)) if s == "true" => Ok(Expr::Literal(Value::Bool(true))),
+            Some(Token::Ident(s)) if s == "false" => Ok(Expr::Literal(Value::Bool(false))),
+            Some(Token::Ident(s)) => {
+                if is_supported_records().contains(&s) {
+                    Ok(Expr::Literal(Value::Str(s)))
+                } else {
+                    let field = ALIASES.iter().find(|(alias, _)| *alias == s).map(|(_, field)| field.to_string());
+                    Ok(Expr::Field(field.unwrap_or(s)))
+                }
+            }
+            Some(token) => Err(query_error(format!("unexpected {:?}", token))),
+            None => Err(query_error("unexpected end of expression".to_string())),
+        }
+    }
+}
+
+/// The record being evaluated, with its context.
+struct Subject<'r, 'a> {
+    record: &'r V4<'a>,
+    fields: OnceCell<serde_json::Value>,
+    part: Option<&'r serde_json::Value>,
+    wafer_id: Option<&'r str>,
+}
+
+impl Subject<'_, '_> {
+    fn lookup(&self, name: &str) -> Value {
+        if name == "rec" {
+            return Value::Str(self.record.name());
+        }
+        let fields = self.fields.get_or_init(|| self.record.to_json());
+        if let Some(value) = fields.get(name) {
+            return Value::from_json(value);
+        }
+        if let Some(value) = self.part.and_then(|part| part.get(name)) {
+            return Value::from_json(value);
+        }
+        match (name, self.wafer_id) {
+            ("wafer_id", Some(wafer_id)) => Value::Str(wafer_id.to_string()),
+            _ => Value::Missing,
+        }
+    }
+}
+
+/// A parsed query expression over record fields.
+///
+/// The grammar is a small boolean expression language:
+///
+/// * `rec` is the record name, record names (`PTR`, `PRR`, ...) are literals.
+/// * Fields are referred to by their (lower case) STDF name, eg: `test_num`, `site_num`, `lo_limit`.
+/// * The part-level context (the PRR of the part a test record belongs to) is available
+///   through the PRR field names (`x_coord`, `y_coord`, `hard_bin`, `soft_bin`, `part_id`, ...),
+///   the wafer through `wafer_id`. `x`, `y`, `hbin`, `sbin` and `wafer` are short hands.
+/// * Comparisons: `==`, `!=`, `<`, `<=`, `>`, `>=`, `in a..b` (b excluded), `in a..=b`, `in [a, b, c]`.
+/// * Logic: `&&`, `||`, `!` and parentheses.
+///
+/// A missing field never compares equal.
+///
+/// # Examples
+///
+/// ```
+/// use stdf::query::Query;
+///
+/// let query = Query::parse("rec == PTR && test_num in 1000..2000 && site_num == 3").unwrap();
+/// assert!(!query.needs_part_context());
+/// let query = Query::parse("rec == PTR && wafer == 12 && x == 3 && y == 4").unwrap();
+/// assert!(query.needs_part_context());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    /// Parses a query expression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error describing the problem when the expression is malformed.
+    pub fn parse(text: &str) -> Result<Query> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(query_error(format!("unexpected {:?}", token)));
+        }
+        Ok(Query { expr })
+    }
+
+    /// Tells if the query refers to part-level fields, which are only known at the end of a part.
+    pub fn needs_part_context(&self) -> bool {
+        fn walk(expr: &Expr) -> bool {
+            match expr {
+                Expr::Literal(_) => false,
+                Expr::Field(name) => PART_FIELDS.contains(&name.as_str()),
+                Expr::Not(e) | Expr::InRange(e, _, _, _) | Expr::InList(e, _) => walk(e),
+                Expr::And(a, b) | Expr::Or(a, b) | Expr::Compare(a, _, b) => walk(a) || walk(b),
+            }
+        }
+        walk(&self.expr)
+    }
+
+    /// Evaluates the query against a record.
+    ///
+    /// `part` is the decoded PRR (see [`V4::to_json`]) of the part the record belongs to, if known.
+    pub fn matches(&self, record: &V4, part: Option<&serde_json::Value>, wafer_id: Option<&str>) -> bool {
+        let subject = Subject {
+            record,
+            fields: OnceCell::new(),
+            part,
+            wafer_id,
+        };
+        evaluate(&self.expr, &subject).is_true()
+    }
+}
+
+fn evaluate(expr: &Expr, subject: &Subject) -> Value {
+    match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Field(name) => subject.lookup(name),
+        Expr::Not(e) => Value::Bool(!evaluate(e, subject).is_true()),
+        Expr::And(a, b) => Value::Bool(evaluate(a, subject).is_true() && evaluate(b, subject).is_true()),
+        Expr::Or(a, b) => Value::Bool(evaluate(a, subject).is_true() || evaluate(b, subject).is_true()),
+        Expr::Compare(a, op, b) => {
+            let ordering = evaluate(a, subject).compare(&evaluate(b, subject));
+            Value::Bool(match (op, ordering) {
+                (CmpOp::Ne, None) => true,
+                (_, None) => false,
+                (CmpOp::Eq, Some(o)) => o.is_eq(),
+                (CmpOp::Ne, Some(o)) => o.is_ne(),
+                (CmpOp::Lt, Some(o)) => o.is_lt(),
+                (CmpOp::Le, Some(o)) => o.is_le(),
+                (CmpOp::Gt, Some(o)) => o.is_gt(),
+                (CmpOp::Ge, Some(o)) => o.is_ge(),
+            })
+        }
+        Expr::InRange(e, from, to, inclusive) => {
+            let value = evaluate(e, subject).as_num();
+            Value::Bool(match value {
+                Some(v) => v >= *from && (v < *to || (*inclusive && v == *to)),
+                None => false,
+            })
+        }
+        Expr::InList(e, values) => {
+            let value = evaluate(e, subject);
+            Value::Bool(values.iter().any(|v| value.compare(v).is_some_and(|o| o.is_eq())))
+        }
+    }
+}
+
+/// A record that passed a [`RecordFilter`], with its location in the file.
+pub struct Selected<'a> {
+    pub range: Range<usize>,
+    pub record: V4<'a>,
+}
+
+struct Pending<'a> {
+    range: Range<usize>,
+    record: V4<'a>,
+    wafer_id: Option<String>,
+    selected: Option<bool>,
+}
+
+/// Applies a [`Query`] to a stream of records, in file order.
+///
+/// When the query needs the part-level context, the records of a part are held
+/// back until the PRR of their head/site is seen, which also works for
+/// interleaved multi-site data. The selected records are always returned in
+/// file order.
+pub struct RecordFilter<'a> {
+    query: Query,
+    part_context: bool,
+    /// Records of these (rec_typ, rec_sub) are selected regardless of the query.
+    pub keep: HashSet<(u8, u8)>,
+    pending: VecDeque<Pending<'a>>,
+    first_seq: usize,
+    open_parts: HashMap<(u8, u8), Vec<usize>>,
+    wafer_id: Option<String>,
+}
+
+impl<'a> RecordFilter<'a> {
+    pub fn new(query: Query) -> Self {
+        RecordFilter {
+            part_context: query.needs_part_context(),
+            query,
+            keep: HashSet::new(),
+            pending: VecDeque::new(),
+            first_seq: 0,
+            open_parts: HashMap::new(),
+            wafer_id: None,
+        }
+    }
+
+    /// Feeds the next record, returns the records that are now known to be selected.
+    pub fn push(&mut self, range: Range<usize>, record: V4<'a>) -> Vec<Selected<'a>> {
+        if let V4::WIR(ref wir) = record {
+            self.wafer_id = Some(wir.wafer_id.to_string());
+        }
+        let seq = self.first_seq + self.pending.len();
+        let part_key = match &record {
+            V4::PIR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::PRR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::PTR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::MPR(r) => Some((r.head_num.0, r.site_num.0)),
+            V4::FTR(r) => Some((r.head_num.0, r.site_num.0)),
+            _ => None,
+        };
+        let is_pir = matches!(record, V4::PIR(_));
+        let is_prr = matches!(record, V4::PRR(_));
+        self.pending.push_back(Pending {
+            range,
+            record,
+            wafer_id: self.wafer_id.clone(),
+            selected: None,
+        });
+
+        match part_key {
+            Some(key) if self.part_context && is_pir => {
+                self.open_parts.insert(key, vec![seq]);
+            }
+            Some(key) if self.part_context && self.open_parts.contains_key(&key) => {
+                let members = self.open_parts.get_mut(&key).unwrap();
+                members.push(seq);
+                if is_prr {
+                    let members = self.open_parts.remove(&key).unwrap();
+                    let part = self.pending[seq - self.first_seq].record.to_json();
+                    for member in members {
+                        self.decide(member - self.first_seq, Some(&part));
+                    }
+                }
+            }
+            _ => self.decide(seq - self.first_seq, None),
+        }
+        self.drain()
+    }
+
+    /// Flushes the records of parts that never got a PRR (evaluated without part context).
+    pub fn finish(&mut self) -> Vec<Selected<'a>> {
+        self.open_parts.clear();
+        for index in 0..self.pending.len() {
+            if self.pending[index].selected.is_none() {
+                self.decide(index, None);
+            }
+        }
+        self.drain()
+    }
+
+    fn decide(&mut self, index: usize, part: Option<&serde_json::Value>) {
+        let pending = &self.pending[index];
+        let selected = self.keep.contains(&pending.record.rec_typ_sub())
+            || self.query.matches(&pending.record, part, pending.wafer_id.as_deref());
+        self.pending[index].selected = Some(selected);
+    }
+
+    fn drain(&mut self) -> Vec<Selected<'a>> {
+        let mut selected = Vec::new();
+        while let Some(Some(is_selected)) = self.pending.front().map(|pending| pending.selected) {
+            let pending = self.pending.pop_front().unwrap();
+            self.first_seq += 1;
+            if is_selected {
+                selected.push(Selected {
+                    range: pending.range,
+                    record: pending.record,
+                });
+            }
+        }
+        selected
+    }
+}
+
+/// Writes the records of an STDF file that match `query` to a new STDF file.
+///
+/// The FAR, MIR and MRR records are always written so the result is a valid
+/// STDF file, the selected records are copied byte-exact.
+///
+/// # Returns
+///
+/// The number of records written.
+///
+/// # Errors
+///
+/// This function will return an error if the input is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::Result;
+/// use stdf::query::{filter_file, Query};
+///
+/// fn main() -> Result<()> {
+///     let mut input = File::open("tests/fixtures/test.std")?;
+///     let mut output = File::create("test_ptr.std")?;
+///     let query = Query::parse("rec == PTR && test_num in 1000..2000 && site_num == 3")?;
+///     let written = filter_file(&mut input, &mut output, query)?;
+///     println!("{} records written", written);
+///     Ok(())
+/// }
+/// ```
+pub fn filter_file<W: Write>(input: &mut File, output: &mut W, query: Query) -> Result<u32> {
+    let endian = match get_endian_from_file(input)? {
+        Some(endian) => endian,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    };
+    let mmap = unsafe { MmapOptions::new().map(input)? };
+    let bytes = &mmap[..];
+    let mut filter = RecordFilter::new(query);
+    filter.keep.extend(crate::strip::MANDATORY_RECORDS);
+    let offset = &mut 0;
+    let mut written: u32 = 0;
+    loop {
+        let start = *offset;
+        let selected = match bytes.read_with::<V4>(offset, endian) {
+            Ok(record) => filter.push(start..*offset, record),
+            Err(_) => filter.finish(),
+        };
+        for record in selected.iter() {
+            output.write_all(&bytes[record.range.clone()])?;
+            written += 1;
+        }
+        if start == *offset {
+            break;
+        }
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::{PIR, PRR, PTR};
+    use crate::types::*;
+
+    fn ptr(test_num: u32, site_num: u8, result: f32) -> V4<'static> {
+        V4::PTR(PTR {
+            test_num: U4(test_num),
+            head_num: U1(1),
+            site_num: U1(site_num),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            result: R4(result),
+            test_txt: Cn(b"vdd"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(0),
+            res_scal: I1(0),
+            llm_scal: I1(0),
+            hlm_scal: I1(0),
+            lo_limit: R4(0.0),
+            hi_limit: R4(1.0),
+            units: Cn(b"V"),
+            c_resfmt: Cn(b""),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        })
+    }
+
+    fn prr(site_num: u8, x: i16, y: i16) -> V4<'static> {
+        V4::PRR(PRR {
+            head_num: U1(1),
+            site_num: U1(site_num),
+            part_flg: B1(0),
+            num_test: U2(1),
+            hard_bin: U2(1),
+            soft_bin: U2(1),
+            x_coord: I2(x),
+            y_coord: I2(y),
+            test_t: U4(0),
+            part_id: Cn(b""),
+            part_txt: Cn(b""),
+            part_fix: Bn(b""),
+        })
+    }
+
+    fn pir(site_num: u8) -> V4<'static> {
+        V4::PIR(PIR {
+            head_num: U1(1),
+            site_num: U1(site_num),
+        })
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("result in -5..-0.5 and not x == -1").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("result".to_string()),
+                Token::Ident("in".to_string()),
+                Token::Num(-5.0),
+                Token::Op(".."),
+                Token::Num(-0.5),
+                Token::Op("&&"),
+                Token::Op("!"),
+                Token::Ident("x".to_string()),
+                Token::Op("=="),
+                Token::Num(-1.0),
+            ]
+        );
+        assert_eq!(tokenize("test_num in [-1, -2]").unwrap()[3], Token::Num(-1.0));
+        // a subtraction is not supported
+        assert!(tokenize("x -1").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("rec == ").is_err());
+        assert!(Query::parse("test_num in 1..").is_err());
+        assert!(Query::parse("(rec == PTR").is_err());
+        assert!(Query::parse("rec == 'PTR").is_err());
+        assert!(Query::parse("rec == PTR PTR").is_err());
+        assert!(Query::parse("rec # PTR").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let record = ptr(1500, 3, 0.5);
+        let check = |text: &str| Query::parse(text).unwrap().matches(&record, None, Some("12"));
+        assert!(check("rec == PTR && test_num in 1000..2000 && site_num == 3"));
+        assert!(!check("rec == PTR && test_num in 1000..1500"));
+        assert!(check("test_num in 1000..=1500"));
+        assert!(check("test_num in [1, 1500, 3]"));
+        assert!(check("result > -1 && result <= 0.5 && result >= 5e-1"));
+        assert!(check("test_txt == 'vdd' && units == \"V\""));
+        assert!(check("!(site_num != 3) || false"));
+        assert!(check("result in -5..5 and not (site_num != 3) or false"));
+        assert!(!check("result in -5..-1"));
+        assert!(check("wafer == 12 && wafer_id == '12'"));
+        assert!(!check("x == 0"));
+        assert!(check("x != 0"));
+        assert!(!check("rec == PRR"));
+    }
+
+    #[test]
+    fn test_filter_with_part_context() {
+        let query = Query::parse("rec == PTR && x == 2").unwrap();
+        let mut filter = RecordFilter::new(query);
+        let mut selected = Vec::new();
+        let records = vec![
+            pir(1),
+            pir(2),
+            ptr(1, 1, 0.1),
+            ptr(1, 2, 0.2),
+            ptr(2, 1, 0.3),
+            prr(2, 2, 5),
+            ptr(2, 2, 0.4),
+            prr(1, 1, 5),
+        ];
+        for (i, record) in records.into_iter().enumerate() {
+            selected.extend(filter.push(i..i + 1, record));
+        }
+        selected.extend(filter.finish());
+        let offsets: Vec<usize> = selected.iter().map(|s| s.range.start).collect();
+        // the PTR after the PRR of site 2 is not part of a part anymore
+        assert_eq!(offsets, vec![3]);
+    }
+
+    #[test]
+    fn test_filter_keeps_order() {
+        let query = Query::parse("rec == PTR || (rec == PRR && hbin == 1)").unwrap();
+        let mut filter = RecordFilter::new(query);
+        let mut selected = Vec::new();
+        let records = vec![pir(1), pir(2), ptr(1, 1, 0.1), ptr(1, 2, 0.2), prr(2, 2, 5), prr(1, 1, 5)];
+        for (i, record) in records.into_iter().enumerate() {
+            selected.extend(filter.push(i..i + 1, record));
+        }
+        selected.extend(filter.finish());
+        let offsets: Vec<usize> = selected.iter().map(|s| s.range.start).collect();
+        assert_eq!(offsets, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_filter_file() {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let query = Query::parse("rec == PTR && site_num == 1").unwrap();
+        let written = filter_file(&mut input, &mut output, query).unwrap();
+        let endian = get_endian_from_file(&mut input).unwrap().unwrap();
+        let offset = &mut 0;
+        let mut names = Vec::new();
+        while let Ok(record) = output.read_with::<V4>(offset, endian) {
+            if let V4::PTR(ref ptr) = record {
+                assert_eq!(ptr.site_num, U1(1));
+            }
+            names.push(record.name());
+        }
+        assert_eq!(names.len() as u32, written);
+        assert_eq!(names.first().unwrap(), "FAR");
+        assert_eq!(names.last().unwrap(), "MRR");
+        assert!(names.iter().filter(|name| *name == "PTR").count() > 0);
+    }
+}
@@ -1,9 +1,12 @@
 #![allow(unused_parens)]
 use byte::ctx;
-use byte::{BytesExt, TryRead, TryWrite};
+use byte::{check_len, BytesExt, TryRead, TryWrite};
 use std::fmt;
 use crate::types::*;
-use serde::Serialize;
+use crate::units::Measurement;
+use crate::flags::{FtrOptFlag, OptFlag, Orientation, ParmFlag, PartFlag, TestFlag};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, UtcOffset};
 use serde_json;
 
 pub trait IsTestRecord {
@@ -11,7 +14,9 @@ pub trait IsTestRecord {
     fn get_name_as_string(&self) -> String;
 }
 
+/// Writes a record as ATDF text (see `doc/ATDF-spec.pdf`), implemented in [`crate::atdf`].
 pub trait Atdf {
+    /// The record line, without line continuations nor line terminator.
     fn to_atdf(&self) -> String;
 }
 
@@ -89,37 +94,16 @@ macro_rules! record_id {
     };
 }
 
-//TODO: move this declarative macro to the STDFRecord derive macro
-macro_rules! atdf {
-    ($record_name:ident, $($lt:lifetime)?) => {
-        impl Atdf for $record_name<$($lt)?> {
-            fn to_atdf(&self) -> String {
-                let serialized = serde_json::to_string(self).unwrap();
-                let json: serde_json::Value = serde_json::from_str(&serialized).unwrap();
-                if let serde_json::Value::Object(map) = json {
-                    let result = map.values()
-                        .map(|value| format!("{}", value))
-                        .collect::<Vec<String>>()
-                        .join("|");
-                    return format!("{}\n", result.replace("\"", ""));
-                }
-                String::new() // fallback
-            }
-        }
-    };
-}
-
 // ========================================================
 // FAR : File Attribute Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct FAR {
     pub cpu_type: U1,
     pub stdf_ver: U1,
 }
 
 record_id!(FAR, false,  );
-atdf!(FAR,);
 
 impl fmt::Display for FAR {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -132,29 +116,43 @@ impl fmt::Display for FAR {
 // ========================================================
 // ATR : Audit Trail Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct ATR<'a> {
     #[default(U4E::from(0))]
     pub mod_tim: U4E,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub cmd_line: Cn<'a>,
 }
 
 record_id!(ATR, false, '_);
-atdf!(ATR, '_);
 
-impl fmt::Display for ATR<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl ATR<'_> {
+    /// The time the file was modified, `None` if not set.
+    pub fn mod_time(&self) -> Option<OffsetDateTime> {
+        self.mod_tim.to_datetime()
+    }
+}
+
+impl ATR<'_> {
+    /// Writes the record as `Display` does, with the timestamps shown at `offset`.
+    fn fmt_in(&self, f: &mut fmt::Formatter<'_>, offset: Option<UtcOffset>) -> fmt::Result {
         writeln!(f, "ATR : Audit Trail Record")?;
-        writeln!(f, "   MOD_TIM [U4E]: {}", self.mod_tim)?;
+        writeln!(f, "   MOD_TIM [U4E]: {}", self.mod_tim.display_in(offset))?;
         writeln!(f, "   CMD_LINE [Cn]: '{}'", self.cmd_line)
     }
 }
 
+impl fmt::Display for ATR<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_in(f, None)
+    }
+}
+
 // ========================================================
 // MIR : Master Information Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct MIR<'a> {
     #[default(U4E::from(0))]
     pub setup_t: U4E,
@@ -173,75 +171,117 @@ pub struct MIR<'a> {
     #[default(C1(b' '))]
     pub cmod_cod: C1,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub lot_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub part_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub node_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub tstr_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub job_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub job_rev: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub sblot_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub oper_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub exec_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub exec_ver: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub test_cod: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub tst_temp: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub user_txt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub aux_file: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub pkg_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub famly_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub date_cod: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub facil_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub floor_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub proc_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub oper_frq: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub spec_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub spec_ver: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub flow_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub setup_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub dsgn_rev: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub eng_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub rom_cod: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub serl_num: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub supr_nam: Cn<'a>,
 }
 
 record_id!(MIR, false, '_);
-atdf!(MIR, '_);
 
-impl fmt::Display for MIR<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl MIR<'_> {
+    /// The time the tester was set up for the lot, `None` if not set.
+    pub fn setup_time(&self) -> Option<OffsetDateTime> {
+        self.setup_t.to_datetime()
+    }
+
+    /// The time the first part of the lot was tested, `None` if not set.
+    pub fn start_time(&self) -> Option<OffsetDateTime> {
+        self.start_t.to_datetime()
+    }
+}
+
+impl MIR<'_> {
+    /// Writes the record as `Display` does, with the timestamps shown at `offset`.
+    fn fmt_in(&self, f: &mut fmt::Formatter<'_>, offset: Option<UtcOffset>) -> fmt::Result {
         writeln!(f, "MIR : Master Information Record")?;
-        writeln!(f, "   SETUP_T [U4E] : {}", self.setup_t)?;
-        writeln!(f, "   START_T [U4E] : {}", self.start_t)?;
+        writeln!(f, "   SETUP_T [U4E] : {}", self.setup_t.display_in(offset))?;
+        writeln!(f, "   START_T [U4E] : {}", self.start_t.display_in(offset))?;
         writeln!(f, "   STAT_NUM [U1] : {}", self.stat_num)?;
         writeln!(f, "   MODE_COD [C1] : '{}'", self.mode_cod)?;
         writeln!(f, "   RTST_COD [C1] : '{}'", self.rtst_cod)?;
@@ -281,27 +321,42 @@ impl fmt::Display for MIR<'_> {
     }
 }
 
+impl fmt::Display for MIR<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_in(f, None)
+    }
+}
+
 // ========================================================
 // MRR : Master Result Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct MRR<'a> {
     pub finish_t: U4E,
     #[default(C1::from(b' '))]
     pub disp_cod: C1,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub usr_desc: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub exc_desc: Cn<'a>,
 }
 
 record_id!(MRR, false, '_);
-atdf!(MRR, '_);
 
-impl fmt::Display for MRR<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl MRR<'_> {
+    /// The time the last part of the lot was tested, `None` if not set.
+    pub fn finish_time(&self) -> Option<OffsetDateTime> {
+        self.finish_t.to_datetime()
+    }
+}
+
+impl MRR<'_> {
+    /// Writes the record as `Display` does, with the timestamps shown at `offset`.
+    fn fmt_in(&self, f: &mut fmt::Formatter<'_>, offset: Option<UtcOffset>) -> fmt::Result {
         writeln!(f, "MRR : Master Result Record")?;
-        writeln!(f, "   FINISH_T [U4] : {}", self.finish_t)?;
+        writeln!(f, "   FINISH_T [U4] : {}", self.finish_t.display_in(offset))?;
         writeln!(f, "   DISP_COD [C1] : {}", if self.disp_cod.to_string() == " " {
                                                  "∕".to_string()
                                              } else {
@@ -320,10 +375,16 @@ impl fmt::Display for MRR<'_> {
     }
 }
 
+impl fmt::Display for MRR<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_in(f, None)
+    }
+}
+
 // ========================================================
 // PCR : Part Count Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PCR {
     pub head_num: U1,
     pub site_num: U1,
@@ -339,7 +400,6 @@ pub struct PCR {
 }
 
 record_id!(PCR, false,);
-atdf!(PCR,);
 
 impl fmt::Display for PCR {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -381,7 +441,7 @@ impl fmt::Display for PCR {
 // ========================================================
 // HBR : Hard Bin Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct HBR<'a> {
     pub head_num: U1,
     pub site_num: U1,
@@ -390,11 +450,11 @@ pub struct HBR<'a> {
     #[default(C1::from(0x20))]
     pub hbin_pf: C1,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub hbin_nam: Cn<'a>,
 }
 
 record_id!(HBR, false, '_);
-atdf!(HBR, '_);
 
 impl fmt::Display for HBR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -427,7 +487,7 @@ impl fmt::Display for HBR<'_> {
 // ========================================================
 // SBR : Soft Bin Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct SBR<'a> {
     pub head_num: U1,
     pub site_num: U1,
@@ -436,11 +496,11 @@ pub struct SBR<'a> {
     #[default(C1::from(0x20))]
     pub sbin_pf: C1,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub sbin_nam: Cn<'a>,
 }
 
 record_id!(SBR, false, '_);
-atdf!(SBR, '_);
 
 impl fmt::Display for SBR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -473,16 +533,19 @@ impl fmt::Display for SBR<'_> {
 // ========================================================
 // PMR : Pin Map Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PMR<'a> {
     pub pmr_index: U2,
     #[default(U2::from(0))]
     pub chan_typ: U2,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub chan_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub phy_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub log_nam: Cn<'a>,
     #[default(U1::from(1))]
     pub head_num: U1,
@@ -491,7 +554,6 @@ pub struct PMR<'a> {
 }
 
 record_id!(PMR, false, '_);
-atdf!(PMR, '_);
 
 impl fmt::Display for PMR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -521,10 +583,11 @@ impl fmt::Display for PMR<'_> {
 // ========================================================
 // PGR : Pin Group Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PGR<'a> {
     pub grp_indx: U2,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub grp_nam: Cn<'a>,
     #[default(U2::from(0))]
     pub indx_cnt: U2,
@@ -534,7 +597,6 @@ pub struct PGR<'a> {
 }
 
 record_id!(PGR, false, '_);
-atdf!(PGR, '_);
 
 impl fmt::Display for PGR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -549,7 +611,7 @@ impl fmt::Display for PGR<'_> {
 // ========================================================
 // PLR : Pin List Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PLR<'a> {
     pub grp_cnt: U2,
     #[array_length(grp_cnt)]
@@ -563,20 +625,23 @@ pub struct PLR<'a> {
     pub grp_radx: Vec<U1>,
     #[array_length(grp_cnt)]
     #[array_type(Cn)]
+    #[serde(borrow)]
     pub pgm_char: Vec<Cn<'a>>,
     #[array_length(grp_cnt)]
     #[array_type(Cn)]
+    #[serde(borrow)]
     pub rtn_char: Vec<Cn<'a>>,
     #[array_length(grp_cnt)]
     #[array_type(Cn)]
+    #[serde(borrow)]
     pub pgm_chal: Vec<Cn<'a>>,
     #[array_length(grp_cnt)]
     #[array_type(Cn)]
+    #[serde(borrow)]
     pub rtn_chal: Vec<Cn<'a>>,
 }
 
 record_id!(PLR, false, '_);
-atdf!(PLR, '_);
 
 impl fmt::Display for PLR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -595,7 +660,7 @@ impl fmt::Display for PLR<'_> {
 // ========================================================
 // RDR : Retest Data Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct RDR {
     pub num_bins: U2,
     #[array_length(num_bins)]
@@ -604,7 +669,6 @@ pub struct RDR {
 }
 
 record_id!(RDR, false,);
-atdf!(RDR,);
 
 impl fmt::Display for RDR {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -617,7 +681,7 @@ impl fmt::Display for RDR {
 // ========================================================
 // SDR : Site Description Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct SDR<'a> {
     pub head_num: U1,
     pub site_grp: U1,
@@ -626,41 +690,56 @@ pub struct SDR<'a> {
     #[array_type(U1)]
     pub site_num: Vec<U1>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub hand_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub hand_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub card_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub card_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub load_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub load_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub dib_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub dib_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub cabl_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub cabl_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub cont_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub cont_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub lasr_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub lasr_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub extr_typ: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub extr_id: Cn<'a>,
 }
 
 record_id!(SDR, false, '_);
-atdf!(SDR, '_);
 
 impl fmt::Display for SDR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -691,25 +770,33 @@ impl fmt::Display for SDR<'_> {
 // ========================================================
 // WIR : Wafer Information Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct WIR<'a> {
     pub head_num: U1,
     #[default(U1::from(255))]
     pub site_grp: U1,
     pub start_t: U4E,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub wafer_id: Cn<'a>,
 }
 
 record_id!(WIR, false, '_);
-atdf!(WIR, '_);
 
-impl fmt::Display for WIR<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl WIR<'_> {
+    /// The time the first part of the wafer was tested, `None` if not set.
+    pub fn start_time(&self) -> Option<OffsetDateTime> {
+        self.start_t.to_datetime()
+    }
+}
+
+impl WIR<'_> {
+    /// Writes the record as `Display` does, with the timestamps shown at `offset`.
+    fn fmt_in(&self, f: &mut fmt::Formatter<'_>, offset: Option<UtcOffset>) -> fmt::Result {
         writeln!(f, "WIR : Wafer Information Record")?;
         writeln!(f, "   HEAD_NUM  [U1] : {}", self.head_num)?;
         writeln!(f, "   SITE_GRP  [U1] : {}", self.site_grp)?;
-        writeln!(f, "   START_T  [U4E] : {}", self.start_t)?;
+        writeln!(f, "   START_T  [U4E] : {}", self.start_t.display_in(offset))?;
         writeln!(f, "   WAFER_ID  [Cn] : '{}'", self.wafer_id)
     }
 }	
@@ -717,7 +804,7 @@ impl fmt::Display for WIR<'_> {
 // ========================================================
 // WRR : Wafer Result Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct WRR<'a> {
     pub head_num: U1,
     #[default(U1::from(255))]
@@ -733,28 +820,47 @@ pub struct WRR<'a> {
     #[default(U4::from(0xffffffff))]
     pub func_cnt: U4,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub wafer_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub fabwf_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub frame_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub mask_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub usr_desc: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub exc_desc: Cn<'a>,
 }
 
+impl fmt::Display for WIR<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_in(f, None)
+    }
+}
+
 record_id!(WRR, false, '_);
-atdf!(WRR, '_);
 
-impl fmt::Display for WRR<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl WRR<'_> {
+    /// The time the last part of the wafer was tested, `None` if not set.
+    pub fn finish_time(&self) -> Option<OffsetDateTime> {
+        self.finish_t.to_datetime()
+    }
+}
+
+impl WRR<'_> {
+    /// Writes the record as `Display` does, with the timestamps shown at `offset`.
+    fn fmt_in(&self, f: &mut fmt::Formatter<'_>, offset: Option<UtcOffset>) -> fmt::Result {
         writeln!(f, "WRR : Wafer Result Record")?;
         writeln!(f, "   HEAD_NUM [U1] : {}", self.head_num)?;
         writeln!(f, "   SITE_GRP [U1] : {}", self.site_grp)?;
-        writeln!(f, "   FINISH_T [U4] : {}", self.finish_t)?;
+        writeln!(f, "   FINISH_T [U4] : {}", self.finish_t.display_in(offset))?;
         writeln!(f, "   PART_CNT [U4] : {}", self.part_cnt)?;
         writeln!(f, "   RTST_CNT [U4] : {}", self.rtst_cnt)?;
         writeln!(f, "   ABRT_CNT [U4] : {}", self.abrt_cnt)?;
@@ -769,10 +875,16 @@ impl fmt::Display for WRR<'_> {
     }
 }
 
+impl fmt::Display for WRR<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_in(f, None)
+    }
+}
+
 // ========================================================
 // WCS : Wafer Configuration Record
 // ========================================================
-#[derive(Debug, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct WCR {
     #[default(R4::from(0.0))]
     pub wafr_siz: R4,
@@ -795,7 +907,22 @@ pub struct WCR {
 }
 
 record_id!(WCR, false,);
-atdf!(WCR,);
+
+impl WCR {
+    pub fn flat_orientation(&self) -> Orientation {
+        Orientation::from(self.wf_flat)
+    }
+
+    /// The direction in which X coordinates increase.
+    pub fn positive_x(&self) -> Orientation {
+        Orientation::from(self.pos_x)
+    }
+
+    /// The direction in which Y coordinates increase.
+    pub fn positive_y(&self) -> Orientation {
+        Orientation::from(self.pos_y)
+    }
+}
 
 impl fmt::Display for WCR {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -804,25 +931,24 @@ impl fmt::Display for WCR {
         writeln!(f, "   DIE_HT   [R4] : {}", self.die_ht)?;
         writeln!(f, "   DIE_WID  [R4] : {}", self.die_wid)?;
         writeln!(f, "   WF_UNITS [U1] : {}", self.wf_units)?;
-        writeln!(f, "   WF_FLAT  [C1] : {}", self.wf_flat)?;
+        writeln!(f, "   WF_FLAT  [C1] : '{}' → {}", self.wf_flat, self.flat_orientation())?;
         writeln!(f, "   CENTER_X [I2] : {}", self.center_x)?;
         writeln!(f, "   CENTER_Y [I2] : {}", self.center_y)?;
-        writeln!(f, "   POS_X    [C1] : '{}'", self.pos_x)?;
-        writeln!(f, "   POS_Y    [C1] : '{}'", self.pos_y)
+        writeln!(f, "   POS_X    [C1] : '{}' → {}", self.pos_x, self.positive_x())?;
+        writeln!(f, "   POS_Y    [C1] : '{}' → {}", self.pos_y, self.positive_y())
     }
 }
 
 // ========================================================
 // PIR : Part Information Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PIR {
     pub head_num: U1,
     pub site_num: U1,
 }
 
 record_id!(PIR, false,);
-atdf!(PIR,);
 
 impl fmt::Display for PIR {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -835,7 +961,7 @@ impl fmt::Display for PIR {
 // ========================================================
 // PRR : Part Results Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PRR<'a> {
     pub head_num: U1,
     pub site_num: U1,
@@ -851,22 +977,30 @@ pub struct PRR<'a> {
     #[default(U4::from(0))]
     pub test_t: U4,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub part_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub part_txt: Cn<'a>,
     #[default(Bn(b""))]
+    #[serde(skip_deserializing)]
     pub part_fix: Bn<'a>,
 }
 
 record_id!(PRR, false, '_);
-atdf!(PRR, '_);
+
+impl PRR<'_> {
+    pub fn part_flags(&self) -> PartFlag {
+        PartFlag::from(self.part_flg)
+    }
+}
 
 impl fmt::Display for PRR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "PRR : Part Results Record")?;
         writeln!(f, "   HEAD_NUM [U1] : {}", self.head_num)?;
         writeln!(f, "   SITE_NUM [U1] : {}", self.site_num)?;
-        writeln!(f, "   PART_FLG [B1] : {} {}", self.part_flg, prr_part_flg(self.part_flg))?;
+        writeln!(f, "   PART_FLG [B1] : {} {}", self.part_flg, self.part_flags())?;
         writeln!(f, "   NUM_TEST [U2] : {}", self.num_test)?;
         writeln!(f, "   HARD_BIN [U2] : {}", self.hard_bin)?;
         writeln!(f, "   SOFT_BIN [U2] : {}", if u16::from(self.soft_bin) == 65535_u16 {
@@ -907,32 +1041,10 @@ impl fmt::Display for PRR<'_> {
     }
 }
 
-fn prr_part_flg(part_flg: B1) -> String {
-    let mut msg = String::new();
-
-    msg.push_str("→ ");
-
-    if u8::from(part_flg) & 0b0001_0000 == 0b0001_0000  { 
-        msg.push('?');
-    } else if u8::from(part_flg) & 0b0000_1000 == 0b0000_1000 {
-        msg.push_str("FAIL");
-    } else {
-        msg.push_str("PASS");
-    }
-
-    if u8::from(part_flg) & 0b0000_0100 == 0b0000_0100  { 
-        msg.push_str(" (");
-        msg.push_str("Abnormal end of testing");
-        msg.push(')');
-    }
-
-    msg
-}
-
 // ========================================================
 // TSR : Test Synopsis Record
 // ========================================================
-#[derive(Debug, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct TSR<'a> {
     pub head_num: U1,
     pub site_num: U1,
@@ -942,10 +1054,13 @@ pub struct TSR<'a> {
     pub fail_cnt: U4,
     pub alrm_cnt: U4,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub test_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub seq_name: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub test_lbl: Cn<'a>,
     #[default(B1::from(0xff))]
     pub opt_flag: B1,
@@ -962,7 +1077,6 @@ pub struct TSR<'a> {
 }
 
 record_id!(TSR, false, '_);
-atdf!(TSR, '_);
 
 impl fmt::Display for TSR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -989,7 +1103,7 @@ impl fmt::Display for TSR<'_> {
 // ========================================================
 // PTR : Parametric Test Record
 // ========================================================
-#[derive(Debug, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct PTR<'a> {
     pub test_num: U4,
     pub head_num: U1,
@@ -999,8 +1113,10 @@ pub struct PTR<'a> {
     #[default(R4::from(f32::NAN))]
     pub result: R4,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub test_txt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub alarm_id: Cn<'a>,
     #[default(B1::from(0x00))]
     pub opt_flag: B1,
@@ -1015,12 +1131,16 @@ pub struct PTR<'a> {
     #[default(R4::from(f32::NAN))]
     pub hi_limit: R4,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub units: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub c_resfmt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub c_llmfmt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub c_hlmfmt: Cn<'a>,
     #[default(R4::from(f32::NAN))]
     pub lo_spec: R4,
@@ -1029,7 +1149,35 @@ pub struct PTR<'a> {
 }
 
 record_id!(PTR, true, '_);
-atdf!(PTR, '_);
+
+impl PTR<'_> {
+    pub fn test_flags(&self) -> TestFlag {
+        TestFlag::from(self.test_flg)
+    }
+
+    pub fn parm_flags(&self) -> ParmFlag {
+        ParmFlag::from(self.parm_flg)
+    }
+
+    pub fn opt_flags(&self) -> OptFlag {
+        OptFlag::from(self.opt_flag)
+    }
+
+    /// The scale of RESULT, `None` if the record has none (or marks it invalid).
+    pub fn measurement_scale(&self) -> Option<i8> {
+        if self.res_scal.0 == i8::MIN || self.opt_flags().res_scal_invalid() {
+            None
+        } else {
+            Some(self.res_scal.0)
+        }
+    }
+
+    /// `value` in the units of the record, shown with `scal` (eg: LLM_SCAL for LO_LIMIT).
+    pub fn measurement(&self, value: R4, scal: I1) -> Measurement {
+        let scale = if scal.0 == i8::MIN { None } else { Some(scal.0) };
+        Measurement::new(value.0 as f64, &self.units.to_string(), scale)
+    }
+}
 
 impl fmt::Display for PTR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1037,21 +1185,21 @@ impl fmt::Display for PTR<'_> {
         writeln!(f, "   TEST_NUM [U4] : {}", self.test_num)?;
         writeln!(f, "   HEAD_NUM [U1] : {}", self.head_num)?;
         writeln!(f, "   SITE_NUM [U1] : {}", self.site_num)?;
-        writeln!(f, "   TEST_FLG [B1] : {} {}", self.test_flg, ptr_test_flg(self.test_flg))?;
-        writeln!(f, "   PARM_FLG [B1] : {} {}", self.parm_flg, ptr_parm_flg(self.parm_flg))?;
-        writeln!(f, "   RESULT   [R4] : {}", self.result)?;
+        writeln!(f, "   TEST_FLG [B1] : {} {}", self.test_flg, self.test_flags())?;
+        writeln!(f, "   PARM_FLG [B1] : {} {}", self.parm_flg, self.parm_flags())?;
+        writeln!(f, "   RESULT   [R4] : {}{}", self.result, scaled(self.result, &self.c_resfmt, || Measurement::new(self.result.0 as f64, &self.units.to_string(), self.measurement_scale())))?;
         writeln!(f, "   TEST_TXT [Cn] : '{}'", self.test_txt)?;
         if self.opt_flag == B1::from(0x00) {
             writeln!(f, "   ALARM_ID [Cn] : {}", self.alarm_id)
         } else {
             writeln!(f, "   ALARM_ID [Cn] : {}", self.alarm_id)?;
             writeln!(f, "   --------------")?;
-            writeln!(f, "   OPT_FLAG [B1] : {} {}", self.opt_flag, ptr_opt_flag(self.opt_flag))?;
+            writeln!(f, "   OPT_FLAG [B1] : {} {}", self.opt_flag, self.opt_flags())?;
             writeln!(f, "   RES_SCAL [I1] : {}", self.res_scal)?;
             writeln!(f, "   LLM_SCAL [I1] : {}", self.llm_scal)?;
             writeln!(f, "   HLM_SCAL [I1] : {}", self.hlm_scal)?;
-            writeln!(f, "   LO_LIMIT [R4] : {}", self.lo_limit)?;
-            writeln!(f, "   HI_LIMIT [R4] : {}", self.hi_limit)?;
+            writeln!(f, "   LO_LIMIT [R4] : {}{}", self.lo_limit, scaled(self.lo_limit, &self.c_llmfmt, || self.measurement(self.lo_limit, self.llm_scal)))?;
+            writeln!(f, "   HI_LIMIT [R4] : {}{}", self.hi_limit, scaled(self.hi_limit, &self.c_hlmfmt, || self.measurement(self.hi_limit, self.hlm_scal)))?;
             writeln!(f, "   UNITS    [Cn] : '{}'", self.units)?;
             writeln!(f, "   C_RESFMT [Cn] : '{}'", self.c_resfmt)?;
             writeln!(f, "   C_LLMFMT [Cn] : '{}'", self.c_llmfmt)?;
@@ -1062,71 +1210,24 @@ impl fmt::Display for PTR<'_> {
     }
 }
 
-fn ptr_test_flg(test_flg: B1) -> String {
-    let mut msg = String::new();
-    let mut info: Vec<String> = Vec::new(); 
-
-    if u8::from(test_flg) & 0b0000_0001 == 0b0000_0001  { info.push(String::from("Alarm")); }
-    if u8::from(test_flg) & 0b0000_0010 == 0b0000_0010  { info.push(String::from("RESULT not valid")); }
-    if u8::from(test_flg) & 0b0000_0100 == 0b0000_0100  { info.push(String::from("RESULT is unreliable")); }
-    if u8::from(test_flg) & 0b0000_1000 == 0b0000_1000  { info.push(String::from("Timeout")); }
-    if u8::from(test_flg) & 0b0001_0000 == 0b0001_0000  { info.push(String::from("Test not executed")); }
-    if u8::from(test_flg) & 0b0010_0000 == 0b0010_0000  { info.push(String::from("Test aborted")); }
-
-    msg.push('(');
-    msg.push_str(&info.join(", "));
-    msg.push_str(") → ");
-
-    if u8::from(test_flg) & 0b0100_0000 == 0b0100_0000  { 
-        msg.push('?');
-    } else if u8::from(test_flg) & 0b1000_0000 == 0b1000_0000 {
-        msg.push_str("FAIL");
+/// The value with its units, scale and format applied, eg: ` → 12.3 mV`, if that shows more than the raw value.
+/// The format is the C printf-style C_RESFMT, C_LLMFMT or C_HLMFMT of the value.
+fn scaled(value: R4, format: &Cn, measurement: impl Fn() -> Measurement) -> String {
+    if value.0.is_nan() {
+        return String::new();
+    }
+    let measurement = measurement();
+    if measurement.unit.is_empty() && measurement.scale == 0 && format.0.is_empty() {
+        String::new()
     } else {
-        msg.push_str("PASS");
+        format!(" → {}", measurement.to_string_with(&format.to_string()))
     }
-    msg
-}
-
-fn ptr_parm_flg(parm_flg: B1) -> String {
-    let mut msg = String::new();
-    let mut info: Vec<String> = Vec::new();
-
-    if u8::from(parm_flg) & 0b0000_0001 == 0b0000_0001  { info.push(String::from("Scale error")); }
-    if u8::from(parm_flg) & 0b0000_0010 == 0b0000_0010  { info.push(String::from("Drift error")); }
-    if u8::from(parm_flg) & 0b0000_0100 == 0b0000_0100  { info.push(String::from("Oscillation detected")); }
-    if u8::from(parm_flg) & 0b0000_1000 == 0b0000_1000  { info.push(String::from("RESULT > HI_LIMIT")); }
-    if u8::from(parm_flg) & 0b0001_0000 == 0b0001_0000  { info.push(String::from("RESULT < LO_LIMIT")); }
-
-    msg.push('(');
-    msg.push_str(&info.join(", "));
-    msg.push(')');
-
-    msg
-}
-
-fn ptr_opt_flag(opt_flag:B1) -> String {
-    let mut msg = String::new();
-    let mut info: Vec<String> = Vec::new();
-
-    if u8::from(opt_flag) & 0b0000_0001 == 0b0000_0001  { info.push(String::from("RES_SCAL is invalid")); }
-    if u8::from(opt_flag) & 0b0000_0100 == 0b0000_0100  { info.push(String::from("No low spec limit")); }
-    if u8::from(opt_flag) & 0b0000_1000 == 0b0000_1000  { info.push(String::from("No high spec limit")); }
-    if u8::from(opt_flag) & 0b0001_0000 == 0b0001_0000  { info.push(String::from("LO_LIMIT and LLM_SCAL are invalid")); }
-    if u8::from(opt_flag) & 0b0010_0000 == 0b0010_0000  { info.push(String::from("HI_LIMIT and HLM_SCAL are invalid")); }  
-    if u8::from(opt_flag) & 0b0100_0000 == 0b0100_0000  { info.push(String::from("no LO_LIMIT")); } 
-    if u8::from(opt_flag) & 0b1000_0000 == 0b1000_0000  { info.push(String::from("no HI_LIMIT")); }
-
-    msg.push('(');
-    msg.push_str(&info.join(", "));
-    msg.push(')');
-
-    msg
 }
 
 // ========================================================
 // MRR : Multiple-Result Record
 // ========================================================
-#[derive(Debug, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct MPR<'a> {
     pub test_num: U4,
     pub head_num: U1,
@@ -1144,8 +1245,10 @@ pub struct MPR<'a> {
     #[array_type(R4)]
     pub rtn_rslt: Vec<R4>, // kxR4
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub test_txt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub alarm_id: Cn<'a>,
     #[default(B1::from(0x00))]
     pub opt_flag: B1,
@@ -1167,14 +1270,19 @@ pub struct MPR<'a> {
     #[array_type(U2)]
     pub rtn_indx: Vec<U2>, // jxU2
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub units: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub units_in: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub c_resfmt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub c_llmfmt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub c_hlmfmt: Cn<'a>,
     #[default(R4::from(f32::NAN))]
     pub lo_spec: R4,
@@ -1183,7 +1291,54 @@ pub struct MPR<'a> {
 }
 
 record_id!(MPR, true, '_);
-atdf!(MPR, '_);
+
+impl MPR<'_> {
+    pub fn test_flags(&self) -> TestFlag {
+        TestFlag::from(self.test_flg)
+    }
+
+    pub fn parm_flags(&self) -> ParmFlag {
+        ParmFlag::from(self.parm_flg)
+    }
+
+    pub fn opt_flags(&self) -> OptFlag {
+        OptFlag::from(self.opt_flag)
+    }
+
+    /// The scale of RTN_RSLT, `None` if the record has none (or marks it invalid).
+    pub fn measurement_scale(&self) -> Option<i8> {
+        if self.res_scal.0 == i8::MIN || self.opt_flags().res_scal_invalid() {
+            None
+        } else {
+            Some(self.res_scal.0)
+        }
+    }
+
+    /// `value` in the units of the record, shown with `scal` (eg: LLM_SCAL for LO_LIMIT).
+    pub fn measurement(&self, value: R4, scal: I1) -> Measurement {
+        let scale = if scal.0 == i8::MIN { None } else { Some(scal.0) };
+        Measurement::new(value.0 as f64, &self.units.to_string(), scale)
+    }
+
+    /// RTN_RSLT with the units, RES_SCAL and C_RESFMT applied, eg: ` → [12.3 mV, 4.5 mV]`, empty if that shows nothing more.
+    fn scaled_results(&self) -> String {
+        let units = self.units.to_string();
+        let results: Vec<String> = self
+            .rtn_rslt
+            .iter()
+            .map(|result| match scaled(*result, &self.c_resfmt, || Measurement::new(result.0 as f64, &units, self.measurement_scale())) {
+                shown if shown.is_empty() => result.to_string(),
+                shown => shown.trim_start_matches(" → ").to_string(),
+            })
+            .collect();
+        let raw: Vec<String> = self.rtn_rslt.iter().map(|result| result.to_string()).collect();
+        if results == raw {
+            String::new()
+        } else {
+            format!(" → [{}]", results.join(", "))
+        }
+    }
+}
 
 impl fmt::Display for MPR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1191,23 +1346,23 @@ impl fmt::Display for MPR<'_> {
         writeln!(f, "   TEST_NUM   [U4] : {}", self.test_num)?;
         writeln!(f, "   HEAD_NUM   [U1] : {}", self.head_num)?;
         writeln!(f, "   SITE_NUM   [U1] : {}", self.site_num)?;
-        writeln!(f, "   TEST_FLG   [B1] : {}", self.test_flg)?;
-        writeln!(f, "   PARM_FLG   [B1] : {}", self.parm_flg)?;
+        writeln!(f, "   TEST_FLG   [B1] : {} {}", self.test_flg, self.test_flags())?;
+        writeln!(f, "   PARM_FLG   [B1] : {} {}", self.parm_flg, self.parm_flags())?;
         writeln!(f, "   RTN_ICNT j [U2] : {}", self.rtn_icnt)?;
         writeln!(f, "   RSLT_CNT k [U2] : {}", self.rslt_cnt)?;
         writeln!(f, "   RTN_STAT [jxN1] : {:?}", self.rtn_stat)?; //TODO: implement std:fmt::Display for Vec<N1>
-        writeln!(f, "   RTN_RSLT [kxR4] : {:?}", self.rtn_rslt)?;  //TODO: implement std:fmt::Display for Vec<R4>
+        writeln!(f, "   RTN_RSLT [kxR4] : {:?}{}", self.rtn_rslt, self.scaled_results())?;  //TODO: implement std:fmt::Display for Vec<R4>
         writeln!(f, "   TEST_TXT   [Cn] : '{}'", self.test_txt)?;
         if self.opt_flag == B1::from(0x00) {
             writeln!(f, "   ALARM_ID   [Cn] : '{}'", self.alarm_id)
         } else {
             writeln!(f, "   ALARM_ID   [Cn] : '{}'", self.alarm_id)?;
-            writeln!(f, "   OPT_FLAG   [B1] : {}", self.opt_flag)?;
+            writeln!(f, "   OPT_FLAG   [B1] : {} {}", self.opt_flag, self.opt_flags())?;
             writeln!(f, "   RES_SCAL   [I1] : {}", self.res_scal)?;
             writeln!(f, "   LLM_SCAL   [I1] : {}", self.llm_scal)?;
             writeln!(f, "   HLM_SCAL   [I1] : {}", self.hlm_scal)?;
-            writeln!(f, "   LO_LIMIT   [R4] : {}", self.lo_limit)?;
-            writeln!(f, "   HI_LIMIT   [R4] : {}", self.hi_limit)?;
+            writeln!(f, "   LO_LIMIT   [R4] : {}{}", self.lo_limit, scaled(self.lo_limit, &self.c_llmfmt, || self.measurement(self.lo_limit, self.llm_scal)))?;
+            writeln!(f, "   HI_LIMIT   [R4] : {}{}", self.hi_limit, scaled(self.hi_limit, &self.c_hlmfmt, || self.measurement(self.hi_limit, self.hlm_scal)))?;
             writeln!(f, "   START_IN   [R4] : {}", self.start_in)?;
             writeln!(f, "   INCR_IN    [R4] : {}", self.incr_in)?;
             writeln!(f, "   RTN_INDX [jxU2] : {:?}", self.rtn_indx)?; //TODO: implement std:fmt::Display for Vec<U2>
@@ -1225,7 +1380,7 @@ impl fmt::Display for MPR<'_> {
 // ========================================================
 // FTR : Functional Test Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct FTR<'a> {
     pub test_num: U4,
     pub head_num: U1,
@@ -1264,29 +1419,48 @@ pub struct FTR<'a> {
     #[array_type(N1)]
     pub pgm_stat: Vec<N1>,
     #[default(Dn(0, b""))]
+    #[serde(skip_deserializing)]
     pub fail_pin: Dn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub vect_nam: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub time_set: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub op_code: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub test_txt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub alarm_id: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub prog_txt: Cn<'a>,
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub rslt_txt: Cn<'a>,
     #[default(U1::from(0xff))]
     pub patg_num: U1,
     #[default(Dn(0, b""))]
+    #[serde(skip_deserializing)]
     pub spin_map: Dn<'a>,
 }
 
 record_id!(FTR, true, '_);
 
+impl FTR<'_> {
+    pub fn test_flags(&self) -> TestFlag {
+        TestFlag::from(self.test_flg)
+    }
+
+    pub fn opt_flags(&self) -> FtrOptFlag {
+        FtrOptFlag::from(self.opt_flag)
+    }
+}
+
 impl fmt::Display for FTR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "FTR : Functional Test Record")?;
@@ -1294,10 +1468,10 @@ impl fmt::Display for FTR<'_> {
         writeln!(f, "   HEAD_NUM   [U1] : {}", self.head_num)?;
         writeln!(f, "   SITE_NUM   [U1] : {}", self.site_num)?;
         if self.opt_flag == B1::from(0x00) {
-            writeln!(f, "   TEST_FLG   [B1] : {}", self.test_flg)
+            writeln!(f, "   TEST_FLG   [B1] : {} {}", self.test_flg, self.test_flags())
         } else {
-            writeln!(f, "   TEST_FLG   [B1] : {}", self.test_flg)?;
-            writeln!(f, "   OPT_FLAG   [B1] : {}", self.opt_flag)?;
+            writeln!(f, "   TEST_FLG   [B1] : {} {}", self.test_flg, self.test_flags())?;
+            writeln!(f, "   OPT_FLAG   [B1] : {} {}", self.opt_flag, self.opt_flags())?;
             writeln!(f, "   CYCL_CNT   [U4] : {}", self.cycl_cnt)?;
             writeln!(f, "   REL_VADR   [U4] : {}", self.rel_vadr)?;
             writeln!(f, "   REPT_CNT   [U4] : {}", self.rept_cnt)?;
@@ -1328,14 +1502,14 @@ impl fmt::Display for FTR<'_> {
 // ========================================================
 // BPS : Begin Program Section
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct BPS<'a> {
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub seq_name: Cn<'a>,
 }
 
 record_id!(BPS, false, '_);
-atdf!(BPS, '_);
 
 impl fmt::Display for BPS<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1347,11 +1521,10 @@ impl fmt::Display for BPS<'_> {
 // ========================================================
 // EPS : End Program Section
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct EPS;
 
 record_id!(EPS, false,);
-atdf!(EPS,);
 
 impl fmt::Display for EPS {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1362,17 +1535,17 @@ impl fmt::Display for EPS {
 // ========================================================
 // GDR : Generic Data Record
 // ========================================================
-#[derive(Debug, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct GDR<'a> {
     #[default(U2::from(0))]
     pub fld_cnt: U2,
     #[array_length(fld_cnt)]
     #[array_type(Vn<'a>)]
+    #[serde(skip_deserializing)]
     pub gen_data: Vec<Vn<'a>>,
 }
 
 record_id!(GDR, false, '_);
-atdf!(GDR, '_);
 
 impl fmt::Display for GDR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1385,14 +1558,14 @@ impl fmt::Display for GDR<'_> {
 // ========================================================
 // DTR : Datalog Text Record
 // ========================================================
-#[derive(Debug, Eq, PartialEq, Serialize, STDFRecord)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, STDFRecord)]
 pub struct DTR<'a> {
     #[default(Cn(b""))]
+    #[serde(borrow)]
     pub text_dat: Cn<'a>,
 }
 
 record_id!(DTR, false, '_);
-atdf!(DTR, '_);
 
 impl fmt::Display for DTR<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1415,7 +1588,7 @@ pub struct Raw<'a> {
 //TODO: Implement TryWrite for Raw
 //TODO: Implement Display for Raw
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum V4<'a> {
     FAR(FAR),
     ATR(ATR<'a>),
@@ -1510,6 +1683,53 @@ impl V4<'_> {
             V4::Invalid(ref r) => (u8::from(&r.rec_typ), u8::from(&r.rec_sub)),
         }
     }
+
+    /// Returns the decoded fields of the record as a JSON object (`Null` for Unknown and Invalid records).
+    pub fn to_json(&self) -> serde_json::Value {
+        let value = match self {
+            V4::FAR(rec) => serde_json::to_value(rec),
+            V4::ATR(rec) => serde_json::to_value(rec),
+            V4::MIR(rec) => serde_json::to_value(rec),
+            V4::MRR(rec) => serde_json::to_value(rec),
+            V4::PCR(rec) => serde_json::to_value(rec),
+            V4::HBR(rec) => serde_json::to_value(rec),
+            V4::SBR(rec) => serde_json::to_value(rec),
+            V4::PMR(rec) => serde_json::to_value(rec),
+            V4::PGR(rec) => serde_json::to_value(rec),
+            V4::PLR(rec) => serde_json::to_value(rec),
+            V4::RDR(rec) => serde_json::to_value(rec),
+            V4::SDR(rec) => serde_json::to_value(rec),
+            V4::WIR(rec) => serde_json::to_value(rec),
+            V4::WRR(rec) => serde_json::to_value(rec),
+            V4::WCR(rec) => serde_json::to_value(rec),
+            V4::PIR(rec) => serde_json::to_value(rec),
+            V4::PRR(rec) => serde_json::to_value(rec),
+            V4::TSR(rec) => serde_json::to_value(rec),
+            V4::PTR(rec) => serde_json::to_value(rec),
+            V4::MPR(rec) => serde_json::to_value(rec),
+            V4::FTR(rec) => serde_json::to_value(rec),
+            V4::BPS(rec) => serde_json::to_value(rec),
+            V4::EPS(_) => Ok(serde_json::Value::Object(serde_json::Map::new())),
+            V4::GDR(rec) => serde_json::to_value(rec),
+            V4::DTR(rec) => serde_json::to_value(rec),
+            V4::Unknown(_) | V4::Invalid(_) => Ok(serde_json::Value::Null),
+        };
+        value.unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Serializes the record, header included, into a buffer of its size.
+    pub fn to_bytes(self, endian: ctx::Endian) -> byte::Result<Vec<u8>> {
+        thread_local! {
+            // a record is written into this buffer of the largest record size first, reused between calls
+            static SCRATCH: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::new(vec![0; 4 + u16::MAX as usize]);
+        }
+        SCRATCH.with(|scratch| {
+            let mut scratch = scratch.borrow_mut();
+            let offset = &mut 0;
+            scratch.write_with::<V4>(offset, self, endian)?;
+            Ok(scratch[..*offset].to_vec())
+        })
+    }
 }
 
 impl<'a> TryRead<'a, ctx::Endian> for V4<'a> {
@@ -1573,7 +1793,10 @@ impl TryWrite<ctx::Endian> for V4<'_> {
     fn try_write(self, bytes: &mut [u8], endian: ctx::Endian) -> byte::Result<usize> {
         let offset = &mut 0;
         let (typ, sub) = self.rec_typ_sub();
-        let mut rec_bytes: Vec<u8> = vec![];
+        check_len(bytes, 4)?;
+        // the fields are written after the header, which is written once their length is known
+        let end = bytes.len().min(4 + u16::MAX as usize);
+        let rec_bytes = &mut bytes[4..end];
         let rec_offset = &mut 0;
         match self {
             V4::FAR(r) => rec_bytes.write_with::<FAR>(rec_offset, r, endian),
@@ -1601,31 +1824,114 @@ impl TryWrite<ctx::Endian> for V4<'_> {
             V4::EPS(_) => Ok(()),
             V4::GDR(r) => rec_bytes.write_with::<GDR>(rec_offset, r, endian),
             V4::DTR(r) => rec_bytes.write_with::<DTR>(rec_offset, r, endian),
-            V4::Unknown(_) => return Ok(0), // TODO: write unknown records
-            V4::Invalid(_) => return Ok(0),
+            V4::Unknown(r) => rec_bytes.write::<&[u8]>(rec_offset, r.contents),
+            V4::Invalid(r) => rec_bytes.write::<&[u8]>(rec_offset, r.contents),
         }?;
+        let rec_len = *rec_offset;
         let header = Header {
-            rec_len: U2::from(*rec_offset as u16),
+            rec_len: U2::from(rec_len as u16),
             rec_typ: U1::from(typ),
             rec_sub: U1::from(sub),
         };
         bytes.write_with::<Header>(offset, header, endian)?;
-        bytes.write::<&[u8]>(offset, &rec_bytes)?;
-        Ok(*offset)
+        Ok(*offset + rec_len)
     }
 }
 
 
-//TODO: Implement std::fmt::Display for V4
-// impl <'a> fmt::Display for V4<'a> {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         match self {
-//             V4::MRR(rec) => write!(f, "{}", rec),
-//             _ => todo!(),
-//         }
-//     }
-// }
-//TODO: Implement to_atdf for V4
+impl fmt::Display for V4<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            V4::FAR(rec) => write!(f, "{}", rec),
+            V4::ATR(rec) => write!(f, "{}", rec),
+            V4::MIR(rec) => write!(f, "{}", rec),
+            V4::MRR(rec) => write!(f, "{}", rec),
+            V4::PCR(rec) => write!(f, "{}", rec),
+            V4::HBR(rec) => write!(f, "{}", rec),
+            V4::SBR(rec) => write!(f, "{}", rec),
+            V4::PMR(rec) => write!(f, "{}", rec),
+            V4::PGR(rec) => write!(f, "{}", rec),
+            V4::PLR(rec) => write!(f, "{}", rec),
+            V4::RDR(rec) => write!(f, "{}", rec),
+            V4::SDR(rec) => write!(f, "{}", rec),
+            V4::WIR(rec) => write!(f, "{}", rec),
+            V4::WRR(rec) => write!(f, "{}", rec),
+            V4::WCR(rec) => write!(f, "{}", rec),
+            V4::PIR(rec) => write!(f, "{}", rec),
+            V4::PRR(rec) => write!(f, "{}", rec),
+            V4::TSR(rec) => write!(f, "{}", rec),
+            V4::PTR(rec) => write!(f, "{}", rec),
+            V4::MPR(rec) => write!(f, "{}", rec),
+            V4::FTR(rec) => write!(f, "{}", rec),
+            V4::BPS(rec) => write!(f, "{}", rec),
+            V4::EPS(rec) => write!(f, "{}", rec),
+            V4::GDR(rec) => write!(f, "{}", rec),
+            V4::DTR(rec) => write!(f, "{}", rec),
+            V4::Unknown(_) | V4::Invalid(_) => writeln!(f, "???"),
+        }
+    }
+}
+
+/// A record shown at a given offset, see [`V4::display_in`].
+pub struct V4Display<'r, 'a> {
+    record: &'r V4<'a>,
+    offset: Option<UtcOffset>,
+}
+
+impl fmt::Display for V4Display<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.record {
+            V4::ATR(rec) => rec.fmt_in(f, self.offset),
+            V4::MIR(rec) => rec.fmt_in(f, self.offset),
+            V4::MRR(rec) => rec.fmt_in(f, self.offset),
+            V4::WIR(rec) => rec.fmt_in(f, self.offset),
+            V4::WRR(rec) => rec.fmt_in(f, self.offset),
+            record => write!(f, "{}", record),
+        }
+    }
+}
+
+impl<'a> V4<'a> {
+    /// The record as shown by `Display`, but with its timestamps as RFC 3339 at `offset` if given
+    /// (see [`U4E::display_in`]).
+    pub fn display_in(&self, offset: Option<UtcOffset>) -> V4Display<'_, 'a> {
+        V4Display { record: self, offset }
+    }
+}
+
+impl V4<'_> {
+    /// The record as an ATDF line, `None` for Unknown and Invalid records.
+    pub fn to_atdf(&self) -> Option<String> {
+        match self {
+            V4::FAR(rec) => Some(rec.to_atdf()),
+            V4::ATR(rec) => Some(rec.to_atdf()),
+            V4::MIR(rec) => Some(rec.to_atdf()),
+            V4::MRR(rec) => Some(rec.to_atdf()),
+            V4::PCR(rec) => Some(rec.to_atdf()),
+            V4::HBR(rec) => Some(rec.to_atdf()),
+            V4::SBR(rec) => Some(rec.to_atdf()),
+            V4::PMR(rec) => Some(rec.to_atdf()),
+            V4::PGR(rec) => Some(rec.to_atdf()),
+            V4::PLR(rec) => Some(rec.to_atdf()),
+            V4::RDR(rec) => Some(rec.to_atdf()),
+            V4::SDR(rec) => Some(rec.to_atdf()),
+            V4::WIR(rec) => Some(rec.to_atdf()),
+            V4::WRR(rec) => Some(rec.to_atdf()),
+            V4::WCR(rec) => Some(rec.to_atdf()),
+            V4::PIR(rec) => Some(rec.to_atdf()),
+            V4::PRR(rec) => Some(rec.to_atdf()),
+            V4::TSR(rec) => Some(rec.to_atdf()),
+            V4::PTR(rec) => Some(rec.to_atdf()),
+            V4::MPR(rec) => Some(rec.to_atdf()),
+            V4::FTR(rec) => Some(rec.to_atdf()),
+            V4::BPS(rec) => Some(rec.to_atdf()),
+            V4::EPS(rec) => Some(rec.to_atdf()),
+            V4::GDR(rec) => Some(rec.to_atdf()),
+            V4::DTR(rec) => Some(rec.to_atdf()),
+            V4::Unknown(_) | V4::Invalid(_) => None,
+        }
+    }
+}
 
 pub fn is_supported_records() -> Vec<String> {
     vec![
@@ -1790,7 +2096,7 @@ mod tests {
 
     macro_rules! assert_float {
         ($x:expr, $y:expr, $d:expr) => {
-            if !($x - $y < $d || $y - $x < $d) {
+            if ($x - $y).abs() >= $d {
                 panic!();
             }
         };
@@ -1864,6 +2170,18 @@ mod tests {
         assert_eq!(Header::detect_endian(b).unwrap(), BE);
     }
 
+    #[test]
+    fn test_v4_write() {
+        let far = || V4::FAR(FAR { cpu_type: U1::from(2), stdf_ver: U1::from(4) });
+        // written in place, into a buffer of the exact record size
+        let mut out = vec![0; 6];
+        assert_eq!(out.write_with(&mut 0, far(), LE), Ok(()));
+        assert_eq!(out, [0x02, 0x00, 0u8, 10u8, 2u8, 4u8]);
+        assert!([0u8; 5].write_with(&mut 0, far(), LE).is_err());
+        assert_eq!(far().to_bytes(BE).unwrap(), [0x00, 0x02, 0u8, 10u8, 2u8, 4u8]);
+        assert_eq!(V4::EPS(EPS).to_bytes(LE).unwrap(), [0x00, 0x00, 20u8, 20u8]);
+    }
+
     #[test]
     fn test_mpr_single_nibble() {
         // BE representation
@@ -1947,4 +2265,55 @@ mod tests {
         out.write_with(offset, mpr, BE).unwrap();
         assert_eq!(b, out.as_slice());
     }
+
+    #[test]
+    fn test_display_in() {
+        let wrr = V4::WRR(WRR {
+            head_num: U1::from(1),
+            site_grp: U1::from(255),
+            finish_t: U4E(1526348699),
+            part_cnt: U4::from(1),
+            rtst_cnt: U4::MAX,
+            abrt_cnt: U4::MAX,
+            good_cnt: U4::MAX,
+            func_cnt: U4::MAX,
+            wafer_id: Cn(b""),
+            fabwf_id: Cn(b""),
+            frame_id: Cn(b""),
+            mask_id: Cn(b""),
+            usr_desc: Cn(b""),
+            exc_desc: Cn(b""),
+        });
+        let cest = UtcOffset::from_hms(2, 0, 0).unwrap();
+        assert!(wrr.display_in(Some(cest)).to_string().contains("FINISH_T [U4] : 1526348699 → 2018-05-15T03:44:59+02:00\n"));
+        assert_eq!(wrr.display_in(None).to_string(), wrr.to_string());
+        assert!(wrr.to_string().contains("FINISH_T [U4] : 1526348699 → Tue, 15 May 2018 01:44:59 +0000\n"));
+        let far = V4::FAR(FAR { cpu_type: U1::from(2), stdf_ver: U1::from(4) });
+        assert_eq!(far.display_in(Some(cest)).to_string(), far.to_string());
+    }
+
+    #[test]
+    fn test_mpr_display() {
+        let b: &[u8] = &[
+            0u8, 0u8, 0u8, 103u8, // test_num 103
+            1u8, 2u8, // head_num 1, site_num 2
+            0u8, 0u8, // test_flg, parm_flg
+            0u8, 0u8, // rtn_icnt 0
+            0u8, 2u8, // rslt_cnt 2
+            0x3c, 0x23, 0xd7, 0x0a, // 0.01
+            0x3d, 0xcc, 0xcc, 0xcd, // 0.1
+        ];
+        let mut mpr = b.read_with::<MPR>(&mut 0, BE).unwrap();
+        assert!(mpr.to_string().contains("RTN_RSLT [kxR4] : [R4(0.01), R4(0.1)]\n"));
+        mpr.opt_flag = B1::from(0b0000_1110);
+        mpr.units = Cn(b"A");
+        mpr.res_scal = I1::from(3);
+        mpr.llm_scal = I1::from(6);
+        mpr.c_resfmt = Cn(b"%5.1f");
+        mpr.c_llmfmt = Cn(b"%.0f");
+        mpr.lo_limit = R4::from(0.000_5);
+        let display = mpr.to_string();
+        assert!(display.contains("RTN_RSLT [kxR4] : [R4(0.01), R4(0.1)] → [ 10.0 mA, 100.0 mA]\n"));
+        assert!(display.contains("LO_LIMIT   [R4] : 0.0005 → 500 uA\n"));
+    }
 }
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+
+use byte::BytesExt;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::records::V4;
+use crate::table::{map, PartRows, TestColumn};
+
+/// The tables of the database, the keys are SQLite rowids.
+///
+/// A lot is the content of one STDF file: its identity is the MIR (lot, sublot, part type, job,
+/// tester, test code, flow and the setup/start times), so importing a file twice is a no-op.
+/// The tests (with the limits of their first occurrence) belong to a lot, there is one test per
+/// pin of an MPR. The results are in the base units of the tests, i.e. without RES_SCAL, and
+/// NULL when they are not a number or flagged invalid (TEST_FLG bit 1) or not executed (bit 4).
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS lots (
+    lot_key INTEGER PRIMARY KEY,
+    lot_id TEXT NOT NULL,
+    sblot_id TEXT NOT NULL,
+    part_typ TEXT NOT NULL,
+    job_nam TEXT NOT NULL,
+    job_rev TEXT NOT NULL,
+    node_nam TEXT NOT NULL,
+    tstr_typ TEXT NOT NULL,
+    test_cod TEXT NOT NULL,
+    flow_id TEXT NOT NULL,
+    oper_nam TEXT NOT NULL,
+    tst_temp TEXT NOT NULL,
+    mode_cod TEXT NOT NULL,
+    setup_t INTEGER NOT NULL,
+    start_t INTEGER NOT NULL,
+    finish_t INTEGER,
+    file_name TEXT NOT NULL,
+    UNIQUE (lot_id, sblot_id, part_typ, job_nam, job_rev, node_nam, test_cod, flow_id, setup_t, start_t)
+);
+CREATE TABLE IF NOT EXISTS wafers (
+    wafer_key INTEGER PRIMARY KEY,
+    lot_key INTEGER NOT NULL REFERENCES lots (lot_key),
+    head_num INTEGER NOT NULL,
+    wafer_id TEXT NOT NULL,
+    start_t INTEGER,
+    finish_t INTEGER,
+    part_cnt INTEGER,
+    good_cnt INTEGER
+);
+CREATE TABLE IF NOT EXISTS parts (
+    part_key INTEGER PRIMARY KEY,
+    lot_key INTEGER NOT NULL REFERENCES lots (lot_key),
+    wafer_key INTEGER REFERENCES wafers (wafer_key),
+    head_num INTEGER NOT NULL,
+    site_num INTEGER NOT NULL,
+    part_id TEXT NOT NULL,
+    x_coord INTEGER,
+    y_coord INTEGER,
+    hard_bin INTEGER NOT NULL,
+    soft_bin INTEGER,
+    test_t INTEGER,
+    passed INTEGER
+);
+CREATE TABLE IF NOT EXISTS tests (
+    test_key INTEGER PRIMARY KEY,
+    lot_key INTEGER NOT NULL REFERENCES lots (lot_key),
+    rec TEXT NOT NULL,
+    test_num INTEGER NOT NULL,
+    test_txt TEXT NOT NULL,
+    pin TEXT,
+    units TEXT NOT NULL,
+    res_scal INTEGER,
+    lo_limit REAL,
+    hi_limit REAL
+);
+CREATE TABLE IF NOT EXISTS ptr_results (
+    part_key INTEGER NOT NULL REFERENCES parts (part_key),
+    test_key INTEGER NOT NULL REFERENCES tests (test_key),
+    result REAL,
+    passed INTEGER
+);
+CREATE TABLE IF NOT EXISTS mpr_results (
+    part_key INTEGER NOT NULL REFERENCES parts (part_key),
+    test_key INTEGER NOT NULL REFERENCES tests (test_key),
+    result REAL,
+    passed INTEGER
+);
+CREATE TABLE IF NOT EXISTS ftr_results (
+    part_key INTEGER NOT NULL REFERENCES parts (part_key),
+    test_key INTEGER NOT NULL REFERENCES tests (test_key),
+    passed INTEGER
+);
+CREATE TABLE IF NOT EXISTS bins (
+    lot_key INTEGER NOT NULL REFERENCES lots (lot_key),
+    kind TEXT NOT NULL CHECK (kind IN ('H', 'S')),
+    head_num INTEGER NOT NULL,
+    site_num INTEGER NOT NULL,
+    bin_num INTEGER NOT NULL,
+    bin_cnt INTEGER NOT NULL,
+    bin_pf TEXT NOT NULL,
+    bin_nam TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS wafers_lot ON wafers (lot_key);
+CREATE INDEX IF NOT EXISTS parts_lot ON parts (lot_key);
+CREATE INDEX IF NOT EXISTS parts_wafer ON parts (wafer_key);
+CREATE INDEX IF NOT EXISTS tests_lot ON tests (lot_key);
+CREATE INDEX IF NOT EXISTS tests_test_num ON tests (test_num);
+CREATE INDEX IF NOT EXISTS ptr_results_part ON ptr_results (part_key);
+CREATE INDEX IF NOT EXISTS ptr_results_test ON ptr_results (test_key);
+CREATE INDEX IF NOT EXISTS mpr_results_part ON mpr_results (part_key);
+CREATE INDEX IF NOT EXISTS mpr_results_test ON mpr_results (test_key);
+CREATE INDEX IF NOT EXISTS ftr_results_part ON ftr_results (part_key);
+CREATE INDEX IF NOT EXISTS ftr_results_test ON ftr_results (test_key);
+CREATE INDEX IF NOT EXISTS bins_lot ON bins (lot_key);
+";
+
+fn sql_error(e: rusqlite::Error) -> Error {
+    Error::other(e)
+}
+
+fn no_mir() -> Error {
+    Error::new(ErrorKind::InvalidData, "No MIR before the first part, wafer or bin")
+}
+
+fn insert_test(tx: &Transaction, lot_key: i64, column: &TestColumn) -> rusqlite::Result<i64> {
+    tx.prepare_cached(
+        "INSERT INTO tests (lot_key, rec, test_num, test_txt, pin, units, res_scal, lo_limit, hi_limit)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?
+    .execute(params![
+        lot_key,
+        column.rec,
+        column.test_num,
+        column.test_txt,
+        column.pin,
+        column.limits.units,
+        column.limits.res_scal,
+        column.limits.lo_limit,
+        column.limits.hi_limit,
+    ])?;
+    Ok(tx.last_insert_rowid())
+}
+
+/// Adds an STDF file to an SQLite database, creating the tables (see [`SCHEMA`]) if needed.
+///
+/// `file_name` is recorded with the lot. The file is added in a single transaction: nothing
+/// is written if it fails. `progress` is called after each part with the offset read up to
+/// in the file.
+///
+/// # Returns
+///
+/// The number of parts added, `None` if the lot (MIR) is already in the database.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file, if it has no MIR,
+/// on any I/O error or if the database can not be written.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use rusqlite::Connection;
+/// use stdf::sqlite::write_sqlite;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let mut connection = Connection::open("lots.db").unwrap();
+/// match write_sqlite(&mut file, &mut connection, "test.std", |_| {}).unwrap() {
+///     Some(parts) => println!("{} parts added", parts),
+///     None => println!("already in the database"),
+/// }
+/// ```
+pub fn write_sqlite<F: FnMut(usize)>(
+    file: &mut File,
+    connection: &mut Connection,
+    file_name: &str,
+    mut progress: F,
+) -> Result<Option<usize>> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+
+    connection.execute_batch(SCHEMA).map_err(sql_error)?;
+    let tx = connection.transaction().map_err(sql_error)?;
+    let mut lot_key: Option<i64> = None;
+    let mut wafer_keys: HashMap<String, i64> = HashMap::new();
+    // the key of each column of the catalog, the columns are added as the tests first appear
+    let mut test_keys: Vec<i64> = Vec::new();
+    let mut parts = 0;
+    let mut rows = PartRows::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        match &record {
+            V4::MIR(mir) => {
+                let [lot_id, sblot_id, part_typ, job_nam, job_rev, node_nam, test_cod, flow_id, tstr_typ, oper_nam, tst_temp] = [
+                    &mir.lot_id,
+                    &mir.sblot_id,
+                    &mir.part_typ,
+                    &mir.job_nam,
+                    &mir.job_rev,
+                    &mir.node_nam,
+                    &mir.test_cod,
+                    &mir.flow_id,
+                    &mir.tstr_typ,
+                    &mir.oper_nam,
+                    &mir.tst_temp,
+                ]
+                .map(|field| field.to_string().trim().to_string());
+                let existing: Option<i64> = tx
+                    .query_row(
+                        "SELECT lot_key FROM lots WHERE lot_id = ?1 AND sblot_id = ?2 AND part_typ = ?3
+                         AND job_nam = ?4 AND job_rev = ?5 AND node_nam = ?6 AND test_cod = ?7 AND flow_id = ?8
+                         AND setup_t = ?9 AND start_t = ?10",
+                        params![lot_id, sblot_id, part_typ, job_nam, job_rev, node_nam, test_cod, flow_id, mir.setup_t.0, mir.start_t.0],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(sql_error)?;
+                if existing.is_some() {
+                    return Ok(None);
+                }
+                tx.execute(
+                    "INSERT INTO lots (lot_id, sblot_id, part_typ, job_nam, job_rev, node_nam, test_cod, flow_id,
+                     setup_t, start_t, tstr_typ, oper_nam, tst_temp, mode_cod, file_name)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                    params![
+                        lot_id,
+                        sblot_id,
+                        part_typ,
+                        job_nam,
+                        job_rev,
+                        node_nam,
+                        test_cod,
+                        flow_id,
+                        mir.setup_t.0,
+                        mir.start_t.0,
+                        tstr_typ,
+                        oper_nam,
+                        tst_temp,
+                        mir.mode_cod.to_string().trim(),
+                        file_name,
+                    ],
+                )
+                .map_err(sql_error)?;
+                lot_key = Some(tx.last_insert_rowid());
+            }
+            V4::MRR(mrr) => {
+                let lot_key = lot_key.ok_or_else(no_mir)?;
+                tx.execute("UPDATE lots SET finish_t = ?1 WHERE lot_key = ?2", params![mrr.finish_t.0, lot_key])
+                    .map_err(sql_error)?;
+            }
+            V4::WIR(wir) => {
+                let lot_key = lot_key.ok_or_else(no_mir)?;
+                let wafer_id = wir.wafer_id.to_string().trim().to_string();
+                tx.execute(
+                    "INSERT INTO wafers (lot_key, head_num, wafer_id, start_t) VALUES (?1, ?2, ?3, ?4)",
+                    params![lot_key, wir.head_num.0, wafer_id, wir.start_t.0],
+                )
+                .map_err(sql_error)?;
+                wafer_keys.insert(wafer_id, tx.last_insert_rowid());
+            }
+            V4::WRR(wrr) => {
+                let wafer_id = wrr.wafer_id.to_string().trim().to_string();
+                if let Some(wafer_key) = wafer_keys.get(&wafer_id) {
+                    let good_cnt = Some(wrr.good_cnt.0).filter(|cnt| *cnt != u32::MAX);
+                    tx.execute(
+                        "UPDATE wafers SET finish_t = ?1, part_cnt = ?2, good_cnt = ?3 WHERE wafer_key = ?4",
+                        params![wrr.finish_t.0, wrr.part_cnt.0, good_cnt, wafer_key],
+                    )
+                    .map_err(sql_error)?;
+                }
+            }
+            V4::HBR(hbr) => {
+                let lot_key = lot_key.ok_or_else(no_mir)?;
+                tx.prepare_cached("INSERT INTO bins VALUES (?1, 'H', ?2, ?3, ?4, ?5, ?6, ?7)")
+                    .and_then(|mut insert| {
+                        insert.execute(params![
+                            lot_key,
+                            hbr.head_num.0,
+                            hbr.site_num.0,
+                            hbr.hbin_num.0,
+                            hbr.hbin_cnt.0,
+                            hbr.hbin_pf.to_string().trim(),
+                            hbr.hbin_nam.to_string().trim(),
+                        ])
+                    })
+                    .map_err(sql_error)?;
+            }
+            V4::SBR(sbr) => {
+                let lot_key = lot_key.ok_or_else(no_mir)?;
+                tx.prepare_cached("INSERT INTO bins VALUES (?1, 'S', ?2, ?3, ?4, ?5, ?6, ?7)")
+                    .and_then(|mut insert| {
+                        insert.execute(params![
+                            lot_key,
+                            sbr.head_num.0,
+                            sbr.site_num.0,
+                            sbr.sbin_num.0,
+                            sbr.sbin_cnt.0,
+                            sbr.sbin_pf.to_string().trim(),
+                            sbr.sbin_nam.to_string().trim(),
+                        ])
+                    })
+                    .map_err(sql_error)?;
+            }
+            _ => {}
+        }
+        let row = match rows.push(record) {
+            Some(row) => row,
+            None => continue,
+        };
+        let lot_key = lot_key.ok_or_else(no_mir)?;
+        for column in rows.catalog.columns[test_keys.len()..].iter() {
+            test_keys.push(insert_test(&tx, lot_key, column).map_err(sql_error)?);
+        }
+        let wafer_key = wafer_keys.get(&row.wafer_id);
+        tx.prepare_cached(
+            "INSERT INTO parts (lot_key, wafer_key, head_num, site_num, part_id, x_coord, y_coord, hard_bin,
+             soft_bin, test_t, passed) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )
+        .and_then(|mut insert| {
+            insert.execute(params![
+                lot_key,
+                wafer_key,
+                row.head_num,
+                row.site_num,
+                row.part_id,
+                row.x,
+                row.y,
+                row.hard_bin,
+                row.soft_bin,
+                row.test_t,
+                row.passed,
+            ])
+        })
+        .map_err(sql_error)?;
+        let part_key = tx.last_insert_rowid();
+        for value in row.values.iter() {
+            let test_key = test_keys[value.column];
+            let inserted = match rows.catalog.columns[value.column].rec.as_str() {
+                "PTR" => tx
+                    .prepare_cached("INSERT INTO ptr_results VALUES (?1, ?2, ?3, ?4)")
+                    .and_then(|mut insert| insert.execute(params![part_key, test_key, value.result, value.passed])),
+                "MPR" => tx
+                    .prepare_cached("INSERT INTO mpr_results VALUES (?1, ?2, ?3, ?4)")
+                    .and_then(|mut insert| insert.execute(params![part_key, test_key, value.result, value.passed])),
+                _ => tx
+                    .prepare_cached("INSERT INTO ftr_results VALUES (?1, ?2, ?3)")
+                    .and_then(|mut insert| insert.execute(params![part_key, test_key, value.passed])),
+            };
+            inserted.map_err(sql_error)?;
+        }
+        parts += 1;
+        progress(*offset);
+    }
+    if lot_key.is_none() {
+        return Err(no_mir());
+    }
+    tx.commit().map_err(sql_error)?;
+    Ok(Some(parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{B1, R4};
+    use std::io::{Seek, SeekFrom, Write};
+
+    fn count(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    /// test.std with a NaN result in its first PTR and its second PTR flagged invalid.
+    fn invalid_results() -> File {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let (mmap, endian) = map(&mut input).unwrap();
+        let bytes = &mmap[..];
+        let mut output = tempfile::tempfile().unwrap();
+        let mut ptrs = 0;
+        let offset = &mut 0;
+        loop {
+            let start = *offset;
+            match bytes.read_with::<V4>(offset, endian) {
+                Ok(V4::PTR(mut ptr)) if ptrs < 2 => {
+                    match ptrs {
+                        0 => ptr.result = R4(f32::NAN),
+                        _ => ptr.test_flg = B1(0b0000_0010),
+                    }
+                    ptrs += 1;
+                    output.write_all(&V4::PTR(ptr).to_bytes(endian).unwrap()).unwrap();
+                }
+                Ok(_) => output.write_all(&bytes[start..*offset]).unwrap(),
+                Err(_) => break,
+            }
+        }
+        output.seek(SeekFrom::Start(0)).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_invalid_results() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        let mut file = invalid_results();
+        let parts = write_sqlite(&mut file, &mut connection, "invalid.std", |_| {}).unwrap();
+        assert_eq!(parts, Some(22));
+        assert_eq!(count(&connection, "ptr_results"), 572);
+        let nulls: i64 = connection
+            .query_row("SELECT COUNT(*) FROM ptr_results WHERE result IS NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(nulls, 2);
+    }
+
+    #[test]
+    fn test_write_sqlite() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let parts = write_sqlite(&mut file, &mut connection, "test.std", |_| {}).unwrap();
+        assert_eq!(parts, Some(22));
+        assert_eq!(count(&connection, "lots"), 1);
+        assert_eq!(count(&connection, "parts"), 22);
+        assert_eq!(count(&connection, "tests"), 26);
+        assert_eq!(count(&connection, "ptr_results"), 572);
+        assert_eq!(count(&connection, "mpr_results"), 0);
+        assert!(count(&connection, "bins") > 0);
+        let lot_id: String = connection.query_row("SELECT lot_id FROM lots", [], |row| row.get(0)).unwrap();
+        assert_eq!(lot_id, "F6N910.1");
+        let part_id: String = connection
+            .query_row("SELECT part_id FROM parts ORDER BY part_key LIMIT 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(part_id, "1");
+
+        // the same lot is not added twice
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let parts = write_sqlite(&mut file, &mut connection, "copy.std", |_| {}).unwrap();
+        assert_eq!(parts, None);
+        assert_eq!(count(&connection, "lots"), 1);
+        assert_eq!(count(&connection, "parts"), 22);
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Result, Write};
+
+use byte::BytesExt;
+use serde::Serialize;
+
+use crate::flags::TestFlag;
+use crate::inheritance::{Limits, PtrResolver};
+use crate::records::V4;
+use crate::table::map;
+
+/// A running estimate of a quantile, with the P² algorithm (Jain and Chlamtac, 1985).
+///
+/// Five markers are kept whatever the number of values, the estimate is exact up to five values.
+#[derive(Debug, Clone)]
+pub struct Quantile {
+    p: f64,
+    count: usize,
+    /// The marker heights, the first values while there are less than five.
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl Quantile {
+    /// A new estimator of the `p` quantile, eg: 0.5 for the median.
+    pub fn new(p: f64) -> Self {
+        Quantile {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+        let h = &mut self.heights;
+        let k = if value < h[0] {
+            h[0] = value;
+            0
+        } else if value >= h[4] {
+            h[4] = value;
+            3
+        } else {
+            (0..4).find(|i| value < h[i + 1]).unwrap_or(3)
+        };
+        for position in self.positions[k + 1..].iter_mut() {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments.iter()) {
+            *desired += increment;
+        }
+        // moves the middle markers towards their desired positions
+        let n = &mut self.positions;
+        for i in 1..4 {
+            let d = self.desired[i] - n[i];
+            if (d >= 1.0 && n[i + 1] - n[i] > 1.0) || (d <= -1.0 && n[i - 1] - n[i] < -1.0) {
+                let d = d.signum();
+                let parabolic = h[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (h[i + 1] - h[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (h[i] - h[i - 1]) / (n[i] - n[i - 1]));
+                h[i] = if h[i - 1] < parabolic && parabolic < h[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    h[i] + d * (h[j] - h[i]) / (n[j] - n[i])
+                };
+                n[i] += d;
+            }
+        }
+    }
+
+    /// The estimate, `None` without values.
+    pub fn value(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            1..=4 => {
+                // interpolated between the closest ranks, as numpy.quantile does
+                let mut values = self.heights[..self.count].to_vec();
+                values.sort_by(f64::total_cmp);
+                let rank = self.p * (self.count - 1) as f64;
+                let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+                Some(values[below] + (rank - below as f64) * (values[above] - values[below]))
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+/// The running statistics of the results of a test, memory does not grow with the results:
+/// Welford's algorithm for the mean and variance, P² estimates for the quartiles.
+#[derive(Debug, Clone)]
+pub struct RunningStats {
+    pub count: u64,
+    pub fails: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    m2: f64,
+    quartiles: [Quantile; 3],
+}
+
+impl Default for RunningStats {
+    fn default() -> Self {
+        RunningStats {
+            count: 0,
+            fails: 0,
+            min: f64::NAN,
+            max: f64::NAN,
+            mean: 0.0,
+            m2: 0.0,
+            quartiles: [Quantile::new(0.25), Quantile::new(0.5), Quantile::new(0.75)],
+        }
+    }
+}
+
+impl RunningStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a result, a NaN or an infinity is skipped.
+    pub fn add(&mut self, value: f64, failed: bool) {
+        if !value.is_finite() {
+            return;
+        }
+        if self.count == 0 || value < self.min {
+            self.min = value;
+        }
+        if self.count == 0 || value > self.max {
+            self.max = value;
+        }
+        self.count += 1;
+        if failed {
+            self.fails += 1;
+        }
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        for quartile in self.quartiles.iter_mut() {
+            quartile.add(value);
+        }
+    }
+
+    /// The sample standard deviation, `None` under two values.
+    pub fn std_dev(&self) -> Option<f64> {
+        (self.count > 1).then(|| (self.m2 / (self.count - 1) as f64).sqrt())
+    }
+
+    /// The first quartile, the median and the third quartile.
+    pub fn quartiles(&self) -> [Option<f64>; 3] {
+        [self.quartiles[0].value(), self.quartiles[1].value(), self.quartiles[2].value()]
+    }
+
+    /// The process capability `(hi - lo) / 6σ`, `None` without both limits or without spread.
+    pub fn cp(&self, lo: Option<f64>, hi: Option<f64>) -> Option<f64> {
+        let sigma = self.std_dev().filter(|sigma| *sigma > 0.0)?;
+        Some((hi? - lo?) / (6.0 * sigma))
+    }
+
+    /// The process capability index: the distance of the mean to the closest limit over 3σ,
+    /// one sided if the test has a single limit.
+    pub fn cpk(&self, lo: Option<f64>, hi: Option<f64>) -> Option<f64> {
+        let sigma = self.std_dev().filter(|sigma| *sigma > 0.0)?;
+        let upper = hi.map(|hi| (hi - self.mean) / (3.0 * sigma));
+        let lower = lo.map(|lo| (self.mean - lo) / (3.0 * sigma));
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => Some(lower.min(upper)),
+            (lower, upper) => lower.or(upper),
+        }
+    }
+}
+
+/// How the statistics of each test are broken down, all off for one row per test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Breakdown {
+    pub head: bool,
+    pub site: bool,
+    pub wafer: bool,
+}
+
+/// The statistics of a test, or of a test on one head, site and/or wafer.
+///
+/// The values are in the units the results are shown in, i.e. scaled with RES_SCAL (eg: mV),
+/// so that the results of testers using different scales compare. The limits are scaled the same.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestStatistics {
+    pub test_num: u32,
+    pub test_txt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_num: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_num: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wafer_id: Option<String>,
+    pub units: String,
+    /// The effective limits of the first result of the test (in the group).
+    pub lo_limit: Option<f64>,
+    pub hi_limit: Option<f64>,
+    pub count: u64,
+    pub fails: u64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+    pub q1: Option<f64>,
+    pub median: Option<f64>,
+    pub q3: Option<f64>,
+    pub cp: Option<f64>,
+    pub cpk: Option<f64>,
+}
+
+impl TestStatistics {
+    fn new(key: GroupKey, limits: &Limits, stats: &RunningStats) -> Self {
+        let scaled = |limit: f32| shortest(limits.result(limit).scaled() as f32);
+        let lo = limits.lo_limit.map(scaled);
+        let hi = limits.hi_limit.map(scaled);
+        let [q1, median, q3] = stats.quartiles();
+        let any = stats.count > 0;
+        TestStatistics {
+            test_num: key.test_num,
+            test_txt: key.test_txt,
+            head_num: key.head_num,
+            site_num: key.site_num,
+            wafer_id: key.wafer_id,
+            units: limits.result(0.0).scaled_unit(),
+            lo_limit: lo,
+            hi_limit: hi,
+            count: stats.count,
+            fails: stats.fails,
+            min: any.then(|| shortest(stats.min as f32)),
+            max: any.then(|| shortest(stats.max as f32)),
+            mean: any.then_some(stats.mean),
+            std_dev: stats.std_dev(),
+            q1,
+            median,
+            q3,
+            cp: stats.cp(lo, hi),
+            cpk: stats.cpk(lo, hi),
+        }
+    }
+}
+
+/// The shortest decimal form of an R4, so that `0.1` is not shown as `0.100000001490116`.
+pub(crate) fn shortest(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct GroupKey {
+    test_num: u32,
+    test_txt: String,
+    head_num: Option<u8>,
+    site_num: Option<u8>,
+    wafer_id: Option<String>,
+}
+
+/// Computes the statistics of the PTR results of an STDF file, per test (test number and name).
+///
+/// A PTR may leave out its TEST_TXT after the first one of its test number, it then belongs
+/// to the test of that first one. Results flagged as invalid or not executed are left out.
+/// A result fails if its TEST_FLG says so, or, without a pass/fail indication, if it is out of
+/// its effective limits. The file is read once and the memory used does not depend on the
+/// number of results. `progress` is called after each record with the offset read up to in the file.
+///
+/// # Returns
+///
+/// The statistics, sorted by test number and name, then by head, site and wafer.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use stdf::stats::{test_statistics, Breakdown};
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let breakdown = Breakdown { site: true, ..Breakdown::default() };
+/// for test in test_statistics(&mut file, breakdown, |_| {}).unwrap() {
+///     println!("{} {} site {:?} : {:?} / {:?}", test.test_num, test.test_txt, test.site_num, test.mean, test.cpk);
+/// }
+/// ```
+pub fn test_statistics<F: FnMut(usize)>(file: &mut File, breakdown: Breakdown, mut progress: F) -> Result<Vec<TestStatistics>> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+    let mut resolver = PtrResolver::new();
+    let mut names: HashMap<u32, String> = HashMap::new();
+    let mut groups: BTreeMap<GroupKey, (Limits, RunningStats)> = BTreeMap::new();
+    let mut wafer_id = String::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        match record {
+            V4::WIR(wir) => wafer_id = wir.wafer_id.to_string().trim().to_string(),
+            V4::PTR(ptr) => {
+                let limits = resolver.resolve(&ptr);
+                let flag = TestFlag(ptr.test_flg.0);
+                if flag.result_invalid() || flag.test_not_executed() {
+                    continue;
+                }
+                let test_txt = ptr.test_txt.to_string().trim().to_string();
+                let test_txt = match names.get(&ptr.test_num.0) {
+                    Some(name) if test_txt.is_empty() => name.clone(),
+                    Some(_) => test_txt,
+                    None => names.entry(ptr.test_num.0).or_insert(test_txt).clone(),
+                };
+                let result = ptr.result.0;
+                let failed = flag.passed().or_else(|| limits.passes(result)) == Some(false);
+                let measurement = limits.result(result);
+                let key = GroupKey {
+                    test_num: ptr.test_num.0,
+                    test_txt,
+                    head_num: breakdown.head.then_some(ptr.head_num.0),
+                    site_num: breakdown.site.then_some(ptr.site_num.0),
+                    wafer_id: breakdown.wafer.then(|| wafer_id.clone()),
+                };
+                groups
+                    .entry(key)
+                    .or_insert_with(|| (limits, RunningStats::new()))
+                    .1
+                    .add(measurement.scaled(), failed);
+            }
+            _ => {}
+        }
+        progress(*offset);
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(key, (limits, stats))| TestStatistics::new(key, &limits, &stats))
+        .collect())
+}
+
+/// The column titles of the statistics, after the breakdown ones.
+const COLUMNS: [&str; 17] = [
+    "test_num", "test_txt", "units", "lo_limit", "hi_limit", "count", "fails", "min", "max", "mean", "std_dev", "q1",
+    "median", "q3", "cp", "cpk", "fail_pct",
+];
+
+/// The breakdown columns present in `stats`.
+fn breakdown_of(stats: &[TestStatistics]) -> Breakdown {
+    Breakdown {
+        head: stats.iter().any(|test| test.head_num.is_some()),
+        site: stats.iter().any(|test| test.site_num.is_some()),
+        wafer: stats.iter().any(|test| test.wafer_id.is_some()),
+    }
+}
+
+fn cells(test: &TestStatistics, breakdown: Breakdown, float: fn(f64) -> String) -> Vec<String> {
+    let option = |value: Option<f64>| value.map(float).unwrap_or_default();
+    let mut cells = Vec::new();
+    if breakdown.head {
+        cells.push(test.head_num.map(|head| head.to_string()).unwrap_or_default());
+    }
+    if breakdown.site {
+        cells.push(test.site_num.map(|site| site.to_string()).unwrap_or_default());
+    }
+    if breakdown.wafer {
+        cells.push(test.wafer_id.clone().unwrap_or_default());
+    }
+    cells.extend([
+        test.test_num.to_string(),
+        test.test_txt.clone(),
+        test.units.clone(),
+        option(test.lo_limit),
+        option(test.hi_limit),
+        test.count.to_string(),
+        test.fails.to_string(),
+        option(test.min),
+        option(test.max),
+        option(test.mean),
+        option(test.std_dev),
+        option(test.q1),
+        option(test.median),
+        option(test.q3),
+        option(test.cp),
+        option(test.cpk),
+        format!("{:.2}", 100.0 * test.fails as f64 / test.count.max(1) as f64),
+    ]);
+    cells
+}
+
+fn titles(breakdown: Breakdown) -> Vec<&'static str> {
+    let mut titles = Vec::new();
+    if breakdown.head {
+        titles.push("head_num");
+    }
+    if breakdown.site {
+        titles.push("site_num");
+    }
+    if breakdown.wafer {
+        titles.push("wafer_id");
+    }
+    titles.extend(COLUMNS);
+    titles
+}
+
+/// Formats `value` with 6 significant digits, in scientific notation if it is very small or large.
+fn significant(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+    let trimmed = |text: &str| match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => text.to_string(),
+    };
+    let exponent = value.abs().log10().floor() as i32;
+    if (-4..6).contains(&exponent) {
+        trimmed(&format!("{:.*}", (5 - exponent) as usize, value))
+    } else {
+        let text = format!("{:.5e}", value);
+        let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+        format!("{}e{}", trimmed(mantissa), exponent)
+    }
+}
+
+/// Writes the statistics as CSV, one row per test (and head, site or wafer).
+pub fn write_stats_csv<W: Write>(stats: &[TestStatistics], writer: W) -> Result<()> {
+    let breakdown = breakdown_of(stats);
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(titles(breakdown))?;
+    for test in stats {
+        csv.write_record(cells(test, breakdown, |value| value.to_string()))?;
+    }
+    csv.flush()
+}
+
+/// Writes the statistics as an aligned text table, the floats with 6 significant digits.
+pub fn write_stats_table<W: Write>(stats: &[TestStatistics], mut writer: W) -> Result<()> {
+    let breakdown = breakdown_of(stats);
+    let mut rows = vec![titles(breakdown).into_iter().map(str::to_string).collect::<Vec<_>>()];
+    rows.extend(stats.iter().map(|test| cells(test, breakdown, significant)));
+    let mut widths = vec![0; rows[0].len()];
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let text = usize::from(breakdown.head) + usize::from(breakdown.site) + usize::from(breakdown.wafer);
+    for row in rows.iter() {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                // the test name, the units and the wafer are left aligned
+                if i == text + 1 || i == text + 2 || (breakdown.wafer && i == text - 1) {
+                    format!("{:<width$}", cell, width = width)
+                } else {
+                    format!("{:>width$}", cell, width = width)
+                }
+            })
+            .collect();
+        writeln!(writer, "{}", line.join("  ").trim_end())?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantile() {
+        let mut median = Quantile::new(0.5);
+        assert_eq!(median.value(), None);
+        for value in [3.0, 1.0, 2.0, 4.0] {
+            median.add(value);
+        }
+        assert_eq!(median.value(), Some(2.5));
+
+        // a permutation of 0..1001
+        let mut quartiles = [Quantile::new(0.25), Quantile::new(0.5), Quantile::new(0.75)];
+        for i in 0..1001 {
+            for quartile in quartiles.iter_mut() {
+                quartile.add(((i * 7919) % 1001) as f64);
+            }
+        }
+        for (quartile, expected) in quartiles.iter().zip([250.0, 500.0, 750.0]) {
+            assert!((quartile.value().unwrap() - expected).abs() < 10.0, "{:?}", quartile.value());
+        }
+    }
+
+    #[test]
+    fn test_running_stats() {
+        let mut stats = RunningStats::new();
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            stats.add(value, value > 8.0);
+        }
+        assert_eq!((stats.count, stats.fails, stats.min, stats.max, stats.mean), (8, 1, 2.0, 9.0, 5.0));
+        let sigma = (32.0f64 / 7.0).sqrt();
+        assert!((stats.std_dev().unwrap() - sigma).abs() < 1e-12);
+        assert!((stats.cp(Some(-1.0), Some(11.0)).unwrap() - 12.0 / (6.0 * sigma)).abs() < 1e-12);
+        assert!((stats.cpk(Some(-1.0), Some(8.0)).unwrap() - 3.0 / (3.0 * sigma)).abs() < 1e-12);
+        assert!((stats.cpk(Some(-1.0), None).unwrap() - 6.0 / (3.0 * sigma)).abs() < 1e-12);
+        assert_eq!(stats.cp(None, Some(8.0)), None);
+        assert_eq!(stats.cpk(None, None), None);
+        assert_eq!(RunningStats::new().std_dev(), None);
+
+        stats.add(f64::NAN, true);
+        stats.add(f64::INFINITY, false);
+        assert_eq!((stats.count, stats.fails, stats.max, stats.mean), (8, 1, 9.0, 5.0));
+    }
+
+    #[test]
+    fn test_significant() {
+        assert_eq!(significant(0.0), "0");
+        assert_eq!(significant(1.5), "1.5");
+        assert_eq!(significant(-0.00123456789), "-0.00123457");
+        assert_eq!(significant(123456.0), "123456");
+        assert_eq!(significant(1234567.0), "1.23457e6");
+        assert_eq!(significant(3.2e-7), "3.2e-7");
+    }
+
+    #[test]
+    fn test_test_statistics() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let stats = test_statistics(&mut file, Breakdown::default(), |_| {}).unwrap();
+        assert_eq!(stats.len(), 26);
+        assert_eq!(stats.iter().map(|test| test.count).sum::<u64>(), 572);
+        assert!(stats.windows(2).all(|pair| pair[0].test_num <= pair[1].test_num));
+        // scaled with RES_SCAL, as the results are shown
+        let contact = stats.iter().find(|test| test.test_num == 10).unwrap();
+        assert_eq!((contact.units.as_str(), contact.lo_limit, contact.hi_limit), ("mV", Some(-800.0), Some(-100.0)));
+        assert!(contact.mean.unwrap() < -500.0);
+        for test in stats.iter().filter(|test| test.count > 0) {
+            let (min, max) = (test.min.unwrap(), test.max.unwrap());
+            assert!(min <= test.mean.unwrap() && test.mean.unwrap() <= max);
+            assert!(min <= test.median.unwrap() && test.median.unwrap() <= max);
+            assert!(test.q1.unwrap() <= test.q3.unwrap());
+            assert!(test.site_num.is_none());
+        }
+
+        let by_site = test_statistics(&mut file, Breakdown { site: true, ..Breakdown::default() }, |_| {}).unwrap();
+        assert!(by_site.len() > stats.len());
+        assert!(by_site.iter().all(|test| test.site_num.is_some()));
+        assert_eq!(by_site.iter().map(|test| test.count).sum::<u64>(), 572);
+
+        let mut csv = Vec::new();
+        write_stats_csv(&by_site, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("site_num,test_num,test_txt,units,"));
+        assert_eq!(csv.lines().count(), by_site.len() + 1);
+
+        let mut table = Vec::new();
+        write_stats_table(&stats, &mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table.lines().next().unwrap().trim_start().starts_with("test_num"));
+        assert_eq!(table.lines().count(), stats.len() + 1);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use byte::ctx::Endian;
+use byte::BytesExt;
+use memmap::MmapOptions;
+
+use crate::get_endian_from_file;
+use crate::records::{name_to_typ_sub, Header, V4};
+
+/// The records that make up the skeleton of an STDF file, these are never stripped.
+pub const MANDATORY_RECORDS: [(u8, u8); 3] = [(0, 10), (1, 10), (1, 20)]; // FAR, MIR, MRR
+
+/// Selects the records to remove from an STDF file.
+#[derive(Debug, Clone, Default)]
+pub struct StripConfig {
+    /// The (rec_typ, rec_sub) of the records to remove.
+    pub records: HashSet<(u8, u8)>,
+    /// The test numbers of which all PTR, MPR, FTR and TSR records are removed.
+    pub test_nums: HashSet<u32>,
+}
+
+impl StripConfig {
+    /// Builds a configuration from record names (eg: "DTR") and test numbers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for an unknown record name or for one of the [`MANDATORY_RECORDS`].
+    pub fn new(record_names: &[String], test_nums: &[u32]) -> Result<Self> {
+        let mut records = HashSet::new();
+        for name in record_names {
+            let typ_sub = name_to_typ_sub(&name.to_uppercase());
+            if typ_sub == (0, 0) {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("Unknown record '{}'", name)));
+            }
+            if MANDATORY_RECORDS.contains(&typ_sub) {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("Record '{}' can not be stripped", name)));
+            }
+            records.insert(typ_sub);
+        }
+        Ok(StripConfig {
+            records,
+            test_nums: test_nums.iter().copied().collect(),
+        })
+    }
+
+    /// Tells if the record (header included) in `bytes` is to be removed.
+    pub fn strips(&self, bytes: &[u8], endian: Endian) -> bool {
+        let header = match bytes.read_with::<Header>(&mut 0, endian) {
+            Ok(header) => header,
+            Err(_) => return false,
+        };
+        let typ_sub = (header.rec_typ.0, header.rec_sub.0);
+        if self.records.contains(&typ_sub) {
+            return true;
+        }
+        if self.test_nums.is_empty() || !matches!(typ_sub, (10, 30) | (15, 10) | (15, 15) | (15, 20)) {
+            return false;
+        }
+        let test_num = match bytes.read_with::<V4>(&mut 0, endian) {
+            Ok(V4::TSR(tsr)) => tsr.test_num.0,
+            Ok(V4::PTR(ptr)) => ptr.test_num.0,
+            Ok(V4::MPR(mpr)) => mpr.test_num.0,
+            Ok(V4::FTR(ftr)) => ftr.test_num.0,
+            _ => return false,
+        };
+        self.test_nums.contains(&test_num)
+    }
+}
+
+/// Writes a copy of an STDF file without the records selected by `config`.
+///
+/// The records that are kept are copied byte-exact, as are the bytes of a truncated last record.
+///
+/// # Returns
+///
+/// The number of records that were removed.
+///
+/// # Errors
+///
+/// This function will return an error if the input is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::Result;
+/// use stdf::strip::{strip_file, StripConfig};
+///
+/// fn main() -> Result<()> {
+///     let mut input = File::open("tests/fixtures/test.std")?;
+///     let mut output = File::create("test_stripped.std")?;
+///     let config = StripConfig::new(&["DTR".to_string(), "GDR".to_string()], &[])?;
+///     let removed = strip_file(&mut input, &mut output, &config)?;
+///     println!("{} records removed", removed);
+///     Ok(())
+/// }
+/// ```
+pub fn strip_file<W: Write>(input: &mut File, output: &mut W, config: &StripConfig) -> Result<u32> {
+    let endian = match get_endian_from_file(input)? {
+        Some(endian) => endian,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    };
+    let mmap = unsafe { MmapOptions::new().map(input)? };
+    let bytes = &mmap[..];
+    let mut offset: usize = 0;
+    let mut removed: u32 = 0;
+    while bytes.len() - offset >= 4 {
+        let mut cursor = offset;
+        let header = match bytes.read_with::<Header>(&mut cursor, endian) {
+            Ok(header) => header,
+            Err(_) => break,
+        };
+        let end = offset + 4 + header.rec_len.0 as usize;
+        if end > bytes.len() {
+            // a truncated record, copied with the rest of the file below
+            break;
+        }
+        let record = &bytes[offset..end];
+        if config.strips(record, endian) {
+            removed += 1;
+        } else {
+            output.write_all(record)?;
+        }
+        offset = end;
+    }
+    output.write_all(&bytes[offset..])?;
+    output.flush()?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_index_from_stdf_file;
+    use std::io::{Seek, SeekFrom};
+    use tempfile::tempfile;
+
+    #[test]
+    fn test_strip_config_new() {
+        let config = StripConfig::new(&["dtr".to_string(), "GDR".to_string()], &[100]).unwrap();
+        assert!(config.records.contains(&(50, 30)));
+        assert!(config.records.contains(&(50, 10)));
+        assert!(config.test_nums.contains(&100));
+        assert!(StripConfig::new(&["XYZ".to_string()], &[]).is_err());
+        assert!(StripConfig::new(&["MIR".to_string()], &[]).is_err());
+    }
+
+    #[test]
+    fn test_strip_file() {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let input_index = get_index_from_stdf_file(&mut input).unwrap();
+        let mut output = tempfile().unwrap();
+        let config = StripConfig::new(&["DTR".to_string(), "PMR".to_string()], &[]).unwrap();
+        let removed = strip_file(&mut input, &mut output, &config).unwrap();
+        assert_eq!(removed, 33 + 120);
+
+        output.seek(SeekFrom::Start(0)).unwrap();
+        let output_index = get_index_from_stdf_file(&mut output).unwrap();
+        assert!(!output_index.contains_key(&(50, 30)));
+        assert!(!output_index.contains_key(&(1, 60)));
+        assert_eq!(output_index[&(15, 10)].len(), input_index[&(15, 10)].len());
+        assert_eq!(output_index[&(1, 20)].len(), 1);
+    }
+
+    #[test]
+    fn test_strip_file_truncated() {
+        let mut bytes = std::fs::read("tests/fixtures/test.std").unwrap();
+        // the header and the first bytes of a PTR that is cut short
+        bytes.extend_from_slice(&[0x20, 0x00, 15, 10, 1, 2, 3]);
+        let mut input = tempfile().unwrap();
+        input.write_all(&bytes).unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let config = StripConfig::new(&[], &[]).unwrap();
+        assert_eq!(strip_file(&mut input, &mut output, &config).unwrap(), 0);
+        assert_eq!(output, bytes);
+    }
+
+    #[test]
+    fn test_strip_file_by_test_num() {
+        let mut input = File::open("tests/fixtures/test.std").unwrap();
+        let endian = get_endian_from_file(&mut input).unwrap().unwrap();
+        let mmap = unsafe { MmapOptions::new().map(&input).unwrap() };
+        let bytes = &mmap[..];
+        let offset = &mut 0;
+        let mut test_num = None;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            if let V4::PTR(ptr) = record {
+                test_num = Some(ptr.test_num.0);
+                break;
+            }
+        }
+        let test_num = test_num.unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        let config = StripConfig::new(&[], &[test_num]).unwrap();
+        let removed = strip_file(&mut input, &mut output, &config).unwrap();
+        assert!(removed > 0);
+        let offset = &mut 0;
+        let mut record_count = 0;
+        while let Ok(record) = output.read_with::<V4>(offset, endian) {
+            record_count += 1;
+            match record {
+                V4::PTR(ptr) => assert_ne!(ptr.test_num.0, test_num),
+                V4::TSR(tsr) => assert_ne!(tsr.test_num.0, test_num),
+                _ => {}
+            }
+        }
+        assert_eq!(record_count + removed, 918);
+    }
+}
@@ -0,0 +1,713 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use byte::ctx::Endian;
+use byte::BytesExt;
+use memmap::{Mmap, MmapOptions};
+use serde::Serialize;
+
+use crate::flags::TestFlag;
+use crate::get_endian_from_file;
+use crate::inheritance::{Limits, MprResolver, PtrResolver};
+use crate::parts::{test_passed, PartAssembler};
+use crate::pins::PinMap;
+use crate::records::V4;
+use crate::units::Measurement;
+
+/// A column of the part × test table: a PTR or FTR test, or one pin of an MPR test.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestColumn {
+    pub rec: String,
+    pub test_num: u32,
+    pub test_txt: String,
+    /// The pin name of an MPR column, `None` for PTR and FTR columns.
+    pub pin: Option<String>,
+    /// The limits of the first record of the test.
+    pub limits: Limits,
+}
+
+impl TestColumn {
+    /// The test name, with the pin of an MPR column, eg: `leakage [D0]`.
+    pub fn name(&self) -> String {
+        match &self.pin {
+            Some(pin) => format!("{} [{}]", self.test_txt, pin),
+            None => self.test_txt.clone(),
+        }
+    }
+
+    /// The unit the results of the column are shown in (with the RES_SCAL prefix).
+    pub fn units(&self) -> String {
+        Measurement::new(0.0, &self.limits.units, self.limits.res_scal).scaled_unit()
+    }
+
+    /// `value` (in base units) as shown in the units of the column.
+    pub fn scaled(&self, value: f32) -> f32 {
+        scaled(value, &self.limits)
+    }
+
+    /// The low limit, shown in the units of the column.
+    pub fn lo(&self) -> Option<f32> {
+        self.limits.lo_limit.map(|lo| self.scaled(lo))
+    }
+
+    /// The high limit, shown in the units of the column.
+    pub fn hi(&self) -> Option<f32> {
+        self.limits.hi_limit.map(|hi| self.scaled(hi))
+    }
+
+    /// `value` (in base units) as text in the units of the column, with its C_RESFMT.
+    pub fn format(&self, value: f32) -> String {
+        self.limits.result(value).value_with(&self.limits.c_resfmt)
+    }
+}
+
+/// The limits as text in the units of the results, with their C_LLMFMT and C_HLMFMT.
+fn format_limits(limits: &Limits) -> [String; 2] {
+    [(limits.lo_limit, &limits.c_llmfmt), (limits.hi_limit, &limits.c_hlmfmt)]
+        .map(|(limit, format)| text(limit.map(|limit| limits.result(limit).value_with(format))))
+}
+
+/// A PTR/MPR result, `None` if it is flagged invalid or not executed, or not a number.
+pub(crate) fn valid_result(result: Option<f32>, test_flg: u8) -> Option<f32> {
+    let flag = TestFlag(test_flg);
+    if flag.result_invalid() || flag.test_not_executed() {
+        return None;
+    }
+    result.filter(|result| result.is_finite())
+}
+
+/// `value` (in base units) as shown with the units and RES_SCAL of `limits`.
+fn scaled(value: f32, limits: &Limits) -> f32 {
+    limits.result(value).scaled() as f32
+}
+
+/// One result of a part: a cell of the part × test table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestValue {
+    /// The index of the column in the [`TestCatalog`].
+    pub column: usize,
+    /// The result in base units, `None` for an FTR, an MPR pin without result, or a
+    /// result that is flagged invalid or not executed, or that is not a number.
+    pub result: Option<f32>,
+    /// `None` when the test flags carry no (valid) pass/fail indication.
+    pub passed: Option<bool>,
+    /// The limits of the record, with the ones of the first record of the test filled in.
+    pub limits: Limits,
+}
+
+impl TestValue {
+    /// The result as shown, with the units and RES_SCAL of the record.
+    pub fn scaled(&self) -> Option<f32> {
+        self.result.map(|result| scaled(result, &self.limits))
+    }
+
+    /// The result as text, with the units and RES_SCAL of the record and its C_RESFMT.
+    pub fn format(&self) -> Option<String> {
+        self.result.map(|result| self.limits.result(result).value_with(&self.limits.c_resfmt))
+    }
+}
+
+/// A row of the part × test table: one part with its results.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartRow {
+    pub lot_id: String,
+    /// The wafer of the last WIR, empty for final test.
+    pub wafer_id: String,
+    pub x: Option<i16>,
+    pub y: Option<i16>,
+    pub site_num: u8,
+    pub head_num: u8,
+    pub part_id: String,
+    pub hard_bin: u16,
+    pub soft_bin: Option<u16>,
+    /// The test time in milliseconds, `None` if not known.
+    pub test_t: Option<u32>,
+    pub passed: Option<bool>,
+    /// The results, in the order they were tested.
+    pub values: Vec<TestValue>,
+}
+
+/// The columns of the part × test table, in the order the tests first appear in the file.
+#[derive(Debug, Default)]
+pub struct TestCatalog {
+    pub columns: Vec<TestColumn>,
+    /// (record name, test number, position of the pin in an MPR) to column index.
+    index: HashMap<(&'static str, u32, Option<usize>), usize>,
+}
+
+impl TestCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the columns of all the tests of an STDF file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file is not an STDF file or on any I/O error.
+    pub fn from_file(file: &mut File) -> Result<TestCatalog> {
+        let (mmap, endian) = map(file)?;
+        let bytes = &mmap[..];
+        let mut rows = PartRows::new();
+        let offset = &mut 0;
+        while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+            rows.push(record);
+        }
+        Ok(rows.catalog)
+    }
+
+    /// The index of the column of a test, `pin` is the position of the pin in an MPR.
+    pub fn column(&self, rec: &str, test_num: u32, pin: Option<usize>) -> Option<usize> {
+        let rec = match rec {
+            "PTR" => "PTR",
+            "MPR" => "MPR",
+            "FTR" => "FTR",
+            _ => return None,
+        };
+        self.index.get(&(rec, test_num, pin)).copied()
+    }
+
+    fn add(&mut self, rec: &'static str, test_num: u32, pin: Option<usize>, column: impl FnOnce() -> TestColumn) -> usize {
+        let columns = &mut self.columns;
+        *self.index.entry((rec, test_num, pin)).or_insert_with(|| {
+            columns.push(column());
+            columns.len() - 1
+        })
+    }
+}
+
+/// Turns the records of an STDF file into part rows, one per PRR.
+///
+/// The tests are resolved in file order (see [`PtrResolver`] and [`MprResolver`]),
+/// and only the results of the parts being tested are kept, so memory does not grow
+/// with the size of the file. The columns are added to `catalog` as they are found.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use byte::BytesExt;
+/// use memmap::MmapOptions;
+/// use stdf::get_endian_from_file;
+/// use stdf::records::V4;
+/// use stdf::table::PartRows;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let endian = get_endian_from_file(&mut file).unwrap().unwrap();
+/// let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+/// let bytes = &mmap[..];
+/// let mut rows = PartRows::new();
+/// let offset = &mut 0;
+/// while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+///     if let Some(row) = rows.push(record) {
+///         println!("{} : {} results", row.part_id, row.values.len());
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct PartRows<'a> {
+    pub catalog: TestCatalog,
+    assembler: PartAssembler<'a>,
+    ptr_resolver: PtrResolver,
+    mpr_resolver: MprResolver,
+    pin_map: PinMap,
+    lot_id: String,
+    wafer_id: String,
+    open: HashMap<(u8, u8), Vec<TestValue>>,
+}
+
+impl<'a> PartRows<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the next record, returns the row of the part that the record completes (if it is a PRR).
+    pub fn push(&mut self, record: V4<'a>) -> Option<PartRow> {
+        match &record {
+            V4::MIR(mir) => self.lot_id = mir.lot_id.to_string().trim().to_string(),
+            V4::WIR(wir) => self.wafer_id = wir.wafer_id.to_string().trim().to_string(),
+            V4::PMR(pmr) => self.pin_map.add_pmr(pmr),
+            V4::PGR(pgr) => self.pin_map.add_pgr(pgr),
+            V4::PLR(plr) => self.pin_map.add_plr(plr),
+            V4::PIR(pir) => {
+                self.open.insert((pir.head_num.0, pir.site_num.0), Vec::new());
+            }
+            V4::PTR(ptr) => {
+                let limits = self.ptr_resolver.resolve(ptr);
+                let test_txt = ptr.test_txt.to_string();
+                let column = self.catalog.add("PTR", ptr.test_num.0, None, || TestColumn {
+                    rec: "PTR".to_string(),
+                    test_num: ptr.test_num.0,
+                    test_txt,
+                    pin: None,
+                    limits: limits.clone(),
+                });
+                if let Some(values) = self.open.get_mut(&(ptr.head_num.0, ptr.site_num.0)) {
+                    values.push(TestValue {
+                        column,
+                        result: valid_result(Some(ptr.result.0), ptr.test_flg.0),
+                        passed: test_passed(ptr.test_flg.0),
+                        limits,
+                    });
+                }
+            }
+            V4::MPR(mpr) => {
+                let resolved = self.mpr_resolver.resolve(mpr, &self.pin_map);
+                let passed = test_passed(mpr.test_flg.0);
+                let test_txt = mpr.test_txt.to_string();
+                let mut values = Vec::with_capacity(resolved.pins.len());
+                for (i, pin) in resolved.pins.iter().enumerate() {
+                    let column = self.catalog.add("MPR", mpr.test_num.0, Some(i), || TestColumn {
+                        rec: "MPR".to_string(),
+                        test_num: mpr.test_num.0,
+                        test_txt: test_txt.clone(),
+                        pin: Some(pin.pin_name.clone()),
+                        limits: resolved.limits.clone(),
+                    });
+                    values.push(TestValue {
+                        column,
+                        result: valid_result(pin.result, mpr.test_flg.0),
+                        passed,
+                        limits: resolved.limits.clone(),
+                    });
+                }
+                if let Some(open) = self.open.get_mut(&(mpr.head_num.0, mpr.site_num.0)) {
+                    open.extend(values);
+                }
+            }
+            V4::FTR(ftr) => {
+                let test_txt = ftr.test_txt.to_string();
+                let column = self.catalog.add("FTR", ftr.test_num.0, None, || TestColumn {
+                    rec: "FTR".to_string(),
+                    test_num: ftr.test_num.0,
+                    test_txt,
+                    pin: None,
+                    limits: Limits::default(),
+                });
+                if let Some(values) = self.open.get_mut(&(ftr.head_num.0, ftr.site_num.0)) {
+                    values.push(TestValue {
+                        column,
+                        result: None,
+                        passed: test_passed(ftr.test_flg.0),
+                        limits: Limits::default(),
+                    });
+                }
+            }
+            _ => {}
+        }
+        // the assembler only needs the PIR/PRR, the results are kept resolved in `open`
+        if !matches!(record, V4::PIR(_) | V4::PRR(_)) {
+            return None;
+        }
+        let part = self.assembler.push(record)?;
+        let values = self.open.remove(&(part.head_num(), part.site_num())).unwrap_or_default();
+        let (x, y) = match part.xy() {
+            Some((x, y)) => (Some(x), Some(y)),
+            None => (None, None),
+        };
+        Some(PartRow {
+            lot_id: self.lot_id.clone(),
+            wafer_id: self.wafer_id.clone(),
+            x,
+            y,
+            site_num: part.site_num(),
+            head_num: part.head_num(),
+            part_id: part.part_id(),
+            hard_bin: part.prr.hard_bin.0,
+            soft_bin: Some(part.prr.soft_bin.0).filter(|bin| *bin != 0xffff),
+            test_t: Some(part.prr.test_t.0).filter(|t| *t != 0),
+            passed: part.passed(),
+            values,
+        })
+    }
+}
+
+/// Maps an STDF file, with its endianness.
+pub(crate) fn map(file: &mut File) -> Result<(Mmap, Endian)> {
+    let endian = match get_endian_from_file(file)? {
+        Some(endian) => endian,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Endianess not detected")),
+    };
+    let mmap = unsafe { MmapOptions::new().map(&*file)? };
+    Ok((mmap, endian))
+}
+
+/// The layout of the CSV written by [`write_csv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvLayout {
+    /// One row per part and one column per test, after header rows with the test
+    /// numbers, names, units and limits.
+    #[default]
+    Wide,
+    /// One row per result, with the part and the test in columns.
+    Long,
+}
+
+const PART_COLUMNS: [&str; 10] = [
+    "lot_id", "wafer_id", "x_coord", "y_coord", "site_num", "head_num", "part_id", "hard_bin", "soft_bin", "test_t",
+];
+
+fn text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn pass_fail(passed: Option<bool>) -> &'static str {
+    match passed {
+        Some(true) => "P",
+        Some(false) => "F",
+        None => "",
+    }
+}
+
+fn part_fields(row: &PartRow) -> [String; 10] {
+    [
+        row.lot_id.clone(),
+        row.wafer_id.clone(),
+        text(row.x),
+        text(row.y),
+        row.site_num.to_string(),
+        row.head_num.to_string(),
+        row.part_id.clone(),
+        row.hard_bin.to_string(),
+        text(row.soft_bin),
+        text(row.test_t),
+    ]
+}
+
+/// Writes the parts of an STDF file as CSV.
+///
+/// Results and limits are shown with the units and RES_SCAL of the test (eg: `mV`) and
+/// their C_RESFMT, C_LLMFMT and C_HLMFMT formats, as the records show them; an FTR shows
+/// `P` or `F`. Results flagged invalid or not executed are left empty. In the [`CsvLayout::Wide`] layout, the first rows are the test
+/// numbers (after the names of the part columns), then the test names, units, low and
+/// high limits, labeled in the first column. The file is read twice (to collect the
+/// columns first) but the parts are written as they are read, so memory stays flat.
+///
+/// `progress` is called after each part with the offset read up to in the file.
+///
+/// # Returns
+///
+/// The number of parts written.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::stdout;
+/// use stdf::table::{write_csv, CsvLayout};
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let parts = write_csv(&mut file, stdout(), CsvLayout::Wide, |_| {}).unwrap();
+/// eprintln!("{} parts", parts);
+/// ```
+pub fn write_csv<W: Write, F: FnMut(usize)>(file: &mut File, writer: W, layout: CsvLayout, mut progress: F) -> Result<usize> {
+    let mut csv = csv::Writer::from_writer(writer);
+    let mut parts = 0;
+    match layout {
+        CsvLayout::Wide => {
+            let catalog = TestCatalog::from_file(file)?;
+            let columns = &catalog.columns;
+            let header = |label: &str, cell: &dyn Fn(&TestColumn) -> String| {
+                let mut record = vec![String::new(); PART_COLUMNS.len()];
+                record[0] = label.to_string();
+                record.extend(columns.iter().map(cell));
+                record
+            };
+            let mut numbers: Vec<String> = PART_COLUMNS.iter().map(|c| c.to_string()).collect();
+            numbers.extend(columns.iter().map(|c| c.test_num.to_string()));
+            csv.write_record(&numbers)?;
+            csv.write_record(header("Test Name", &|c| c.name()))?;
+            csv.write_record(header("Units", &|c| c.units()))?;
+            csv.write_record(header("Low Limit", &|c| format_limits(&c.limits)[0].clone()))?;
+            csv.write_record(header("High Limit", &|c| format_limits(&c.limits)[1].clone()))?;
+            let (mmap, endian) = map(file)?;
+            let bytes = &mmap[..];
+            let mut rows = PartRows::new();
+            let mut cells = vec![String::new(); columns.len()];
+            let offset = &mut 0;
+            while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+                let row = match rows.push(record) {
+                    Some(row) => row,
+                    None => continue,
+                };
+                cells.iter_mut().for_each(|cell| cell.clear());
+                for value in row.values.iter() {
+                    let column = &columns[value.column];
+                    cells[value.column] = match column.rec.as_str() {
+                        "FTR" => pass_fail(value.passed).to_string(),
+                        _ => text(value.result.map(|result| column.format(result))),
+                    };
+                }
+                csv.write_record(part_fields(&row).iter().chain(cells.iter()))?;
+                parts += 1;
+                progress(*offset);
+            }
+        }
+        CsvLayout::Long => {
+            let mut header: Vec<&str> = PART_COLUMNS.to_vec();
+            header.extend(["rec", "test_num", "test_txt", "pin", "result", "units", "lo_limit", "hi_limit", "passed"]);
+            csv.write_record(&header)?;
+            let (mmap, endian) = map(file)?;
+            let bytes = &mmap[..];
+            let mut rows = PartRows::new();
+            let offset = &mut 0;
+            while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+                let row = match rows.push(record) {
+                    Some(row) => row,
+                    None => continue,
+                };
+                let part = part_fields(&row);
+                for value in row.values.iter() {
+                    let column = &rows.catalog.columns[value.column];
+                    let units = match column.rec.as_str() {
+                        "FTR" => String::new(),
+                        _ => Measurement::new(0.0, &value.limits.units, value.limits.res_scal).scaled_unit(),
+                    };
+                    let [lo_limit, hi_limit] = format_limits(&value.limits);
+                    csv.write_record(part.iter().cloned().chain([
+                        column.rec.clone(),
+                        column.test_num.to_string(),
+                        column.test_txt.clone(),
+                        column.pin.clone().unwrap_or_default(),
+                        text(value.format()),
+                        units,
+                        lo_limit,
+                        hi_limit,
+                        pass_fail(value.passed).to_string(),
+                    ]))?;
+                }
+                parts += 1;
+                progress(*offset);
+            }
+        }
+    }
+    csv.flush()?;
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::{FTR, MPR, PIR, PMR, PRR, PTR};
+    use crate::types::*;
+
+    #[test]
+    fn test_catalog() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let catalog = TestCatalog::from_file(&mut file).unwrap();
+        assert!(!catalog.columns.is_empty());
+        assert!(catalog.columns.iter().all(|c| c.rec == "PTR" && c.pin.is_none()));
+        for (i, column) in catalog.columns.iter().enumerate() {
+            assert_eq!(catalog.column("PTR", column.test_num, None), Some(i));
+        }
+        assert_eq!(catalog.column("FTR", catalog.columns[0].test_num, None), None);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let columns = TestCatalog::from_file(&mut file).unwrap().columns.len();
+
+        let mut wide = Vec::new();
+        let parts = write_csv(&mut file, &mut wide, CsvLayout::Wide, |_| {}).unwrap();
+        assert_eq!(parts, 22);
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(&wide[..]);
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 5 + 22);
+        assert!(records.iter().all(|r| r.len() == PART_COLUMNS.len() + columns));
+        assert_eq!(&records[0][4], "site_num");
+        assert_eq!(&records[1][0], "Test Name");
+        assert_eq!(&records[4][0], "High Limit");
+        assert_eq!(&records[5][6], "1");
+
+        let mut long = Vec::new();
+        let parts = write_csv(&mut file, &mut long, CsvLayout::Long, |_| {}).unwrap();
+        assert_eq!(parts, 22);
+        let mut reader = csv::Reader::from_reader(&long[..]);
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        // one row per PTR of the parts
+        assert_eq!(records.len(), 572);
+        assert!(records.iter().all(|r| &r[10] == "PTR" && !r[14].is_empty()));
+        // the long and wide layouts show the same results
+        let wide = csv::ReaderBuilder::new().has_headers(false).from_reader(&wide[..]);
+        let wide_cells: usize = wide
+            .into_records()
+            .skip(5)
+            .map(|r| r.unwrap().iter().skip(PART_COLUMNS.len()).filter(|c| !c.is_empty()).count())
+            .sum();
+        assert_eq!(wide_cells, 572);
+    }
+
+    fn mpr(rtn_indx: &[u16], results: &[f32]) -> V4<'static> {
+        V4::MPR(MPR {
+            test_num: U4(500),
+            head_num: U1(1),
+            site_num: U1(2),
+            test_flg: B1(0),
+            parm_flg: B1(0),
+            rtn_icnt: U2(rtn_indx.len() as u16),
+            rslt_cnt: U2(results.len() as u16),
+            rtn_stat: rtn_indx.iter().map(|_| N1(1)).collect(),
+            rtn_rslt: results.iter().map(|r| R4(*r)).collect(),
+            test_txt: Cn(b"leakage"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(if rtn_indx.is_empty() { 0b0011_0011 } else { 0b0000_0010 }),
+            res_scal: I1(6),
+            llm_scal: I1(6),
+            hlm_scal: I1(6),
+            lo_limit: R4(-1e-6),
+            hi_limit: R4(1e-6),
+            start_in: R4(f32::NAN),
+            incr_in: R4(f32::NAN),
+            rtn_indx: rtn_indx.iter().map(|i| U2(*i)).collect(),
+            units: Cn(b"A"),
+            units_in: Cn(b""),
+            c_resfmt: Cn(b""),
+            c_llmfmt: Cn(b""),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        })
+    }
+
+    fn ptr(test_flg: u8, result: f32) -> V4<'static> {
+        V4::PTR(PTR {
+            test_num: U4(400),
+            head_num: U1(1),
+            site_num: U1(2),
+            test_flg: B1(test_flg),
+            parm_flg: B1(0),
+            result: R4(result),
+            test_txt: Cn(b"vdd"),
+            alarm_id: Cn(b""),
+            opt_flag: B1(0),
+            res_scal: I1(3),
+            llm_scal: I1(3),
+            hlm_scal: I1(3),
+            lo_limit: R4(0.001),
+            hi_limit: R4(0.002),
+            units: Cn(b"V"),
+            c_resfmt: Cn(b"%.3f"),
+            c_llmfmt: Cn(b"%.1f"),
+            c_hlmfmt: Cn(b""),
+            lo_spec: R4(f32::NAN),
+            hi_spec: R4(f32::NAN),
+        })
+    }
+
+    fn ftr(test_flg: u8) -> V4<'static> {
+        V4::FTR(FTR {
+            test_num: U4(600),
+            head_num: U1(1),
+            site_num: U1(2),
+            test_flg: B1(test_flg),
+            opt_flag: B1(0xff),
+            cycl_cnt: U4(0),
+            rel_vadr: U4(0),
+            rept_cnt: U4(0),
+            num_fail: U4(0),
+            xfail_ad: I4(0),
+            yfail_ad: I4(0),
+            vect_off: I2(0),
+            rtn_icnt: U2(0),
+            pgm_icnt: U2(0),
+            rtn_indx: vec![],
+            rtn_stat: vec![],
+            pgm_indx: vec![],
+            pgm_stat: vec![],
+            fail_pin: Dn(0, b""),
+            vect_nam: Cn(b""),
+            time_set: Cn(b""),
+            op_code: Cn(b""),
+            test_txt: Cn(b"scan"),
+            alarm_id: Cn(b""),
+            prog_txt: Cn(b""),
+            rslt_txt: Cn(b""),
+            patg_num: U1(255),
+            spin_map: Dn(0, b""),
+        })
+    }
+
+    fn part(part_id: &'static [u8], tests: Vec<V4<'static>>) -> Vec<V4<'static>> {
+        let mut records = vec![V4::PIR(PIR { head_num: U1(1), site_num: U1(2) })];
+        records.extend(tests);
+        records.push(V4::PRR(PRR {
+            head_num: U1(1),
+            site_num: U1(2),
+            part_flg: B1(0),
+            num_test: U2(2),
+            hard_bin: U2(1),
+            soft_bin: U2(0xffff),
+            x_coord: I2(3),
+            y_coord: I2(-4),
+            test_t: U4(0),
+            part_id: Cn(part_id),
+            part_txt: Cn(b""),
+            part_fix: Bn(b""),
+        }));
+        records
+    }
+
+    #[test]
+    fn test_part_rows() {
+        let mut records = Vec::new();
+        for (index, name) in [(1, b"D0"), (2, b"D1")] {
+            records.push(V4::PMR(PMR {
+                pmr_index: U2(index),
+                chan_typ: U2(0),
+                chan_nam: Cn(b""),
+                phy_nam: Cn(b""),
+                log_nam: Cn(name),
+                head_num: U1(1),
+                site_num: U1(2),
+            }));
+        }
+        records.extend(part(b"1", vec![mpr(&[1, 2], &[2e-7, 3e-7]), ftr(0)]));
+        // the second MPR leaves out the pins and limits of the first
+        records.extend(part(b"2", vec![mpr(&[], &[4e-7, 5e-7]), ftr(0b1000_0000)]));
+
+        let mut part_rows = PartRows::new();
+        let rows: Vec<PartRow> = records.into_iter().filter_map(|r| part_rows.push(r)).collect();
+        let columns = &part_rows.catalog.columns;
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns[0].name(), "leakage [D0]");
+        assert_eq!(columns[1].name(), "leakage [D1]");
+        assert_eq!(columns[0].units(), "uA");
+        assert_eq!((columns[0].lo(), columns[0].hi()), (Some(-1.0), Some(1.0)));
+        assert_eq!(columns[2].rec, "FTR");
+        assert_eq!(part_rows.catalog.column("MPR", 500, Some(1)), Some(1));
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].x, rows[0].y, rows[0].soft_bin, rows[0].test_t), (Some(3), Some(-4), None, None));
+        let second = &rows[1];
+        assert_eq!(second.part_id, "2");
+        assert_eq!(second.values.iter().map(|v| v.column).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(second.values[1].scaled(), Some(0.5));
+        assert_eq!(second.values[1].limits.lo_limit, Some(-1e-6));
+        assert_eq!(second.values[2].passed, Some(false));
+    }
+
+    #[test]
+    fn test_invalid_results() {
+        let mut records = Vec::new();
+        // valid, flagged invalid (bit 1), not executed (bit 4) and not a number
+        for (part_id, test_flg, result) in [(b"1", 0, 0.0015), (b"2", 0b0000_0010, 0.0015), (b"3", 0b0001_0000, 0.0015), (b"4", 0, f32::NAN)] {
+            records.extend(part(part_id, vec![ptr(test_flg, result)]));
+        }
+        let mut part_rows = PartRows::new();
+        let rows: Vec<PartRow> = records.into_iter().filter_map(|r| part_rows.push(r)).collect();
+        let results: Vec<Option<f32>> = rows.iter().map(|row| row.values[0].result).collect();
+        assert_eq!(results, vec![Some(0.0015), None, None, None]);
+        assert_eq!(rows[0].values[0].format(), Some("1.500".to_string()));
+        assert_eq!(rows[1].values[0].format(), None);
+
+        let column = &part_rows.catalog.columns[0];
+        assert_eq!(column.format(0.0015), "1.500");
+        assert_eq!(format_limits(&column.limits), ["1.0".to_string(), "2".to_string()]);
+    }
+}
@@ -1,9 +1,12 @@
 use std::fs::File;
 use std::io::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use byte::BytesExt;
 
 use crate::get_index_from_stdf_file;
-use crate::records::typ_sub_to_name;
+use crate::records::{typ_sub_to_name, V4};
+use crate::table::map;
 use std::io::{Error, ErrorKind};
 
 /// Counts the records in an STDF (Standard Test Data Format) file and optionally prints detailed information.
@@ -36,7 +39,7 @@ use std::io::{Error, ErrorKind};
 /// use stdf::tally::count_records;
 ///
 /// fn main() -> Result<()> {
-///     let mut file = File::open("tests/fixtures/test.stdf")?;
+///     let mut file = File::open("tests/fixtures/test.std")?;
 ///     let record_count = count_records(&mut file, false)?;
 ///     println!("Total record count: {:?}", record_count);
 ///     Ok(())
@@ -89,3 +92,33 @@ pub fn count_parts(file: &mut File) -> Result<u32> {
     Ok(*pir_count)
 }
 
+/// Counts the unique tests (test numbers) of the PTR, MPR and FTR records of an STDF file,
+/// per record type.
+pub fn count_tests(file: &mut File) -> Result<BTreeMap<String, usize>> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+    let mut tests: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        let test_num = match &record {
+            V4::PTR(ptr) => ptr.test_num.0,
+            V4::MPR(mpr) => mpr.test_num.0,
+            V4::FTR(ftr) => ftr.test_num.0,
+            _ => continue,
+        };
+        tests.entry(record.name()).or_default().insert(test_num);
+    }
+    Ok(tests.into_iter().map(|(name, tests)| (name, tests.len())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_tests() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let tests = count_tests(&mut file).unwrap();
+        assert_eq!(tests, BTreeMap::from([("PTR".to_string(), 26)]));
+    }
+}
@@ -1,47 +1,49 @@
 use std::convert;
 use std::fmt;
 use std::fmt::Write;
+use std::io;
 
 extern crate byte;
 use byte::ctx;
 use byte::{check_len, BytesExt, TryRead, TryWrite};
-use time::{OffsetDateTime, format_description::well_known::Rfc2822};
-use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use time::{OffsetDateTime, UtcOffset, format_description::well_known::{Rfc2822, Rfc3339}};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct B1(pub u8);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct C1(pub u8);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct U1(pub u8);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct N1(pub u8);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct U2(pub u16);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct U4(pub u32);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct U4E(pub u32);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct U8(pub u64);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct I1(pub i8);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct I2(pub i16);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct I4(pub i32);
 
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct I8(pub i64);
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
@@ -50,13 +52,13 @@ pub struct R4(pub f32);
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
 pub struct R8(pub f64);
 
-#[derive(Clone, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Cn<'a>(pub &'a [u8]);
 
-#[derive(Clone, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Clone, Default, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Bn<'a>(pub &'a [u8]);
 
-#[derive(Clone, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Clone, Default, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Dn<'a>(pub u16, pub &'a [u8]);
 
 macro_rules! single_byte_type {
@@ -209,11 +211,152 @@ impl fmt::Display for U4 {
     }
 }
 
+/// The time zone `U4E` timestamps are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeZone {
+    Utc,
+    /// The time zone of the computer.
+    Local,
+    /// A fixed offset, eg: the time zone of the test site.
+    Fixed(UtcOffset),
+}
+
+impl TimeZone {
+    /// The offset from UTC.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the zone is `Local` and its offset can not be
+    /// determined, which is the case on some systems once the process has several threads.
+    pub fn offset(&self) -> io::Result<UtcOffset> {
+        match self {
+            TimeZone::Utc => Ok(UtcOffset::UTC),
+            TimeZone::Local => UtcOffset::current_local_offset()
+                .map_err(|e| io::Error::other(format!("The local time zone can not be determined: {}", e))),
+            TimeZone::Fixed(offset) => Ok(*offset),
+        }
+    }
+
+    /// Parses `utc`, `local` or a fixed offset as `+HH:MM`, `-HH:MM`, `+HHMM` or `+HH`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stdf::types::TimeZone;
+    ///
+    /// assert_eq!(TimeZone::parse("utc"), Some(TimeZone::Utc));
+    /// assert_eq!(TimeZone::parse("+02:00").unwrap().offset().unwrap().whole_hours(), 2);
+    /// assert_eq!(TimeZone::parse("CEST"), None);
+    /// ```
+    pub fn parse(text: &str) -> Option<TimeZone> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "utc" | "z" => return Some(TimeZone::Utc),
+            "local" => return Some(TimeZone::Local),
+            _ => {}
+        }
+        let text = text.trim();
+        let sign: i8 = match text.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let digits: String = text[1..].chars().filter(|c| *c != ':').collect();
+        if !digits.chars().all(|c| c.is_ascii_digit()) || !matches!(digits.len(), 2 | 4) {
+            return None;
+        }
+        let hours: i8 = digits[0..2].parse().ok()?;
+        let minutes: i8 = if digits.len() == 4 { digits[2..4].parse().ok()? } else { 0 };
+        UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok().map(TimeZone::Fixed)
+    }
+}
+
+impl U4E {
+    /// A timestamp of 0 means the time is not known.
+    pub fn is_set(&self) -> bool {
+        self.0 != 0
+    }
+
+    /// The timestamp in UTC, `None` if it is not set.
+    pub fn to_datetime(&self) -> Option<OffsetDateTime> {
+        if !self.is_set() {
+            return None;
+        }
+        OffsetDateTime::from_unix_timestamp(self.0 as i64).ok()
+    }
+
+    /// The timestamp in `zone`, `None` if it is not set.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the offset of `zone` can not be determined.
+    pub fn to_datetime_in(&self, zone: TimeZone) -> io::Result<Option<OffsetDateTime>> {
+        let offset = zone.offset()?;
+        Ok(self.to_datetime().map(|datetime| datetime.to_offset(offset)))
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.0 as u64)
+    }
+
+    /// The timestamp as RFC 3339 (eg: `2018-05-15T03:44:59+02:00`), `None` if it is not set.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the offset of `zone` can not be determined.
+    pub fn to_rfc3339(&self, zone: TimeZone) -> io::Result<Option<String>> {
+        Ok(self.to_datetime_in(zone)?.and_then(|datetime| datetime.format(&Rfc3339).ok()))
+    }
+
+    /// The timestamp as shown by `Display`, but as RFC 3339 at `offset` if given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stdf::types::{TimeZone, U4E};
+    ///
+    /// let cest = TimeZone::parse("+02:00").unwrap().offset().unwrap();
+    /// assert_eq!(U4E(1526348699).display_in(Some(cest)).to_string(), "1526348699 → 2018-05-15T03:44:59+02:00");
+    /// assert_eq!(U4E(1526348699).display_in(None).to_string(), U4E(1526348699).to_string());
+    /// ```
+    pub fn display_in(&self, offset: Option<UtcOffset>) -> U4EDisplay {
+        U4EDisplay { timestamp: *self, offset }
+    }
+
+    /// The timestamp of `datetime`, `None` before 1970 or after 2106.
+    pub fn from_datetime(datetime: OffsetDateTime) -> Option<U4E> {
+        u32::try_from(datetime.unix_timestamp()).ok().map(U4E)
+    }
+
+    /// The timestamp of `time`, `None` before 1970 or after 2106.
+    pub fn from_system_time(time: SystemTime) -> Option<U4E> {
+        let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        u32::try_from(seconds).ok().map(U4E)
+    }
+}
+
+/// A `U4E` shown at a given offset, see [`U4E::display_in`].
+#[derive(Debug, Clone, Copy)]
+pub struct U4EDisplay {
+    timestamp: U4E,
+    offset: Option<UtcOffset>,
+}
+
+impl fmt::Display for U4EDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = self.timestamp.to_datetime().and_then(|datetime| match self.offset {
+            Some(offset) => datetime.to_offset(offset).format(&Rfc3339).ok(),
+            None => datetime.format(&Rfc2822).ok(),
+        });
+        match shown {
+            Some(formatted_datetime) => write!(f, "{} → {}", self.timestamp.0, formatted_datetime),
+            None => write!(f, "{}", self.timestamp.0),
+        }
+    }
+}
+
 impl fmt::Display for U4E {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let datetime  = OffsetDateTime::from_unix_timestamp(self.0 as i64).unwrap();
-        let formatted_datetime = datetime.format(&Rfc2822).unwrap();
-        write!(f, "{} → {}", self.0, formatted_datetime)
+        self.display_in(None).fmt(f)
     }
 }
 
@@ -286,6 +429,19 @@ impl fmt::Display for R8 {
     }
 }
 
+// JSON has no NaN nor infinities, serde_json writes them as `null`, which is read back as NaN.
+impl<'de> Deserialize<'de> for R4 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(R4(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::NAN)))
+    }
+}
+
+impl<'de> Deserialize<'de> for R8 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(R8(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN)))
+    }
+}
+
 macro_rules! variable_length_type {
     ($field_type:ident) => {
         impl<'a> TryRead<'a, ctx::Endian> for $field_type<'a> {
@@ -329,6 +485,44 @@ impl fmt::Display for Cn<'_> {
     }
 }
 
+/// A `Cn` is serialized as a string if it is UTF-8, as its bytes otherwise
+/// (eg: `[181, 65]` for `µA` in Latin-1), so it is not altered.
+impl Serialize for Cn<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(self.0) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => serializer.serialize_bytes(self.0),
+        }
+    }
+}
+
+/// A `Cn` borrows its text, so it can only be deserialized from a borrowed string or borrowed
+/// bytes (eg: from a `&serde_json::Value`, see [`JsonRecord`](crate::jsonl::JsonRecord) for
+/// the texts serialized as bytes).
+impl<'de: 'a, 'a> Deserialize<'de> for Cn<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CnVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for CnVisitor {
+            type Value = Cn<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a borrowed string")
+            }
+
+            fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(Cn(v.as_bytes()))
+            }
+
+            fn visit_borrowed_bytes<E: serde::de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+                Ok(Cn(v))
+            }
+        }
+
+        deserializer.deserialize_str(CnVisitor)
+    }
+}
+
 fn to_hex_string(bytes: &[u8]) -> String {
     //TODO: remove the commented out code after verification the new code works.
     // bytes
@@ -359,11 +553,50 @@ impl fmt::Debug for Dn<'_> {
     }
 }
 
+impl Dn<'_> {
+    /// The number of bits.
+    pub fn bit_len(&self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The value of `bit` (bit 0 is the least significant bit of the first byte), `None` beyond the bit length.
+    pub fn get(&self, bit: usize) -> Option<bool> {
+        if bit >= self.bit_len() {
+            return None;
+        }
+        self.1.get(bit / 8).map(|byte| byte & (1 << (bit % 8)) != 0)
+    }
+
+    /// Iterates over all bits, from bit 0.
+    pub fn bits(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.bit_len()).map(|bit| self.get(bit).unwrap_or(false))
+    }
+
+    /// Iterates over the indices of the bits that are set.
+    pub fn set_bits(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.0).filter(|bit| self.get(*bit as usize) == Some(true))
+    }
+
+    /// The bits as '0' and '1' from bit 0, grouped by nibble, eg: `0100 0010 1`.
+    pub fn to_binary_string(&self) -> String {
+        let mut text = String::with_capacity(self.bit_len() + self.bit_len() / 4);
+        for (i, bit) in self.bits().enumerate() {
+            if i > 0 && i % 4 == 0 {
+                text.push(' ');
+            }
+            text.push(if bit { '1' } else { '0' });
+        }
+        text
+    }
+}
+
 impl fmt::Display for Dn<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // TODO: implement display for Dn, self.0 = # of nibbles, self.1 = data 
-        // it should display something like '0100 0010 1111' ... or maybe with /n and nibble numbers ...
-        write!(f, "{}", to_hex_string(self.1))
+        write!(f, "{}", self.to_binary_string())
     }
 }
 
@@ -371,7 +604,7 @@ impl<'a> TryRead<'a, ctx::Endian> for Dn<'a> {
     fn try_read(bytes: &'a [u8], endian: ctx::Endian) -> byte::Result<(Self, usize)> {
         let offset = &mut 0;
         let d_len = bytes.read_with::<U2>(offset, endian)?.0;
-        let b_len = d_len / 8 + (if d_len % 8 > 0 { 1 } else { 0 });
+        let b_len = d_len.div_ceil(8);
         Ok((
             Dn(
                 d_len,
@@ -386,18 +619,25 @@ impl TryWrite<ctx::Endian> for Dn<'_> {
     fn try_write(self, bytes: &mut [u8], endian: ctx::Endian) -> byte::Result<usize> {
         let offset = &mut 0;
         let mut d_len = self.0;
-        let mut b_len = (d_len / 8 + (if d_len % 8 > 0 { 1 } else { 0 })) as usize;
+        let mut b_len = d_len.div_ceil(8) as usize;
         if b_len > self.1.len() {
             b_len = self.1.len();
             d_len = (b_len * 8) as u16;
         }
         bytes.write_with::<u16>(offset, d_len, endian)?;
-        bytes.write::<&[u8]>(offset, &self.1[0..b_len])?;
-        Ok(self.1.len() + 2)
+        if let Some((last, full)) = self.1[0..b_len].split_last() {
+            bytes.write::<&[u8]>(offset, full)?;
+            // the pad bits of the last byte are zero
+            let used = d_len % 8;
+            let mask = if used == 0 { 0xff } else { (1u8 << used) - 1 };
+            bytes.write::<u8>(offset, last & mask)?;
+        }
+        Ok(*offset)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+/// The `Bn` and `Dn` values borrow their bytes, they are not deserialized (see `jsonl::JsonRecord`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Vn<'a> {
     B0,
     U1(U1),
@@ -408,8 +648,11 @@ pub enum Vn<'a> {
     I4(I4),
     R4(R4),
     R8(R8),
+    #[serde(borrow)]
     Cn(Cn<'a>),
+    #[serde(skip_deserializing)]
     Bn(Bn<'a>),
+    #[serde(skip_deserializing)]
     Dn(Dn<'a>),
     N1(N1),
 }
@@ -617,6 +860,19 @@ mod tests {
         assert_eq!(b, out);
     }
 
+    #[test]
+    fn test_cn_serde() {
+        assert_eq!(serde_json::to_string(&Cn("µA".as_bytes())).unwrap(), r#""µA""#);
+        // µA in Latin-1 is not UTF-8
+        assert_eq!(serde_json::to_string(&Cn(b"\xb5A")).unwrap(), "[181,65]");
+        // the escapes are decoded in the Value, the Cn borrows from it
+        let value: serde_json::Value = serde_json::from_str(r#""\u00b5A""#).unwrap();
+        assert_eq!(Cn::deserialize(&value).unwrap(), Cn("µA".as_bytes()));
+        // without a Value, only the strings without escapes can be borrowed
+        assert_eq!(serde_json::from_str::<Cn>(r#""A""#).unwrap(), Cn(b"A"));
+        assert!(serde_json::from_str::<Cn>(r#""\u00b5A""#).is_err());
+    }
+
     #[test]
     fn test_bn() {
         let b: &[u8] = &[0x05, 0x68, 0x65, 0x6c, 0x6c, 0x6f];
@@ -636,7 +892,62 @@ mod tests {
         assert_eq!(v, Dn(13, &[0x68, 0x65]));
         let mut out = [0u8; 5];
         out.write_with(&mut 0, v, BE).unwrap();
-        assert_ne!(b, out);
-        assert_eq!(b[..4], out[..4]);
+        // the last byte is not part of the field, it stays untouched
+        assert_eq!(out, [0x00, 0x0d, 0x68, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn test_dn_partial_byte() {
+        let v = Dn(10, &[0b1010_0101, 0b1111_1110, 0xff]);
+        let mut out = [0xaau8; 6];
+        let offset = &mut 0;
+        out.write_with(offset, v, BE).unwrap();
+        assert_eq!(*offset, 4);
+        // the 6 pad bits of the last byte are written as zero
+        assert_eq!(out, [0x00, 0x0a, 0b1010_0101, 0b0000_0010, 0xaa, 0xaa]);
+        let v = Dn(16, &[0x01]);
+        let offset = &mut 0;
+        out.write_with(offset, v, BE).unwrap();
+        assert_eq!(*offset, 3);
+        assert_eq!(out[..3], [0x00, 0x08, 0x01]);
+    }
+
+    #[test]
+    fn test_u4e() {
+        let timestamp = U4E(1526348699);
+        assert_eq!(timestamp.to_rfc3339(TimeZone::Utc).unwrap().unwrap(), "2018-05-15T01:44:59Z");
+        let cest = TimeZone::parse("+02:00").unwrap();
+        assert_eq!(timestamp.to_rfc3339(cest).unwrap().unwrap(), "2018-05-15T03:44:59+02:00");
+        assert_eq!(TimeZone::parse("-0530").unwrap().offset().unwrap().whole_minutes(), -330);
+        assert_eq!(TimeZone::parse("+2"), None);
+        assert_eq!(U4E::from_system_time(timestamp.to_system_time()), Some(timestamp));
+        assert_eq!(U4E::from_datetime(timestamp.to_datetime_in(cest).unwrap().unwrap()), Some(timestamp));
+        assert_eq!(U4E(0).to_rfc3339(cest).unwrap(), None);
+        assert_eq!(U4E(0).to_datetime(), None);
+        assert_eq!(U4E(0).to_string(), "0");
+        assert_eq!(U4E(0).display_in(Some(cest.offset().unwrap())).to_string(), "0");
+        assert_eq!(timestamp.display_in(Some(UtcOffset::UTC)).to_string(), "1526348699 → 2018-05-15T01:44:59Z");
+        assert_eq!(U4E(u32::MAX).to_string(), "4294967295 → Sun, 07 Feb 2106 06:28:15 +0000");
+    }
+
+    #[test]
+    fn test_dn_bits() {
+        let v = Dn(10, &[0b1010_0101, 0b1111_1110]);
+        assert_eq!(v.bit_len(), 10);
+        assert_eq!(v.get(0), Some(true));
+        assert_eq!(v.get(1), Some(false));
+        assert_eq!(v.get(9), Some(true));
+        assert_eq!(v.get(10), None);
+        assert_eq!(v.set_bits().collect::<Vec<_>>(), vec![0, 2, 5, 7, 9]);
+        assert_eq!(v.to_string(), "1010 0101 01");
+        assert_eq!(Dn(0, b"").to_string(), "");
+    }
+
+    #[test]
+    fn test_set_bits() {
+        let set_bits = |dn: Dn| dn.set_bits().collect::<Vec<u16>>();
+        assert_eq!(set_bits(Dn(10, &[0b0000_1010, 0b0000_0011])), vec![1, 3, 8, 9]);
+        assert_eq!(set_bits(Dn(9, &[0, 0b0000_0011])), vec![8]);
+        assert!(set_bits(Dn(16, &[0xff])).len() == 8);
     }
 }
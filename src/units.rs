@@ -0,0 +1,252 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::printf::CFormat;
+
+/// The STDF scale exponents (RES_SCAL, LLM_SCAL, HLM_SCAL) and their unit prefixes.
+const PREFIXES: [(i8, &str); 11] = [
+    (15, "f"),
+    (12, "p"),
+    (9, "n"),
+    (6, "u"),
+    (3, "m"),
+    (2, "%"),
+    (0, ""),
+    (-3, "K"),
+    (-6, "M"),
+    (-9, "G"),
+    (-12, "T"),
+];
+
+/// The units that a prefix is stripped from, eg: `mV` is 10⁻³ `V`.
+const BASE_UNITS: [&str; 17] = [
+    "V", "A", "s", "S", "Hz", "W", "Ohm", "ohm", "Ω", "F", "H", "C", "J", "K", "g", "m", "Pa",
+];
+
+/// The unit prefix of an STDF scale exponent, `None` if the exponent has no prefix.
+pub fn prefix(scale: i8) -> Option<&'static str> {
+    PREFIXES.iter().find(|(s, _)| *s == scale).map(|(_, p)| *p)
+}
+
+/// Splits a prefixed unit (eg: `mV`, `kHz`, `µA`) into its scale exponent and base unit.
+///
+/// Units without a (known) prefix are returned as is, with a scale of 0.
+pub fn split_unit(units: &str) -> (i8, &str) {
+    let units = units.trim();
+    if BASE_UNITS.contains(&units) {
+        return (0, units);
+    }
+    let mut chars = units.chars();
+    let scale = match chars.next() {
+        Some('f') => 15,
+        Some('p') => 12,
+        Some('n') => 9,
+        Some('u') | Some('µ') | Some('μ') => 6,
+        Some('m') => 3,
+        Some('k') | Some('K') => -3,
+        Some('M') => -6,
+        Some('G') => -9,
+        Some('T') => -12,
+        _ => return (0, units),
+    };
+    let base = chars.as_str();
+    if BASE_UNITS.contains(&base) {
+        (scale, base)
+    } else {
+        (0, units)
+    }
+}
+
+/// A value in base SI units, with the scale it is to be shown in.
+///
+/// STDF stores results and limits in base units (`V`, `A`, `s`...), the scale exponent
+/// (RES_SCAL, LLM_SCAL, HLM_SCAL) only tells how to show them: a result of 0.0123 `V`
+/// with a scale of 3 is shown as 12.3 `mV`. Some testers write prefixed units instead,
+/// those are converted to base units when the measurement is made.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    /// The value in `unit`.
+    pub value: f64,
+    /// The base (not prefixed) unit.
+    pub unit: String,
+    /// The power of ten the value is multiplied by for display.
+    pub scale: i8,
+}
+
+impl Measurement {
+    /// Makes a measurement of `value` in `units`, shown with `scale` (or with the prefix of `units` if `None`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stdf::units::Measurement;
+    ///
+    /// let result = Measurement::new(0.0123, "V", Some(3));
+    /// assert_eq!(result.to_string(), "12.3 mV");
+    /// let result = Measurement::new(12.3, "mV", None);
+    /// assert_eq!(result.unit, "V");
+    /// assert_eq!(result.to_string(), "12.3 mV");
+    /// ```
+    pub fn new(value: f64, units: &str, scale: Option<i8>) -> Self {
+        let (unit_scale, unit) = split_unit(units);
+        Measurement {
+            value: value * 10f64.powi(-(unit_scale as i32)),
+            unit: unit.to_string(),
+            scale: scale.filter(|s| prefix(*s).is_some()).unwrap_or(unit_scale),
+        }
+    }
+
+    /// The value as shown, multiplied by 10^scale.
+    pub fn scaled(&self) -> f64 {
+        self.value * 10f64.powi(self.scale as i32)
+    }
+
+    /// The unit as shown, with the prefix of the scale.
+    pub fn scaled_unit(&self) -> String {
+        match prefix(self.scale) {
+            Some("%") => "%".to_string(),
+            Some(prefix) => format!("{}{}", prefix, self.unit),
+            None => self.unit.clone(),
+        }
+    }
+
+    /// The same value, shown with a scale of 0.
+    pub fn to_base(&self) -> Measurement {
+        Measurement {
+            scale: 0,
+            ..self.clone()
+        }
+    }
+
+    /// The same value, shown with the engineering prefix that puts it between 1 and 1000.
+    pub fn to_engineering(&self) -> Measurement {
+        let magnitude = self.value.abs();
+        let scale = if magnitude == 0.0 || !magnitude.is_finite() {
+            0
+        } else {
+            let exponent = (magnitude.log10().floor() as i32).div_euclid(3) * 3;
+            -exponent.clamp(-15, 12) as i8
+        };
+        Measurement {
+            scale,
+            ..self.clone()
+        }
+    }
+
+    /// Shows the scaled value with a C printf-style format (eg: C_RESFMT) and the unit.
+    ///
+    /// The unit is left out when the format has text of its own (eg: `%5.2f V`), and
+    /// the plain `Display` is used when the format is empty or not supported.
+    pub fn to_string_with(&self, format: &str) -> String {
+        match CFormat::parse(format) {
+            Some(spec) => {
+                let value = spec.format(self.scaled());
+                let unit = self.scaled_unit();
+                if unit.is_empty() || spec.has_text() {
+                    value
+                } else {
+                    format!("{} {}", value, unit)
+                }
+            }
+            None => self.to_string(),
+        }
+    }
+
+    /// Shows the scaled value alone, with a C printf-style format as [`Measurement::to_string_with`] does.
+    pub fn value_with(&self, format: &str) -> String {
+        match CFormat::parse(format) {
+            Some(spec) => spec.format(self.scaled()),
+            None => format!("{}", self.scaled() as f32),
+        }
+    }
+
+    /// Tells if the measurement is within `lo` and `hi`, compared in base units.
+    ///
+    /// Returns `None` if there is no limit or if a limit has another unit.
+    pub fn within(&self, lo: Option<&Measurement>, hi: Option<&Measurement>) -> Option<bool> {
+        if lo.is_none() && hi.is_none() {
+            return None;
+        }
+        let above = match lo {
+            Some(lo) => self.partial_cmp(lo)? != Ordering::Less,
+            None => true,
+        };
+        let below = match hi {
+            Some(hi) => self.partial_cmp(hi)? != Ordering::Greater,
+            None => true,
+        };
+        Some(above && below)
+    }
+}
+
+/// Measurements compare by value when their units are the same (or one has no unit).
+impl PartialOrd for Measurement {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.unit != other.unit && !self.unit.is_empty() && !other.unit.is_empty() {
+            return None;
+        }
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+/// Shows the scaled value and unit, eg: `12.3 mV`. A precision applies to the scaled value.
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = self.scaled_unit();
+        let value = match f.precision() {
+            Some(precision) => format!("{:.*}", precision, self.scaled()),
+            // results are R4, so the shortest f32 representation avoids digits that were never measured
+            None => format!("{}", self.scaled() as f32),
+        };
+        if unit.is_empty() {
+            write!(f, "{}", value)
+        } else {
+            write!(f, "{} {}", value, unit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_unit() {
+        assert_eq!(split_unit("mV"), (3, "V"));
+        assert_eq!(split_unit("kHz"), (-3, "Hz"));
+        assert_eq!(split_unit("µA"), (6, "A"));
+        assert_eq!(split_unit("m"), (0, "m"));
+        assert_eq!(split_unit("mm"), (3, "m"));
+        assert_eq!(split_unit("Hz"), (0, "Hz"));
+        assert_eq!(split_unit("dB"), (0, "dB"));
+        assert_eq!(split_unit("mil"), (0, "mil"));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Measurement::new(0.0123, "V", Some(3)).to_string(), "12.3 mV");
+        assert_eq!(Measurement::new(1.5e-6, "A", Some(6)).to_string(), "1.5 uA");
+        assert_eq!(Measurement::new(2500.0, "Hz", Some(-3)).to_string(), "2.5 KHz");
+        assert_eq!(Measurement::new(0.25, "", Some(2)).to_string(), "25 %");
+        assert_eq!(format!("{:.2}", Measurement::new(0.0123, "V", Some(3))), "12.30 mV");
+        assert_eq!(Measurement::new(3.0, "", None).to_string(), "3");
+        assert_eq!(Measurement::new(0.000047, "s", None).to_engineering().to_string(), "47 us");
+        assert_eq!(Measurement::new(47.0, "us", None).to_base().to_string(), "0.000047 s");
+        assert_eq!(Measurement::new(0.0123, "V", Some(3)).to_string_with("%7.3f"), " 12.300 mV");
+        assert_eq!(Measurement::new(0.0123, "V", Some(3)).to_string_with("%q"), "12.3 mV");
+        assert_eq!(Measurement::new(-0.66, "v", Some(0)).to_string_with("%5.2f v"), "-0.66 v");
+    }
+
+    #[test]
+    fn test_within() {
+        let result = Measurement::new(12.0, "mV", None);
+        let lo = Measurement::new(0.01, "V", Some(3));
+        let hi = Measurement::new(15.0, "mV", None);
+        assert_eq!(result.within(Some(&lo), Some(&hi)), Some(true));
+        assert_eq!(result.within(None, Some(&lo)), Some(false));
+        assert_eq!(result.within(None, None), None);
+        assert_eq!(result.within(Some(&Measurement::new(0.0, "A", None)), None), None);
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use byte::BytesExt;
+use time::UtcOffset;
+use umya_spreadsheet::{
+    new_file_empty_worksheet, writer, Pane, PaneStateValues, PaneValues, SheetView, SheetViews, Spreadsheet, Worksheet,
+};
+
+use crate::records::V4;
+use crate::stats::{shortest, RunningStats};
+use crate::table::{map, PartRows, TestColumn, TestValue};
+use crate::types::{TimeZone, U4E};
+
+/// The fill and font colors of a failing result (the "Bad" cell style of Excel).
+const FAIL_FILL: &str = "FFFFC7CE";
+const FAIL_FONT: &str = "FF9C0006";
+
+const PART_COLUMNS: [&str; 10] = [
+    "Lot", "Wafer", "X", "Y", "Site", "Head", "Part ID", "Hard Bin", "Soft Bin", "Test Time [ms]",
+];
+
+/// The rows of the part × test sheet above the parts.
+const HEADER_ROWS: u32 = 5;
+
+/// The size of an Excel sheet.
+const MAX_ROWS: u64 = 1_048_576;
+const MAX_COLUMNS: usize = 16_384;
+
+/// The count, name and pass/fail of a bin.
+#[derive(Debug, Default)]
+struct Bin {
+    name: String,
+    pf: String,
+    count: u64,
+}
+
+/// The timestamp as RFC 3339 at `offset`, empty if not set.
+fn time(timestamp: U4E, offset: UtcOffset) -> String {
+    timestamp.to_rfc3339(TimeZone::Fixed(offset)).ok().flatten().unwrap_or_default()
+}
+
+/// Checks that `parts` rows and `tests` columns fit in the part × test sheet.
+fn check_fits(parts: u64, tests: usize) -> Result<()> {
+    if parts + HEADER_ROWS as u64 > MAX_ROWS {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("More than {} parts, an Excel sheet has at most {} rows", MAX_ROWS - HEADER_ROWS as u64, MAX_ROWS),
+        ));
+    }
+    if tests + PART_COLUMNS.len() > MAX_COLUMNS {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("More than {} tests, an Excel sheet has at most {} columns", MAX_COLUMNS - PART_COLUMNS.len(), MAX_COLUMNS),
+        ));
+    }
+    Ok(())
+}
+
+/// Tells if a result fails its limits, a functional test if it failed.
+fn fails(column: &TestColumn, value: &TestValue) -> bool {
+    match (column.rec.as_str(), value.result) {
+        ("FTR", _) => value.passed == Some(false),
+        (_, Some(result)) => value.limits.passes(result) == Some(false),
+        (_, None) => false,
+    }
+}
+
+/// Freezes the first `columns` columns and `rows` rows of `sheet`.
+fn freeze(sheet: &mut Worksheet, columns: u32, rows: u32) {
+    let mut view = SheetView::default();
+    view.set_workbook_view_id(0);
+    if columns > 0 || rows > 0 {
+        let mut pane = Pane::default();
+        if columns > 0 {
+            pane.set_horizontal_split(columns as f64);
+        }
+        if rows > 0 {
+            pane.set_vertical_split(rows as f64);
+        }
+        pane.set_active_pane(match (columns > 0, rows > 0) {
+            (true, true) => PaneValues::BottomRight,
+            (true, false) => PaneValues::TopRight,
+            _ => PaneValues::BottomLeft,
+        });
+        pane.set_state(PaneStateValues::Frozen);
+        pane.get_top_left_cell_mut().set_col_num(columns + 1).set_row_num(rows + 1);
+        view.set_pane(pane);
+    }
+    let mut views = SheetViews::default();
+    views.add_sheet_view_list_mut(view);
+    sheet.set_sheets_views(views);
+}
+
+fn header(sheet: &mut Worksheet, column: u32, row: u32, text: &str) {
+    sheet
+        .get_cell_mut((column, row))
+        .set_value_string(text)
+        .get_style_mut()
+        .get_font_mut()
+        .set_bold(true);
+}
+
+fn new_sheet<'a>(book: &'a mut Spreadsheet, name: &str) -> Result<&'a mut Worksheet> {
+    book.new_sheet(name).map_err(Error::other)
+}
+
+/// Writes the lot summary (MIR, MRR and SDR fields and the yield) as label/value rows.
+fn write_summary(sheet: &mut Worksheet, fields: &[(String, String)]) {
+    header(sheet, 1, 1, "Field");
+    header(sheet, 2, 1, "Value");
+    for (i, (label, value)) in fields.iter().enumerate() {
+        let row = i as u32 + 2;
+        sheet.get_cell_mut((1, row)).set_value_string(label);
+        sheet.get_cell_mut((2, row)).set_value_string(value);
+    }
+    sheet.get_column_dimension_mut("A").set_width(24.0);
+    sheet.get_column_dimension_mut("B").set_width(40.0);
+    freeze(sheet, 0, 1);
+}
+
+fn write_bins(sheet: &mut Worksheet, bins: &BTreeMap<u16, Bin>, parts: u64) {
+    for (i, title) in ["Bin", "Name", "P/F", "Count", "%"].iter().enumerate() {
+        header(sheet, i as u32 + 1, 1, title);
+    }
+    for (i, (number, bin)) in bins.iter().enumerate() {
+        let row = i as u32 + 2;
+        sheet.get_cell_mut((1, row)).set_value_number(*number);
+        sheet.get_cell_mut((2, row)).set_value_string(&bin.name);
+        sheet.get_cell_mut((3, row)).set_value_string(&bin.pf);
+        sheet.get_cell_mut((4, row)).set_value_number(bin.count as f64);
+        if parts > 0 {
+            sheet
+                .get_cell_mut((5, row))
+                .set_value_number(100.0 * bin.count as f64 / parts as f64);
+        }
+    }
+    sheet.get_column_dimension_mut("B").set_width(30.0);
+    freeze(sheet, 0, 1);
+}
+
+fn write_statistics(sheet: &mut Worksheet, columns: &[TestColumn], stats: &[RunningStats]) {
+    let titles = [
+        "Test", "Name", "Units", "Low Limit", "High Limit", "Count", "Fails", "Fail %", "Min", "Max", "Mean", "Std Dev",
+    ];
+    for (i, title) in titles.iter().enumerate() {
+        header(sheet, i as u32 + 1, 1, title);
+    }
+    for (i, (column, stats)) in columns.iter().zip(stats.iter()).enumerate() {
+        let row = i as u32 + 2;
+        sheet.get_cell_mut((1, row)).set_value_number(column.test_num);
+        sheet.get_cell_mut((2, row)).set_value_string(column.name());
+        sheet.get_cell_mut((3, row)).set_value_string(column.units());
+        if let Some(lo) = column.lo() {
+            sheet.get_cell_mut((4, row)).set_value_number(shortest(lo));
+        }
+        if let Some(hi) = column.hi() {
+            sheet.get_cell_mut((5, row)).set_value_number(shortest(hi));
+        }
+        sheet.get_cell_mut((6, row)).set_value_number(stats.count as f64);
+        sheet.get_cell_mut((7, row)).set_value_number(stats.fails as f64);
+        if stats.count > 0 {
+            sheet
+                .get_cell_mut((8, row))
+                .set_value_number(100.0 * stats.fails as f64 / stats.count as f64);
+        }
+        // functional tests have no values
+        if column.rec != "FTR" && stats.count > 0 {
+            sheet.get_cell_mut((9, row)).set_value_number(stats.min);
+            sheet.get_cell_mut((10, row)).set_value_number(stats.max);
+            sheet.get_cell_mut((11, row)).set_value_number(stats.mean);
+        }
+        if let Some(std_dev) = stats.std_dev().filter(|_| column.rec != "FTR") {
+            sheet.get_cell_mut((12, row)).set_value_number(std_dev);
+        }
+    }
+    sheet.get_column_dimension_mut("B").set_width(40.0);
+    freeze(sheet, 2, 1);
+}
+
+/// Writes the header rows of the part × test sheet: the test numbers, names, units and limits.
+fn write_data_header(sheet: &mut Worksheet, columns: &[TestColumn]) {
+    for (i, title) in PART_COLUMNS.iter().enumerate() {
+        header(sheet, i as u32 + 1, 1, title);
+    }
+    for (i, title) in ["Test Name", "Units", "Low Limit", "High Limit"].iter().enumerate() {
+        header(sheet, 1, i as u32 + 2, title);
+    }
+    for (i, column) in columns.iter().enumerate() {
+        let col = (PART_COLUMNS.len() + i) as u32 + 1;
+        sheet
+            .get_cell_mut((col, 1))
+            .set_value_number(column.test_num)
+            .get_style_mut()
+            .get_font_mut()
+            .set_bold(true);
+        sheet.get_cell_mut((col, 2)).set_value_string(column.name());
+        sheet.get_cell_mut((col, 3)).set_value_string(column.units());
+        if let Some(lo) = column.lo() {
+            sheet.get_cell_mut((col, 4)).set_value_number(shortest(lo));
+        }
+        if let Some(hi) = column.hi() {
+            sheet.get_cell_mut((col, 5)).set_value_number(shortest(hi));
+        }
+    }
+    freeze(sheet, PART_COLUMNS.len() as u32, HEADER_ROWS);
+}
+
+/// Writes a lot report of an STDF file as an XLSX workbook.
+///
+/// The workbook has the sheets:
+/// - `Summary`: the MIR, MRR and SDR fields and the yield,
+/// - `Hard Bins` and `Soft Bins`: the parts per bin, named from the HBR/SBR,
+/// - `Test Statistics`: the count, fails, min, max, mean and standard deviation per test,
+/// - `Data`: one row per part and one column per test (see [`crate::table`]), the results
+///   out of their limits (or failing functional tests) are highlighted.
+///
+/// Results and limits are shown with the units and RES_SCAL of the test, the timestamps as
+/// RFC 3339 at `time_offset`, the headers are frozen.
+///
+/// `progress` is called after each part with the offset read up to in the file.
+///
+/// # Returns
+///
+/// The number of parts written.
+///
+/// # Errors
+///
+/// This function will return an error if the file is not an STDF file, if the parts or
+/// the tests do not fit in an Excel sheet (1,048,576 rows, 16,384 columns) or on any I/O error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use stdf::xlsx::write_xlsx;
+/// use time::UtcOffset;
+///
+/// let mut file = File::open("tests/fixtures/test.std").unwrap();
+/// let output = BufWriter::new(File::create("test.xlsx").unwrap());
+/// let parts = write_xlsx(&mut file, output, UtcOffset::UTC, |_| {}).unwrap();
+/// println!("{} parts", parts);
+/// ```
+pub fn write_xlsx<W: Write, F: FnMut(usize)>(file: &mut File, writer: W, time_offset: UtcOffset, mut progress: F) -> Result<usize> {
+    let (mmap, endian) = map(file)?;
+    let bytes = &mmap[..];
+
+    let mut book = new_file_empty_worksheet();
+    for name in ["Summary", "Hard Bins", "Soft Bins", "Test Statistics", "Data"] {
+        new_sheet(&mut book, name)?;
+    }
+    book.set_active_sheet(0);
+
+    let mut summary: Vec<(String, String)> = Vec::new();
+    let mut hard_bins: BTreeMap<u16, Bin> = BTreeMap::new();
+    let mut soft_bins: BTreeMap<u16, Bin> = BTreeMap::new();
+    let mut stats: Vec<RunningStats> = Vec::new();
+    let mut rows = PartRows::new();
+    let (mut parts, mut good) = (0u64, 0u64);
+
+    let data = book.get_sheet_by_name_mut("Data").unwrap();
+    let offset = &mut 0;
+    while let Ok(record) = bytes.read_with::<V4>(offset, endian) {
+        let mut add = |label: &str, value: String| {
+            let value = value.trim().to_string();
+            if !value.is_empty() {
+                summary.push((label.to_string(), value));
+            }
+        };
+        match &record {
+            V4::MIR(mir) => {
+                add("Lot", mir.lot_id.to_string());
+                add("Sublot", mir.sblot_id.to_string());
+                add("Part Type", mir.part_typ.to_string());
+                add("Job", mir.job_nam.to_string());
+                add("Job Revision", mir.job_rev.to_string());
+                add("Test Code", mir.test_cod.to_string());
+                add("Mode", mir.mode_cod.to_string());
+                add("Temperature", mir.tst_temp.to_string());
+                add("Tester", mir.node_nam.to_string());
+                add("Tester Type", mir.tstr_typ.to_string());
+                add("Executive", format!("{} {}", mir.exec_typ, mir.exec_ver));
+                add("Operator", mir.oper_nam.to_string());
+                add("Facility", mir.facil_id.to_string());
+                add("Flow", mir.flow_id.to_string());
+                add("Package", mir.pkg_typ.to_string());
+                add("Setup Time", time(mir.setup_t, time_offset));
+                add("Start Time", time(mir.start_t, time_offset));
+            }
+            V4::MRR(mrr) => {
+                add("Finish Time", time(mrr.finish_t, time_offset));
+                add("Disposition", mrr.disp_cod.to_string());
+                add("Description", mrr.usr_desc.to_string());
+                add("Exceptions", mrr.exc_desc.to_string());
+            }
+            V4::SDR(sdr) => {
+                let sites: Vec<String> = sdr.site_num.iter().map(|site| site.0.to_string()).collect();
+                let group = format!("Site Group {} (head {})", sdr.site_grp.0, sdr.head_num.0);
+                add(&group, format!("sites {}", sites.join(", ")));
+                for (kind, typ, id) in [
+                    ("Handler", &sdr.hand_typ, &sdr.hand_id),
+                    ("Probe Card", &sdr.card_typ, &sdr.card_id),
+                    ("Load Board", &sdr.load_typ, &sdr.load_id),
+                    ("DIB", &sdr.dib_typ, &sdr.dib_id),
+                    ("Cable", &sdr.cabl_typ, &sdr.cabl_id),
+                    ("Contactor", &sdr.cont_typ, &sdr.cont_id),
+                    ("Laser", &sdr.lasr_typ, &sdr.lasr_id),
+                ] {
+                    add(&format!("{} {}", group, kind), format!("{} {}", typ, id));
+                }
+            }
+            // the summary (head 255) names take precedence over the ones of a site
+            V4::HBR(hbr) => {
+                let bin = hard_bins.entry(hbr.hbin_num.0).or_default();
+                if bin.name.is_empty() || hbr.head_num.0 == 255 {
+                    bin.name = hbr.hbin_nam.to_string().trim().to_string();
+                    bin.pf = hbr.hbin_pf.to_string().trim().to_string();
+                }
+            }
+            V4::SBR(sbr) => {
+                let bin = soft_bins.entry(sbr.sbin_num.0).or_default();
+                if bin.name.is_empty() || sbr.head_num.0 == 255 {
+                    bin.name = sbr.sbin_nam.to_string().trim().to_string();
+                    bin.pf = sbr.sbin_pf.to_string().trim().to_string();
+                }
+            }
+            _ => {}
+        }
+        let row = match rows.push(record) {
+            Some(row) => row,
+            None => continue,
+        };
+        parts += 1;
+        check_fits(parts, rows.catalog.columns.len())?;
+        if row.passed == Some(true) {
+            good += 1;
+        }
+        hard_bins.entry(row.hard_bin).or_default().count += 1;
+        if let Some(soft_bin) = row.soft_bin {
+            soft_bins.entry(soft_bin).or_default().count += 1;
+        }
+
+        let r = parts as u32 + HEADER_ROWS;
+        data.get_cell_mut((1, r)).set_value_string(&row.lot_id);
+        data.get_cell_mut((2, r)).set_value_string(&row.wafer_id);
+        if let (Some(x), Some(y)) = (row.x, row.y) {
+            data.get_cell_mut((3, r)).set_value_number(x);
+            data.get_cell_mut((4, r)).set_value_number(y);
+        }
+        data.get_cell_mut((5, r)).set_value_number(row.site_num);
+        data.get_cell_mut((6, r)).set_value_number(row.head_num);
+        data.get_cell_mut((7, r)).set_value_string(&row.part_id);
+        data.get_cell_mut((8, r)).set_value_number(row.hard_bin);
+        if let Some(soft_bin) = row.soft_bin {
+            data.get_cell_mut((9, r)).set_value_number(soft_bin);
+        }
+        if let Some(test_t) = row.test_t {
+            data.get_cell_mut((10, r)).set_value_number(test_t);
+        }
+        let columns = &rows.catalog.columns;
+        stats.resize_with(columns.len(), RunningStats::new);
+        for value in row.values.iter() {
+            let column = &columns[value.column];
+            let failed = fails(column, value);
+            let cell = data.get_cell_mut(((PART_COLUMNS.len() + value.column) as u32 + 1, r));
+            match (column.rec.as_str(), value.result) {
+                ("FTR", _) => {
+                    cell.set_value_string(match value.passed {
+                        Some(true) => "P",
+                        Some(false) => "F",
+                        None => "",
+                    });
+                    // functional tests are only counted, they have no values
+                    stats[value.column].count += 1;
+                    stats[value.column].fails += u64::from(failed);
+                }
+                (_, Some(result)) => {
+                    let shown = shortest(column.scaled(result));
+                    cell.set_value_number(shown);
+                    stats[value.column].add(shown, failed);
+                }
+                (_, None) => continue,
+            }
+            if failed {
+                let style = cell.get_style_mut();
+                style.set_background_color(FAIL_FILL);
+                style.get_font_mut().get_color_mut().set_argb(FAIL_FONT);
+            }
+        }
+        progress(*offset);
+    }
+    write_data_header(data, &rows.catalog.columns);
+
+    summary.push(("Parts Tested".to_string(), parts.to_string()));
+    summary.push(("Good Parts".to_string(), good.to_string()));
+    if parts > 0 {
+        summary.push(("Yield".to_string(), format!("{:.2} %", 100.0 * good as f64 / parts as f64)));
+    }
+    write_summary(book.get_sheet_by_name_mut("Summary").unwrap(), &summary);
+    write_bins(book.get_sheet_by_name_mut("Hard Bins").unwrap(), &hard_bins, parts);
+    write_bins(book.get_sheet_by_name_mut("Soft Bins").unwrap(), &soft_bins, parts);
+    write_statistics(
+        book.get_sheet_by_name_mut("Test Statistics").unwrap(),
+        &rows.catalog.columns,
+        &stats,
+    );
+    writer::xlsx::write_writer(&book, writer).map_err(|e| Error::other(e.to_string()))?;
+    Ok(parts as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_check_fits() {
+        assert!(check_fits(1_048_571, 16_374).is_ok());
+        let error = check_fits(1_048_572, 10).unwrap_err();
+        assert_eq!(error.to_string(), "More than 1048571 parts, an Excel sheet has at most 1048576 rows");
+        assert!(check_fits(10, 16_375).unwrap_err().to_string().starts_with("More than 16374 tests"));
+    }
+
+    #[test]
+    fn test_write_xlsx() {
+        let mut file = File::open("tests/fixtures/test.std").unwrap();
+        let mut output = Vec::new();
+        let cest = UtcOffset::from_hms(2, 0, 0).unwrap();
+        let parts = write_xlsx(&mut file, &mut output, cest, |_| {}).unwrap();
+        assert_eq!(parts, 22);
+
+        let book = umya_spreadsheet::reader::xlsx::read_reader(Cursor::new(output), true).unwrap();
+        let names: Vec<&str> = book.get_sheet_collection().iter().map(|s| s.get_name()).collect();
+        assert_eq!(names, ["Summary", "Hard Bins", "Soft Bins", "Test Statistics", "Data"]);
+
+        let summary = book.get_sheet_by_name("Summary").unwrap();
+        assert_eq!(summary.get_value("A2"), "Lot");
+        assert_eq!(summary.get_value("B2"), "F6N910.1");
+        let start = (1..=summary.get_highest_row()).find(|row| summary.get_value((1, *row)) == "Start Time").unwrap();
+        assert!(summary.get_value((2, start)).ends_with("+02:00"));
+
+        let hard_bins = book.get_sheet_by_name("Hard Bins").unwrap();
+        let counted: f64 = (2..2 + hard_bins.get_highest_row())
+            .map(|row| hard_bins.get_value((4, row)).parse::<f64>().unwrap_or(0.0))
+            .sum();
+        assert_eq!(counted, 22.0);
+
+        let data = book.get_sheet_by_name("Data").unwrap();
+        assert_eq!(data.get_highest_row(), HEADER_ROWS + 22);
+        assert_eq!(data.get_value("A2"), "Test Name");
+        assert_eq!(data.get_value("G6"), "1");
+        let pane = data.get_sheets_views().get_sheet_view_list()[0].get_pane().unwrap();
+        assert_eq!(pane.get_top_left_cell().to_string(), "K6");
+
+        let statistics = book.get_sheet_by_name("Test Statistics").unwrap();
+        assert_eq!(statistics.get_highest_row() as usize, data.get_highest_column() as usize - PART_COLUMNS.len() + 1);
+    }
+}